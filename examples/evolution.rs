@@ -0,0 +1,32 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rust_simple_nn::datasets::*;
+use rust_simple_nn::evolution::*;
+use rust_simple_nn::nn::*;
+
+/// Number of correctly classified samples : a fitness without a gradient
+fn accuracy(nn : &mut NeuralNetWork, data : &[Sample]) -> f64 {
+	data.iter().filter(|(input,target)| (nn.predict(input)[0] >= 0.5) == (target[0] >= 0.5)).count() as f64 / data.len() as f64
+}
+
+fn main(){
+	let mut rng = ChaCha8Rng::seed_from_u64(0);
+	let data = gen_quadrant(500, &mut rng);
+
+	let mut nn = NeuralNetWork::empty(2, "quadratic", rng);
+	nn.add(6, "tanh");
+	nn.add(1, "sigmoid");
+	println!("{} parameters, starting accuracy {:.3}",nn.nb_parameters(),accuracy(&mut nn, &data));
+
+	let options = GeneticOptions { population : 60, mutation_std : 0.3, threads : 4, seed : 1, ..GeneticOptions::default() };
+	let mut ga = GeneticAlgorithm::new(&nn, options);
+	for stats in ga.run(40, |nn : &mut NeuralNetWork| accuracy(nn, &data)) {
+		if stats.generation % 5 == 0 {
+			println!("generation {:>3}: best {:.3} mean {:.3}",stats.generation,stats.best,stats.mean);
+		}
+	}
+
+	let mut es = EvolutionStrategy::new(&ga.best_network(), EsOptions { population : 30, threads : 4, ..EsOptions::default() });
+	es.run(20, |nn : &mut NeuralNetWork| accuracy(nn, &data));
+	println!("genetic algorithm {:.3}, evolution strategies from its best individual {:.3}",ga.best().unwrap().fitness.unwrap(),accuracy(&mut es.network(), &data));
+}
@@ -0,0 +1,69 @@
+//! Train a digit classifier on the MNIST files
+//!
+//! Download and uncompress the 4 files from http://yann.lecun.com/exdb/mnist/ in a directory, then
+//! `cargo run --release --example mnist -- <directory>`
+use std::path::Path;
+use std::process::ExitCode;
+
+use rand::{SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
+
+use rust_simple_nn::config::*;
+use rust_simple_nn::idx::*;
+use rust_simple_nn::nn::*;
+use rust_simple_nn::optimizer::*;
+
+/// index of the largest value
+fn argmax(values : &[f64]) -> usize {
+	values.iter().enumerate().fold(0, |best,(i,value)| if *value > values[best] { i } else { best })
+}
+
+fn accuracy(nn : &mut NeuralNetWork, data : &[Sample]) -> f64 {
+	let correct = data.iter().filter(|(input,output)| argmax(&nn.predict(input)) == argmax(output)).count();
+	correct as f64 / data.len() as f64
+}
+
+fn main() -> ExitCode {
+	let Some(dir) = std::env::args().nth(1) else {
+		eprintln!("usage: mnist <directory with the uncompressed MNIST files>");
+		return ExitCode::from(2);
+	};
+	let dir = Path::new(&dir);
+
+	let load = |images : &str, labels : &str| load_labeled_images(dir.join(images), dir.join(labels), 10);
+	let (mut train,test) = match (load("train-images-idx3-ubyte","train-labels-idx1-ubyte"),load("t10k-images-idx3-ubyte","t10k-labels-idx1-ubyte")) {
+		(Ok(train),Ok(test)) => (train,test),
+		(Err(error),_) | (_,Err(error)) => {
+			eprintln!("error: {error}");
+			return ExitCode::from(1);
+		}
+	};
+	println!("{} training images, {} test images",train.len(),test.len());
+
+	let mut nn = NetworkBuilder::new(784)
+		.layer_config(LayerConfig::new(128, "relu").init("he"))
+		.layer_config(LayerConfig::new(10, "sigmoid").init("xavier"))
+		.loss("cross_entropy")
+		.seed(42)
+		.build()
+		.unwrap();
+
+	let options = TrainOptions {
+		mini_batch_size : 64,
+		epochs : 1,
+		learning_rate : 0.001,
+		optimizer : Optimizer::adam(),
+		schedule : LearningRateSchedule::Constant,
+		verbose : false,
+		..TrainOptions::default()
+	};
+
+	let mut rng = ChaCha8Rng::seed_from_u64(42);
+	for epoch in 0..5 {
+		train.shuffle(&mut rng);
+		let cost = nn.train_with(&train, &options)[0];
+		println!("epoch {epoch}: cost {cost:.4}, test accuracy {:.2}%",100.0*accuracy(&mut nn, &test));
+	}
+
+	ExitCode::SUCCESS
+}
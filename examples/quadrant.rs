@@ -0,0 +1,56 @@
+use rust_simple_nn::datasets::*;
+use rust_simple_nn::metrics::*;
+use rust_simple_nn::nn::*;
+use rust_simple_nn::preprocessing::*;
+use rand::{seq::SliceRandom, thread_rng};
+
+fn main(){
+
+
+	let mut neural_network = NeuralNetWork::new(
+		&[2,4,1],
+		"default",
+		"relu",
+		"sigmoid"
+	);
+
+	println!("===========================");
+	neural_network.input(&[1.0,2.0]);
+	neural_network.print_output();
+
+	println!("Generating data ....");
+	let mut rng = thread_rng();
+	let mut data = gen_quadrant(DEFAULT_SAMPLES, &mut rng);
+	println!("data generated, strating shuffling");
+	data.shuffle(&mut rng);
+	// the inputs below go far outside [-10,10], standardized inputs keep the network in its range
+	neural_network.fit_scalers(&data, Some(ScalerKind::Standard), None);
+	println!("data shuffled, strating training");	
+	neural_network.train(&data, 120,100, 0.5,true);
+	
+	println!("===========================");
+	neural_network.input(&[10.0,20.0]);
+	neural_network.print_output();
+
+	println!("===========================");
+	neural_network.input(&[-30000.0,2.1]);
+	neural_network.print_output();
+
+	println!("===========================");
+	neural_network.input(&[-2.1,3000000.0]);
+	neural_network.print_output();
+
+	println!("===========================");
+	neural_network.input(&[0.0,0.0]);
+	neural_network.print_output();
+
+	println!("===========================");
+	neural_network.input(&[-10.0,10.0]);
+	neural_network.print_output();
+
+	println!("===========================");
+	let test_data = gen_quadrant(10000, &mut rng);
+	print!("{}",neural_network.evaluate(&test_data, Task::classification()));
+
+
+}
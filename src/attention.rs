@@ -0,0 +1,598 @@
+use rand::Rng;
+
+use crate::block::*;
+use crate::init::*;
+use crate::matrix::*;
+use crate::nn::NORM_EPSILON;
+use crate::tensor::*;
+
+
+/* -------------------------------------------------------------------------- */
+/*                              Matrix helpers                                */
+/* -------------------------------------------------------------------------- */
+
+
+fn product(a : &Matrix<f64>, b : &Matrix<f64>) -> Matrix<f64> {
+	let mut result = Matrix::new_dot_result(a, b);
+	a.dot(&mut result, b);
+	result
+}
+
+/// The columns `start..start+len` of a matrix
+fn columns(matrix : &Matrix<f64>, start : usize, len : usize) -> Matrix<f64> {
+	let values = (0..matrix.rows).flat_map(|i| matrix.values[i*matrix.cols+start..i*matrix.cols+start+len].iter().copied()).collect();
+	Matrix::from_vec(matrix.rows, len, values)
+}
+
+/// Write `part` in the columns of `matrix` starting at `start`
+fn set_columns(matrix : &mut Matrix<f64>, start : usize, part : &Matrix<f64>){
+	for i in 0..matrix.rows {
+		matrix.values[i*matrix.cols+start..i*matrix.cols+start+part.cols].copy_from_slice(&part.values[i*part.cols..(i+1)*part.cols]);
+	}
+}
+
+fn check_sequence(input_shape : &[usize], dimension : usize){
+	assert!(input_shape.len()==2 && input_shape[1]==dimension,"expected a [time,{dimension}] sequence, got {input_shape:?}");
+}
+
+
+/// A fully connected layer applied to each row, `x·W + b` with `W` of shape `[in,out]`
+#[derive(Debug,Clone)]
+struct Linear {
+	weights : Param,
+	biases : Param,
+	input : Matrix<f64>,
+}
+
+impl Linear {
+
+	fn new<R : Rng + ?Sized>(inputs : usize, outputs : usize, init : Init, rng : &mut R) -> Linear {
+		Linear {
+			weights : Param::new(init.weights(outputs, inputs, rng).transpose()),
+			biases : Param::new(init.biases(outputs, rng).transpose()),
+			input : Matrix::new(0, 0),
+		}
+	}
+
+	fn forward(&mut self, input : &Matrix<f64>) -> Matrix<f64> {
+		self.input = input.clone();
+		let mut output = product(input, &self.weights.value);
+		for row in output.values.chunks_mut(output.cols) {
+			row.iter_mut().zip(&self.biases.value.values).for_each(|(x,b)| *x += b);
+		}
+		output
+	}
+
+	fn backward(&mut self, grad_output : &Matrix<f64>) -> Matrix<f64> {
+		self.input.trans_dot_add(&mut self.weights.grad, grad_output);
+		for row in grad_output.values.chunks(grad_output.cols) {
+			self.biases.grad.values.iter_mut().zip(row).for_each(|(g,d)| *g += d);
+		}
+		let mut grad_input = Matrix::new(self.input.rows, self.input.cols);
+		grad_output.dot_trans_add(&mut grad_input, &self.weights.value);
+		grad_input
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		vec![&self.weights,&self.biases]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![&mut self.weights,&mut self.biases]
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                        Scaled dot-product attention                        */
+/* -------------------------------------------------------------------------- */
+
+
+/// `softmax(Q·Kᵀ/√d)·V` for `[time,d]` queries, keys and values, returns the output and the attention weights.
+/// With `causal` a step only attends to itself and the steps before it.
+pub fn scaled_dot_product_attention(queries : &Matrix<f64>, keys : &Matrix<f64>, values : &Matrix<f64>, causal : bool) -> (Matrix<f64>,Matrix<f64>) {
+	assert!(queries.cols==keys.cols && keys.rows==values.rows,"queries {}x{}, keys {}x{} and values {}x{} don't match",queries.rows,queries.cols,keys.rows,keys.cols,values.rows,values.cols);
+	let scale = 1.0 / (queries.cols as f64).sqrt();
+	let mut weights = Matrix::new(queries.rows, keys.rows);
+	queries.dot_trans_add(&mut weights, keys);
+
+	for (i,row) in weights.values.chunks_mut(keys.rows).enumerate() {
+		for (j,x) in row.iter_mut().enumerate() {
+			*x = if causal && j > i { f64::NEG_INFINITY } else { *x * scale };
+		}
+		let max = row.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+		row.iter_mut().for_each(|x| *x = (*x - max).exp());
+		let sum : f64 = row.iter().sum();
+		row.iter_mut().for_each(|x| *x /= sum);
+	}
+
+	(product(&weights, values),weights)
+}
+
+/// Gradients of the queries, keys and values for the gradient of the output of `scaled_dot_product_attention`
+pub fn scaled_dot_product_attention_backward(queries : &Matrix<f64>, keys : &Matrix<f64>, values : &Matrix<f64>, weights : &Matrix<f64>, grad_output : &Matrix<f64>) -> (Matrix<f64>,Matrix<f64>,Matrix<f64>) {
+	let scale = 1.0 / (queries.cols as f64).sqrt();
+
+	let mut grad_values = Matrix::new(values.rows, values.cols);
+	weights.trans_dot_add(&mut grad_values, grad_output);
+
+	// softmax backward, row by row, scaled back to the scores
+	let mut grad_scores = Matrix::new(weights.rows, weights.cols);
+	grad_output.dot_trans_add(&mut grad_scores, values);
+	for (row,weights) in grad_scores.values.chunks_mut(weights.cols).zip(weights.values.chunks(weights.cols)) {
+		let dot : f64 = row.iter().zip(weights).map(|(g,w)| g*w).sum();
+		row.iter_mut().zip(weights).for_each(|(g,w)| *g = w * (*g - dot) * scale);
+	}
+
+	let grad_queries = product(&grad_scores, keys);
+	let mut grad_keys = Matrix::new(keys.rows, keys.cols);
+	grad_scores.trans_dot_add(&mut grad_keys, queries);
+	(grad_queries,grad_keys,grad_values)
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                          Multi-head self-attention                         */
+/* -------------------------------------------------------------------------- */
+
+
+/// Self-attention of a `[time,dimension]` sequence split in heads of `dimension/heads` features
+#[derive(Debug,Clone)]
+pub struct MultiHeadAttention {
+	heads : usize,
+	causal : bool,
+	queries : Linear,
+	keys : Linear,
+	values : Linear,
+	output : Linear,
+	/// queries, keys and values of the last forward pass
+	projections : [Matrix<f64>;3],
+	/// attention weights of each head
+	weights : Vec<Matrix<f64>>,
+}
+
+impl MultiHeadAttention {
+
+	/// # Arguments
+	/// * `dimension` - features of each step, divisible by `heads`
+	/// * `heads` - number of attention heads
+	/// * `init` - how the projections are drawn
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new<R : Rng + ?Sized>(dimension : usize, heads : usize, init : Init, rng : &mut R) -> MultiHeadAttention {
+		assert!(heads > 0 && dimension.is_multiple_of(heads),"the dimension {dimension} should be divisible by the {heads} heads");
+		MultiHeadAttention {
+			heads,
+			causal : false,
+			queries : Linear::new(dimension, dimension, init, rng),
+			keys : Linear::new(dimension, dimension, init, rng),
+			values : Linear::new(dimension, dimension, init, rng),
+			output : Linear::new(dimension, dimension, init, rng),
+			projections : [Matrix::new(0, 0),Matrix::new(0, 0),Matrix::new(0, 0)],
+			weights : vec![],
+		}
+	}
+
+	/// Mask the future steps, for autoregressive models
+	pub fn causal(mut self, causal : bool) -> MultiHeadAttention {
+		self.causal = causal;
+		self
+	}
+
+	pub fn dimension(&self) -> usize {
+		self.output.weights.value.cols
+	}
+
+	/// The `[time,time]` attention weights of each head in the last forward pass
+	pub fn attention_weights(&self) -> &[Matrix<f64>] {
+		&self.weights
+	}
+
+	fn forward_matrix(&mut self, input : &Matrix<f64>) -> Matrix<f64> {
+		let head_size = self.dimension() / self.heads;
+		self.projections = [self.queries.forward(input),self.keys.forward(input),self.values.forward(input)];
+		let [queries,keys,values] = &self.projections;
+
+		let mut concatenated = Matrix::new(input.rows, self.dimension());
+		self.weights.clear();
+		for head in 0..self.heads {
+			let start = head*head_size;
+			let (output,weights) = scaled_dot_product_attention(&columns(queries, start, head_size), &columns(keys, start, head_size), &columns(values, start, head_size), self.causal);
+			set_columns(&mut concatenated, start, &output);
+			self.weights.push(weights);
+		}
+		self.output.forward(&concatenated)
+	}
+
+	fn backward_matrix(&mut self, grad_output : &Matrix<f64>) -> Matrix<f64> {
+		let head_size = self.dimension() / self.heads;
+		let grad_concatenated = self.output.backward(grad_output);
+		let [queries,keys,values] = &self.projections;
+
+		let mut grads = [Matrix::new(queries.rows, queries.cols),Matrix::new(keys.rows, keys.cols),Matrix::new(values.rows, values.cols)];
+		for head in 0..self.heads {
+			let start = head*head_size;
+			let (grad_queries,grad_keys,grad_values) = scaled_dot_product_attention_backward(
+				&columns(queries, start, head_size), &columns(keys, start, head_size), &columns(values, start, head_size),
+				&self.weights[head], &columns(&grad_concatenated, start, head_size));
+			set_columns(&mut grads[0], start, &grad_queries);
+			set_columns(&mut grads[1], start, &grad_keys);
+			set_columns(&mut grads[2], start, &grad_values);
+		}
+
+		let mut grad_input = self.queries.backward(&grads[0]);
+		grad_input.add_mut(&self.keys.backward(&grads[1]));
+		grad_input.add_mut(&self.values.backward(&grads[2]));
+		grad_input
+	}
+}
+
+impl Block for MultiHeadAttention {
+
+	fn name(&self) -> String {
+		format!("{}attention {}x{}",if self.causal { "causal " } else { "" },self.heads,self.dimension()/self.heads)
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		check_sequence(input_shape, self.dimension());
+		input_shape.to_vec()
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		check_sequence(&input.shape, self.dimension());
+		Tensor::from_matrix(self.forward_matrix(&input.clone().into_matrix()))
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		Tensor::from_matrix(self.backward_matrix(&grad_output.clone().into_matrix()))
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		[&self.queries,&self.keys,&self.values,&self.output].into_iter().flat_map(|linear| linear.params()).collect()
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		[&mut self.queries,&mut self.keys,&mut self.values,&mut self.output].into_iter().flat_map(|linear| linear.params_mut()).collect()
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                            Positional encoding                             */
+/* -------------------------------------------------------------------------- */
+
+
+/// Add the sinusoidal encoding of the position of each step, `sin(t/10000^(2i/d))` on even
+/// features and `cos` on odd ones, so attention can tell the steps apart
+#[derive(Debug,Clone,Default)]
+pub struct PositionalEncoding;
+
+impl PositionalEncoding {
+
+	pub fn new() -> PositionalEncoding {
+		PositionalEncoding
+	}
+
+	/// The encoding of a `[time,dimension]` sequence
+	pub fn encoding(time : usize, dimension : usize) -> Matrix<f64> {
+		let values = (0..time).flat_map(|t| (0..dimension).map(move |i| {
+			let angle = t as f64 / 10000f64.powf((i - i%2) as f64 / dimension as f64);
+			if i%2 == 0 { angle.sin() } else { angle.cos() }
+		})).collect();
+		Matrix::from_vec(time, dimension, values)
+	}
+}
+
+impl Block for PositionalEncoding {
+
+	fn name(&self) -> String {
+		"positional encoding".to_string()
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		assert!(input_shape.len()==2,"expected a [time,features] sequence, got {input_shape:?}");
+		input_shape.to_vec()
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		self.output_shape(&input.shape);
+		let mut output = input.clone();
+		output.add_mut(&Tensor::from_matrix(PositionalEncoding::encoding(input.shape[0], input.shape[1])));
+		output
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		grad_output.clone()
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                             Layer normalisation                            */
+/* -------------------------------------------------------------------------- */
+
+
+/// Normalise each step of a `[time,dimension]` sequence over its features, followed by a learned gain and shift
+#[derive(Debug,Clone)]
+pub struct LayerNormalization {
+	gain : Param,
+	shift : Param,
+	normalized : Matrix<f64>,
+	inv_std : Vec<f64>,
+}
+
+impl LayerNormalization {
+
+	pub fn new(dimension : usize) -> LayerNormalization {
+		LayerNormalization {
+			gain : Param::new(Matrix::from_vec(1, dimension, vec![1.0;dimension])),
+			shift : Param::new(Matrix::new(1, dimension)),
+			normalized : Matrix::new(0, 0),
+			inv_std : vec![],
+		}
+	}
+
+	pub fn dimension(&self) -> usize {
+		self.gain.value.cols
+	}
+
+	fn forward_matrix(&mut self, input : &Matrix<f64>) -> Matrix<f64> {
+		let n = input.cols as f64;
+		self.normalized = input.clone();
+		self.inv_std.clear();
+		let mut output = Matrix::new(input.rows, input.cols);
+
+		for (row,out) in self.normalized.values.chunks_mut(input.cols).zip(output.values.chunks_mut(input.cols)) {
+			let mean = row.iter().sum::<f64>() / n;
+			let var = row.iter().map(|x| (x-mean).powi(2)).sum::<f64>() / n;
+			let inv_std = 1.0 / (var + NORM_EPSILON).sqrt();
+			self.inv_std.push(inv_std);
+			for (i,x) in row.iter_mut().enumerate() {
+				*x = (*x - mean) * inv_std;
+				out[i] = self.gain.value.values[i] * *x + self.shift.value.values[i];
+			}
+		}
+		output
+	}
+
+	fn backward_matrix(&mut self, grad_output : &Matrix<f64>) -> Matrix<f64> {
+		let n = grad_output.cols as f64;
+		let mut grad_input = Matrix::new(grad_output.rows, grad_output.cols);
+
+		let rows = grad_output.values.chunks(grad_output.cols).zip(self.normalized.values.chunks(grad_output.cols));
+		for (r,(delta,normalized)) in rows.enumerate() {
+			let mut d_normalized = vec![0.0;delta.len()];
+			for i in 0..delta.len() {
+				self.shift.grad.values[i] += delta[i];
+				self.gain.grad.values[i] += delta[i] * normalized[i];
+				d_normalized[i] = delta[i] * self.gain.value.values[i];
+			}
+			let sum : f64 = d_normalized.iter().sum();
+			let dot : f64 = d_normalized.iter().zip(normalized).map(|(d,x)| d*x).sum();
+			for i in 0..delta.len() {
+				grad_input.values[r*grad_output.cols+i] = self.inv_std[r] / n * (n*d_normalized[i] - sum - normalized[i]*dot);
+			}
+		}
+		grad_input
+	}
+}
+
+impl Block for LayerNormalization {
+
+	fn name(&self) -> String {
+		format!("layer norm {}",self.dimension())
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		check_sequence(input_shape, self.dimension());
+		input_shape.to_vec()
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		check_sequence(&input.shape, self.dimension());
+		Tensor::from_matrix(self.forward_matrix(&input.clone().into_matrix()))
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		Tensor::from_matrix(self.backward_matrix(&grad_output.clone().into_matrix()))
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		vec![&self.gain,&self.shift]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![&mut self.gain,&mut self.shift]
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                             Transformer encoder                            */
+/* -------------------------------------------------------------------------- */
+
+
+/// `x = norm(x + attention(x))` then `norm(x + feed_forward(x))`, the feed forward
+/// network being two fully connected layers with a relu applied to each step
+#[derive(Debug,Clone)]
+pub struct TransformerEncoder {
+	attention : MultiHeadAttention,
+	attention_norm : LayerNormalization,
+	hidden : Linear,
+	projection : Linear,
+	feed_forward_norm : LayerNormalization,
+	/// pre-activation of the hidden layer
+	hidden_values : Matrix<f64>,
+}
+
+impl TransformerEncoder {
+
+	/// # Arguments
+	/// * `dimension` - features of each step, divisible by `heads`
+	/// * `heads` - number of attention heads
+	/// * `hidden_size` - size of the hidden layer of the feed forward network
+	/// * `init` - how the weights are drawn
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new<R : Rng + ?Sized>(dimension : usize, heads : usize, hidden_size : usize, init : Init, rng : &mut R) -> TransformerEncoder {
+		TransformerEncoder {
+			attention : MultiHeadAttention::new(dimension, heads, init, rng),
+			attention_norm : LayerNormalization::new(dimension),
+			hidden : Linear::new(dimension, hidden_size, init, rng),
+			projection : Linear::new(hidden_size, dimension, init, rng),
+			feed_forward_norm : LayerNormalization::new(dimension),
+			hidden_values : Matrix::new(0, 0),
+		}
+	}
+
+	/// See `MultiHeadAttention::causal`
+	pub fn causal(mut self, causal : bool) -> TransformerEncoder {
+		self.attention = self.attention.causal(causal);
+		self
+	}
+
+	pub fn attention(&self) -> &MultiHeadAttention {
+		&self.attention
+	}
+}
+
+impl Block for TransformerEncoder {
+
+	fn name(&self) -> String {
+		format!("transformer encoder ({}, ff {})",self.attention.name(),self.hidden.weights.value.cols)
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		self.attention.output_shape(input_shape)
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		check_sequence(&input.shape, self.attention.dimension());
+		let input = input.clone().into_matrix();
+
+		let mut residual = self.attention.forward_matrix(&input);
+		residual.add_mut(&input);
+		let attended = self.attention_norm.forward_matrix(&residual);
+
+		self.hidden_values = self.hidden.forward(&attended);
+		let mut activated = self.hidden_values.clone();
+		activated.values.iter_mut().for_each(|x| *x = x.max(0.0));
+		let mut residual = self.projection.forward(&activated);
+		residual.add_mut(&attended);
+		Tensor::from_matrix(self.feed_forward_norm.forward_matrix(&residual))
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		let grad_residual = self.feed_forward_norm.backward_matrix(&grad_output.clone().into_matrix());
+		let mut grad_hidden = self.projection.backward(&grad_residual);
+		grad_hidden.values.iter_mut().zip(&self.hidden_values.values).for_each(|(g,x)| if *x <= 0.0 { *g = 0.0 });
+		let mut grad_attended = self.hidden.backward(&grad_hidden);
+		grad_attended.add_mut(&grad_residual);
+
+		let grad_residual = self.attention_norm.backward_matrix(&grad_attended);
+		let mut grad_input = self.attention.backward_matrix(&grad_residual);
+		grad_input.add_mut(&grad_residual);
+		Tensor::from_matrix(grad_input)
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		let mut params = self.attention.params();
+		params.extend(self.attention_norm.params());
+		params.extend(self.hidden.params());
+		params.extend(self.projection.params());
+		params.extend(self.feed_forward_norm.params());
+		params
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		let mut params = self.attention.params_mut();
+		params.extend(self.attention_norm.params_mut());
+		params.extend(self.hidden.params_mut());
+		params.extend(self.projection.params_mut());
+		params.extend(self.feed_forward_norm.params_mut());
+		params
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	fn sequence(time : usize, dimension : usize, seed : u64) -> Tensor {
+		let mut rng = ChaCha8Rng::seed_from_u64(seed);
+		Tensor::from_vec(&[time,dimension], (0..time*dimension).map(|_| rng.gen_range(-1.0..1.0)).collect())
+	}
+
+	#[test]
+	fn causal_attention_ignores_the_future(){
+		let mut rng = ChaCha8Rng::seed_from_u64(0);
+		let mut attention = MultiHeadAttention::new(4, 2, Init::Xavier, &mut rng).causal(true);
+		let input = sequence(5, 4, 1);
+		let output = attention.forward(&input);
+		assert!(attention.attention_weights().iter().all(|weights| (0..5).all(|i| (i+1..5).all(|j| weights.values[i*5+j] == 0.0))));
+
+		// changing the last step doesn't change the others
+		let mut changed = input.clone();
+		changed.row_mut(4).fill(3.0);
+		let changed = attention.forward(&changed);
+		assert!(output.values[..16] == changed.values[..16] && output.values[16..] != changed.values[16..]);
+	}
+
+	#[test]
+	fn attention_gradient_checks(){
+		let mut rng = ChaCha8Rng::seed_from_u64(2);
+		let input = sequence(4, 6, 3);
+		check_gradients(&mut MultiHeadAttention::new(6, 2, Init::Xavier, &mut rng), &input);
+		check_gradients(&mut MultiHeadAttention::new(6, 3, Init::Xavier, &mut rng).causal(true), &input);
+		check_gradients(&mut PositionalEncoding::new(), &input);
+		check_gradients(&mut LayerNormalization::new(6), &input);
+		check_gradients(&mut TransformerEncoder::new(6, 2, 8, Init::Xavier, &mut rng), &input);
+		check_gradients(&mut TransformerEncoder::new(6, 2, 8, Init::Xavier, &mut rng).causal(true), &input);
+	}
+
+	#[test]
+	fn learns_token_order(){
+		// one-hot tokens a, b and c, the label tells whether a comes before b
+		let data : Vec<TensorSample> = (0..5).flat_map(|a| (0..5).filter(move |b| *b != a).map(move |b| {
+			let mut input = Tensor::new(&[5,4]);
+			for t in 0..5 {
+				let token = if t == a { 0 } else if t == b { 1 } else { 2 };
+				*input.at_mut(&[t,token]) = 1.0;
+			}
+			(input,vec![if a < b { 1.0 } else { 0.0 }])
+		})).collect();
+
+		let mut rng = ChaCha8Rng::seed_from_u64(4);
+		let mut model = Sequential::new(&[5,4], "cross_entropy", ChaCha8Rng::seed_from_u64(5));
+		model.add(PositionalEncoding::new());
+		model.add(TransformerEncoder::new(4, 2, 16, Init::Xavier, &mut rng));
+		model.dense(1, "sigmoid");
+
+		let options = adam_options(4, 150);
+		model.train_with(&data, &options);
+		let correct = data.iter().filter(|(input,output)| (model.predict(input)[0] > 0.5) == (output[0] == 1.0)).count();
+		assert!(correct == data.len(), "{correct}/{}",data.len());
+	}
+}
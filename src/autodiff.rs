@@ -0,0 +1,667 @@
+use std::cell::RefCell;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::matrix::*;
+use crate::nn::*;
+use crate::preprocessing::*;
+use crate::utils::*;
+
+
+/// Records the operations on its variables so the gradient of a scalar can be computed in reverse
+#[derive(Debug,Default)]
+pub struct Tape {
+	nodes : RefCell<Vec<Node>>,
+}
+
+#[derive(Debug)]
+struct Node {
+	value : Matrix<f64>,
+	op : Op,
+}
+
+/// The operation that produced a node, with the indexes of its operands.
+/// The second operand of `Add`, `Sub` and `Mul` is broadcast when it's a column or a scalar.
+#[derive(Debug,Clone,Copy)]
+enum Op {
+	Leaf,
+	Dot(usize,usize),
+	Add(usize,usize),
+	Sub(usize,usize),
+	Mul(usize,usize),
+	Scale(usize,f64),
+	Unary(usize,Unary),
+	Sum(usize),
+	Mean(usize),
+	Transpose(usize),
+}
+
+/// Element wise functions
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Unary {
+	Sigmoid,
+	Tanh,
+	Relu,
+	Exp,
+	Ln,
+	Abs,
+	Powf(f64),
+	/// the gradient is zero outside of the bounds
+	Clamp(f64,f64),
+}
+
+impl Unary {
+
+	pub fn apply(self, x : f64) -> f64 {
+		match self {
+			Unary::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+			Unary::Tanh => x.tanh(),
+			Unary::Relu => x.max(0.0),
+			Unary::Exp => x.exp(),
+			Unary::Ln => x.ln(),
+			Unary::Abs => x.abs(),
+			Unary::Powf(p) => x.powf(p),
+			Unary::Clamp(min,max) => x.clamp(min, max),
+		}
+	}
+
+	/// Derivative at `x`, `y` being the result of the function
+	fn derivative(self, x : f64, y : f64) -> f64 {
+		match self {
+			Unary::Sigmoid => y*(1.0-y),
+			Unary::Tanh => 1.0-y*y,
+			Unary::Relu => if x > 0.0 { 1.0 } else { 0.0 },
+			Unary::Exp => y,
+			Unary::Ln => 1.0/x,
+			Unary::Abs => x.signum(),
+			Unary::Powf(p) => p*x.powf(p-1.0),
+			Unary::Clamp(min,max) => if (min..=max).contains(&x) { 1.0 } else { 0.0 },
+		}
+	}
+}
+
+/// A matrix recorded on a tape, the operators and methods record new variables
+#[derive(Debug,Clone,Copy)]
+pub struct Var<'t> {
+	tape : &'t Tape,
+	index : usize,
+}
+
+/// Gradient of a scalar with respect to the variables of the tape
+#[derive(Debug)]
+pub struct Gradients {
+	grads : Vec<Option<Matrix<f64>>>,
+}
+
+impl Tape {
+
+	pub fn new() -> Tape {
+		Tape::default()
+	}
+
+	/// A leaf variable, ex : a parameter or an input
+	pub fn var(&self, value : Matrix<f64>) -> Var<'_> {
+		self.push(value, Op::Leaf)
+	}
+
+	/// A column vector leaf
+	pub fn column(&self, values : &[f64]) -> Var<'_> {
+		self.var(Matrix::from_vec(values.len(), 1, values.to_vec()))
+	}
+
+	pub fn scalar(&self, value : f64) -> Var<'_> {
+		self.var(Matrix::from_vec(1, 1, vec![value]))
+	}
+
+	/// Number of recorded variables
+	pub fn len(&self) -> usize {
+		self.nodes.borrow().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.nodes.borrow().is_empty()
+	}
+
+	fn push(&self, value : Matrix<f64>, op : Op) -> Var<'_> {
+		let mut nodes = self.nodes.borrow_mut();
+		nodes.push(Node { value, op });
+		Var { tape : self, index : nodes.len()-1 }
+	}
+
+	/// Backward pass from a scalar `output`, every variable recorded before it gets a gradient
+	pub fn gradients(&self, output : Var) -> Gradients {
+		assert!(std::ptr::eq(output.tape, self),"the variable belongs to another tape");
+		let nodes = self.nodes.borrow();
+		let value = &nodes[output.index].value;
+		assert!(value.rows==1 && value.cols==1,"the gradient is computed for a scalar, got a {}x{} matrix",value.rows,value.cols);
+
+		let mut grads : Vec<Option<Matrix<f64>>> = vec![None;nodes.len()];
+		grads[output.index] = Some(Matrix::from_vec(1, 1, vec![1.0]));
+
+		for index in (0..=output.index).rev() {
+			let Some(grad) = grads[index].take() else {
+				continue;
+			};
+			let node = &nodes[index];
+			let value = |i : usize| &nodes[i].value;
+
+			match node.op {
+				Op::Leaf => {},
+				Op::Dot(a,b) => {
+					let mut grad_a = Matrix::new(value(a).rows, value(a).cols);
+					grad.dot_trans_add(&mut grad_a, value(b));
+					accumulate(&mut grads[a], grad_a);
+					let mut grad_b = Matrix::new(value(b).rows, value(b).cols);
+					value(a).trans_dot_add(&mut grad_b, &grad);
+					accumulate(&mut grads[b], grad_b);
+				},
+				Op::Add(a,b) => {
+					accumulate(&mut grads[b], reduce_to(&grad, value(b)));
+					accumulate(&mut grads[a], grad.clone());
+				},
+				Op::Sub(a,b) => {
+					let mut grad_b = reduce_to(&grad, value(b));
+					grad_b.values.iter_mut().for_each(|g| *g = -*g);
+					accumulate(&mut grads[b], grad_b);
+					accumulate(&mut grads[a], grad.clone());
+				},
+				Op::Mul(a,b) => {
+					let mut grad_a = grad.clone();
+					grad_a.values.iter_mut().enumerate().for_each(|(i,g)| *g *= broadcast_at(value(b), value(a), i));
+					let mut grad_b = grad.clone();
+					grad_b.values.iter_mut().zip(&value(a).values).for_each(|(g,x)| *g *= x);
+					accumulate(&mut grads[b], reduce_to(&grad_b, value(b)));
+					accumulate(&mut grads[a], grad_a);
+				},
+				Op::Scale(a,factor) => {
+					let mut grad_a = grad.clone();
+					grad_a.values.iter_mut().for_each(|g| *g *= factor);
+					accumulate(&mut grads[a], grad_a);
+				},
+				Op::Unary(a,function) => {
+					let mut grad_a = grad.clone();
+					for (i,g) in grad_a.values.iter_mut().enumerate() {
+						*g *= function.derivative(value(a).values[i], node.value.values[i]);
+					}
+					accumulate(&mut grads[a], grad_a);
+				},
+				Op::Sum(a) | Op::Mean(a) => {
+					let n = value(a).values.len();
+					let g = if let Op::Mean(_) = node.op { grad.values[0] / n as f64 } else { grad.values[0] };
+					accumulate(&mut grads[a], Matrix::from_vec(value(a).rows, value(a).cols, vec![g;n]));
+				},
+				Op::Transpose(a) => {
+					accumulate(&mut grads[a], grad.transpose());
+				},
+			}
+			grads[index] = Some(grad);
+		}
+
+		Gradients { grads }
+	}
+}
+
+fn accumulate(grad : &mut Option<Matrix<f64>>, value : Matrix<f64>){
+	match grad {
+		Some(grad) => grad.add_mut(&value),
+		None => *grad = Some(value),
+	}
+}
+
+/// The value of the broadcast operand `b` at the position `i` of `a`
+fn broadcast_at(b : &Matrix<f64>, a : &Matrix<f64>, i : usize) -> f64 {
+	if b.rows == a.rows && b.cols == a.cols {
+		b.values[i]
+	} else if b.cols == 1 && b.rows == a.rows {
+		b.values[i / a.cols]
+	} else {
+		b.values[0]
+	}
+}
+
+/// Sum `grad` over the dimensions along which `b` was broadcast
+fn reduce_to(grad : &Matrix<f64>, b : &Matrix<f64>) -> Matrix<f64> {
+	if grad.rows == b.rows && grad.cols == b.cols {
+		return grad.clone();
+	}
+	let mut reduced = Matrix::new(b.rows, b.cols);
+	for (i,g) in grad.values.iter().enumerate() {
+		let position = if b.rows == grad.rows { i / grad.cols } else { 0 };
+		reduced.values[position] += g;
+	}
+	reduced
+}
+
+impl Gradients {
+
+	/// `None` if the output doesn't depend on the variable
+	pub fn wrt(&self, var : Var) -> Option<&Matrix<f64>> {
+		self.grads.get(var.index).and_then(|grad| grad.as_ref())
+	}
+}
+
+impl<'t> Var<'t> {
+
+	pub fn value(&self) -> Matrix<f64> {
+		self.tape.nodes.borrow()[self.index].value.clone()
+	}
+
+	/// The value of a 1x1 variable
+	pub fn item(&self) -> f64 {
+		let nodes = self.tape.nodes.borrow();
+		let value = &nodes[self.index].value;
+		assert!(value.values.len()==1,"item of a {}x{} matrix",value.rows,value.cols);
+		value.values[0]
+	}
+
+	/// (rows,cols)
+	pub fn shape(&self) -> (usize,usize) {
+		let nodes = self.tape.nodes.borrow();
+		(nodes[self.index].value.rows,nodes[self.index].value.cols)
+	}
+
+	fn map(self, op : Op, function : impl FnOnce(&Matrix<f64>) -> Matrix<f64>) -> Var<'t> {
+		let value = function(&self.tape.nodes.borrow()[self.index].value);
+		self.tape.push(value, op)
+	}
+
+	fn binary(self, other : Var<'t>, op : Op, function : fn(f64,f64) -> f64) -> Var<'t> {
+		assert!(std::ptr::eq(self.tape, other.tape),"the variables belong to different tapes");
+		let nodes = self.tape.nodes.borrow();
+		let (a,b) = (&nodes[self.index].value,&nodes[other.index].value);
+		let broadcast = (b.rows==a.rows && (b.cols==a.cols || b.cols==1)) || b.values.len()==1;
+		assert!(broadcast,"a {}x{} matrix can't be broadcast to {}x{}",b.rows,b.cols,a.rows,a.cols);
+		let values = a.values.iter().enumerate().map(|(i,x)| function(*x, broadcast_at(b, a, i))).collect();
+		let value = Matrix::from_vec(a.rows, a.cols, values);
+		drop(nodes);
+		self.tape.push(value, op)
+	}
+
+	/// Matrix product
+	pub fn dot(self, other : Var<'t>) -> Var<'t> {
+		assert!(std::ptr::eq(self.tape, other.tape),"the variables belong to different tapes");
+		let nodes = self.tape.nodes.borrow();
+		let (a,b) = (&nodes[self.index].value,&nodes[other.index].value);
+		let mut value = Matrix::new_dot_result(a, b);
+		a.dot(&mut value, b);
+		drop(nodes);
+		self.tape.push(value, Op::Dot(self.index,other.index))
+	}
+
+	pub fn unary(self, function : Unary) -> Var<'t> {
+		self.map(Op::Unary(self.index,function), |a| {
+			Matrix::from_vec(a.rows, a.cols, a.values.iter().map(|x| function.apply(*x)).collect())
+		})
+	}
+
+	pub fn sigmoid(self) -> Var<'t> {
+		self.unary(Unary::Sigmoid)
+	}
+
+	pub fn tanh(self) -> Var<'t> {
+		self.unary(Unary::Tanh)
+	}
+
+	pub fn relu(self) -> Var<'t> {
+		self.unary(Unary::Relu)
+	}
+
+	pub fn exp(self) -> Var<'t> {
+		self.unary(Unary::Exp)
+	}
+
+	pub fn ln(self) -> Var<'t> {
+		self.unary(Unary::Ln)
+	}
+
+	pub fn abs(self) -> Var<'t> {
+		self.unary(Unary::Abs)
+	}
+
+	pub fn powf(self, exponent : f64) -> Var<'t> {
+		self.unary(Unary::Powf(exponent))
+	}
+
+	pub fn clamp(self, min : f64, max : f64) -> Var<'t> {
+		self.unary(Unary::Clamp(min,max))
+	}
+
+	/// The activation of a layer by its name (same names as `get_activation`), `None` if the name is unknown
+	pub fn activation(self, name : &str) -> Option<Var<'t>> {
+		match name.trim().to_lowercase().as_str() {
+			"sigmoid" | "sigmoïd" => Some(self.sigmoid()),
+			"relu" | "default" => Some(self.relu()),
+			"tanh" => Some(self.tanh()),
+			"id" | "identity" => Some(self),
+			_ => None,
+		}
+	}
+
+	/// Sum of the values, a scalar
+	pub fn sum(self) -> Var<'t> {
+		self.map(Op::Sum(self.index), |a| Matrix::from_vec(1, 1, vec![a.values.iter().sum()]))
+	}
+
+	/// Mean of the values, a scalar
+	pub fn mean(self) -> Var<'t> {
+		self.map(Op::Mean(self.index), |a| Matrix::from_vec(1, 1, vec![a.values.iter().sum::<f64>() / a.values.len() as f64]))
+	}
+
+	pub fn transpose(self) -> Var<'t> {
+		self.map(Op::Transpose(self.index), |a| a.transpose())
+	}
+
+	pub fn scale(self, factor : f64) -> Var<'t> {
+		self.map(Op::Scale(self.index,factor), |a| {
+			Matrix::from_vec(a.rows, a.cols, a.values.iter().map(|x| x*factor).collect())
+		})
+	}
+}
+
+impl<'t> Add for Var<'t> {
+	type Output = Var<'t>;
+
+	fn add(self, other : Var<'t>) -> Var<'t> {
+		self.binary(other, Op::Add(self.index,other.index), |a,b| a+b)
+	}
+}
+
+impl<'t> Sub for Var<'t> {
+	type Output = Var<'t>;
+
+	fn sub(self, other : Var<'t>) -> Var<'t> {
+		self.binary(other, Op::Sub(self.index,other.index), |a,b| a-b)
+	}
+}
+
+/// Element wise product
+impl<'t> Mul for Var<'t> {
+	type Output = Var<'t>;
+
+	fn mul(self, other : Var<'t>) -> Var<'t> {
+		self.binary(other, Op::Mul(self.index,other.index), |a,b| a*b)
+	}
+}
+
+impl<'t> Add<f64> for Var<'t> {
+	type Output = Var<'t>;
+
+	fn add(self, other : f64) -> Var<'t> {
+		self + self.tape.scalar(other)
+	}
+}
+
+impl<'t> Sub<f64> for Var<'t> {
+	type Output = Var<'t>;
+
+	fn sub(self, other : f64) -> Var<'t> {
+		self - self.tape.scalar(other)
+	}
+}
+
+impl<'t> Mul<f64> for Var<'t> {
+	type Output = Var<'t>;
+
+	fn mul(self, factor : f64) -> Var<'t> {
+		self.scale(factor)
+	}
+}
+
+impl<'t> Neg for Var<'t> {
+	type Output = Var<'t>;
+
+	fn neg(self) -> Var<'t> {
+		self.scale(-1.0)
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Costs                                   */
+/* -------------------------------------------------------------------------- */
+
+
+pub fn quadratic<'t>(output : Var<'t>, target : Var<'t>) -> Var<'t> {
+	(output - target).powf(2.0).sum()
+}
+
+pub fn cross_entropy<'t>(output : Var<'t>, target : Var<'t>) -> Var<'t> {
+	let output = output.clamp(CROSS_ENTROPY_CLAMP, 1.0-CROSS_ENTROPY_CLAMP);
+	let one_minus = |var : Var<'t>| -var + 1.0;
+	-(target*output.ln() + one_minus(target)*one_minus(output).ln()).sum()
+}
+
+/// The cost by its name (same names as `get_cost`), `None` if the name is unknown
+pub fn cost<'t>(name : &str, output : Var<'t>, target : Var<'t>) -> Option<Var<'t>> {
+	match name.trim().to_lowercase().as_str() {
+		"quadratic" | "default" => Some(quadratic(output, target)),
+		"cross_entropy" | "crossentropy" => Some(cross_entropy(output, target)),
+		_ => None,
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                          Dense network on a tape                           */
+/* -------------------------------------------------------------------------- */
+
+
+/// A network recorded on a tape
+#[derive(Debug)]
+pub struct DenseGraph<'t> {
+	pub output : Var<'t>,
+	/// for each layer, its parameters in the order of `NeuralNetWork::parameters`
+	pub parameters : Vec<Vec<Var<'t>>>,
+}
+
+impl NeuralNetWork {
+
+	/// Record the forward pass of an already scaled input on `tape`, without dropout.
+	/// Networks with output heads can't be recorded.
+	pub fn record<'t>(&self, tape : &'t Tape, input : Var<'t>) -> DenseGraph<'t> {
+		assert!(self.heads().is_empty(),"a network with output heads can't be recorded on a tape");
+		let mut values = input;
+		let mut parameters = vec![];
+		for layer in &self.layers {
+			let layer = layer.borrow();
+			let weights = tape.var(layer.w_matrix.clone());
+			let biases = tape.var(layer.b_matrix.clone());
+			values = weights.dot(values) + biases;
+			let mut layer_parameters = vec![weights,biases];
+
+			if let Some(norm) = &layer.norm {
+				let (gain,shift) = (tape.var(norm.gamma.clone()),tape.var(norm.beta.clone()));
+				let centered = values - values.mean();
+				let inv_std = (centered.powf(2.0).mean() + NORM_EPSILON).powf(-0.5);
+				values = gain*(centered*inv_std) + shift;
+				layer_parameters.extend([gain,shift]);
+			}
+
+			values = values.activation(&layer.activation_name).unwrap_or_else(|| values.relu());
+			parameters.push(layer_parameters);
+		}
+		DenseGraph { output : values, parameters }
+	}
+
+	/// Train with a loss written with tape operations instead of the cost of the network,
+	/// returns the mean loss of the first mini batch at the end of each epoch.
+	/// Inputs and targets are scaled like `train_with`, dropout isn't applied.
+	/// `options.clip` and `options.non_finite` apply as in `train_with`, the loss of a mini batch is checked instead of its activations.
+	///
+	/// # Arguments
+	/// * `data` - the samples
+	/// * `options` - the hyperparameters, as `train_with`
+	/// * `loss` - the scalar loss of an output and its target
+	pub fn train_with_loss<L>(&mut self, data : &[Sample], options : &TrainOptions, loss : L) -> Vec<f64>
+	where L : for<'t> Fn(Var<'t>,Var<'t>) -> Var<'t>
+	{
+		let TrainOptions { mini_batch_size, epochs, learning_rate, optimizer, schedule, verbose, clip, non_finite } = *options;
+
+		let mut last_good = self.layers.clone();
+		let mut lr_calculated = learning_rate;
+		let mut cost_array = vec![];
+		for epoch in 0..epochs {
+			let mut first_batch_loss = None;
+			for (i,batch) in data.chunks(mini_batch_size).enumerate() {
+				self.zero_grad();
+				let mut batch_loss = 0.0;
+				for (input,output) in batch {
+					batch_loss += self.accumulate_loss_gradients(input, output, &loss);
+				}
+				// a non-finite loss stands for the activations, which aren't checked one by one
+				let result = match non_finite != NonFinite::Ignore && !batch_loss.is_finite() {
+					true => Err(NonFiniteError { value : NonFiniteValue::Cost, layer : None, epoch, batch : Some(i) }),
+					false => self.apply_gradients(batch.len() as f64, lr_calculated, &optimizer, clip, non_finite),
+				};
+				if let Err(error) = result {
+					self.handle_non_finite(NonFiniteError { epoch, batch : Some(i), ..error }, non_finite, &last_good, verbose).unwrap_or_else(|error| panic!("{error}"));
+				}
+				first_batch_loss.get_or_insert(batch_loss / batch.len() as f64);
+			}
+
+			let cost = first_batch_loss.expect("no data to train on");
+			self.end_epoch(cost, epoch, non_finite, &mut last_good, verbose).unwrap_or_else(|error| panic!("{error}"));
+			cost_array.push(cost);
+			if verbose {
+				display_progress(1, 1, cost, epoch, epochs);
+			}
+			lr_calculated = schedule.next(learning_rate, lr_calculated, epoch, &cost_array);
+		}
+		cost_array
+	}
+
+	/// Add the gradient of the loss of one sample to the layers gradient, returns the loss
+	fn accumulate_loss_gradients<L>(&mut self, input : &[f64], output : &[f64], loss : &L) -> f64
+	where L : for<'t> Fn(Var<'t>,Var<'t>) -> Var<'t>
+	{
+		let tape = Tape::new();
+		let scaled = |scaler : Option<&Scaler>, values : &[f64]| scaler.map_or_else(|| values.to_vec(), |scaler| scaler.transform(values));
+		let input = tape.column(&scaled(self.input_scaler(), input));
+		let target = tape.column(&scaled(self.target_scaler(), output));
+		let graph = self.record(&tape, input);
+		let loss = loss(graph.output, target);
+		let gradients = tape.gradients(loss);
+
+		for (layer,parameters) in self.layers.iter().zip(&graph.parameters) {
+			let mut layer = layer.borrow_mut();
+			let layer = &mut *layer;
+			let mut grads = vec![&mut layer.grad_w,&mut layer.grad_b];
+			if let Some(norm) = &mut layer.norm {
+				grads.extend([&mut norm.grad_gamma,&mut norm.grad_beta]);
+			}
+			for (grad,parameter) in grads.into_iter().zip(parameters) {
+				if let Some(gradient) = gradients.wrt(*parameter) {
+					grad.add_mut(gradient);
+				}
+			}
+		}
+		loss.item()
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::init::*;
+	use crate::optimizer::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	/// Compare the gradient of `function` with central differences at `point`
+	fn check(point : Matrix<f64>, function : impl for<'t> Fn(Var<'t>) -> Var<'t>){
+		let tape = Tape::new();
+		let x = tape.var(point.clone());
+		let gradients = tape.gradients(function(x));
+		let analytic = gradients.wrt(x).unwrap();
+
+		let eps = 1e-6;
+		let at = |values : Matrix<f64>| {
+			let tape = Tape::new();
+			function(tape.var(values)).item()
+		};
+		for i in 0..point.values.len() {
+			let (mut plus,mut minus) = (point.clone(),point.clone());
+			plus.values[i] += eps;
+			minus.values[i] -= eps;
+			let numeric = (at(plus) - at(minus)) / (2.0*eps);
+			assert!((analytic.values[i] - numeric).abs() < 1e-6 + 1e-4*numeric.abs(),"{i} : {} != {numeric}",analytic.values[i]);
+		}
+	}
+
+	#[test]
+	fn operation_gradients(){
+		let point = Matrix::from_vec(2, 3, vec![0.5,-1.2,2.0,0.3,0.8,-0.4]);
+		check(point.clone(), |x| x.sigmoid().sum());
+		check(point.clone(), |x| x.tanh().mean());
+		check(point.clone(), |x| (x.exp() + x.powf(2.0)*3.0).sum());
+		check(point.clone(), |x| (x.abs() + 1.0).ln().sum());
+		check(point.clone(), |x| (x*x - x.relu()).sum());
+		check(point.clone(), |x| {
+			let tape = x.tape;
+			let w = tape.var(Matrix::from_vec(3, 2, vec![1.0,-2.0,0.5,0.3,-0.7,1.1]));
+			let column = tape.column(&[0.2,-0.1]);
+			(x.dot(w) + column).tanh().dot(x).transpose().sum()
+		});
+		// the same variable used twice, broadcast of a scalar
+		check(point, |x| (x*x.mean() - x.sum()).sigmoid().sum());
+	}
+
+	#[test]
+	fn same_gradients_as_backprop(){
+		let mut nn = NeuralNetWork::empty(3, "cross_entropy", ChaCha8Rng::seed_from_u64(0));
+		nn.add_layer(4, "tanh", Init::Xavier, 0.0, true);
+		nn.add(2, "sigmoid");
+		let (input,output) = (vec![0.5,-0.3,0.9],vec![1.0,0.0]);
+
+		nn.zero_grad();
+		nn.backprop_input(&input, &output);
+		let expected : Vec<Matrix<f64>> = nn.layers.iter().flat_map(|layer| {
+			let layer = layer.borrow();
+			let norm = layer.norm.iter().flat_map(|norm| [norm.grad_gamma.clone(),norm.grad_beta.clone()]);
+			[layer.grad_w.clone(),layer.grad_b.clone()].into_iter().chain(norm).collect::<Vec<_>>()
+		}).collect();
+
+		let tape = Tape::new();
+		let graph = nn.record(&tape, tape.column(&input));
+		let gradients = tape.gradients(cost("cross_entropy", graph.output, tape.column(&output)).unwrap());
+		nn.input(&input);
+		assert!((graph.output.value().values.iter().zip(&nn.output()).all(|(a,b)| (a-b).abs() < 1e-12)));
+
+		for (expected,parameter) in expected.iter().zip(graph.parameters.iter().flatten()) {
+			let gradient = gradients.wrt(*parameter).unwrap();
+			assert!(expected.values.iter().zip(&gradient.values).all(|(a,b)| (a-b).abs() < 1e-9));
+		}
+	}
+
+	#[test]
+	fn custom_loss_training(){
+		// mean absolute error, robust to the outlier
+		let mut data : Vec<Sample> = (0..20).map(|i| {
+			let x = i as f64 / 10.0;
+			(vec![x],vec![2.0*x - 1.0])
+		}).collect();
+		data.push((vec![1.0],vec![50.0]));
+
+		let mut nn = NeuralNetWork::empty(1, "quadratic", ChaCha8Rng::seed_from_u64(1));
+		nn.add(1, "identity");
+		let options = TrainOptions { mini_batch_size : 21, epochs : 2000, learning_rate : 0.05, optimizer : Optimizer::Sgd, schedule : LearningRateSchedule::Constant, ..TrainOptions::default() };
+		let losses = nn.train_with_loss(&data, &options, |output,target| (output - target).abs().sum());
+
+		assert!(losses.last().unwrap() < &losses[0]);
+		assert!((nn.predict(&[0.5])[0] - 0.0).abs() < 0.1, "{:?}",nn.predict(&[0.5]));
+	}
+
+	#[test]
+	#[should_panic(expected = "non-finite cost at epoch 0, batch 0")]
+	fn custom_loss_non_finite(){
+		let mut nn = NeuralNetWork::empty(1, "quadratic", ChaCha8Rng::seed_from_u64(1));
+		nn.add(1, "identity");
+		let options = TrainOptions { epochs : 1, non_finite : NonFinite::Abort, ..TrainOptions::default() };
+		nn.train_with_loss(&[(vec![1.0],vec![f64::NAN])], &options, |output,target| (output - target).abs().sum());
+	}
+}
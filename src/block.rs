@@ -0,0 +1,591 @@
+use std::fmt;
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::init::*;
+use crate::matrix::*;
+use crate::nn::*;
+use crate::optimizer::*;
+use crate::tensor::*;
+use crate::utils::*;
+
+/// One (input tensor,expected output) pair
+pub type TensorSample = (Tensor,Vec<f64>);
+
+
+/// A trainable matrix of a block with its accumulated gradient and its optimizer state
+#[derive(Debug,Clone,PartialEq)]
+pub struct Param {
+	pub value : Matrix<f64>,
+	pub grad : Matrix<f64>,
+	state : ParamState,
+}
+
+impl Param {
+
+	pub fn new(value : Matrix<f64>) -> Param {
+		Param { grad : Matrix::new(value.rows, value.cols), value, state : ParamState::default() }
+	}
+
+	pub fn zero_grad(&mut self){
+		self.grad.zero();
+	}
+
+	/// Apply the gradient summed over `mean_value` samples
+	pub fn update(&mut self, mean_value : f64, learning_rate : f64, optimizer : &Optimizer){
+		optimizer.update(&mut self.value.values, &self.grad.values, &mut self.state, learning_rate, mean_value);
+	}
+}
+
+
+/// A differentiable layer working on tensors, ex : a convolution or a recurrent layer.
+/// `forward` keeps what `backward` needs, so a backward pass uses the last forward pass.
+pub trait Block : fmt::Debug + Send {
+
+	/// Short description, ex : `conv2d 3x3 1->8`
+	fn name(&self) -> String;
+
+	/// Shape of the output for an input of this shape, panics if the block can't take it
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize>;
+
+	fn forward(&mut self, input : &Tensor) -> Tensor;
+
+	/// Add the gradient of the parameters to their `grad` and return the gradient of the input
+	///
+	/// # Arguments
+	/// * `grad_output` - gradient of the cost with respect to the output of the last forward pass
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor;
+
+	fn params(&self) -> Vec<&Param> {
+		vec![]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![]
+	}
+
+	/// Reset the gradients before a mini batch
+	fn zero_grad(&mut self){
+		for param in self.params_mut() {
+			param.zero_grad();
+		}
+	}
+
+	/// Apply the gradients summed over `mean_value` samples
+	fn update(&mut self, mean_value : f64, learning_rate : f64, optimizer : &Optimizer){
+		for param in self.params_mut() {
+			param.update(mean_value, learning_rate, optimizer);
+		}
+	}
+
+	fn clone_box(&self) -> Box<dyn Block>;
+
+	fn nb_parameters(&self) -> usize {
+		self.params().iter().map(|param| param.value.values.len()).sum()
+	}
+}
+
+impl Clone for Box<dyn Block> {
+	fn clone(&self) -> Self {
+		self.clone_box()
+	}
+}
+
+
+/// Apply an activation function (see `get_activation`) to every value
+#[derive(Debug,Clone)]
+pub struct Activation {
+	name : String,
+	function : fn(f64)->f64,
+	derivative : fn(f64)->f64,
+	input : Tensor,
+}
+
+impl Activation {
+
+	/// Panics if the activation is unknown
+	pub fn new(name : &str) -> Activation {
+		let Some((function,derivative)) = get_activation(name) else {
+			panic!("unknown activation `{name}`");
+		};
+		Activation { name : name.trim().to_lowercase(), function, derivative, input : Tensor::new(&[0]) }
+	}
+}
+
+impl Block for Activation {
+
+	fn name(&self) -> String {
+		self.name.clone()
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		input_shape.to_vec()
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		self.input = input.clone();
+		Tensor::from_vec(&input.shape, input.values.iter().map(|x| (self.function)(*x)).collect())
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		let values = grad_output.values.iter().zip(&self.input.values).map(|(g,x)| g*(self.derivative)(*x)).collect();
+		Tensor::from_vec(&grad_output.shape, values)
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+/// A fully connected layer `activation(W·x + b)` on the flattened input, the output is `[outputs]`
+#[derive(Debug,Clone)]
+pub struct Dense {
+	weights : Param,
+	biases : Param,
+	activation : Activation,
+	input : Tensor,
+}
+
+impl Dense {
+
+	/// # Arguments
+	/// * `inputs` - number of values of the input
+	/// * `outputs` - number of neurons
+	/// * `activation_str` - name of the activation function, panics if it's unknown
+	/// * `init` - how the weights and biases are drawn
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new<R : Rng + ?Sized>(inputs : usize, outputs : usize, activation_str : &str, init : Init, rng : &mut R) -> Dense {
+		assert!(inputs > 0 && outputs > 0,"a dense layer needs at least one input and one neuron");
+		Dense {
+			weights : Param::new(init.weights(outputs, inputs, rng)),
+			biases : Param::new(init.biases(outputs, rng)),
+			activation : Activation::new(activation_str),
+			input : Tensor::new(&[0]),
+		}
+	}
+
+	pub fn weights(&self) -> &Matrix<f64> {
+		&self.weights.value
+	}
+
+	pub fn biases(&self) -> &Matrix<f64> {
+		&self.biases.value
+	}
+}
+
+impl Block for Dense {
+
+	fn name(&self) -> String {
+		format!("dense {} {}",self.weights.value.rows,self.activation.name())
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		assert!(shape_len(input_shape)==self.weights.value.cols,"the dense layer takes {} values, got {input_shape:?}",self.weights.value.cols);
+		vec![self.weights.value.rows]
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		let shape = self.output_shape(&input.shape);
+		self.input = input.clone();
+		let mut pre_activation = Matrix::new(shape[0], 1);
+		self.weights.value.dot_vec(&mut pre_activation, &input.values);
+		pre_activation.add_mut(&self.biases.value);
+		self.activation.forward(&Tensor::from_vec(&shape, pre_activation.values))
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		let delta = self.activation.backward(grad_output);
+		self.weights.grad.matrix_weight_compute(&self.input.values, &delta.values);
+		self.biases.grad.values.iter_mut().zip(&delta.values).for_each(|(g,d)| *g += d);
+
+		let mut grad_input = Matrix::new(self.input.len(), 1);
+		self.weights.value.trans_dot(&mut grad_input, &Matrix::from_vec(delta.len(), 1, delta.values));
+		Tensor::from_vec(&self.input.shape, grad_input.values)
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		vec![&self.weights,&self.biases]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![&mut self.weights,&mut self.biases]
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                              Sequential model                              */
+/* -------------------------------------------------------------------------- */
+
+
+/// Blocks applied one after the other, followed by a fully connected `NeuralNetWork` head
+/// fed with the flattened output of the last block
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand_chacha::ChaCha8Rng;
+/// use rust_simple_nn::block::*;
+/// use rust_simple_nn::conv::*;
+/// use rust_simple_nn::init::Init;
+/// use rust_simple_nn::tensor::Tensor;
+///
+/// let mut rng = ChaCha8Rng::seed_from_u64(0);
+/// let mut model = Sequential::new(&[1,6,6], "cross_entropy", ChaCha8Rng::seed_from_u64(1));
+/// model.add(Conv2D::new(1, 4, (3,3), Init::He, &mut rng).padding(1));
+/// model.add(Activation::new("relu"));
+/// model.add(MaxPool2D::new(2));
+/// assert_eq!(model.output_shape(), vec![4,3,3]);
+/// model.dense(2, "sigmoid");
+/// assert_eq!(model.predict(&Tensor::new(&[1,6,6])).len(), 2);
+/// ```
+#[derive(Debug,Clone)]
+pub struct Sequential {
+	input_shape : Vec<usize>,
+	blocks : Vec<Box<dyn Block>>,
+	head : Option<NeuralNetWork>,
+	cost_name : String,
+	rng : ChaCha8Rng,
+}
+
+impl Sequential {
+
+	/// # Arguments
+	/// * `input_shape` - shape of the input tensors
+	/// * `cost_str` - name of the cost function of the head
+	/// * `rng` - the random source of the head, seed it for reproducible results
+	pub fn new(input_shape : &[usize], cost_str : &str, rng : ChaCha8Rng) -> Sequential {
+		Sequential { input_shape : input_shape.to_vec(), blocks : vec![], head : None, cost_name : cost_str.to_string(), rng }
+	}
+
+	/// Add a block after the others, the blocks come before the dense layers
+	pub fn add(&mut self, block : impl Block + 'static){
+		assert!(self.head.is_none(),"blocks can't be added after the dense layers");
+		// panics early if the block doesn't accept the current output
+		block.output_shape(&self.output_shape());
+		self.blocks.push(Box::new(block));
+	}
+
+	/// Shape of the output of the last block
+	pub fn output_shape(&self) -> Vec<usize> {
+		self.blocks.iter().fold(self.input_shape.clone(), |shape,block| block.output_shape(&shape))
+	}
+
+	pub fn input_shape(&self) -> &[usize] {
+		&self.input_shape
+	}
+
+	pub fn blocks(&self) -> &[Box<dyn Block>] {
+		&self.blocks
+	}
+
+	/// The dense head, created without layers the first time, its input is the flattened output of the last block
+	pub fn head_mut(&mut self) -> &mut NeuralNetWork {
+		let features = shape_len(&self.output_shape());
+		let (cost_name,rng) = (&self.cost_name,&self.rng);
+		self.head.get_or_insert_with(|| NeuralNetWork::empty(features, cost_name, rng.clone()))
+	}
+
+	/// Add a fully connected layer to the head, see `NeuralNetWork::add_layer` for the other options
+	pub fn dense(&mut self, nb_neurons : usize, activation_str : &str){
+		self.head_mut().add(nb_neurons, activation_str);
+	}
+
+	fn head(&mut self) -> &mut NeuralNetWork {
+		match &mut self.head {
+			Some(head) if !head.layers.is_empty() => head,
+			_ => panic!("the model needs at least one dense layer"),
+		}
+	}
+
+	pub fn nb_parameters(&self) -> usize {
+		self.blocks.iter().map(|block| block.nb_parameters()).sum::<usize>() + self.head.as_ref().map_or(0, |head| head.nb_parameters())
+	}
+
+	/// Output of the last block, flattened
+	pub fn features(&mut self, input : &Tensor) -> Vec<f64> {
+		assert!(input.shape==self.input_shape,"the model expects inputs of shape {:?}, got {:?}",self.input_shape,input.shape);
+		let mut output = input.clone();
+		for block in &mut self.blocks {
+			output = block.forward(&output);
+		}
+		output.values
+	}
+
+	/// Output of the network, in the scale of the targets
+	pub fn predict(&mut self, input : &Tensor) -> Vec<f64> {
+		let features = self.features(input);
+		self.head().predict(&features)
+	}
+
+	pub fn batch_cost(&mut self, data : &[TensorSample]) -> f64 {
+		let features : Vec<Vec<f64>> = data.iter().map(|(input,_)| self.features(input)).collect();
+		self.head().cost_of(features.iter().zip(data).map(|(features,(_,output))| (features.as_slice(),output.as_slice())))
+	}
+
+	/// Add the gradient of one sample to every parameter
+	fn backprop(&mut self, input : &Tensor, output : &[f64]){
+		let features = self.features(input);
+		let gradient = self.head().backprop_input(&features, output);
+		let mut gradient = Tensor::from_vec(&self.output_shape(), gradient);
+		for block in self.blocks.iter_mut().rev() {
+			gradient = block.backward(&gradient);
+		}
+	}
+
+	/// Train the blocks and the head like `NeuralNetWork::train_with`, returns the cost at the end of each epoch.
+	/// The mini batches follow the order of `data`, shuffle it beforehand if it's sorted.
+	pub fn train_with(&mut self, data : &[TensorSample], options : &TrainOptions) -> Vec<f64> {
+		self.try_train_with(data, options).unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	/// `train_with` returning the error that stopped the training when `options.non_finite` is `NonFinite::Abort`
+	pub fn try_train_with(&mut self, data : &[TensorSample], options : &TrainOptions) -> Result<Vec<f64>,NonFiniteError> {
+		train_epochs(self, data, options)
+	}
+}
+
+impl MiniBatchModel for Sequential {
+	type Sample = TensorSample;
+
+	fn zero_grad(&mut self){
+		self.head().zero_grad();
+		for block in &mut self.blocks {
+			block.zero_grad();
+		}
+	}
+
+	fn backprop(&mut self, (input,output) : &TensorSample){
+		Sequential::backprop(self, input, output);
+	}
+
+	fn gradients_mut(&mut self) -> Vec<Vec<&mut [f64]>> {
+		let blocks = self.blocks.iter_mut().map(|block| block.params_mut().into_iter().map(|param| param.grad.values.as_mut_slice()).collect());
+		let layers = self.head.iter_mut().flat_map(|head| head.layers.iter_mut().map(|layer| layer.get_mut().gradients_mut().collect()));
+		blocks.chain(layers).collect()
+	}
+
+	fn update(&mut self, mean_value : f64, learning_rate : f64, optimizer : &Optimizer){
+		self.head().update_parameters(mean_value, learning_rate, optimizer);
+		for block in &mut self.blocks {
+			block.update(mean_value, learning_rate, optimizer);
+		}
+	}
+
+	fn batch_cost(&mut self, data : &[TensorSample]) -> f64 {
+		Sequential::batch_cost(self, data)
+	}
+}
+
+impl fmt::Display for Sequential {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		let mut shape = self.input_shape.clone();
+		writeln!(f, "input {shape:?}")?;
+		for block in &self.blocks {
+			shape = block.output_shape(&shape);
+			writeln!(f, "{:<24}{:>16}{:>10}",block.name(),format!("{shape:?}"),block.nb_parameters())?;
+		}
+		if let Some(head) = &self.head {
+			for layer in &head.layers {
+				let layer = layer.borrow();
+				writeln!(f, "{:<24}{:>16}{:>10}",format!("dense {}",layer.activation_name()),format!("[{}]",layer.len()),layer.weights().values.len()+layer.biases().values.len())?;
+			}
+		}
+		write!(f, "total parameters: {}",self.nb_parameters())
+	}
+}
+
+
+/// A model made of blocks trained by mini batches, `Sequential` and `Graph` share their training loop `train_epochs`
+pub(crate) trait MiniBatchModel : Clone {
+	type Sample;
+
+	/// Reset the gradients before a mini batch
+	fn zero_grad(&mut self);
+
+	/// Add the gradient of one sample to every parameter
+	fn backprop(&mut self, sample : &Self::Sample);
+
+	/// The gradients grouped by block or dense layer, for the clipping and the non-finite checks
+	fn gradients_mut(&mut self) -> Vec<Vec<&mut [f64]>>;
+
+	/// Apply the gradients summed over `mean_value` samples
+	fn update(&mut self, mean_value : f64, learning_rate : f64, optimizer : &Optimizer);
+
+	fn batch_cost(&mut self, data : &[Self::Sample]) -> f64;
+}
+
+/// Train `model` like `NeuralNetWork::train_with`, returns the cost at the end of each epoch.
+/// The mini batches follow the order of `data`, which isn't shuffled between epochs.
+pub(crate) fn train_epochs<M : MiniBatchModel>(model : &mut M, data : &[M::Sample], options : &TrainOptions) -> Result<Vec<f64>,NonFiniteError> {
+	let TrainOptions { mini_batch_size, epochs, learning_rate, optimizer, schedule, verbose, clip, non_finite } = *options;
+
+	let mut last_good = model.clone();
+	let mut lr_calculated = learning_rate;
+	let mut cost_array : Vec<f64> = vec![];
+	for epoch in 0..epochs {
+		let chunks_size = data.chunks(mini_batch_size).len();
+		for (i,batch) in data.chunks(mini_batch_size).enumerate() {
+			model.zero_grad();
+			for sample in batch {
+				model.backprop(sample);
+			}
+
+			let mean_value = batch.len() as f64;
+			let mut gradients = model.gradients_mut();
+			let bad_block = gradients.iter().position(|block| block.iter().any(|grad| grad.iter().any(|g| !g.is_finite())));
+			match bad_block {
+				Some(block) if non_finite != NonFinite::Ignore => {
+					let error = NonFiniteError { value : NonFiniteValue::Gradient, layer : Some(block), epoch, batch : Some(i) };
+					non_finite.handle(error, verbose, || *model = last_good.clone())?;
+				},
+				_ => {
+					clip.apply(&mut gradients, mean_value);
+					model.update(mean_value, lr_calculated, &optimizer);
+				},
+			}
+		}
+
+		let cost = model.batch_cost(&data[0..mini_batch_size.min(data.len())]);
+		if non_finite != NonFinite::Ignore {
+			match cost.is_finite() {
+				true => last_good = model.clone(),
+				false => non_finite.handle(NonFiniteError { value : NonFiniteValue::Cost, layer : None, epoch, batch : None }, verbose, || *model = last_good.clone())?,
+			}
+		}
+		cost_array.push(cost);
+		if verbose {
+			display_progress(chunks_size as i32, chunks_size, cost, epoch, epochs);
+		}
+		lr_calculated = schedule.next(learning_rate, lr_calculated, epoch, &cost_array);
+	}
+
+	Ok(cost_array)
+}
+
+
+/// Options of the training tests : small batches, adam and a constant learning rate of 0.01
+#[cfg(test)]
+pub(crate) fn adam_options(mini_batch_size : usize, epochs : usize) -> TrainOptions {
+	TrainOptions { mini_batch_size, epochs, learning_rate : 0.01, optimizer : Optimizer::adam(), schedule : LearningRateSchedule::Constant, ..TrainOptions::default() }
+}
+
+
+/// Compare the gradients of `backward` with central finite differences of the cost
+/// `sum(output * coefficients)`, shared by the tests of the blocks
+#[cfg(test)]
+pub(crate) fn check_gradients(block : &mut dyn Block, input : &Tensor){
+	const EPSILON : f64 = 1e-5;
+
+	let coefficients : Vec<f64> = (0..shape_len(&block.output_shape(&input.shape))).map(|i| ((i+1) as f64).sin()).collect();
+	let cost = |block : &mut dyn Block, input : &Tensor| -> f64 {
+		block.forward(input).values.iter().zip(&coefficients).map(|(x,c)| x*c).sum()
+	};
+	let close = |analytic : f64, numeric : f64, what : &str| {
+		assert!((analytic-numeric).abs() <= 1e-6 + 1e-4*numeric.abs(), "{what}: backward {analytic}, finite differences {numeric}");
+	};
+
+	block.zero_grad();
+	let output = block.forward(input);
+	let grad_input = block.backward(&Tensor::from_vec(&output.shape, coefficients.clone()));
+	assert!(grad_input.shape==input.shape);
+
+	for i in 0..input.len() {
+		let mut shifted = input.clone();
+		shifted.values[i] += EPSILON;
+		let plus = cost(block, &shifted);
+		shifted.values[i] -= 2.0*EPSILON;
+		let minus = cost(block, &shifted);
+		close(grad_input.values[i], (plus-minus)/(2.0*EPSILON), &format!("input {i}"));
+	}
+
+	let grads : Vec<Vec<f64>> = block.params().iter().map(|param| param.grad.values.clone()).collect();
+	for (p,grad) in grads.iter().enumerate() {
+		for (i,analytic) in grad.iter().enumerate() {
+			block.params_mut()[p].value.values[i] += EPSILON;
+			let plus = cost(block, input);
+			block.params_mut()[p].value.values[i] -= 2.0*EPSILON;
+			let minus = cost(block, input);
+			block.params_mut()[p].value.values[i] += EPSILON;
+			close(*analytic, (plus-minus)/(2.0*EPSILON), &format!("param {p} value {i}"));
+		}
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+
+	#[test]
+	fn activation_and_dense_gradients(){
+		let input = Tensor::from_vec(&[2,3], vec![-1.0,0.5,2.0,-0.3,0.1,1.5]);
+		check_gradients(&mut Activation::new("tanh"), &input);
+		check_gradients(&mut Activation::new("sigmoid"), &input);
+		check_gradients(&mut Dense::new(6, 4, "tanh", Init::Xavier, &mut ChaCha8Rng::seed_from_u64(1)), &input);
+	}
+
+	#[test]
+	fn sequential_gradient_reaches_blocks(){
+		let mut model = Sequential::new(&[2,2], "quadratic", ChaCha8Rng::seed_from_u64(0));
+		model.add(Activation::new("tanh"));
+		model.dense(1, "identity");
+
+		// without parameters in the blocks the head trains like a network on tanh(x)
+		let data : Vec<TensorSample> = (0..20).map(|i| {
+			let x = vec![(i%5) as f64 / 5.0, (i/5) as f64 / 4.0, 0.3, -0.2];
+			let y = vec![x[0].tanh() - x[1].tanh()];
+			(Tensor::from_vec(&[2,2], x),y)
+		}).collect();
+		let options = TrainOptions { mini_batch_size : 5, epochs : 300, schedule : LearningRateSchedule::Constant, ..TrainOptions::default() };
+		let costs = model.train_with(&data, &options);
+		assert!(*costs.last().unwrap() < 1e-3);
+		assert!(model.to_string().contains("total parameters: 5"));
+	}
+
+	#[test]
+	#[should_panic]
+	fn dense_layers_come_last(){
+		let mut model = Sequential::new(&[4], "quadratic", ChaCha8Rng::seed_from_u64(0));
+		model.dense(2, "relu");
+		model.add(Activation::new("relu"));
+	}
+
+	#[test]
+	fn sequential_clip_and_non_finite(){
+		let mut model = Sequential::new(&[2], "quadratic", ChaCha8Rng::seed_from_u64(0));
+		model.add(Dense::new(2, 2, "tanh", Init::Xavier, &mut ChaCha8Rng::seed_from_u64(1)));
+		model.dense(1, "identity");
+		let parameters = |model : &Sequential| -> Vec<f64> {
+			let blocks = model.blocks.iter().flat_map(|block| block.params().into_iter().flat_map(|param| param.value.values.clone()));
+			blocks.chain(model.head.as_ref().unwrap().parameters()).collect()
+		};
+		let data = vec![(Tensor::from_vec(&[2], vec![0.5,1.0]),vec![3.0]),(Tensor::from_vec(&[2], vec![f64::NAN,1.0]),vec![1.0])];
+		let options = TrainOptions { mini_batch_size : 1, epochs : 1, learning_rate : 1.0, schedule : LearningRateSchedule::Constant, ..TrainOptions::default() };
+
+		// the blocks and the head move by at most the clipped value
+		let mut clipped = model.clone();
+		clipped.train_with(&data[0..1], &TrainOptions { clip : Clip::Value(1e-3), ..options });
+		assert!(parameters(&clipped).iter().zip(parameters(&model)).all(|(after,before)| (after-before).abs() <= 1e-3 + 1e-12));
+
+		let error = model.clone().try_train_with(&data, &TrainOptions { non_finite : NonFinite::Abort, ..options }).unwrap_err();
+		assert!(error == NonFiniteError { value : NonFiniteValue::Gradient, layer : Some(0), epoch : 0, batch : Some(1) });
+
+		let mut skipped = model.clone();
+		skipped.train_with(&data, &TrainOptions { non_finite : NonFinite::SkipBatch, ..options });
+		let mut first_only = model.clone();
+		first_only.train_with(&data[0..1], &options);
+		assert!(parameters(&skipped) == parameters(&first_only));
+	}
+}
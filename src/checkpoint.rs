@@ -0,0 +1,400 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::dataset::Loader;
+use crate::model::*;
+use crate::nn::*;
+use crate::optimizer::ParamState;
+
+/// Bumped when the layout of the checkpoint file changes
+const CHECKPOINT_FORMAT : u32 = 1;
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    State                                   */
+/* -------------------------------------------------------------------------- */
+
+
+/// Position of a `ChaCha8Rng` in its stream, the rng rebuilt from it gives the same numbers
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+pub struct RngState {
+	seed : [u8;32],
+	stream : u64,
+	word_pos : u128,
+}
+
+impl RngState {
+
+	pub fn of(rng : &ChaCha8Rng) -> RngState {
+		RngState { seed : rng.get_seed(), stream : rng.get_stream(), word_pos : rng.get_word_pos() }
+	}
+
+	pub fn rng(&self) -> ChaCha8Rng {
+		let mut rng = ChaCha8Rng::from_seed(self.seed);
+		rng.set_stream(self.stream);
+		rng.set_word_pos(self.word_pos);
+		rng
+	}
+}
+
+/// What a `Loader` needs to replay an epoch
+#[derive(Debug,Clone,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct LoaderState {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub rng : Option<RngState>,
+	/// order of the samples before the epoch, the shuffle starts from it
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub order : Vec<usize>,
+}
+
+/// Parameters of the layers with the running values of the optimizer
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct LayersState {
+	pub layers : Vec<LayerFile>,
+	/// weights, biases, norm gain, norm shift of each layer
+	pub optimizer : Vec<Vec<ParamState>>,
+}
+
+impl LayersState {
+
+	fn of(layers : &[RefCell<Layer>]) -> LayersState {
+		LayersState {
+			layers : layer_files(layers),
+			optimizer : layers.iter().map(|layer| layer.borrow().optimizer_state.clone()).collect(),
+		}
+	}
+}
+
+/// Everything needed to continue a `train_loader` run as if it was never interrupted
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Checkpoint {
+	pub format : u32,
+	/// the architecture and the current parameters
+	pub model : ModelFile,
+	pub optimizer : Vec<Vec<ParamState>>,
+	/// the parameters restored by `NonFinite::Rollback`, when they aren't the current ones
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub last_good : Option<LayersState>,
+	/// random source of the dropout
+	pub rng : RngState,
+	pub loader : LoaderState,
+	/// the epoch being trained and the number of its batches already trained
+	pub epoch : usize,
+	pub batch : usize,
+	pub learning_rate : f64,
+	/// cost at the end of each finished epoch
+	pub costs : Vec<f64>,
+}
+
+impl Checkpoint {
+
+	pub(crate) fn capture(nn : &NeuralNetWork, progress : &TrainProgress) -> Checkpoint {
+		let model = nn.to_model_file();
+		let optimizer : Vec<Vec<ParamState>> = nn.layers.iter().map(|layer| layer.borrow().optimizer_state.clone()).collect();
+		let last_good = LayersState::of(&progress.last_good);
+		let same = last_good.layers == model.layers && last_good.optimizer == optimizer;
+
+		Checkpoint {
+			format : CHECKPOINT_FORMAT,
+			model,
+			optimizer,
+			last_good : (!same).then_some(last_good),
+			rng : RngState::of(nn.rng()),
+			loader : progress.loader.clone(),
+			epoch : progress.epoch,
+			batch : progress.batch,
+			learning_rate : progress.learning_rate,
+			costs : progress.costs.clone(),
+		}
+	}
+
+	/// The network with its optimizer state and dropout random source
+	pub fn network(&self) -> io::Result<NeuralNetWork> {
+		if self.format != CHECKPOINT_FORMAT {
+			return Err(invalid_data(format!("unsupported checkpoint format {} (expected {CHECKPOINT_FORMAT})",self.format)));
+		}
+		let mut nn = NeuralNetWork::from_model_file(&self.model)?;
+		set_optimizer_state(&nn.layers, &self.optimizer)?;
+		nn.set_rng(self.rng.rng());
+		Ok(nn)
+	}
+
+	fn progress(&self, nn : &NeuralNetWork) -> io::Result<TrainProgress> {
+		let last_good = match &self.last_good {
+			Some(state) => {
+				let model = ModelFile { layers : state.layers.clone(), ..self.model.clone() };
+				let layers = NeuralNetWork::from_model_file(&model)?.layers;
+				set_optimizer_state(&layers, &state.optimizer)?;
+				layers
+			},
+			None => nn.layers.clone(),
+		};
+		Ok(TrainProgress {
+			epoch : self.epoch,
+			batch : self.batch,
+			learning_rate : self.learning_rate,
+			costs : self.costs.clone(),
+			loader : self.loader.clone(),
+			last_good,
+		})
+	}
+
+	pub fn save(&self, path : impl AsRef<Path>) -> io::Result<()> {
+		fs::write(path, serde_json::to_string(self).expect("a checkpoint is always representable in JSON"))
+	}
+
+	pub fn load(path : impl AsRef<Path>) -> io::Result<Checkpoint> {
+		serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| invalid_data(e.to_string()))
+	}
+}
+
+fn set_optimizer_state(layers : &[RefCell<Layer>], state : &[Vec<ParamState>]) -> io::Result<()> {
+	if state.len() != layers.len() {
+		return Err(invalid_data(format!("the optimizer state should have {} layers",layers.len())));
+	}
+	for (layer,state) in layers.iter().zip(state) {
+		layer.borrow_mut().optimizer_state = state.clone();
+	}
+	Ok(())
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                   Policy                                   */
+/* -------------------------------------------------------------------------- */
+
+
+/// When and where checkpoints are written
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use rust_simple_nn::checkpoint::Checkpoints;
+///
+/// // at the end of every 5th epoch and every 15 minutes, only the 3 newest files are kept
+/// let checkpoints = Checkpoints::new("checkpoints").every_epochs(5).every(Duration::from_secs(15*60)).keep_last(3);
+/// ```
+#[derive(Debug,Clone,PartialEq)]
+pub struct Checkpoints {
+	pub dir : PathBuf,
+	/// write at the end of every n-th epoch
+	pub every_epochs : Option<usize>,
+	/// write after a batch once this time passed since the last checkpoint
+	pub every : Option<Duration>,
+	/// delete the older files, `None` keeps them all
+	pub keep_last : Option<usize>,
+}
+
+impl Checkpoints {
+
+	/// A checkpoint at the end of every epoch, all of them kept
+	pub fn new(dir : impl Into<PathBuf>) -> Checkpoints {
+		Checkpoints { dir : dir.into(), every_epochs : Some(1), every : None, keep_last : None }
+	}
+
+	pub fn every_epochs(mut self, epochs : usize) -> Checkpoints {
+		assert!(epochs > 0, "checkpoints should be at least one epoch apart");
+		self.every_epochs = Some(epochs);
+		self
+	}
+
+	/// Also write during an epoch when `interval` passed since the last checkpoint
+	pub fn every(mut self, interval : Duration) -> Checkpoints {
+		self.every = Some(interval);
+		self
+	}
+
+	pub fn keep_last(mut self, keep : usize) -> Checkpoints {
+		assert!(keep > 0, "at least one checkpoint should be kept");
+		self.keep_last = Some(keep);
+		self
+	}
+
+	/// The checkpoint files of the directory, the oldest first
+	pub fn files(&self) -> io::Result<Vec<PathBuf>> {
+		if !self.dir.exists() {
+			return Ok(vec![]);
+		}
+		let mut files = vec![];
+		for entry in fs::read_dir(&self.dir)? {
+			let path = entry?.path();
+			let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+			if name.starts_with("checkpoint-") && name.ends_with(".json") {
+				files.push(path);
+			}
+		}
+		files.sort();
+		Ok(files)
+	}
+
+	/// The newest checkpoint of the directory
+	pub fn latest(&self) -> io::Result<Option<Checkpoint>> {
+		self.files()?.last().map(Checkpoint::load).transpose()
+	}
+
+	/// Write the checkpoint then delete the files beyond `keep_last`.
+	/// The file is renamed once complete, an interrupted write leaves the previous checkpoints intact.
+	pub fn write(&self, checkpoint : &Checkpoint) -> io::Result<PathBuf> {
+		fs::create_dir_all(&self.dir)?;
+		let path = self.dir.join(format!("checkpoint-{:06}-{:08}.json",checkpoint.epoch,checkpoint.batch));
+		let partial = path.with_extension("json.partial");
+		checkpoint.save(&partial)?;
+		fs::rename(&partial, &path)?;
+
+		if let Some(keep) = self.keep_last {
+			let files = self.files()?;
+			for old in &files[..files.len().saturating_sub(keep)] {
+				fs::remove_file(old)?;
+			}
+		}
+		Ok(path)
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                  Training                                  */
+/* -------------------------------------------------------------------------- */
+
+
+impl NeuralNetWork {
+
+	/// `train_loader` writing checkpoints, stops at the first checkpoint that can't be written
+	pub fn train_checkpointed(&mut self, loader : &mut impl Loader, options : &TrainOptions, checkpoints : &Checkpoints) -> io::Result<Vec<f64>> {
+		let progress = TrainProgress {
+			epoch : 0,
+			batch : 0,
+			learning_rate : options.learning_rate,
+			costs : vec![],
+			loader : loader.state(),
+			last_good : self.layers.clone(),
+		};
+		self.train_with_checkpoints(loader, options, progress, Some(checkpoints))
+	}
+
+	/// Continue the run saved in `checkpoint` : the network is replaced by the one of the checkpoint and the loader
+	/// should read the same data as the interrupted run. With the same options, the parameters and the returned costs
+	/// (of every epoch, including the ones before the checkpoint) are the ones of an uninterrupted run.
+	pub fn resume_from(&mut self, checkpoint : &Checkpoint, loader : &mut impl Loader, options : &TrainOptions, checkpoints : Option<&Checkpoints>) -> io::Result<Vec<f64>> {
+		*self = checkpoint.network()?;
+		let progress = checkpoint.progress(self)?;
+		loader.set_state(&checkpoint.loader);
+		self.train_with_checkpoints(loader, options, progress, checkpoints)
+	}
+
+	fn train_with_checkpoints(&mut self, loader : &mut impl Loader, options : &TrainOptions, progress : TrainProgress, checkpoints : Option<&Checkpoints>) -> io::Result<Vec<f64>> {
+		let Some(checkpoints) = checkpoints else {
+			return self.train_loader_from(loader, options, progress, &mut |_,_| Ok(()));
+		};
+
+		let mut last_write = Instant::now();
+		self.train_loader_from(loader, options, progress, &mut |nn,progress| {
+			let epoch_end = progress.batch == 0;
+			let due = (epoch_end && checkpoints.every_epochs.is_some_and(|every| progress.epoch % every == 0))
+				|| checkpoints.every.is_some_and(|every| last_write.elapsed() >= every);
+			if due {
+				let path = checkpoints.write(&Checkpoint::capture(nn, progress))?;
+				if options.verbose {
+					println!("checkpoint written to {}",path.display());
+				}
+				last_write = Instant::now();
+			}
+			Ok(())
+		})
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::dataset::*;
+	use crate::init::Init;
+	use crate::optimizer::*;
+
+	fn network() -> NeuralNetWork {
+		let mut nn = NeuralNetWork::empty(2, "cross_entropy", ChaCha8Rng::seed_from_u64(3));
+		nn.add_layer(6, "tanh", Init::Xavier, 0.2, true);
+		nn.add_layer(1, "sigmoid", Init::Xavier, 0.0, false);
+		nn
+	}
+
+	fn loader() -> DataLoader<Vec<(Vec<f64>,Vec<f64>)>> {
+		let data = (0..60).map(|i| {
+			let x = vec![(i%8) as f64 / 8.0, (i/8) as f64 / 8.0];
+			let y = (x[0] > x[1]) as usize as f64;
+			(x,vec![y])
+		}).collect();
+		DataLoader::new(data, 8).shuffle(5)
+	}
+
+	fn temp_dir(name : &str) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("rust_simple_nn_checkpoint_{name}_{}",std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		dir
+	}
+
+	#[test]
+	fn rng_state_round_trip(){
+		let mut rng = ChaCha8Rng::seed_from_u64(9);
+		let _ : [u64;3] = rand::Rng::gen(&mut rng);
+		let state : RngState = serde_json::from_str(&serde_json::to_string(&RngState::of(&rng)).unwrap()).unwrap();
+		assert!(rand::Rng::gen::<u64>(&mut state.rng()) == rand::Rng::gen::<u64>(&mut rng));
+	}
+
+	#[test]
+	fn resume_is_bit_identical(){
+		let options = TrainOptions {
+			epochs : 6,
+			learning_rate : 0.05,
+			optimizer : Optimizer::adam(),
+			schedule : LearningRateSchedule::Step { step_size : 2, gamma : 0.5 },
+			non_finite : NonFinite::Rollback,
+			..TrainOptions::default()
+		};
+		let mut uninterrupted = network();
+		let costs = uninterrupted.train_loader(&mut loader(), &options).unwrap();
+
+		// a checkpoint after every batch
+		let dir = temp_dir("resume");
+		let checkpoints = Checkpoints::new(&dir).every(Duration::ZERO);
+		assert!(network().train_checkpointed(&mut loader(), &options, &checkpoints).unwrap() == costs);
+
+		for file in ["checkpoint-000002-00000003.json","checkpoint-000004-00000000.json"] {
+			let checkpoint = Checkpoint::load(dir.join(file)).unwrap();
+			let mut nn = network();
+			assert!(nn.resume_from(&checkpoint, &mut loader(), &options, None).unwrap() == costs);
+			assert!(nn.parameters() == uninterrupted.parameters());
+			assert!(nn.predict(&[0.2,0.7]) == uninterrupted.predict(&[0.2,0.7]));
+		}
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn keep_last_rotation(){
+		let dir = temp_dir("rotation");
+		let checkpoints = Checkpoints::new(&dir).every_epochs(2).keep_last(2);
+		let options = TrainOptions { epochs : 7, ..TrainOptions::default() };
+		let costs = network().train_checkpointed(&mut loader(), &options, &checkpoints).unwrap();
+
+		let names : Vec<String> = checkpoints.files().unwrap().iter().map(|path| path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+		assert!(names == ["checkpoint-000004-00000000.json","checkpoint-000006-00000000.json"]);
+
+		let latest = checkpoints.latest().unwrap().unwrap();
+		assert!(latest.epoch == 6 && latest.costs == costs[..6]);
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
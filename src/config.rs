@@ -0,0 +1,734 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::csv::{Column, CsvLoader, MissingValues};
+use crate::init::*;
+use crate::nn::*;
+use crate::optimizer::*;
+use crate::preprocessing::ScalerKind;
+
+
+/// Error raised while reading or validating a network configuration
+#[derive(Debug,Clone,PartialEq)]
+pub enum ConfigError {
+	/// the file couldn't be read
+	Io(String),
+	/// the file isn't valid TOML/JSON or doesn't match the expected layout
+	Parse(String),
+	/// a value is invalid, `key` is the path of the offending key (ex: `layers[1].dropout`)
+	Invalid { key : String, message : String },
+	/// the training of a valid configuration stopped, ex : on a non-finite cost with the `abort` policy
+	Training(String),
+}
+
+impl ConfigError {
+	pub(crate) fn invalid(key : impl Into<String>, message : impl Into<String>) -> ConfigError {
+		ConfigError::Invalid { key : key.into(), message : message.into() }
+	}
+}
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ConfigError::Io(message) => write!(f, "cannot read config: {message}"),
+			ConfigError::Parse(message) => write!(f, "cannot parse config: {message}"),
+			ConfigError::Invalid { key, message } => write!(f, "invalid value for `{key}`: {message}"),
+			ConfigError::Training(message) => write!(f, "training failed: {message}"),
+		}
+	}
+}
+
+impl std::error::Error for ConfigError {}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                 Config file                                */
+/* -------------------------------------------------------------------------- */
+
+
+/// Declarative description of a network and of its training, read from a TOML or JSON file
+///
+/// ```toml
+/// input = 2
+/// loss = "cross_entropy"
+/// epochs = 20
+/// batch_size = 50
+/// learning_rate = 0.01
+/// seed = 42
+/// input_scaler = "standard"
+/// non_finite = "rollback"
+///
+/// [[layers]]
+/// size = 8
+/// activation = "relu"
+/// init = "he"
+/// dropout = 0.1
+/// norm = "layer"
+///
+/// [[layers]]
+/// size = 1
+/// activation = "sigmoid"
+///
+/// [optimizer]
+/// type = "adam"
+///
+/// [schedule]
+/// type = "step"
+/// step_size = 5
+/// gamma = 0.5
+///
+/// [clip]
+/// type = "global_norm"
+/// max = 1.0
+///
+/// [data]
+/// type = "builtin"
+/// name = "quadrant"
+/// samples = 10000
+/// ```
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+	pub input : usize,
+	pub layers : Vec<LayerConfig>,
+	#[serde(default = "default_name")]
+	pub loss : String,
+	#[serde(default)]
+	pub optimizer : OptimizerConfig,
+	#[serde(default)]
+	pub schedule : ScheduleConfig,
+	#[serde(default = "default_batch_size")]
+	pub batch_size : usize,
+	#[serde(default = "default_epochs")]
+	pub epochs : usize,
+	#[serde(default = "default_learning_rate")]
+	pub learning_rate : f64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub clip : Option<ClipConfig>,
+	/// what training does with NaN or infinite values : ignore, abort, skip_batch or rollback
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub non_finite : Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub seed : Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub data : Option<DataSource>,
+	/// scaler fit on the training inputs : standard, min_max or robust
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub input_scaler : Option<String>,
+	/// scaler fit on the training targets, for regression
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub target_scaler : Option<String>,
+}
+
+/// One fully connected layer of a `NetworkConfig`
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayerConfig {
+	pub size : usize,
+	#[serde(default = "default_name")]
+	pub activation : String,
+	#[serde(default = "default_name")]
+	pub init : String,
+	#[serde(default)]
+	pub dropout : f64,
+	#[serde(default = "default_norm")]
+	pub norm : String,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OptimizerConfig {
+	#[serde(rename = "type")]
+	pub kind : String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub momentum : Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub decay : Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub beta1 : Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub beta2 : Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub epsilon : Option<f64>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+	#[serde(rename = "type")]
+	pub kind : String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub step_size : Option<usize>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub gamma : Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub min_lr : Option<f64>,
+}
+
+/// Gradient clipping, `type` is value, layer_norm or global_norm
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClipConfig {
+	#[serde(rename = "type")]
+	pub kind : String,
+	pub max : f64,
+}
+
+/// Where the training data comes from
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DataSource {
+	/// one of the synthetic datasets shipped with the crate
+	Builtin { name : String, #[serde(default, skip_serializing_if = "Option::is_none")] samples : Option<usize> },
+	/// a CSV file on disk, the columns are chosen like with `CsvLoader`
+	Csv {
+		path : String,
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		features : Vec<Column>,
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		targets : Vec<Column>,
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		categorical : Vec<Column>,
+		#[serde(default)]
+		missing : MissingValues,
+	},
+}
+
+impl DataSource {
+
+	/// The loader reading a `Csv` source, the last `nb_targets` columns are the targets when none is named
+	pub fn csv_loader(&self, nb_targets : usize) -> Option<CsvLoader> {
+		match self {
+			DataSource::Csv { features, targets, categorical, missing, .. } => Some(CsvLoader {
+				features : features.clone(),
+				targets : targets.clone(),
+				last_targets : nb_targets,
+				categorical : categorical.clone(),
+				missing : *missing,
+				..CsvLoader::default()
+			}),
+			DataSource::Builtin { .. } => None,
+		}
+	}
+}
+
+
+fn default_name() -> String { "default".to_string() }
+fn default_norm() -> String { "none".to_string() }
+fn default_batch_size() -> usize { TrainOptions::default().mini_batch_size }
+fn default_epochs() -> usize { TrainOptions::default().epochs }
+fn default_learning_rate() -> f64 { TrainOptions::default().learning_rate }
+
+impl Default for OptimizerConfig {
+	fn default() -> Self {
+		OptimizerConfig { kind : "sgd".to_string(), momentum : None, decay : None, beta1 : None, beta2 : None, epsilon : None }
+	}
+}
+
+impl Default for ScheduleConfig {
+	fn default() -> Self {
+		ScheduleConfig { kind : "halve_on_increase".to_string(), step_size : None, gamma : None, min_lr : None }
+	}
+}
+
+
+impl NetworkConfig {
+
+	pub fn from_toml_str(text : &str) -> Result<NetworkConfig,ConfigError> {
+		toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+	}
+
+	pub fn from_json_str(text : &str) -> Result<NetworkConfig,ConfigError> {
+		serde_json::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+	}
+
+	/// Read a config file, `.json` files are parsed as JSON and everything else as TOML
+	pub fn from_file(path : impl AsRef<Path>) -> Result<NetworkConfig,ConfigError> {
+		let path = path.as_ref();
+		let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(format!("{}: {e}",path.display())))?;
+
+		match path.extension().and_then(|ext| ext.to_str()) {
+			Some("json") => NetworkConfig::from_json_str(&text),
+			_ => NetworkConfig::from_toml_str(&text),
+		}
+	}
+
+	pub fn to_toml_string(&self) -> String {
+		toml::to_string(self).expect("a network config is always representable in TOML")
+	}
+
+	/// Returns a builder describing the network of this config
+	pub fn builder(&self) -> NetworkBuilder {
+		let mut builder = NetworkBuilder::new(self.input).loss(&self.loss);
+		for layer in &self.layers {
+			builder = builder.layer_config(layer.clone());
+		}
+		if let Some(seed) = self.seed {
+			builder = builder.seed(seed);
+		}
+		builder
+	}
+
+	/// Returns the training hyperparameters of this config
+	pub fn train_options(&self) -> Result<TrainOptions,ConfigError> {
+		if self.batch_size == 0 {
+			return Err(ConfigError::invalid("batch_size", "should be at least 1"));
+		}
+		if self.epochs == 0 {
+			return Err(ConfigError::invalid("epochs", "should be at least 1"));
+		}
+		if !(self.learning_rate > 0.0 && self.learning_rate.is_finite()) {
+			return Err(ConfigError::invalid("learning_rate", "should be a positive number"));
+		}
+
+		Ok(TrainOptions {
+			mini_batch_size : self.batch_size,
+			epochs : self.epochs,
+			learning_rate : self.learning_rate,
+			optimizer : self.optimizer.to_optimizer()?,
+			schedule : self.schedule.to_schedule(self.epochs)?,
+			verbose : false,
+			clip : self.clip.as_ref().map_or(Ok(Clip::None), ClipConfig::to_clip)?,
+			non_finite : match self.non_finite.as_deref().map(|name| name.trim().to_lowercase()).as_deref() {
+				None | Some("ignore") => NonFinite::Ignore,
+				Some("abort") => NonFinite::Abort,
+				Some("skip_batch") => NonFinite::SkipBatch,
+				Some("rollback") => NonFinite::Rollback,
+				Some(_) => return Err(ConfigError::invalid("non_finite", format!("unknown policy `{}` (expected ignore, abort, skip_batch or rollback)",self.non_finite.as_ref().unwrap()))),
+			},
+		})
+	}
+
+	/// The kinds of the input and target scalers, to fit with `NeuralNetWork::fit_scalers` once the data is loaded
+	pub fn scalers(&self) -> Result<(Option<ScalerKind>,Option<ScalerKind>),ConfigError> {
+		let kind = |key : &str, name : &Option<String>| match name {
+			Some(name) => ScalerKind::from_name(name).map(Some)
+				.ok_or_else(|| ConfigError::invalid(key, format!("unknown scaler `{name}` (expected standard, min_max or robust)"))),
+			None => Ok(None),
+		};
+		Ok((kind("input_scaler", &self.input_scaler)?,kind("target_scaler", &self.target_scaler)?))
+	}
+
+	/// Validate the whole config and create the network with its training hyperparameters
+	pub fn build(&self) -> Result<(NeuralNetWork,TrainOptions),ConfigError> {
+		let options = self.train_options()?;
+		self.scalers()?;
+		Ok((self.builder().build()?,options))
+	}
+}
+
+
+impl OptimizerConfig {
+
+	fn to_optimizer(&self) -> Result<Optimizer,ConfigError> {
+		let kind = self.kind.trim().to_lowercase();
+		let (mut optimizer,allowed) : (Optimizer,&[&str]) = match kind.as_str() {
+			"sgd" => (Optimizer::Sgd,&[]),
+			"momentum" => (Optimizer::momentum(),&["momentum"]),
+			"rmsprop" | "rms_prop" => (Optimizer::rms_prop(),&["decay","epsilon"]),
+			"adam" => (Optimizer::adam(),&["beta1","beta2","epsilon"]),
+			_ => return Err(ConfigError::invalid("optimizer.type", format!("unknown optimizer `{}` (expected sgd, momentum, rmsprop or adam)",self.kind))),
+		};
+
+		let params = [("momentum",self.momentum),("decay",self.decay),("beta1",self.beta1),("beta2",self.beta2),("epsilon",self.epsilon)];
+		for (name,value) in params {
+			let key = format!("optimizer.{name}");
+			match value {
+				Some(_) if !allowed.contains(&name) => return Err(ConfigError::invalid(key, format!("not used by the {kind} optimizer"))),
+				Some(x) if name == "epsilon" && x <= 0.0 => return Err(ConfigError::invalid(key, "should be positive")),
+				Some(x) if name != "epsilon" && !(0.0..1.0).contains(&x) => return Err(ConfigError::invalid(key, "should be in [0,1)")),
+				_ => {},
+			}
+		}
+
+		match &mut optimizer {
+			Optimizer::Sgd => {},
+			Optimizer::Momentum { momentum } => *momentum = self.momentum.unwrap_or(*momentum),
+			Optimizer::RmsProp { decay, epsilon } => {
+				*decay = self.decay.unwrap_or(*decay);
+				*epsilon = self.epsilon.unwrap_or(*epsilon);
+			},
+			Optimizer::Adam { beta1, beta2, epsilon } => {
+				*beta1 = self.beta1.unwrap_or(*beta1);
+				*beta2 = self.beta2.unwrap_or(*beta2);
+				*epsilon = self.epsilon.unwrap_or(*epsilon);
+			},
+		}
+
+		Ok(optimizer)
+	}
+}
+
+
+impl ScheduleConfig {
+
+	fn to_schedule(&self, epochs : usize) -> Result<LearningRateSchedule,ConfigError> {
+		let kind = self.kind.trim().to_lowercase();
+		let allowed : &[&str] = match kind.as_str() {
+			"constant" | "halve_on_increase" => &[],
+			"step" => &["step_size","gamma"],
+			"exponential" => &["gamma"],
+			"cosine" => &["min_lr"],
+			_ => return Err(ConfigError::invalid("schedule.type", format!("unknown schedule `{}` (expected constant, step, exponential, cosine or halve_on_increase)",self.kind))),
+		};
+
+		let present = [("step_size",self.step_size.is_some()),("gamma",self.gamma.is_some()),("min_lr",self.min_lr.is_some())];
+		for (name,is_present) in present {
+			if is_present && !allowed.contains(&name) {
+				return Err(ConfigError::invalid(format!("schedule.{name}"), format!("not used by the {kind} schedule")));
+			}
+		}
+
+		let gamma = self.gamma.unwrap_or(0.5);
+		if !(gamma > 0.0 && gamma <= 1.0) {
+			return Err(ConfigError::invalid("schedule.gamma", "should be in (0,1]"));
+		}
+		let min_lr = self.min_lr.unwrap_or(0.0);
+		if min_lr < 0.0 {
+			return Err(ConfigError::invalid("schedule.min_lr", "should be positive"));
+		}
+
+		Ok(match kind.as_str() {
+			"constant" => LearningRateSchedule::Constant,
+			"step" => match self.step_size {
+				Some(0) => return Err(ConfigError::invalid("schedule.step_size", "should be at least 1")),
+				Some(step_size) => LearningRateSchedule::Step { step_size, gamma },
+				None => return Err(ConfigError::invalid("schedule.step_size", "required by the step schedule")),
+			},
+			"exponential" => LearningRateSchedule::Exponential { gamma },
+			"cosine" => LearningRateSchedule::Cosine { epochs, min_lr },
+			_ => LearningRateSchedule::HalveOnIncrease,
+		})
+	}
+}
+
+
+impl ClipConfig {
+
+	fn to_clip(&self) -> Result<Clip,ConfigError> {
+		if !(self.max > 0.0 && self.max.is_finite()) {
+			return Err(ConfigError::invalid("clip.max", "should be a positive number"));
+		}
+		match self.kind.trim().to_lowercase().as_str() {
+			"value" => Ok(Clip::Value(self.max)),
+			"layer_norm" => Ok(Clip::LayerNorm(self.max)),
+			"global_norm" => Ok(Clip::GlobalNorm(self.max)),
+			_ => Err(ConfigError::invalid("clip.type", format!("unknown clipping `{}` (expected value, layer_norm or global_norm)",self.kind))),
+		}
+	}
+}
+
+
+impl LayerConfig {
+
+	/// Layer with the default initialisation, no dropout and no normalisation
+	pub fn new(size : usize, activation : &str) -> LayerConfig {
+		LayerConfig { size, activation : activation.to_string(), init : default_name(), dropout : 0.0, norm : default_norm() }
+	}
+
+	pub fn init(mut self, init : &str) -> LayerConfig {
+		self.init = init.to_string();
+		self
+	}
+
+	pub fn dropout(mut self, dropout : f64) -> LayerConfig {
+		self.dropout = dropout;
+		self
+	}
+
+	pub fn norm(mut self, norm : &str) -> LayerConfig {
+		self.norm = norm.to_string();
+		self
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                   Builder                                  */
+/* -------------------------------------------------------------------------- */
+
+
+/// Step by step construction of a `NeuralNetWork` with validation
+///
+/// ```
+/// use rust_simple_nn::config::*;
+///
+/// let nn = NetworkBuilder::new(2)
+///     .layer(4, "relu")
+///     .layer_config(LayerConfig::new(1, "sigmoid").init("xavier"))
+///     .loss("cross_entropy")
+///     .seed(7)
+///     .build()
+///     .unwrap();
+/// assert_eq!(nn.output_size(), 1);
+/// ```
+#[derive(Debug,Clone,PartialEq)]
+pub struct NetworkBuilder {
+	input : usize,
+	layers : Vec<LayerConfig>,
+	loss : String,
+	seed : Option<u64>,
+}
+
+impl NetworkBuilder {
+
+	pub fn new(input : usize) -> NetworkBuilder {
+		NetworkBuilder { input, layers : vec![], loss : default_name(), seed : None }
+	}
+
+	pub fn layer(self, size : usize, activation : &str) -> NetworkBuilder {
+		self.layer_config(LayerConfig::new(size, activation))
+	}
+
+	pub fn layer_config(mut self, layer : LayerConfig) -> NetworkBuilder {
+		self.layers.push(layer);
+		self
+	}
+
+	pub fn loss(mut self, loss : &str) -> NetworkBuilder {
+		self.loss = loss.to_string();
+		self
+	}
+
+	pub fn seed(mut self, seed : u64) -> NetworkBuilder {
+		self.seed = Some(seed);
+		self
+	}
+
+	/// Check every value, the error names the offending key as it would appear in a config file
+	pub fn validate(&self) -> Result<(),ConfigError> {
+		if self.input == 0 {
+			return Err(ConfigError::invalid("input", "should be at least 1"));
+		}
+		if self.layers.is_empty() {
+			return Err(ConfigError::invalid("layers", "the network needs at least an output layer"));
+		}
+		if get_cost(&self.loss).is_none() {
+			return Err(ConfigError::invalid("loss", format!("unknown loss `{}` (expected quadratic or cross_entropy)",self.loss)));
+		}
+
+		for (i,layer) in self.layers.iter().enumerate() {
+			let key = |name : &str| format!("layers[{i}].{name}");
+
+			if layer.size == 0 {
+				return Err(ConfigError::invalid(key("size"), "should be at least 1"));
+			}
+			if get_activation(&layer.activation).is_none() {
+				return Err(ConfigError::invalid(key("activation"), format!("unknown activation `{}` (expected relu, sigmoid, tanh or identity)",layer.activation)));
+			}
+			if Init::from_name(&layer.init).is_none() {
+				return Err(ConfigError::invalid(key("init"), format!("unknown initialisation `{}`",layer.init)));
+			}
+			if !(0.0..1.0).contains(&layer.dropout) {
+				return Err(ConfigError::invalid(key("dropout"), "should be in [0,1)"));
+			}
+			if layer.dropout != 0.0 && i == self.layers.len()-1 {
+				return Err(ConfigError::invalid(key("dropout"), "the output layer can't use dropout"));
+			}
+			match layer.norm.trim().to_lowercase().as_str() {
+				"none" => {},
+				"layer" if layer.size < 2 => return Err(ConfigError::invalid(key("norm"), "layer normalisation needs at least 2 neurons")),
+				"layer" => {},
+				_ => return Err(ConfigError::invalid(key("norm"), format!("unknown normalisation `{}` (expected none or layer)",layer.norm))),
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn build(&self) -> Result<NeuralNetWork,ConfigError> {
+		self.validate()?;
+
+		let rng = match self.seed {
+			Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+			None => ChaCha8Rng::from_entropy(),
+		};
+		let mut nn = NeuralNetWork::empty(self.input, &self.loss, rng);
+
+		for layer in &self.layers {
+			let init = Init::from_name(&layer.init).unwrap();
+			let layer_norm = layer.norm.trim().to_lowercase() == "layer";
+			nn.add_layer(layer.size, &layer.activation, init, layer.dropout, layer_norm);
+		}
+
+		Ok(nn)
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const EXAMPLE : &str = r#"
+input = 2
+loss = "quadratic"
+epochs = 3
+batch_size = 10
+learning_rate = 0.05
+seed = 42
+
+[[layers]]
+size = 6
+activation = "relu"
+init = "he"
+dropout = 0.1
+norm = "layer"
+
+[[layers]]
+size = 1
+activation = "sigmoid"
+init = "xavier"
+
+[optimizer]
+type = "adam"
+beta1 = 0.8
+
+[schedule]
+type = "step"
+step_size = 2
+
+[clip]
+type = "layer_norm"
+max = 5.0
+
+[data]
+type = "builtin"
+name = "quadrant"
+samples = 100
+"#;
+
+	fn invalid_key(result : Result<(NeuralNetWork,TrainOptions),ConfigError>) -> String {
+		match result {
+			Err(ConfigError::Invalid { key, .. }) => key,
+			other => panic!("expected a validation error, got {other:?}"),
+		}
+	}
+
+	#[test]
+	fn parse_toml(){
+		let config = NetworkConfig::from_toml_str(EXAMPLE).unwrap();
+		let (nn,options) = config.build().unwrap();
+
+		assert!(nn.input_size() == 2 && nn.output_size() == 1);
+		assert!(nn.layers[0].borrow().dropout() == 0.1);
+		assert!(nn.layers[0].borrow().norm().is_some());
+		assert!(options.optimizer == Optimizer::Adam { beta1 : 0.8, beta2 : 0.999, epsilon : 1e-8 });
+		assert!(options.schedule == LearningRateSchedule::Step { step_size : 2, gamma : 0.5 });
+		assert!(options.clip == Clip::LayerNorm(5.0) && options.non_finite == NonFinite::Ignore);
+		assert!(config.data == Some(DataSource::Builtin { name : "quadrant".to_string(), samples : Some(100) }));
+	}
+
+	#[test]
+	fn parse_json(){
+		let config = NetworkConfig::from_json_str(r#"{
+			"input": 3,
+			"layers": [{"size": 4, "activation": "tanh"}, {"size": 2, "activation": "identity"}],
+			"optimizer": {"type": "momentum", "momentum": 0.5}
+		}"#).unwrap();
+		let (nn,options) = config.build().unwrap();
+
+		assert!(nn.input_size() == 3 && nn.output_size() == 2);
+		assert!(options.optimizer == Optimizer::Momentum { momentum : 0.5 });
+		assert!(options.schedule == LearningRateSchedule::HalveOnIncrease);
+	}
+
+	#[test]
+	fn toml_round_trip(){
+		let config = NetworkConfig::from_toml_str(EXAMPLE).unwrap();
+		assert!(NetworkConfig::from_toml_str(&config.to_toml_string()).unwrap() == config);
+	}
+
+	#[test]
+	fn seeded_networks_are_identical(){
+		let config = NetworkConfig::from_toml_str(EXAMPLE).unwrap();
+		let (mut nn1,options) = config.build().unwrap();
+		let (mut nn2,_) = config.build().unwrap();
+		let data : Vec<(Vec<f64>,Vec<f64>)> = (0..50).map(|i| (vec![i as f64 / 50.0, 1.0 - i as f64 / 50.0],vec![(i%2) as f64])).collect();
+
+		assert!(nn1.train_with(&data, &options) == nn2.train_with(&data, &options));
+		assert!(nn1.predict(&[0.3,0.2]) == nn2.predict(&[0.3,0.2]));
+	}
+
+	#[test]
+	fn validation_errors_point_at_key(){
+		let config = NetworkConfig::from_toml_str(EXAMPLE).unwrap();
+
+		let mut wrong = config.clone();
+		wrong.layers[1].dropout = 0.5;
+		assert!(invalid_key(wrong.build()) == "layers[1].dropout");
+
+		let mut wrong = config.clone();
+		wrong.layers[0].activation = "softplus".to_string();
+		assert!(invalid_key(wrong.build()) == "layers[0].activation");
+
+		let mut wrong = config.clone();
+		wrong.layers[1].norm = "layer".to_string();
+		assert!(invalid_key(wrong.build()) == "layers[1].norm");
+
+		let mut wrong = config.clone();
+		wrong.optimizer.momentum = Some(0.9);
+		assert!(invalid_key(wrong.build()) == "optimizer.momentum");
+
+		let mut wrong = config.clone();
+		wrong.schedule.step_size = None;
+		assert!(invalid_key(wrong.build()) == "schedule.step_size");
+
+		let mut wrong = config.clone();
+		wrong.loss = "hinge".to_string();
+		assert!(invalid_key(wrong.build()) == "loss");
+
+		let mut wrong = config.clone();
+		wrong.clip = Some(ClipConfig { kind : "norm".to_string(), max : 1.0 });
+		assert!(invalid_key(wrong.build()) == "clip.type");
+
+		let mut wrong = config.clone();
+		wrong.non_finite = Some("retry".to_string());
+		assert!(invalid_key(wrong.build()) == "non_finite");
+
+		let mut wrong = config;
+		wrong.batch_size = 0;
+		assert!(invalid_key(wrong.build()) == "batch_size");
+	}
+
+	#[test]
+	fn unknown_key_is_a_parse_error(){
+		let error = NetworkConfig::from_toml_str("input = 2\n[[layers]]\nsize = 1\ndropuot = 0.1\n").unwrap_err();
+		match error {
+			ConfigError::Parse(message) => assert!(message.contains("dropuot")),
+			other => panic!("expected a parse error, got {other:?}"),
+		}
+	}
+
+	#[test]
+	fn csv_data_section(){
+		let text = "input = 3\n[[layers]]\nsize = 1\n[data]\ntype = \"csv\"\npath = \"houses.csv\"\nfeatures = [\"rooms\", 2]\ntargets = [\"price\"]\ncategorical = [\"city\"]\nmissing = \"mean\"\n";
+		let config = NetworkConfig::from_toml_str(text).unwrap();
+		let loader = config.data.unwrap().csv_loader(1).unwrap();
+
+		assert!(loader.features == vec![Column::from("rooms"),Column::from(2)]);
+		assert!(loader.targets == vec![Column::from("price")]);
+		assert!(loader.missing == MissingValues::Mean);
+	}
+
+	#[test]
+	fn scaler_names(){
+		let mut config = NetworkConfig::from_toml_str("input = 2\ninput_scaler = \"robust\"\n[[layers]]\nsize = 1\n").unwrap();
+		assert!(config.scalers().unwrap() == (Some(ScalerKind::Robust),None));
+
+		config.target_scaler = Some("log".to_string());
+		assert!(invalid_key(config.build()) == "target_scaler");
+	}
+}
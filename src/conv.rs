@@ -0,0 +1,462 @@
+use rand::Rng;
+
+use crate::block::*;
+use crate::init::*;
+use crate::matrix::*;
+use crate::matrix_at;
+use crate::tensor::*;
+
+
+/// Geometry of a sliding window over a `[channels,height,width]` input
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+struct Window {
+	kernel : (usize,usize),
+	stride : usize,
+	padding : usize,
+}
+
+impl Window {
+
+	/// (channels,height,width) of the input, panics if it isn't an image
+	fn input_dims(input_shape : &[usize]) -> (usize,usize,usize) {
+		assert!(input_shape.len()==3,"expected a [channels,height,width] input, got {input_shape:?}");
+		(input_shape[0],input_shape[1],input_shape[2])
+	}
+
+	/// (height,width) of the output
+	fn output_dims(&self, height : usize, width : usize) -> (usize,usize) {
+		let (kh,kw) = self.kernel;
+		assert!(height+2*self.padding >= kh && width+2*self.padding >= kw,"the kernel {kh}x{kw} is larger than the padded input {height}x{width}");
+		((height + 2*self.padding - kh)/self.stride + 1,(width + 2*self.padding - kw)/self.stride + 1)
+	}
+
+	/// Position in the input of a kernel element, `None` in the padding
+	fn source(&self, height : usize, width : usize, out : (usize,usize), kernel : (usize,usize)) -> Option<(usize,usize)> {
+		let y = (out.0*self.stride + kernel.0).checked_sub(self.padding)?;
+		let x = (out.1*self.stride + kernel.1).checked_sub(self.padding)?;
+		(y < height && x < width).then_some((y,x))
+	}
+
+	/// Unfold the input in a `[channels*kh*kw,out_h*out_w]` matrix, each column is one window
+	fn im2col(&self, input : &Tensor) -> Matrix<f64> {
+		let (channels,height,width) = Window::input_dims(&input.shape);
+		let (out_h,out_w) = self.output_dims(height, width);
+		let (kh,kw) = self.kernel;
+		let mut cols = Matrix::new(channels*kh*kw, out_h*out_w);
+
+		for c in 0..channels {
+			for ki in 0..kh {
+				for kj in 0..kw {
+					let row = (c*kh + ki)*kw + kj;
+					for oy in 0..out_h {
+						for ox in 0..out_w {
+							if let Some((y,x)) = self.source(height, width, (oy,ox), (ki,kj)) {
+								matrix_at!(row,oy*out_w + ox,cols) = input.values[(c*height + y)*width + x];
+							}
+						}
+					}
+				}
+			}
+		}
+		cols
+	}
+
+	/// Fold the columns back in an input, overlapping windows are summed
+	fn col2im(&self, cols : &Matrix<f64>, input_shape : &[usize]) -> Tensor {
+		let (channels,height,width) = Window::input_dims(input_shape);
+		let (out_h,out_w) = self.output_dims(height, width);
+		let (kh,kw) = self.kernel;
+		let mut input = Tensor::new(input_shape);
+
+		for c in 0..channels {
+			for ki in 0..kh {
+				for kj in 0..kw {
+					let row = (c*kh + ki)*kw + kj;
+					for oy in 0..out_h {
+						for ox in 0..out_w {
+							if let Some((y,x)) = self.source(height, width, (oy,ox), (ki,kj)) {
+								input.values[(c*height + y)*width + x] += matrix_at!(row,oy*out_w + ox,cols);
+							}
+						}
+					}
+				}
+			}
+		}
+		input
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                                 Convolution                                */
+/* -------------------------------------------------------------------------- */
+
+
+/// 2D convolution of a `[channels,height,width]` input, computed as a matrix product with the unfolded input
+#[derive(Debug,Clone)]
+pub struct Conv2D {
+	in_channels : usize,
+	out_channels : usize,
+	window : Window,
+	/// `[out_channels,in_channels*kh*kw]`
+	weights : Param,
+	/// `[out_channels,1]`
+	biases : Param,
+	cols : Matrix<f64>,
+	input_shape : Vec<usize>,
+}
+
+impl Conv2D {
+
+	/// A convolution with a stride of 1 and no padding
+	///
+	/// # Arguments
+	/// * `in_channels` - channels of the input
+	/// * `out_channels` - number of kernels
+	/// * `kernel` - (height,width) of the kernels
+	/// * `init` - how the kernels and biases are drawn, the fan in is `in_channels*kh*kw`
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new<R : Rng + ?Sized>(in_channels : usize, out_channels : usize, kernel : (usize,usize), init : Init, rng : &mut R) -> Conv2D {
+		assert!(in_channels > 0 && out_channels > 0 && kernel.0 > 0 && kernel.1 > 0,"a convolution can't have an empty dimension");
+		Conv2D {
+			in_channels,
+			out_channels,
+			window : Window { kernel, stride : 1, padding : 0 },
+			weights : Param::new(init.weights(out_channels, in_channels*kernel.0*kernel.1, rng)),
+			biases : Param::new(init.biases(out_channels, rng)),
+			cols : Matrix::new(0, 0),
+			input_shape : vec![],
+		}
+	}
+
+	pub fn stride(mut self, stride : usize) -> Conv2D {
+		assert!(stride > 0,"the stride should be positive");
+		self.window.stride = stride;
+		self
+	}
+
+	/// Zeros added on each side of the input
+	pub fn padding(mut self, padding : usize) -> Conv2D {
+		self.window.padding = padding;
+		self
+	}
+
+	pub fn weights(&self) -> &Matrix<f64> {
+		&self.weights.value
+	}
+
+	pub fn biases(&self) -> &Matrix<f64> {
+		&self.biases.value
+	}
+}
+
+impl Block for Conv2D {
+
+	fn name(&self) -> String {
+		format!("conv2d {}x{} {}->{}",self.window.kernel.0,self.window.kernel.1,self.in_channels,self.out_channels)
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		let (channels,height,width) = Window::input_dims(input_shape);
+		assert!(channels==self.in_channels,"the convolution expects {} channels, got {channels}",self.in_channels);
+		let (out_h,out_w) = self.window.output_dims(height, width);
+		vec![self.out_channels,out_h,out_w]
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		let shape = self.output_shape(&input.shape);
+		self.cols = self.window.im2col(input);
+		self.input_shape = input.shape.clone();
+
+		let mut output = Matrix::new(self.out_channels, self.cols.cols);
+		self.weights.value.dot(&mut output, &self.cols);
+		for f in 0..self.out_channels {
+			let bias = self.biases.value.values[f];
+			output.values[f*self.cols.cols..(f+1)*self.cols.cols].iter_mut().for_each(|x| *x += bias);
+		}
+		Tensor::from_vec(&shape, output.values)
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		let grad = Matrix::from_vec(self.out_channels, self.cols.cols, grad_output.values.clone());
+		grad.dot_trans_add(&mut self.weights.grad, &self.cols);
+		for f in 0..self.out_channels {
+			self.biases.grad.values[f] += grad.values[f*grad.cols..(f+1)*grad.cols].iter().sum::<f64>();
+		}
+
+		let mut grad_cols = Matrix::new(self.cols.rows, self.cols.cols);
+		self.weights.value.trans_dot(&mut grad_cols, &grad);
+		self.window.col2im(&grad_cols, &self.input_shape)
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		vec![&self.weights,&self.biases]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![&mut self.weights,&mut self.biases]
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                                   Pooling                                  */
+/* -------------------------------------------------------------------------- */
+
+
+/// Maximum of each window of each channel
+#[derive(Debug,Clone)]
+pub struct MaxPool2D {
+	window : Window,
+	/// position in the input of the maximum of each output value
+	argmax : Vec<usize>,
+	input_shape : Vec<usize>,
+}
+
+/// Mean of each window of each channel
+#[derive(Debug,Clone)]
+pub struct AvgPool2D {
+	window : Window,
+	input_shape : Vec<usize>,
+}
+
+/// Positions in the input of each window of each channel, in the order of the output
+fn pool_windows(window : &Window, input_shape : &[usize]) -> impl Iterator<Item = Vec<usize>> {
+	let (channels,height,width) = Window::input_dims(input_shape);
+	let (out_h,out_w) = window.output_dims(height, width);
+	let window = *window;
+	(0..channels).flat_map(move |c| (0..out_h).flat_map(move |oy| (0..out_w).map(move |ox| {
+		let (kh,kw) = window.kernel;
+		(0..kh).flat_map(|ki| (0..kw).map(move |kj| (ki,kj)))
+			.filter_map(|k| window.source(height, width, (oy,ox), k))
+			.map(|(y,x)| (c*height + y)*width + x)
+			.collect()
+	})))
+}
+
+fn pool_shape(window : &Window, input_shape : &[usize]) -> Vec<usize> {
+	let (channels,height,width) = Window::input_dims(input_shape);
+	let (out_h,out_w) = window.output_dims(height, width);
+	vec![channels,out_h,out_w]
+}
+
+impl MaxPool2D {
+
+	/// Square windows of `size`, the stride is the size so the windows don't overlap
+	pub fn new(size : usize) -> MaxPool2D {
+		assert!(size > 0,"the pooling size should be positive");
+		MaxPool2D { window : Window { kernel : (size,size), stride : size, padding : 0 }, argmax : vec![], input_shape : vec![] }
+	}
+
+	pub fn stride(mut self, stride : usize) -> MaxPool2D {
+		assert!(stride > 0,"the stride should be positive");
+		self.window.stride = stride;
+		self
+	}
+}
+
+impl AvgPool2D {
+
+	/// Square windows of `size`, the stride is the size so the windows don't overlap
+	pub fn new(size : usize) -> AvgPool2D {
+		assert!(size > 0,"the pooling size should be positive");
+		AvgPool2D { window : Window { kernel : (size,size), stride : size, padding : 0 }, input_shape : vec![] }
+	}
+
+	pub fn stride(mut self, stride : usize) -> AvgPool2D {
+		assert!(stride > 0,"the stride should be positive");
+		self.window.stride = stride;
+		self
+	}
+}
+
+impl Block for MaxPool2D {
+
+	fn name(&self) -> String {
+		format!("max_pool {}x{}",self.window.kernel.0,self.window.kernel.1)
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		pool_shape(&self.window, input_shape)
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		self.input_shape = input.shape.clone();
+		self.argmax = pool_windows(&self.window, &input.shape)
+			.map(|window| window.into_iter().fold(None, |best : Option<usize>,i| match best {
+				Some(best) if input.values[best] >= input.values[i] => Some(best),
+				_ => Some(i),
+			}).unwrap())
+			.collect();
+		Tensor::from_vec(&self.output_shape(&input.shape), self.argmax.iter().map(|i| input.values[*i]).collect())
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		let mut grad_input = Tensor::new(&self.input_shape);
+		for (i,grad) in self.argmax.iter().zip(&grad_output.values) {
+			grad_input.values[*i] += grad;
+		}
+		grad_input
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+impl Block for AvgPool2D {
+
+	fn name(&self) -> String {
+		format!("avg_pool {}x{}",self.window.kernel.0,self.window.kernel.1)
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		pool_shape(&self.window, input_shape)
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		self.input_shape = input.shape.clone();
+		let values = pool_windows(&self.window, &input.shape)
+			.map(|window| window.iter().map(|i| input.values[*i]).sum::<f64>() / window.len() as f64)
+			.collect();
+		Tensor::from_vec(&self.output_shape(&input.shape), values)
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		let mut grad_input = Tensor::new(&self.input_shape);
+		for (window,grad) in pool_windows(&self.window, &self.input_shape).zip(&grad_output.values) {
+			let share = grad / window.len() as f64;
+			window.into_iter().for_each(|i| grad_input.values[i] += share);
+		}
+		grad_input
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+/// Reshape any input to a single dimension
+#[derive(Debug,Clone,Default)]
+pub struct Flatten {
+	input_shape : Vec<usize>,
+}
+
+impl Flatten {
+
+	pub fn new() -> Flatten {
+		Flatten::default()
+	}
+}
+
+impl Block for Flatten {
+
+	fn name(&self) -> String {
+		"flatten".to_string()
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		vec![shape_len(input_shape)]
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		self.input_shape = input.shape.clone();
+		input.clone().reshape(&[input.len()])
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		grad_output.clone().reshape(&self.input_shape)
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	fn image(shape : &[usize], seed : u64) -> Tensor {
+		let mut rng = ChaCha8Rng::seed_from_u64(seed);
+		Tensor::from_vec(shape, (0..shape_len(shape)).map(|_| rng.gen_range(-1.0..1.0)).collect())
+	}
+
+	#[test]
+	fn convolution_values(){
+		let mut rng = ChaCha8Rng::seed_from_u64(0);
+		let mut conv = Conv2D::new(1, 1, (2,2), Init::Default, &mut rng);
+		conv.weights.value.values = vec![1.0,0.0,0.0,-1.0];
+		conv.biases.value.values = vec![0.5];
+
+		let input = Tensor::from_vec(&[1,3,3], (0..9).map(|x| x as f64).collect());
+		// x[y][x] - x[y+1][x+1] = -4 everywhere
+		assert!(conv.forward(&input) == Tensor::from_vec(&[1,2,2], vec![-3.5;4]));
+
+		let mut padded = conv.clone().padding(1).stride(2);
+		assert!(padded.output_shape(&[1,3,3]) == vec![1,2,2]);
+		// the first window only sees x[0][0] in its bottom right corner
+		assert!(padded.forward(&input).values[0] == 0.5 - 0.0);
+	}
+
+	#[test]
+	fn convolution_gradient_check(){
+		let mut rng = ChaCha8Rng::seed_from_u64(1);
+		check_gradients(&mut Conv2D::new(2, 3, (3,2), Init::Xavier, &mut rng), &image(&[2,5,4], 2));
+		check_gradients(&mut Conv2D::new(2, 2, (3,3), Init::He, &mut rng).stride(2).padding(1), &image(&[2,5,5], 3));
+	}
+
+	#[test]
+	fn pooling(){
+		let input = Tensor::from_vec(&[1,4,4], (0..16).map(|x| x as f64).collect());
+		assert!(MaxPool2D::new(2).forward(&input).values == vec![5.0,7.0,13.0,15.0]);
+		assert!(AvgPool2D::new(2).forward(&input).values == vec![2.5,4.5,10.5,12.5]);
+		assert!(MaxPool2D::new(2).stride(1).output_shape(&[3,4,4]) == vec![3,3,3]);
+
+		check_gradients(&mut MaxPool2D::new(2), &image(&[2,4,5], 4));
+		check_gradients(&mut MaxPool2D::new(3).stride(1), &image(&[1,5,5], 5));
+		check_gradients(&mut AvgPool2D::new(2).stride(1), &image(&[2,3,4], 6));
+		check_gradients(&mut Flatten::new(), &image(&[2,3,4], 7));
+	}
+
+	#[test]
+	fn small_image_classifier(){
+		// 5x5 images with a vertical or a horizontal bar
+		let mut rng = ChaCha8Rng::seed_from_u64(8);
+		let data : Vec<TensorSample> = (0..40).map(|i| {
+			let (vertical,position) = (i%2 == 0,1 + (i/2)%3);
+			let mut image = image(&[1,5,5], i as u64).values.iter().map(|x| 0.1*x).collect::<Vec<f64>>();
+			for j in 0..5 {
+				let index = if vertical { j*5 + position } else { position*5 + j };
+				image[index] += 1.0;
+			}
+			(Tensor::from_vec(&[1,5,5], image),vec![vertical as usize as f64])
+		}).collect();
+
+		let mut model = Sequential::new(&[1,5,5], "cross_entropy", ChaCha8Rng::seed_from_u64(9));
+		model.add(Conv2D::new(1, 4, (3,3), Init::He, &mut rng).padding(1));
+		model.add(Activation::new("relu"));
+		model.add(MaxPool2D::new(2));
+		model.add(Flatten::new());
+		model.dense(1, "sigmoid");
+		assert!(model.output_shape() == vec![16] && model.nb_parameters() == 4*9+4 + 16+1);
+
+		let options = adam_options(8, 40);
+		model.train_with(&data, &options);
+		let correct = data.iter().filter(|(input,target)| (model.predict(input)[0] >= 0.5) == (target[0] == 1.0)).count();
+		assert!(correct == data.len(), "{correct} correct");
+	}
+}
@@ -0,0 +1,647 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::nn::Sample;
+
+/// Values treated as missing (compared after trimming, case insensitive)
+const MISSING_MARKERS : [&str;5] = ["","na","nan","null","?"];
+
+
+/// Error raised while reading a CSV file, lines and columns start at 1
+#[derive(Debug,Clone,PartialEq)]
+pub struct CsvError {
+	pub line : usize,
+	pub column : Option<usize>,
+	pub message : String,
+}
+
+impl CsvError {
+	fn new(line : usize, column : Option<usize>, message : impl Into<String>) -> CsvError {
+		CsvError { line, column, message : message.into() }
+	}
+}
+
+impl fmt::Display for CsvError {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self.column {
+			Some(column) => write!(f, "line {}, column {}: {}",self.line,column,self.message),
+			None => write!(f, "line {}: {}",self.line,self.message),
+		}
+	}
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<io::Error> for CsvError {
+	fn from(error : io::Error) -> Self {
+		CsvError { line : 0, column : None, message : error.to_string() }
+	}
+}
+
+
+/// A column selected by its header name or by its index (starting at 0)
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+	Index(usize),
+	Name(String),
+}
+
+impl From<usize> for Column {
+	fn from(index : usize) -> Self {
+		Column::Index(index)
+	}
+}
+
+impl From<&str> for Column {
+	fn from(name : &str) -> Self {
+		Column::Name(name.to_string())
+	}
+}
+
+impl FromStr for Column {
+	type Err = std::convert::Infallible;
+
+	/// numbers are indexes, everything else is a name
+	fn from_str(text : &str) -> Result<Self,Self::Err> {
+		Ok(match text.trim().parse::<usize>() {
+			Ok(index) => Column::Index(index),
+			Err(_) => Column::Name(text.trim().to_string()),
+		})
+	}
+}
+
+impl fmt::Display for Column {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Column::Index(index) => write!(f, "{index}"),
+			Column::Name(name) => write!(f, "{name}"),
+		}
+	}
+}
+
+
+/// What to do with a missing feature value. A row with a missing target is skipped,
+/// or rejected with `Error`.
+#[derive(Debug,Clone,Copy,PartialEq,Default,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingValues {
+	/// reject the file
+	#[default]
+	Error,
+	/// drop the row
+	Skip,
+	/// replace with the mean of the column (categorical columns get an all zero encoding)
+	Mean,
+	/// replace with a constant (categorical columns get an all zero encoding)
+	Value(f64),
+}
+
+impl FromStr for MissingValues {
+	type Err = String;
+
+	fn from_str(text : &str) -> Result<Self,Self::Err> {
+		match text.trim().to_lowercase().as_str() {
+			"error" => Ok(MissingValues::Error),
+			"skip" => Ok(MissingValues::Skip),
+			"mean" => Ok(MissingValues::Mean),
+			other => other.parse().map(MissingValues::Value).map_err(|_| format!("`{text}` is not error, skip, mean or a number")),
+		}
+	}
+}
+
+
+/// Categories seen for each categorical column, the index of a category is its position in the one-hot vector
+pub type Vocabulary = BTreeMap<String,Vec<String>>;
+
+
+/// Reads a CSV/TSV file into the (input,output) pairs used by `NeuralNetWork::train`
+///
+/// ```
+/// use rust_simple_nn::csv::*;
+///
+/// let text = "size,color,price\n1.5,red,10\n2.0,blue,12\nNA,red,9\n";
+/// let dataset = CsvLoader::new()
+///     .targets(["price"])
+///     .categorical(["color"])
+///     .missing(MissingValues::Mean)
+///     .load(text.as_bytes())
+///     .unwrap();
+///
+/// assert_eq!(dataset.feature_names, vec!["size", "color=blue", "color=red"]);
+/// assert_eq!(dataset.samples[2], (vec![1.75, 0.0, 1.0], vec![9.0]));
+/// ```
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsvLoader {
+	/// field separator, detected from the first line (tab, semicolon or comma) if not set
+	pub delimiter : Option<char>,
+	/// whether the first line is a header, detected if not set (a line without any number)
+	pub header : Option<bool>,
+	/// input columns, every column that isn't a target if empty
+	pub features : Vec<Column>,
+	/// output columns
+	pub targets : Vec<Column>,
+	/// when `targets` is empty, use the last `last_targets` columns as outputs
+	pub last_targets : usize,
+	/// columns holding strings, they are one-hot encoded
+	pub categorical : Vec<Column>,
+	pub missing : MissingValues,
+	/// categories of each categorical column, built from the data if not set
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub vocabulary : Option<Vocabulary>,
+	/// means used by `MissingValues::Mean`, computed from the data if not set
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub means : Option<BTreeMap<String,f64>>,
+}
+
+/// Result of `CsvLoader::load`
+#[derive(Debug,Clone,PartialEq)]
+pub struct CsvDataset {
+	pub samples : Vec<Sample>,
+	/// name of each input value (`column=category` for one-hot encoded columns)
+	pub feature_names : Vec<String>,
+	/// name of each output value
+	pub target_names : Vec<String>,
+	/// number of rows dropped because of missing values
+	pub skipped : usize,
+	/// the loader with the vocabulary and means found in this file, to read new data the same way
+	pub fitted : CsvLoader,
+}
+
+
+/// A selected column once the header is known
+struct Selected {
+	index : usize,
+	name : String,
+	categorical : bool,
+}
+
+
+impl CsvLoader {
+
+	pub fn new() -> CsvLoader {
+		CsvLoader::default()
+	}
+
+	pub fn delimiter(mut self, delimiter : char) -> CsvLoader {
+		self.delimiter = Some(delimiter);
+		self
+	}
+
+	pub fn header(mut self, header : bool) -> CsvLoader {
+		self.header = Some(header);
+		self
+	}
+
+	pub fn features<C : Into<Column>>(mut self, columns : impl IntoIterator<Item = C>) -> CsvLoader {
+		self.features = columns.into_iter().map(Into::into).collect();
+		self
+	}
+
+	pub fn targets<C : Into<Column>>(mut self, columns : impl IntoIterator<Item = C>) -> CsvLoader {
+		self.targets = columns.into_iter().map(Into::into).collect();
+		self
+	}
+
+	pub fn last_targets(mut self, count : usize) -> CsvLoader {
+		self.last_targets = count;
+		self
+	}
+
+	pub fn categorical<C : Into<Column>>(mut self, columns : impl IntoIterator<Item = C>) -> CsvLoader {
+		self.categorical = columns.into_iter().map(Into::into).collect();
+		self
+	}
+
+	pub fn missing(mut self, missing : MissingValues) -> CsvLoader {
+		self.missing = missing;
+		self
+	}
+
+	pub fn vocabulary(mut self, vocabulary : Vocabulary) -> CsvLoader {
+		self.vocabulary = Some(vocabulary);
+		self
+	}
+
+	/// The same loader without targets, to read the inputs of new data
+	pub fn inputs_only(&self) -> CsvLoader {
+		let mut loader = self.clone();
+		loader.targets = vec![];
+		loader.last_targets = 0;
+		loader
+	}
+
+	pub fn load_path(&self, path : impl AsRef<Path>) -> Result<CsvDataset,CsvError> {
+		self.load(BufReader::new(File::open(path)?))
+	}
+
+	pub fn load<R : BufRead>(&self, reader : R) -> Result<CsvDataset,CsvError> {
+		// every non empty line with its number
+		let mut rows : Vec<(usize,Vec<String>)> = vec![];
+		let mut delimiter = self.delimiter;
+		for (index,line) in reader.lines().enumerate() {
+			let line = line.map_err(|e| CsvError::new(index+1, None, e.to_string()))?;
+			if line.trim().is_empty() {
+				continue;
+			}
+			let delimiter = *delimiter.get_or_insert_with(|| detect_delimiter(&line));
+			rows.push((index+1,split_line(&line, delimiter).map_err(|message| CsvError::new(index+1, None, message))?));
+		}
+
+		let Some((first_line,first_fields)) = rows.first().cloned() else {
+			return Ok(CsvDataset { samples : vec![], feature_names : vec![], target_names : vec![], skipped : 0, fitted : self.clone() });
+		};
+		let nb_columns = first_fields.len();
+		let header = self.header.unwrap_or_else(|| first_fields.iter().all(|field| field.trim().parse::<f64>().is_err()));
+		let names : Vec<String> = if header {
+			rows.remove(0);
+			first_fields.iter().map(|field| field.trim().to_string()).collect()
+		} else {
+			(0..nb_columns).map(|i| format!("column_{i}")).collect()
+		};
+
+		let (features,targets) = self.select_columns(&names, first_line)?;
+
+		for (line,fields) in &rows {
+			if fields.len() != nb_columns {
+				return Err(CsvError::new(*line, None, format!("expected {nb_columns} columns, found {}",fields.len())));
+			}
+		}
+
+		let vocabulary = match &self.vocabulary {
+			Some(vocabulary) => {
+				if let Some(column) = features.iter().chain(&targets).find(|c| c.categorical && !vocabulary.contains_key(&c.name)) {
+					return Err(CsvError::new(first_line, Some(column.index+1), format!("no vocabulary for the categorical column `{}`",column.name)));
+				}
+				vocabulary.clone()
+			},
+			None => build_vocabulary(&rows, features.iter().chain(&targets)),
+		};
+
+		let means = match (&self.means,self.missing) {
+			(Some(means),_) => means.clone(),
+			(None,MissingValues::Mean) => compute_means(&rows, &features)?,
+			(None,_) => BTreeMap::new(),
+		};
+
+		let mut samples = Vec::with_capacity(rows.len());
+		let mut skipped = 0;
+		'rows: for (line,fields) in &rows {
+			let mut input = vec![];
+			for column in &features {
+				let field = &fields[column.index];
+				let value = match column.categorical {
+					true if is_missing(field) => None,
+					true => {
+						input.extend(one_hot(&vocabulary[&column.name], field));
+						continue;
+					},
+					false => parse_number(field, *line, column)?,
+				};
+				match (value,self.missing) {
+					(Some(value),_) => input.push(value),
+					(None,MissingValues::Error) => return Err(CsvError::new(*line, Some(column.index+1), format!("missing value in `{}`",column.name))),
+					(None,MissingValues::Skip) => {
+						skipped += 1;
+						continue 'rows;
+					},
+					(None,_) if column.categorical => input.extend(vec![0.0;vocabulary[&column.name].len()]),
+					(None,MissingValues::Mean) => match means.get(&column.name) {
+						Some(mean) => input.push(*mean),
+						None => return Err(CsvError::new(*line, Some(column.index+1), format!("no mean for `{}`",column.name))),
+					},
+					(None,MissingValues::Value(value)) => input.push(value),
+				}
+			}
+
+			let mut output = vec![];
+			for column in &targets {
+				let field = &fields[column.index];
+				if is_missing(field) {
+					if self.missing == MissingValues::Error {
+						return Err(CsvError::new(*line, Some(column.index+1), format!("missing target in `{}`",column.name)));
+					}
+					skipped += 1;
+					continue 'rows;
+				}
+				if column.categorical {
+					let categories = &vocabulary[&column.name];
+					if !categories.iter().any(|category| category == field.trim()) {
+						return Err(CsvError::new(*line, Some(column.index+1), format!("unknown category `{}` for `{}`",field.trim(),column.name)));
+					}
+					output.extend(one_hot(categories, field));
+				} else {
+					output.push(parse_number(field, *line, column)?.unwrap());
+				}
+			}
+
+			samples.push((input,output));
+		}
+
+		let expand = |columns : &[Selected]| -> Vec<String> {
+			columns.iter().flat_map(|column| {
+				if column.categorical {
+					vocabulary[&column.name].iter().map(|category| format!("{}={category}",column.name)).collect()
+				} else {
+					vec![column.name.clone()]
+				}
+			}).collect()
+		};
+
+		// columns are frozen by name (or index without header) so the fitted loader can read files without targets
+		let freeze = |columns : &[Selected]| -> Vec<Column> {
+			columns.iter().map(|column| if header { Column::Name(column.name.clone()) } else { Column::Index(column.index) }).collect()
+		};
+		let mut fitted = self.clone();
+		fitted.features = freeze(&features);
+		fitted.targets = freeze(&targets);
+		fitted.last_targets = 0;
+		fitted.vocabulary = Some(vocabulary.clone());
+		if self.missing == MissingValues::Mean {
+			fitted.means = Some(means.clone());
+		}
+
+		Ok(CsvDataset {
+			feature_names : expand(&features),
+			target_names : expand(&targets),
+			samples,
+			skipped,
+			fitted,
+		})
+	}
+
+	/// Resolve the feature and target columns against the header
+	fn select_columns(&self, names : &[String], header_line : usize) -> Result<(Vec<Selected>,Vec<Selected>),CsvError> {
+		let resolve = |column : &Column| -> Result<usize,CsvError> {
+			match column {
+				Column::Index(index) if *index < names.len() => Ok(*index),
+				Column::Index(index) => Err(CsvError::new(header_line, None, format!("column {index} doesn't exist, the file has {} columns",names.len()))),
+				Column::Name(name) => names.iter().position(|n| n == name)
+					.ok_or_else(|| CsvError::new(header_line, None, format!("no column named `{name}`"))),
+			}
+		};
+
+		let categorical : Vec<usize> = self.categorical.iter().map(resolve).collect::<Result<_,_>>()?;
+		let selected = |index : usize| Selected { index, name : names[index].clone(), categorical : categorical.contains(&index) };
+
+		let target_indexes : Vec<usize> = if self.targets.is_empty() {
+			if self.last_targets >= names.len() {
+				return Err(CsvError::new(header_line, None, format!("{} columns can't hold {} targets and at least one input",names.len(),self.last_targets)));
+			}
+			(names.len()-self.last_targets..names.len()).collect()
+		} else {
+			self.targets.iter().map(resolve).collect::<Result<_,_>>()?
+		};
+
+		let feature_indexes : Vec<usize> = if self.features.is_empty() {
+			(0..names.len()).filter(|index| !target_indexes.contains(index)).collect()
+		} else {
+			self.features.iter().map(resolve).collect::<Result<_,_>>()?
+		};
+
+		if let Some(index) = feature_indexes.iter().find(|index| target_indexes.contains(index)) {
+			return Err(CsvError::new(header_line, Some(index+1), format!("`{}` can't be both a feature and a target",names[*index])));
+		}
+		if feature_indexes.is_empty() {
+			return Err(CsvError::new(header_line, None, "no feature column"));
+		}
+
+		Ok((feature_indexes.into_iter().map(selected).collect(),target_indexes.into_iter().map(selected).collect()))
+	}
+}
+
+
+fn is_missing(field : &str) -> bool {
+	MISSING_MARKERS.contains(&field.trim().to_lowercase().as_str())
+}
+
+/// `None` for a missing value
+fn parse_number(field : &str, line : usize, column : &Selected) -> Result<Option<f64>,CsvError> {
+	if is_missing(field) {
+		return Ok(None);
+	}
+	field.trim().parse::<f64>().map(Some).map_err(|_| {
+		CsvError::new(line, Some(column.index+1), format!("`{}` is not a number (use a categorical column for `{}`)",field.trim(),column.name))
+	})
+}
+
+fn one_hot(categories : &[String], field : &str) -> Vec<f64> {
+	categories.iter().map(|category| if category == field.trim() { 1.0 } else { 0.0 }).collect()
+}
+
+/// sorted categories of each categorical column, missing values aren't a category
+fn build_vocabulary<'a>(rows : &[(usize,Vec<String>)], columns : impl Iterator<Item = &'a Selected>) -> Vocabulary {
+	let mut vocabulary = Vocabulary::new();
+	for column in columns.filter(|column| column.categorical) {
+		let mut categories : Vec<String> = rows.iter()
+			.map(|(_,fields)| fields[column.index].trim().to_string())
+			.filter(|field| !is_missing(field))
+			.collect();
+		categories.sort();
+		categories.dedup();
+		vocabulary.insert(column.name.clone(), categories);
+	}
+	vocabulary
+}
+
+fn compute_means(rows : &[(usize,Vec<String>)], features : &[Selected]) -> Result<BTreeMap<String,f64>,CsvError> {
+	let mut means = BTreeMap::new();
+	for column in features.iter().filter(|column| !column.categorical) {
+		let (mut sum,mut count) = (0.0,0);
+		for (line,fields) in rows {
+			if let Some(value) = parse_number(&fields[column.index], *line, column)? {
+				sum += value;
+				count += 1;
+			}
+		}
+		if count > 0 {
+			means.insert(column.name.clone(), sum / count as f64);
+		}
+	}
+	Ok(means)
+}
+
+fn detect_delimiter(line : &str) -> char {
+	['\t',';',','].into_iter().find(|&delimiter| line.contains(delimiter)).unwrap_or(',')
+}
+
+/// Split a line on the delimiter, fields can be quoted with `"` (a quote inside is written `""`)
+fn split_line(line : &str, delimiter : char) -> Result<Vec<String>,String> {
+	let mut fields = vec![];
+	let mut field = String::new();
+	let mut quoted = false;
+	let mut chars = line.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'"' if quoted && chars.peek() == Some(&'"') => {
+				chars.next();
+				field.push('"');
+			},
+			'"' if quoted => quoted = false,
+			'"' if field.trim().is_empty() => {
+				field.clear();
+				quoted = true;
+			},
+			c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+			c => field.push(c),
+		}
+	}
+	if quoted {
+		return Err("unterminated quoted field".to_string());
+	}
+	fields.push(field);
+
+	Ok(fields)
+}
+
+
+/// Read a CSV (or TSV) file of numbers, the last `nb_targets` columns are the outputs.
+/// A first line without any number is treated as a header and skipped.
+///
+/// # Arguments
+/// * `reader` - the CSV content
+/// * `nb_targets` - number of output columns, 0 to read inputs only
+pub fn read_numeric<R : BufRead>(reader : R, nb_targets : usize) -> Result<Vec<Sample>,CsvError> {
+	Ok(CsvLoader::new().last_targets(nb_targets).load(reader)?.samples)
+}
+
+/// Read a CSV (or TSV) file of numbers, see `read_numeric`
+pub fn read_numeric_file(path : impl AsRef<Path>, nb_targets : usize) -> Result<Vec<Sample>,CsvError> {
+	read_numeric(BufReader::new(File::open(path)?), nb_targets)
+}
+
+/// Write one comma separated line per row
+pub fn write_rows<W : Write>(writer : &mut W, rows : &[Vec<f64>]) -> io::Result<()> {
+	for row in rows {
+		let line : Vec<String> = row.iter().map(|value| value.to_string()).collect();
+		writeln!(writer, "{}",line.join(","))?;
+	}
+	Ok(())
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PEOPLE : &str = "name,age,city,height,label
+alice,31,paris,1.70,1
+bob,NA,lyon,1.82,0
+\"carol, jr\",25,paris,,1
+dave,40,nice,1.75,0
+";
+
+	#[test]
+	fn read_with_header(){
+		let data = read_numeric("x,y,label\n1,2,0\n-3.5,4e2,1\n".as_bytes(), 1).unwrap();
+		assert!(data == vec![(vec![1.0,2.0],vec![0.0]),(vec![-3.5,400.0],vec![1.0])]);
+	}
+
+	#[test]
+	fn read_tsv_without_header(){
+		let data = read_numeric("1\t2\n3\t4\n".as_bytes(), 0).unwrap();
+		assert!(data == vec![(vec![1.0,2.0],vec![]),(vec![3.0,4.0],vec![])]);
+	}
+
+	#[test]
+	fn error_reports_line_and_column(){
+		let error = read_numeric("a,b\n1,2\n3,x\n".as_bytes(), 1).unwrap_err();
+		assert!(error.line == 3 && error.column == Some(2));
+		assert!(error.message.contains("`x` is not a number"));
+
+		let error = read_numeric("1,2\n3\n".as_bytes(), 1).unwrap_err();
+		assert!(error.line == 2 && error.column.is_none());
+	}
+
+	#[test]
+	fn select_by_name_and_index(){
+		let dataset = CsvLoader::new()
+			.features([Column::from("height"),Column::from(1)])
+			.targets(["label"])
+			.missing(MissingValues::Skip)
+			.load(PEOPLE.as_bytes())
+			.unwrap();
+
+		assert!(dataset.feature_names == vec!["height","age"]);
+		assert!(dataset.target_names == vec!["label"]);
+		assert!(dataset.samples == vec![(vec![1.70,31.0],vec![1.0]),(vec![1.75,40.0],vec![0.0])]);
+		assert!(dataset.skipped == 2);
+	}
+
+	#[test]
+	fn impute_and_one_hot(){
+		let dataset = CsvLoader::new()
+			.features(["age","city","height"])
+			.targets(["label"])
+			.categorical(["city"])
+			.missing(MissingValues::Mean)
+			.load(PEOPLE.as_bytes())
+			.unwrap();
+
+		assert!(dataset.feature_names == vec!["age","city=lyon","city=nice","city=paris","height"]);
+		assert!(dataset.samples[1].0 == vec![32.0,1.0,0.0,0.0,1.82]);
+		assert!(dataset.samples[2].0 == vec![25.0,0.0,0.0,1.0,(1.70+1.82+1.75)/3.0]);
+	}
+
+	#[test]
+	fn fitted_loader_reuses_vocabulary(){
+		let loader = CsvLoader::new().targets(["label"]).features(["city","age"]).categorical(["city"]).missing(MissingValues::Mean);
+		let fitted = loader.load(PEOPLE.as_bytes()).unwrap().fitted;
+
+		// serialized with the model, then used on new data with an unseen city and a missing age
+		let fitted : CsvLoader = serde_json::from_str(&serde_json::to_string(&fitted).unwrap()).unwrap();
+		let dataset = fitted.inputs_only().load("name,age,city\nerin,NA,lyon\nfred,50,berlin\n".as_bytes()).unwrap();
+
+		assert!(dataset.samples[0].0 == vec![1.0,0.0,0.0,32.0]);
+		assert!(dataset.samples[1].0 == vec![0.0,0.0,0.0,50.0]);
+	}
+
+	#[test]
+	fn categorical_targets(){
+		let dataset = CsvLoader::new().features(["age"]).targets(["city"]).categorical(["city"]).missing(MissingValues::Skip)
+			.load(PEOPLE.as_bytes()).unwrap();
+
+		assert!(dataset.target_names == vec!["city=lyon","city=nice","city=paris"]);
+		assert!(dataset.samples[0].1 == vec![0.0,0.0,1.0]);
+	}
+
+	#[test]
+	fn loader_errors(){
+		let error = CsvLoader::new().targets(["weight"]).load(PEOPLE.as_bytes()).unwrap_err();
+		assert!(error.line == 1 && error.message.contains("weight"));
+
+		let error = CsvLoader::new().features(["age"]).targets(["label"]).load(PEOPLE.as_bytes()).unwrap_err();
+		assert!(error == CsvError::new(3, Some(2), "missing value in `age`"));
+
+		let error = CsvLoader::new().features(["city"]).targets(["label"]).load(PEOPLE.as_bytes()).unwrap_err();
+		assert!(error.line == 2 && error.column == Some(3));
+
+		// a missing category follows the policy of the numbers
+		let data = "city,label\nparis,1\nNA,0\nnice,1\n";
+		let error = CsvLoader::new().targets(["label"]).categorical(["city"]).load(data.as_bytes()).unwrap_err();
+		assert!(error == CsvError::new(3, Some(1), "missing value in `city`"));
+		let dataset = CsvLoader::new().targets(["label"]).categorical(["city"]).missing(MissingValues::Skip).load(data.as_bytes()).unwrap();
+		assert!(dataset.samples == vec![(vec![0.0,1.0],vec![1.0]),(vec![1.0,0.0],vec![1.0])] && dataset.skipped == 1);
+
+		let error = CsvLoader::new().load("a,b\n\"1,2\n".as_bytes()).unwrap_err();
+		assert!(error.line == 2 && error.message.contains("quoted"));
+	}
+
+	#[test]
+	fn quoted_fields(){
+		assert!(split_line("\"a,b\",\"say \"\"hi\"\"\",c", ',').unwrap() == vec!["a,b","say \"hi\"","c"]);
+	}
+}
@@ -0,0 +1,720 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use rand::{SeedableRng, seq::SliceRandom, Rng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::checkpoint::{LoaderState, RngState};
+use crate::matrix::*;
+use crate::model_selection::class_of;
+use crate::nn::Sample;
+
+/// First bytes of a file written by `FileDataset::write`
+const FILE_MAGIC : &[u8;8] = b"RSNNDATA";
+/// magic, input size, output size, number of samples
+const FILE_HEADER_SIZE : u64 = 32;
+
+
+/// Random access to the (input,output) samples of a dataset
+pub trait Dataset {
+
+	fn len(&self) -> usize;
+
+	fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	fn input_size(&self) -> usize;
+
+	fn output_size(&self) -> usize;
+
+	/// the sample at `index`, panics if `index >= len()`
+	fn get(&self, index : usize) -> Sample;
+
+	/// Copy the sample at `index` into `input` and `output`, override it to avoid allocating a sample
+	fn copy_into(&self, index : usize, input : &mut [f64], output : &mut [f64]) {
+		let (sample_input,sample_output) = self.get(index);
+		input.copy_from_slice(&sample_input);
+		output.copy_from_slice(&sample_output);
+	}
+}
+
+impl Dataset for [Sample] {
+
+	fn len(&self) -> usize {
+		<[Sample]>::len(self)
+	}
+
+	fn input_size(&self) -> usize {
+		self.first().map_or(0, |(input,_)| input.len())
+	}
+
+	fn output_size(&self) -> usize {
+		self.first().map_or(0, |(_,output)| output.len())
+	}
+
+	fn get(&self, index : usize) -> Sample {
+		self[index].clone()
+	}
+
+	fn copy_into(&self, index : usize, input : &mut [f64], output : &mut [f64]) {
+		input.copy_from_slice(&self[index].0);
+		output.copy_from_slice(&self[index].1);
+	}
+}
+
+impl Dataset for Vec<Sample> {
+
+	fn len(&self) -> usize {
+		self.as_slice().len()
+	}
+
+	fn input_size(&self) -> usize {
+		self.as_slice().input_size()
+	}
+
+	fn output_size(&self) -> usize {
+		self.as_slice().output_size()
+	}
+
+	fn get(&self, index : usize) -> Sample {
+		Dataset::get(self.as_slice(), index)
+	}
+
+	fn copy_into(&self, index : usize, input : &mut [f64], output : &mut [f64]) {
+		self.as_slice().copy_into(index, input, output)
+	}
+}
+
+
+/// A dataset stored in two matrices, one sample per row
+#[derive(Debug,Clone,PartialEq)]
+pub struct MatrixDataset {
+	pub inputs : Matrix<f64>,
+	pub outputs : Matrix<f64>,
+}
+
+impl MatrixDataset {
+
+	pub fn new(inputs : Matrix<f64>, outputs : Matrix<f64>) -> MatrixDataset {
+		assert!(inputs.rows == outputs.rows, "inputs and outputs should have the same number of rows");
+		MatrixDataset { inputs, outputs }
+	}
+
+	/// Copy the samples in two contiguous matrices, every sample must have the sizes of the first one
+	pub fn from_samples(samples : &[Sample]) -> MatrixDataset {
+		let (input_size,output_size) = (samples.input_size(),samples.output_size());
+		let mut inputs = Vec::with_capacity(samples.len()*input_size);
+		let mut outputs = Vec::with_capacity(samples.len()*output_size);
+		for (input,output) in samples {
+			assert!(input.len() == input_size && output.len() == output_size, "all the samples should have the same sizes");
+			inputs.extend_from_slice(input);
+			outputs.extend_from_slice(output);
+		}
+		MatrixDataset::new(Matrix::from_vec(samples.len(), input_size, inputs), Matrix::from_vec(samples.len(), output_size, outputs))
+	}
+}
+
+impl Dataset for MatrixDataset {
+
+	fn len(&self) -> usize {
+		self.inputs.rows
+	}
+
+	fn input_size(&self) -> usize {
+		self.inputs.cols
+	}
+
+	fn output_size(&self) -> usize {
+		self.outputs.cols
+	}
+
+	fn get(&self, index : usize) -> Sample {
+		let mut sample = (vec![0.0;self.inputs.cols],vec![0.0;self.outputs.cols]);
+		self.copy_into(index, &mut sample.0, &mut sample.1);
+		sample
+	}
+
+	fn copy_into(&self, index : usize, input : &mut [f64], output : &mut [f64]) {
+		input.copy_from_slice(&self.inputs.values[index*self.inputs.cols..(index+1)*self.inputs.cols]);
+		output.copy_from_slice(&self.outputs.values[index*self.outputs.cols..(index+1)*self.outputs.cols]);
+	}
+}
+
+
+/// A dataset read from disk on demand, for data that doesn't fit in memory.
+/// The file holds a small header followed by the samples as little endian `f64`.
+#[derive(Debug)]
+pub struct FileDataset {
+	file : RefCell<File>,
+	input_size : usize,
+	output_size : usize,
+	len : usize,
+}
+
+impl FileDataset {
+
+	/// Write samples to a file readable by `open`, returns the number of samples written
+	///
+	/// # Arguments
+	/// * `path` - the file to create
+	/// * `samples` - any iterator, it is consumed one sample at a time
+	pub fn write(path : impl AsRef<Path>, input_size : usize, output_size : usize, samples : impl IntoIterator<Item = Sample>) -> io::Result<usize> {
+		let mut writer = BufWriter::new(File::create(path)?);
+		writer.write_all(FILE_MAGIC)?;
+		writer.write_all(&(input_size as u64).to_le_bytes())?;
+		writer.write_all(&(output_size as u64).to_le_bytes())?;
+		writer.write_all(&0u64.to_le_bytes())?;
+
+		let mut len = 0;
+		for (input,output) in samples {
+			if input.len() != input_size || output.len() != output_size {
+				return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("sample {len} has sizes ({},{}) instead of ({input_size},{output_size})",input.len(),output.len())));
+			}
+			for value in input.iter().chain(&output) {
+				writer.write_all(&value.to_le_bytes())?;
+			}
+			len += 1;
+		}
+
+		// the number of samples is only known at the end
+		let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+		file.seek(SeekFrom::Start(24))?;
+		file.write_all(&(len as u64).to_le_bytes())?;
+		Ok(len)
+	}
+
+	pub fn open(path : impl AsRef<Path>) -> io::Result<FileDataset> {
+		let mut file = File::open(path)?;
+		let mut header = [0u8;FILE_HEADER_SIZE as usize];
+		file.read_exact(&mut header)?;
+		if &header[0..8] != FILE_MAGIC {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "not a dataset file"));
+		}
+		let read_u64 = |start : usize| u64::from_le_bytes(header[start..start+8].try_into().unwrap()) as usize;
+		let (input_size,output_size,len) = (read_u64(8),read_u64(16),read_u64(24));
+
+		let expected = FILE_HEADER_SIZE + (len*(input_size+output_size)*8) as u64;
+		if file.metadata()?.len() != expected {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the dataset file should be {expected} bytes long")));
+		}
+
+		Ok(FileDataset { file : RefCell::new(file), input_size, output_size, len })
+	}
+}
+
+impl Dataset for FileDataset {
+
+	fn len(&self) -> usize {
+		self.len
+	}
+
+	fn input_size(&self) -> usize {
+		self.input_size
+	}
+
+	fn output_size(&self) -> usize {
+		self.output_size
+	}
+
+	fn get(&self, index : usize) -> Sample {
+		let mut sample = (vec![0.0;self.input_size],vec![0.0;self.output_size]);
+		self.copy_into(index, &mut sample.0, &mut sample.1);
+		sample
+	}
+
+	fn copy_into(&self, index : usize, input : &mut [f64], output : &mut [f64]) {
+		assert!(index < self.len, "index {index} out of a dataset of {} samples",self.len);
+		let record = (self.input_size+self.output_size)*8;
+		let mut bytes = vec![0u8;record];
+
+		let mut file = self.file.borrow_mut();
+		file.seek(SeekFrom::Start(FILE_HEADER_SIZE + (index*record) as u64))
+			.and_then(|_| file.read_exact(&mut bytes))
+			.expect("the dataset file can't be read");
+
+		let mut values = bytes.chunks_exact(8).map(|value| f64::from_le_bytes(value.try_into().unwrap()));
+		input.iter_mut().chain(output.iter_mut()).for_each(|value| *value = values.next().unwrap());
+	}
+}
+
+
+/// A mini-batch, sample `i` is row `i` of both matrices
+#[derive(Debug,Clone,PartialEq)]
+pub struct Batch {
+	pub inputs : Matrix<f64>,
+	pub outputs : Matrix<f64>,
+}
+
+impl Batch {
+
+	fn with_capacity(input_size : usize, output_size : usize, capacity : usize) -> Batch {
+		Batch {
+			inputs : Matrix { rows : 0, cols : input_size, values : Vec::with_capacity(capacity*input_size) },
+			outputs : Matrix { rows : 0, cols : output_size, values : Vec::with_capacity(capacity*output_size) },
+		}
+	}
+
+	fn push(&mut self, input : &[f64], output : &[f64]) {
+		assert!(input.len() == self.inputs.cols && output.len() == self.outputs.cols, "all the samples should have the same sizes");
+		self.inputs.values.extend_from_slice(input);
+		self.outputs.values.extend_from_slice(output);
+		self.inputs.rows += 1;
+		self.outputs.rows += 1;
+	}
+
+	/// add a zeroed row and returns it to be filled
+	fn push_zeroed(&mut self) -> (&mut [f64],&mut [f64]) {
+		let (input_size,output_size) = (self.inputs.cols,self.outputs.cols);
+		self.inputs.values.resize(self.inputs.values.len()+input_size, 0.0);
+		self.outputs.values.resize(self.outputs.values.len()+output_size, 0.0);
+		self.inputs.rows += 1;
+		self.outputs.rows += 1;
+		let (input_start,output_start) = (self.inputs.values.len()-input_size,self.outputs.values.len()-output_size);
+		(&mut self.inputs.values[input_start..],&mut self.outputs.values[output_start..])
+	}
+
+	pub fn len(&self) -> usize {
+		self.inputs.rows
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn input(&self, index : usize) -> &[f64] {
+		&self.inputs.values[index*self.inputs.cols..(index+1)*self.inputs.cols]
+	}
+
+	pub fn output(&self, index : usize) -> &[f64] {
+		&self.outputs.values[index*self.outputs.cols..(index+1)*self.outputs.cols]
+	}
+
+	/// the (input,output) rows
+	pub fn iter(&self) -> impl ExactSizeIterator<Item = (&[f64],&[f64])> {
+		(0..self.len()).map(|index| (self.input(index),self.output(index)))
+	}
+}
+
+
+/// Something producing the mini-batches of one epoch, see `NeuralNetWork::train_loader`
+pub trait Loader {
+	/// the batches of the next epoch
+	fn epoch(&mut self) -> Box<dyn Iterator<Item = Batch> + '_>;
+
+	/// what is needed to replay the next epoch, saved in checkpoints. Loaders without randomness can keep the default.
+	fn state(&self) -> LoaderState {
+		LoaderState::default()
+	}
+
+	/// Restore a state returned by `state`, the next epoch gives the same batches
+	fn set_state(&mut self, _state : &LoaderState) {}
+}
+
+
+/// Splits a `Dataset` in mini-batches, in a new random order at every epoch if shuffling is enabled
+///
+/// ```
+/// use rust_simple_nn::dataset::*;
+///
+/// let data : Vec<(Vec<f64>,Vec<f64>)> = (0..10).map(|i| (vec![i as f64],vec![0.0])).collect();
+/// let mut loader = DataLoader::new(data, 4).shuffle(7).drop_last(true);
+///
+/// let sizes : Vec<usize> = loader.epoch().map(|batch| batch.len()).collect();
+/// assert_eq!(sizes, vec![4, 4]);
+/// ```
+#[derive(Debug)]
+pub struct DataLoader<D : Dataset> {
+	dataset : D,
+	batch_size : usize,
+	drop_last : bool,
+	rng : Option<ChaCha8Rng>,
+	order : Vec<usize>,
+	/// the resampling and the indexes of the samples of each class
+	balance : Option<(Balance,Vec<Vec<usize>>)>,
+}
+
+impl<D : Dataset> DataLoader<D> {
+
+	/// Batches of `batch_size` samples in the dataset order
+	pub fn new(dataset : D, batch_size : usize) -> DataLoader<D> {
+		assert!(batch_size > 0, "the batch size should be at least 1");
+		let order = (0..dataset.len()).collect();
+		DataLoader { dataset, batch_size, drop_last : false, rng : None, order, balance : None }
+	}
+
+	/// Reshuffle the samples before every epoch, with an rng seeded by `seed`
+	pub fn shuffle(mut self, seed : u64) -> DataLoader<D> {
+		self.rng = Some(ChaCha8Rng::seed_from_u64(seed));
+		self
+	}
+
+	/// Skip the last batch of an epoch when it is smaller than the batch size
+	pub fn drop_last(mut self, drop_last : bool) -> DataLoader<D> {
+		self.drop_last = drop_last;
+		self
+	}
+
+	/// Resample the classes (see `class_of`) to the same number of samples at every epoch,
+	/// the samples are shuffled too, with an rng seeded by `seed` unless `shuffle` already set one
+	pub fn balance(mut self, balance : Balance, seed : u64) -> DataLoader<D> {
+		let mut classes : Vec<Vec<usize>> = vec![];
+		for index in 0..self.dataset.len() {
+			let class = class_of(&self.dataset.get(index).1);
+			if class >= classes.len() {
+				classes.resize(class+1, vec![]);
+			}
+			classes[class].push(index);
+		}
+		classes.retain(|class| !class.is_empty());
+
+		let per_class = balance.samples_per_class(&classes);
+		self.order = (0..per_class*classes.len()).collect();
+		self.balance = Some((balance,classes));
+		self.rng.get_or_insert_with(|| ChaCha8Rng::seed_from_u64(seed));
+		self
+	}
+
+	pub fn dataset(&self) -> &D {
+		&self.dataset
+	}
+
+	pub fn batch_size(&self) -> usize {
+		self.batch_size
+	}
+
+	/// number of batches in an epoch
+	pub fn len(&self) -> usize {
+		if self.drop_last {
+			self.order.len() / self.batch_size
+		} else {
+			self.order.len().div_ceil(self.batch_size)
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+impl<D : Dataset> Loader for DataLoader<D> {
+
+	fn state(&self) -> LoaderState {
+		LoaderState { rng : self.rng.as_ref().map(RngState::of), order : self.order.clone() }
+	}
+
+	fn set_state(&mut self, state : &LoaderState) {
+		assert!(state.order.len() == self.order.len(), "the state should come from a loader over the same dataset");
+		self.rng = state.rng.map(|rng| rng.rng());
+		self.order = state.order.clone();
+	}
+
+	fn epoch(&mut self) -> Box<dyn Iterator<Item = Batch> + '_> {
+		if let (Some((balance,classes)),Some(rng)) = (&self.balance,&mut self.rng) {
+			self.order = balance.resample(classes, rng);
+		}
+		if let Some(rng) = &mut self.rng {
+			self.order.shuffle(rng);
+		}
+		let (dataset,batch_size,drop_last) = (&self.dataset,self.batch_size,self.drop_last);
+
+		Box::new(self.order.chunks(batch_size)
+			.filter(move |indexes| !drop_last || indexes.len() == batch_size)
+			.map(move |indexes| {
+				let mut batch = Batch::with_capacity(dataset.input_size(), dataset.output_size(), indexes.len());
+				for index in indexes {
+					let (input,output) = batch.push_zeroed();
+					dataset.copy_into(*index, input, output);
+				}
+				batch
+			}))
+	}
+}
+
+
+/// Batches read from an iterator, for data generated on the fly or read from a large file line by line.
+/// The source is called at every epoch and can only be shuffled approximately, with a buffer.
+///
+/// ```
+/// use rust_simple_nn::dataset::*;
+///
+/// let mut loader = StreamLoader::new(|| (0..10).map(|i| (vec![i as f64],vec![0.0])), 3).shuffle_buffer(5, 1);
+/// assert_eq!(loader.epoch().map(|batch| batch.len()).sum::<usize>(), 10);
+/// ```
+pub struct StreamLoader<F> {
+	source : F,
+	batch_size : usize,
+	drop_last : bool,
+	buffer_size : usize,
+	rng : ChaCha8Rng,
+}
+
+impl<F,I> StreamLoader<F>
+where
+	F : FnMut() -> I,
+	I : Iterator<Item = Sample>,
+{
+	/// # Arguments
+	/// * `source` - returns the iterator over the samples of one epoch
+	/// * `batch_size` - number of samples per batch
+	pub fn new(source : F, batch_size : usize) -> StreamLoader<F> {
+		assert!(batch_size > 0, "the batch size should be at least 1");
+		StreamLoader { source, batch_size, drop_last : false, buffer_size : 0, rng : ChaCha8Rng::seed_from_u64(0) }
+	}
+
+	/// Shuffle with a buffer of `size` samples : each sample is swapped with a random one of the buffer
+	pub fn shuffle_buffer(mut self, size : usize, seed : u64) -> StreamLoader<F> {
+		self.buffer_size = size;
+		self.rng = ChaCha8Rng::seed_from_u64(seed);
+		self
+	}
+
+	pub fn drop_last(mut self, drop_last : bool) -> StreamLoader<F> {
+		self.drop_last = drop_last;
+		self
+	}
+}
+
+impl<F,I> Loader for StreamLoader<F>
+where
+	F : FnMut() -> I,
+	I : Iterator<Item = Sample> + 'static,
+{
+	fn state(&self) -> LoaderState {
+		LoaderState { rng : Some(RngState::of(&self.rng)), order : vec![] }
+	}
+
+	fn set_state(&mut self, state : &LoaderState) {
+		if let Some(rng) = state.rng {
+			self.rng = rng.rng();
+		}
+	}
+
+	fn epoch(&mut self) -> Box<dyn Iterator<Item = Batch> + '_> {
+		let (batch_size,drop_last,buffer_size) = (self.batch_size,self.drop_last,self.buffer_size);
+		let rng = &mut self.rng;
+		let mut samples = (self.source)();
+		let mut buffer : Vec<Sample> = Vec::with_capacity(buffer_size);
+
+		// next sample of the shuffled stream
+		let mut next = move || -> Option<Sample> {
+			while buffer.len() < buffer_size {
+				match samples.next() {
+					Some(sample) => buffer.push(sample),
+					None => break,
+				}
+			}
+			if buffer.is_empty() {
+				return samples.next();
+			}
+			let index = rng.gen_range(0..buffer.len());
+			Some(buffer.swap_remove(index))
+		};
+
+		Box::new(std::iter::from_fn(move || {
+			let first = next()?;
+			let mut batch = Batch::with_capacity(first.0.len(), first.1.len(), batch_size);
+			batch.push(&first.0, &first.1);
+			while batch.len() < batch_size {
+				match next() {
+					Some((input,output)) => batch.push(&input, &output),
+					None => break,
+				}
+			}
+			(!drop_last || batch.len() == batch_size).then_some(batch)
+		}))
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                              Class imbalance                               */
+/* -------------------------------------------------------------------------- */
+
+
+/// Weight of the samples of each class (see `class_of`) during training, see `NeuralNetWork::train_with_class_weights`
+#[derive(Debug,Clone,PartialEq)]
+pub enum ClassWeights {
+	/// `samples / (classes * samples of the class)`, so every class weighs the same in total
+	Balanced,
+	/// the weight of each class, the classes missing from the map weigh 1
+	Map(BTreeMap<usize,f64>),
+}
+
+impl ClassWeights {
+
+	/// The weight of each class present in the data
+	pub fn class_weights(&self, data : &[Sample]) -> BTreeMap<usize,f64> {
+		let mut counts : BTreeMap<usize,usize> = BTreeMap::new();
+		for (_,target) in data {
+			*counts.entry(class_of(target)).or_default() += 1;
+		}
+		counts.iter().map(|(class,count)| {
+			let weight = match self {
+				ClassWeights::Balanced => data.len() as f64 / (counts.len() * count) as f64,
+				ClassWeights::Map(weights) => weights.get(class).copied().unwrap_or(1.0),
+			};
+			(*class,weight)
+		}).collect()
+	}
+
+	/// The weight of each sample, from its class
+	pub fn sample_weights(&self, data : &[Sample]) -> Vec<f64> {
+		let weights = self.class_weights(data);
+		data.iter().map(|(_,target)| weights[&class_of(target)]).collect()
+	}
+}
+
+
+/// How `DataLoader::balance` gives every class the same number of samples in an epoch
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Balance {
+	/// draw samples of the smaller classes again until they are as large as the largest one
+	Oversample,
+	/// keep a random subset of each class as large as the smallest one, drawn again at every epoch
+	Undersample,
+}
+
+impl Balance {
+
+	fn samples_per_class(&self, classes : &[Vec<usize>]) -> usize {
+		let sizes = classes.iter().map(|class| class.len());
+		match self {
+			Balance::Oversample => sizes.max().unwrap_or(0),
+			Balance::Undersample => sizes.min().unwrap_or(0),
+		}
+	}
+
+	/// indexes of the samples of one epoch, grouped by class
+	fn resample(&self, classes : &[Vec<usize>], rng : &mut ChaCha8Rng) -> Vec<usize> {
+		let per_class = self.samples_per_class(classes);
+		classes.iter().flat_map(|class| {
+			let mut shuffled = class.clone();
+			shuffled.shuffle(rng);
+			// every sample is kept once before any is drawn twice
+			let extra : Vec<usize> = (class.len()..per_class).map(|_| class[rng.gen_range(0..class.len())]).collect();
+			shuffled.into_iter().chain(extra).take(per_class).collect::<Vec<usize>>()
+		}).collect()
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn numbered(len : usize) -> Vec<Sample> {
+		(0..len).map(|i| (vec![i as f64,-(i as f64)],vec![2.0*i as f64])).collect()
+	}
+
+	/// first input of every sample of an epoch
+	fn epoch_order(loader : &mut impl Loader) -> Vec<f64> {
+		loader.epoch().flat_map(|batch| batch.iter().map(|(input,_)| input[0]).collect::<Vec<_>>()).collect()
+	}
+
+	#[test]
+	fn batches_are_contiguous_rows(){
+		let mut loader = DataLoader::new(numbered(5), 2);
+		let batches : Vec<Batch> = loader.epoch().collect();
+
+		assert!(batches.len() == 3 && loader.len() == 3);
+		assert!(batches[1].inputs == Matrix::from_vec(2, 2, vec![2.0,-2.0,3.0,-3.0]));
+		assert!(batches[1].outputs == Matrix::from_vec(2, 1, vec![4.0,6.0]));
+		assert!(batches[2].len() == 1);
+	}
+
+	#[test]
+	fn reshuffled_every_epoch(){
+		let mut loader = DataLoader::new(numbered(50), 8).shuffle(3);
+		let first = epoch_order(&mut loader);
+		let second = epoch_order(&mut loader);
+
+		assert!(first != second);
+		let mut sorted = first.clone();
+		sorted.sort_by(f64::total_cmp);
+		assert!(sorted == (0..50).map(|i| i as f64).collect::<Vec<_>>());
+
+		// the same seed gives the same orders
+		let mut again = DataLoader::new(numbered(50), 8).shuffle(3);
+		assert!(epoch_order(&mut again) == first);
+	}
+
+	#[test]
+	fn drop_last_batch(){
+		let mut loader = DataLoader::new(numbered(10), 4).drop_last(true);
+		assert!(loader.len() == 2);
+		assert!(loader.epoch().all(|batch| batch.len() == 4));
+	}
+
+	#[test]
+	fn matrix_dataset_from_samples(){
+		let data = MatrixDataset::from_samples(&numbered(3));
+		assert!(data.len() == 3 && data.input_size() == 2 && data.output_size() == 1);
+		assert!(data.get(2) == numbered(3)[2]);
+	}
+
+	#[test]
+	fn file_dataset(){
+		let path = std::env::temp_dir().join(format!("rust_simple_nn_dataset_{}.bin",std::process::id()));
+		assert!(FileDataset::write(&path, 2, 1, numbered(20)).unwrap() == 20);
+
+		let data = FileDataset::open(&path).unwrap();
+		assert!(data.len() == 20 && data.get(13) == numbered(20)[13]);
+
+		let mut loader = DataLoader::new(data, 6).shuffle(1);
+		assert!(epoch_order(&mut loader).len() == 20);
+
+		assert!(FileDataset::write(&path, 3, 1, numbered(2)).is_err());
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn class_weights(){
+		let data : Vec<Sample> = (0..10).map(|i| (vec![i as f64],vec![(i < 2) as usize as f64])).collect();
+		let balanced = ClassWeights::Balanced.class_weights(&data);
+		assert!(balanced[&0] == 10.0/16.0 && balanced[&1] == 10.0/4.0);
+
+		let weights = ClassWeights::Map(BTreeMap::from([(1,5.0)])).sample_weights(&data);
+		assert!(weights[0] == 5.0 && weights[2] == 1.0);
+	}
+
+	#[test]
+	fn balanced_loader(){
+		// 2 samples of class 1 and 8 of class 0
+		let data : Vec<Sample> = (0..10).map(|i| (vec![i as f64],vec![(i < 2) as usize as f64])).collect();
+		let minority = |order : &[f64]| order.iter().filter(|x| **x < 2.0).count();
+
+		let mut loader = DataLoader::new(data.clone(), 4).balance(Balance::Oversample, 1);
+		let order = epoch_order(&mut loader);
+		assert!(order.len() == 16 && loader.len() == 4 && minority(&order) == 8);
+		let mut majority : Vec<f64> = order.into_iter().filter(|x| *x >= 2.0).collect();
+		majority.sort_by(f64::total_cmp);
+		assert!(majority == (2..10).map(|i| i as f64).collect::<Vec<_>>());
+
+		let mut loader = DataLoader::new(data, 4).balance(Balance::Undersample, 1);
+		let first = epoch_order(&mut loader);
+		assert!(first.len() == 4 && minority(&first) == 2);
+		// the majority samples kept change between epochs
+		assert!((0..5).any(|_| epoch_order(&mut loader) != first));
+	}
+
+	#[test]
+	fn stream_loader(){
+		let mut loader = StreamLoader::new(|| numbered(23).into_iter(), 5);
+		assert!(epoch_order(&mut loader) == (0..23).map(|i| i as f64).collect::<Vec<_>>());
+
+		let mut loader = StreamLoader::new(|| numbered(23).into_iter(), 5).shuffle_buffer(10, 4).drop_last(true);
+		let first = epoch_order(&mut loader);
+		assert!(first.len() == 20);
+		assert!(first != epoch_order(&mut loader));
+	}
+}
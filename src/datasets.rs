@@ -0,0 +1,111 @@
+use rand::Rng;
+
+/// Names accepted by `builtin`
+pub const BUILTIN_DATASETS : [&str;4] = ["quadrant","function1","function2","xor"];
+
+/// Number of samples generated when none is asked for
+pub const DEFAULT_SAMPLES : usize = 1000000;
+
+
+/// Generate one of the synthetic datasets, `None` if the name is unknown
+///
+/// # Arguments
+/// * `name` - one of `BUILTIN_DATASETS`
+/// * `samples` - number of (input,output) pairs
+/// * `rng` - the random source, seed it for reproducible results
+pub fn builtin<R : Rng + ?Sized>(name : &str, samples : usize, rng : &mut R) -> Option<Vec<(Vec<f64>,Vec<f64>)>> {
+	match name.trim().to_lowercase().as_str() {
+		"quadrant" => Some(gen_quadrant(samples, rng)),
+		"function1" => Some(generate_data(samples, rng)),
+		"function2" => Some(generate_id(samples, rng)),
+		"xor" => Some(generate_xor_data(samples)),
+		_ => None,
+	}
+}
+
+/// Returns the (input,output) sizes of a builtin dataset
+pub fn builtin_shape(name : &str) -> Option<(usize,usize)> {
+	match name.trim().to_lowercase().as_str() {
+		"quadrant" => Some((2,1)),
+		"function1" => Some((2,1)),
+		"function2" => Some((5,5)),
+		"xor" => Some((2,1)),
+		_ => None,
+	}
+}
+
+
+pub fn generate_data<R : Rng + ?Sized>(samples : usize, rng : &mut R)->Vec<(Vec<f64>,Vec<f64>)>{
+	let mut result = vec![];
+	for _ in 0..samples {
+
+		let input = vec![rng.gen_range(1.0..2.0),rng.gen_range(1.0..2.0)];
+		let ouput = function1(&input);
+		result.push((input,ouput));
+	};
+
+	result
+}
+
+pub fn generate_id<R : Rng + ?Sized>(samples : usize, rng : &mut R)->Vec<(Vec<f64>,Vec<f64>)>{
+	let mut result = vec![];
+	for _ in 0..samples {
+
+		let input = vec![rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0)];
+		let ouput = function2(&input);
+		result.push((input,ouput));
+	};
+
+	result
+}
+
+
+pub fn gen_quadrant<R : Rng + ?Sized>(samples : usize, rng : &mut R)->Vec<(Vec<f64>,Vec<f64>)>{
+	let mut result = vec![];
+	for _ in 0..samples {
+
+		let input = vec![rng.gen_range(-10.0..10.0),rng.gen_range(-10.0..10.0)];
+		let ouput = some_quadrant(&input);
+		result.push((input,ouput));
+	};
+
+	result
+}
+
+/// The 4 xor cases repeated until `samples` pairs are generated
+pub fn generate_xor_data(samples : usize)->Vec<(Vec<f64>,Vec<f64>)>{
+	let cases = [
+		(vec![0.0,0.0],vec![0.0]),
+		(vec![0.0,1.0],vec![1.0]),
+		(vec![1.0,0.0],vec![1.0]),
+		(vec![1.0,1.0],vec![0.0]),
+	];
+
+	cases.iter().cycle().take(samples).cloned().collect()
+}
+
+//2 inputs, 1 output
+pub fn function1(input : &[f64]) -> Vec<f64>{
+	vec![((input[0]+input[1]).powi(2))]
+}
+
+//5 inputs, 5 outputs
+pub fn function2(input : &[f64]) -> Vec<f64>{
+	vec![
+		(input[0]+input[1]),
+		(input[0]+input[2]),
+		(input[3]+input[1]),
+		(input[4]+input[0]-input[3]*4.0),
+		(input[4]-input[0]-input[3]),
+	]
+}
+
+// if the coordoante is in a certain qudrant, then it's 1, otherwise it's 0
+pub fn some_quadrant(input : &[f64])-> Vec<f64>{
+	let (x,y) = (input[0],input[1]);
+	if x<0.0 && y>0.0 {
+		return vec![1.0];
+	}
+
+	vec![0.0]
+}
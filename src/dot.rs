@@ -0,0 +1,209 @@
+use std::fmt::Write;
+
+use crate::nn::*;
+
+
+/// What `NeuralNetWork::to_dot_with` draws
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct DotOptions {
+	/// one node per neuron and one edge per weight instead of one node per layer
+	pub neurons : bool,
+	/// neurons drawn per layer, the others are replaced by a `+n` node
+	pub max_neurons : usize,
+}
+
+impl Default for DotOptions {
+	fn default() -> Self {
+		DotOptions { neurons : false, max_neurons : 8 }
+	}
+}
+
+/// `text` between quotes, escaped for DOT
+fn quote(text : &str) -> String {
+	format!("\"{}\"",text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// Edge width for a weight, relative to the largest weight of its layer
+fn pen_width(weight : f64, max : f64) -> f64 {
+	if max == 0.0 { 0.25 } else { 0.25 + 2.75*weight.abs()/max }
+}
+
+
+impl NeuralNetWork {
+
+	/// The layers as a Graphviz DOT graph, see `to_dot_with` for the neurons
+	pub fn to_dot(&self) -> String {
+		self.to_dot_with(&DotOptions::default())
+	}
+
+	/// The network as a Graphviz DOT graph. With `options.neurons`, the edges between neurons are blue for positive
+	/// weights and red for negative ones, thicker for larger magnitudes.
+	/// The text only depends on the network, so it can be compared in tests or diffed between versions.
+	///
+	/// ```
+	/// use rust_simple_nn::nn::NeuralNetWork;
+	///
+	/// let nn = NeuralNetWork::new(&[2,3,1], "quadratic", "relu", "sigmoid");
+	/// let dot = nn.to_dot();
+	/// assert!(dot.starts_with("digraph network {") && dot.contains("layer_0 -> layer_1;"));
+	/// ```
+	pub fn to_dot_with(&self, options : &DotOptions) -> String {
+		assert!(options.max_neurons > 0, "at least one neuron per layer should be drawn");
+		let mut dot = String::new();
+		writeln!(dot, "digraph network {{").unwrap();
+		writeln!(dot, "\trankdir=LR;").unwrap();
+		match options.neurons {
+			true => self.write_neurons(&mut dot, options.max_neurons),
+			false => self.write_layers(&mut dot),
+		}
+		// the heads are fed by their slice of the output layer
+		let mut start = 0;
+		for head in self.heads() {
+			let name = quote(&format!("head_{}",head.name));
+			writeln!(dot, "\t{name} [shape=note, label={}];",quote(&format!("{}\n{} {} {} x{}",head.name,head.size,head.activation,head.cost,head.weight))).unwrap();
+			match options.neurons {
+				true => {
+					for n in start..(start+head.size).min(options.max_neurons) {
+						writeln!(dot, "\tn{}_{n} -> {name} [style=dashed];",self.layers.len()).unwrap();
+					}
+					// the neurons past the cap are in the `+n` node
+					if start+head.size > options.max_neurons {
+						writeln!(dot, "\tn{}_more -> {name} [style=dashed];",self.layers.len()).unwrap();
+					}
+				},
+				false => writeln!(dot, "\tlayer_{} -> {name} [style=dashed];",self.layers.len()-1).unwrap(),
+			}
+			start += head.size;
+		}
+		writeln!(dot, "}}").unwrap();
+		dot
+	}
+
+	fn write_layers(&self, dot : &mut String){
+		writeln!(dot, "\tnode [shape=box];").unwrap();
+		writeln!(dot, "\tinput [label={}];",quote(&format!("input\n{}",self.input_size()))).unwrap();
+		for (i,layer) in self.layers.iter().enumerate() {
+			let layer = layer.borrow();
+			let mut label = format!("dense_{i}\n{} {}",layer.len(),layer.activation_name());
+			if layer.norm().is_some() {
+				label.push_str("\nlayer norm");
+			}
+			if layer.dropout() > 0.0 {
+				write!(label, "\ndropout {}",layer.dropout()).unwrap();
+			}
+			writeln!(dot, "\tlayer_{i} [label={}];",quote(&label)).unwrap();
+		}
+		let mut previous = "input".to_string();
+		for i in 0..self.layers.len() {
+			writeln!(dot, "\t{previous} -> layer_{i};").unwrap();
+			previous = format!("layer_{i}");
+		}
+	}
+
+	fn write_neurons(&self, dot : &mut String, max_neurons : usize){
+		writeln!(dot, "\tsplines=line;").unwrap();
+		writeln!(dot, "\tnode [shape=circle, label=\"\", width=0.3];").unwrap();
+
+		// cluster 0 is the input, cluster i+1 the layer i
+		let mut sizes = vec![self.input_size()];
+		sizes.extend(self.layers.iter().map(|layer| layer.borrow().len()));
+		for (c,&size) in sizes.iter().enumerate() {
+			let label = match c {
+				0 => "input".to_string(),
+				_ => format!("dense_{} {}",c-1,self.layers[c-1].borrow().activation_name()),
+			};
+			writeln!(dot, "\tsubgraph cluster_{c} {{").unwrap();
+			writeln!(dot, "\t\tlabel={};",quote(&label)).unwrap();
+			for n in 0..size.min(max_neurons) {
+				writeln!(dot, "\t\tn{c}_{n};").unwrap();
+			}
+			if size > max_neurons {
+				writeln!(dot, "\t\tn{c}_more [shape=plaintext, label={}];",quote(&format!("+{}",size-max_neurons))).unwrap();
+			}
+			writeln!(dot, "\t}}").unwrap();
+		}
+
+		for (i,layer) in self.layers.iter().enumerate() {
+			let layer = layer.borrow();
+			let weights = layer.weights();
+			let max = weights.values.iter().fold(0.0, |max : f64, w| max.max(w.abs()));
+			for to in 0..weights.rows.min(max_neurons) {
+				for from in 0..weights.cols.min(max_neurons) {
+					let weight = weights.values[to*weights.cols + from];
+					let color = if weight < 0.0 { "red" } else { "blue" };
+					writeln!(dot, "\tn{i}_{from} -> n{}_{to} [color={color}, penwidth={:.2}];",i+1,pen_width(weight, max)).unwrap();
+				}
+			}
+		}
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::init::Init;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	fn network() -> NeuralNetWork {
+		let mut nn = NeuralNetWork::empty(2, "cross_entropy", ChaCha8Rng::seed_from_u64(0));
+		nn.add_layer(3, "relu", Init::Xavier, 0.5, true);
+		nn.add_layer(1, "sigmoid", Init::Xavier, 0.0, false);
+		// weights of the first layer, its biases, its norm gain and shift, then the output layer
+		nn.set_parameters(&[1.0,-0.5,0.0,2.0,-2.0,0.25, 0.0,0.0,0.0, 1.0,1.0,1.0,0.0,0.0,0.0, 0.5,-1.0,0.0, 0.0]);
+		nn
+	}
+
+	#[test]
+	fn layer_graph(){
+		let expected = "digraph network {
+	rankdir=LR;
+	node [shape=box];
+	input [label=\"input\\n2\"];
+	layer_0 [label=\"dense_0\\n3 relu\\nlayer norm\\ndropout 0.5\"];
+	layer_1 [label=\"dense_1\\n1 sigmoid\"];
+	input -> layer_0;
+	layer_0 -> layer_1;
+}
+";
+		assert!(network().to_dot() == expected);
+	}
+
+	#[test]
+	fn neuron_graph(){
+		let dot = network().to_dot_with(&DotOptions { neurons : true, max_neurons : 2 });
+
+		assert!(dot.contains("\tn0_0 -> n1_0 [color=blue, penwidth=1.62];\n"));
+		assert!(dot.contains("\tn0_1 -> n1_0 [color=red, penwidth=0.94];\n"));
+		assert!(dot.contains("\tn0_1 -> n1_1 [color=blue, penwidth=3.00];\n"));
+		assert!(dot.contains("\tn1_1 -> n2_0 [color=red, penwidth=3.00];\n"));
+		// the third neuron of the hidden layer is replaced by a +1 node without edges
+		assert!(dot.contains("\t\tn1_more [shape=plaintext, label=\"+1\"];\n") && !dot.contains("n1_2"));
+		assert!(dot.matches(" -> ").count() == 2*2 + 2);
+		assert!(dot.matches('{').count() == dot.matches('}').count());
+	}
+
+	#[test]
+	fn heads_and_quoting(){
+		let mut nn = NeuralNetWork::empty(2, "quadratic", ChaCha8Rng::seed_from_u64(0));
+		nn.add_layer(3, "tanh", Init::Xavier, 0.0, false);
+		nn.add_heads(&[Head::new("say \"hi\"", 1, "identity", "quadratic"),Head::new("class", 2, "sigmoid", "cross_entropy")], Init::Xavier);
+
+		let dot = nn.to_dot();
+		assert!(dot.contains("\t\"head_say \\\"hi\\\"\" [shape=note, label=\"say \\\"hi\\\"\\n1 identity quadratic x1\"];\n"));
+		assert!(dot.contains("\tlayer_1 -> \"head_class\" [style=dashed];\n"));
+		let dot = nn.to_dot_with(&DotOptions { neurons : true, ..DotOptions::default() });
+		assert!(dot.contains("\tn2_1 -> \"head_class\" [style=dashed];\n\tn2_2 -> \"head_class\" [style=dashed];\n"));
+
+		// the class head starts past the cap, it's fed by the +2 node only
+		let dot = nn.to_dot_with(&DotOptions { neurons : true, max_neurons : 1 });
+		assert!(dot.contains("\tn2_0 -> \"head_say \\\"hi\\\"\" [style=dashed];\n") && dot.contains("\tn2_more -> \"head_class\" [style=dashed];\n"));
+		assert!(dot.matches("-> \"head_").count() == 2);
+	}
+}
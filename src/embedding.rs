@@ -0,0 +1,208 @@
+use std::collections::BTreeSet;
+
+use rand::Rng;
+
+use crate::block::*;
+use crate::init::*;
+use crate::optimizer::*;
+use crate::tensor::*;
+
+
+/// Maps integer ids to learned dense vectors, for categorical inputs with many values.
+/// Only the rows of the ids seen in a mini batch are updated.
+///
+/// The input is `[ids]` with the ids stored as `f64`. With `passthrough(n)` the input is
+/// `[ids + n]` and its last `n` values are dense features concatenated after the embeddings,
+/// so the dense layers of a `Sequential` see both.
+#[derive(Debug,Clone)]
+pub struct Embedding {
+	table : Param,
+	passthrough : usize,
+	/// optimizer state of each row, a row is updated only when it's used
+	row_states : Vec<ParamState>,
+	/// rows with a gradient since the last `zero_grad`
+	touched : BTreeSet<usize>,
+	ids : Vec<usize>,
+}
+
+impl Embedding {
+
+	/// # Arguments
+	/// * `vocabulary_size` - number of ids, from 0 to `vocabulary_size-1`
+	/// * `dimension` - size of the vector of an id
+	/// * `init` - how the vectors are drawn
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new<R : Rng + ?Sized>(vocabulary_size : usize, dimension : usize, init : Init, rng : &mut R) -> Embedding {
+		assert!(vocabulary_size > 0 && dimension > 0,"an embedding needs at least one id and one dimension");
+		Embedding {
+			table : Param::new(init.weights(vocabulary_size, dimension, rng)),
+			passthrough : 0,
+			row_states : vec![ParamState::default();vocabulary_size],
+			touched : BTreeSet::new(),
+			ids : vec![],
+		}
+	}
+
+	/// Concatenate the last `features` values of the input, unchanged, after the embeddings
+	pub fn passthrough(mut self, features : usize) -> Embedding {
+		self.passthrough = features;
+		self
+	}
+
+	pub fn vocabulary_size(&self) -> usize {
+		self.table.value.rows
+	}
+
+	pub fn dimension(&self) -> usize {
+		self.table.value.cols
+	}
+
+	/// The vector of an id
+	pub fn vector(&self, id : usize) -> &[f64] {
+		let dimension = self.dimension();
+		&self.table.value.values[id*dimension..(id+1)*dimension]
+	}
+
+	/// Ids are rounded to the nearest integer, panics if one is out of the vocabulary
+	fn id(&self, value : f64) -> usize {
+		let id = value.round();
+		assert!(id >= 0.0 && (id as usize) < self.vocabulary_size(),"the id {value} is out of the vocabulary of {} ids",self.vocabulary_size());
+		id as usize
+	}
+}
+
+impl Block for Embedding {
+
+	fn name(&self) -> String {
+		match self.passthrough {
+			0 => format!("embedding {}x{}",self.vocabulary_size(),self.dimension()),
+			features => format!("embedding {}x{} +{features}",self.vocabulary_size(),self.dimension()),
+		}
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		assert!(input_shape.len()==1,"an embedding takes a vector of ids, got {input_shape:?}");
+		assert!(input_shape[0] > self.passthrough,"the input has no id before the {} dense features",self.passthrough);
+		let nb_ids = input_shape[0] - self.passthrough;
+		match self.passthrough {
+			0 => vec![nb_ids,self.dimension()],
+			features => vec![nb_ids*self.dimension() + features],
+		}
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		let shape = self.output_shape(&input.shape);
+		let (ids,features) = input.values.split_at(input.len() - self.passthrough);
+		self.ids = ids.iter().map(|value| self.id(*value)).collect();
+
+		let mut output = Vec::with_capacity(shape_len(&shape));
+		for id in &self.ids {
+			output.extend_from_slice(self.vector(*id));
+		}
+		output.extend_from_slice(features);
+		Tensor::from_vec(&shape, output)
+	}
+
+	/// The ids get a zero gradient
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		let dimension = self.dimension();
+		for (i,id) in self.ids.iter().enumerate() {
+			let rows = &mut self.table.grad.values[id*dimension..(id+1)*dimension];
+			rows.iter_mut().zip(&grad_output.values[i*dimension..(i+1)*dimension]).for_each(|(g,d)| *g += d);
+			self.touched.insert(*id);
+		}
+
+		let mut grad_input = vec![0.0;self.ids.len()];
+		grad_input.extend_from_slice(&grad_output.values[self.ids.len()*dimension..]);
+		Tensor::from_vec(&[grad_input.len()], grad_input)
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		vec![&self.table]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![&mut self.table]
+	}
+
+	fn zero_grad(&mut self){
+		let dimension = self.dimension();
+		for id in std::mem::take(&mut self.touched) {
+			self.table.grad.values[id*dimension..(id+1)*dimension].fill(0.0);
+		}
+	}
+
+	fn update(&mut self, mean_value : f64, learning_rate : f64, optimizer : &Optimizer){
+		let dimension = self.dimension();
+		for id in &self.touched {
+			let range = id*dimension..(id+1)*dimension;
+			optimizer.update(&mut self.table.value.values[range.clone()], &self.table.grad.values[range], &mut self.row_states[*id], learning_rate, mean_value);
+		}
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	#[test]
+	fn embedding_gradients(){
+		let mut rng = ChaCha8Rng::seed_from_u64(0);
+		check_gradients(&mut Embedding::new(5, 3, Init::Normal { std : 1.0 }, &mut rng), &Tensor::from_vec(&[3], vec![4.0,1.0,4.0]));
+		let mut embedding = Embedding::new(5, 3, Init::Normal { std : 1.0 }, &mut rng).passthrough(2);
+		assert!(embedding.output_shape(&[4]) == vec![8]);
+		check_gradients(&mut embedding, &Tensor::from_vec(&[4], vec![0.0,3.0,0.7,-1.2]));
+	}
+
+	#[test]
+	fn only_looked_up_rows_change(){
+		let mut rng = ChaCha8Rng::seed_from_u64(1);
+		let mut embedding = Embedding::new(6, 2, Init::Xavier, &mut rng);
+		let before = embedding.clone();
+
+		embedding.zero_grad();
+		embedding.forward(&Tensor::from_vec(&[2], vec![1.0,4.0]));
+		embedding.backward(&Tensor::from_vec(&[2,2], vec![1.0;4]));
+		embedding.update(1.0, 0.1, &Optimizer::adam());
+
+		for id in 0..6 {
+			assert!((embedding.vector(id) != before.vector(id)) == (id == 1 || id == 4));
+		}
+		embedding.zero_grad();
+		assert!(embedding.table.grad.values.iter().all(|g| *g == 0.0));
+	}
+
+	#[test]
+	fn categories_with_dense_features(){
+		// the target depends on the group of the id (id % 3) and on a dense feature
+		let data : Vec<TensorSample> = (0..300).map(|i| {
+			let (id,x) = (i % 30,((i*7) % 11) as f64 / 10.0);
+			let y = [0.2,0.5,0.8][id % 3] + 0.1*x;
+			(Tensor::from_vec(&[2], vec![id as f64,x]),vec![y])
+		}).collect();
+
+		let mut rng = ChaCha8Rng::seed_from_u64(2);
+		let mut model = Sequential::new(&[2], "quadratic", ChaCha8Rng::seed_from_u64(3));
+		model.add(Embedding::new(30, 4, Init::Normal { std : 0.1 }, &mut rng).passthrough(1));
+		model.dense(8, "tanh");
+		model.dense(1, "identity");
+		assert!(model.output_shape() == vec![5]);
+
+		let options = adam_options(10, 40);
+		model.train_with(&data, &options);
+		let error = data.iter().map(|(input,output)| (model.predict(input)[0] - output[0]).abs()).fold(0.0, f64::max);
+		assert!(error < 0.05, "largest error {error}");
+	}
+}
@@ -0,0 +1,473 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::matrix::standard_normal;
+use crate::nn::*;
+
+
+/// A set of parameters of a network (see `NeuralNetWork::parameters`) and its fitness
+#[derive(Debug,Clone,PartialEq)]
+pub struct Individual {
+	pub genome : Vec<f64>,
+	/// `None` until the individual is evaluated
+	pub fitness : Option<f64>,
+}
+
+/// How the parents of the next generation are picked
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Selection {
+	/// the fittest of `size` individuals drawn at random
+	Tournament { size : usize },
+	/// an individual drawn at random among the fittest `fraction` of the population
+	Truncation { fraction : f64 },
+}
+
+/// How two parents are combined
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Crossover {
+	/// the child is a copy of the first parent
+	None,
+	/// each gene comes from either parent
+	Uniform,
+	/// the genes before a random point come from the first parent
+	SinglePoint,
+	/// each gene is a random blend of the genes of the parents
+	Arithmetic,
+}
+
+/// Fitness of each generation
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct GenerationStats {
+	pub generation : usize,
+	pub best : f64,
+	pub mean : f64,
+	pub worst : f64,
+}
+
+
+/// Combine two genomes of the same length
+pub fn crossover(first : &[f64], second : &[f64], kind : Crossover, rng : &mut ChaCha8Rng) -> Vec<f64> {
+	assert!(first.len()==second.len(),"the parents should have the same length");
+	match kind {
+		Crossover::None => first.to_vec(),
+		Crossover::Uniform => first.iter().zip(second).map(|(a,b)| if rng.gen::<bool>() { *a } else { *b }).collect(),
+		Crossover::SinglePoint => {
+			let point = rng.gen_range(0..=first.len());
+			first[..point].iter().chain(&second[point..]).copied().collect()
+		},
+		Crossover::Arithmetic => first.iter().zip(second).map(|(a,b)| {
+			let t : f64 = rng.gen();
+			t*a + (1.0-t)*b
+		}).collect(),
+	}
+}
+
+/// Add gaussian noise of deviation `std` to each gene with probability `rate`
+pub fn mutate(genome : &mut [f64], rate : f64, std : f64, rng : &mut ChaCha8Rng){
+	for gene in genome {
+		if rng.gen::<f64>() < rate {
+			*gene += std*standard_normal(rng);
+		}
+	}
+}
+
+/// Fitness used for comparisons, a fitness that isn't a number is the worst
+fn rank_value(fitness : Option<f64>) -> f64 {
+	fitness.filter(|fitness| !fitness.is_nan()).unwrap_or(f64::NEG_INFINITY)
+}
+
+/// Evaluate each genome with its own copy of `network`, on `threads` threads.
+/// The fitnesses are in the order of the genomes whatever the number of threads.
+fn evaluate<F>(network : &NeuralNetWork, genomes : &[&[f64]], fitness : &F, threads : usize) -> Vec<f64>
+where F : Fn(&mut NeuralNetWork) -> f64 + Sync {
+	let next = AtomicUsize::new(0);
+	let results = Mutex::new(vec![f64::NAN;genomes.len()]);
+
+	std::thread::scope(|scope| {
+		for _ in 0..threads.clamp(1, genomes.len().max(1)) {
+			let mut network = network.clone();
+			let (next,results) = (&next,&results);
+			scope.spawn(move || loop {
+				let index = next.fetch_add(1, Ordering::Relaxed);
+				let Some(genome) = genomes.get(index) else {
+					break;
+				};
+				network.set_parameters(genome);
+				let value = fitness(&mut network);
+				results.lock().unwrap()[index] = value;
+			});
+		}
+	});
+
+	results.into_inner().unwrap()
+}
+
+fn stats(generation : usize, fitnesses : impl Iterator<Item = f64> + Clone) -> GenerationStats {
+	let count = fitnesses.clone().count() as f64;
+	GenerationStats {
+		generation,
+		best : fitnesses.clone().fold(f64::NEG_INFINITY, f64::max),
+		mean : fitnesses.clone().sum::<f64>() / count,
+		worst : fitnesses.fold(f64::INFINITY, f64::min),
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                              Genetic algorithm                             */
+/* -------------------------------------------------------------------------- */
+
+
+/// Hyperparameters of `GeneticAlgorithm`
+#[derive(Debug,Clone,PartialEq)]
+pub struct GeneticOptions {
+	pub population : usize,
+	/// number of the fittest individuals copied unchanged to the next generation
+	pub elite : usize,
+	pub selection : Selection,
+	pub crossover : Crossover,
+	/// probability of combining two parents, otherwise the child copies the first one
+	pub crossover_rate : f64,
+	/// probability of mutating each gene
+	pub mutation_rate : f64,
+	/// deviation of the mutations
+	pub mutation_std : f64,
+	/// deviation of the noise added to the initial network to create the first population
+	pub init_std : f64,
+	/// number of individuals evaluated at the same time
+	pub threads : usize,
+	pub seed : u64,
+}
+
+impl Default for GeneticOptions {
+	fn default() -> Self {
+		GeneticOptions {
+			population : 50,
+			elite : 2,
+			selection : Selection::Tournament { size : 3 },
+			crossover : Crossover::Uniform,
+			crossover_rate : 0.7,
+			mutation_rate : 0.1,
+			mutation_std : 0.1,
+			init_std : 0.1,
+			threads : 1,
+			seed : 0,
+		}
+	}
+}
+
+/// Evolve the parameters of a network with a fitness to maximise, for objectives without a gradient
+///
+/// ```
+/// use rust_simple_nn::evolution::*;
+/// use rust_simple_nn::nn::*;
+///
+/// let data : Vec<Sample> = vec![(vec![0.0],vec![1.0]),(vec![1.0],vec![0.0])];
+/// let nn = NeuralNetWork::new(&[1,3,1], "quadratic", "sigmoid", "sigmoid");
+/// let mut ga = GeneticAlgorithm::new(&nn, GeneticOptions { population : 20, ..GeneticOptions::default() });
+/// let history = ga.run(10, |nn : &mut NeuralNetWork| -nn.batch_cost(&data));
+/// assert!(history.last().unwrap().best >= history[0].best);
+/// let best = ga.best_network();
+/// ```
+#[derive(Debug,Clone)]
+pub struct GeneticAlgorithm {
+	network : NeuralNetWork,
+	options : GeneticOptions,
+	population : Vec<Individual>,
+	best : Option<Individual>,
+	generation : usize,
+	rng : ChaCha8Rng,
+}
+
+impl GeneticAlgorithm {
+
+	/// The first population is `network` and noisy copies of it
+	///
+	/// # Arguments
+	/// * `network` - the architecture and the starting parameters
+	/// * `options` - the hyperparameters and the seed
+	pub fn new(network : &NeuralNetWork, options : GeneticOptions) -> GeneticAlgorithm {
+		assert!(options.population >= 2, "the population should have at least 2 individuals");
+		assert!(options.elite < options.population, "the elite should be smaller than the population");
+		match options.selection {
+			Selection::Tournament { size } => assert!(size >= 1, "a tournament needs at least one individual"),
+			Selection::Truncation { fraction } => assert!(fraction > 0.0 && fraction <= 1.0, "the truncation fraction should be in (0,1]"),
+		}
+
+		let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+		let start = network.parameters();
+		let population = (0..options.population).map(|i| {
+			let mut genome = start.clone();
+			if i > 0 {
+				genome.iter_mut().for_each(|gene| *gene += options.init_std*standard_normal(&mut rng));
+			}
+			Individual { genome, fitness : None }
+		}).collect();
+
+		GeneticAlgorithm { network : network.clone(), options, population, best : None, generation : 0, rng }
+	}
+
+	pub fn population(&self) -> &[Individual] {
+		&self.population
+	}
+
+	/// The fittest individual evaluated so far
+	pub fn best(&self) -> Option<&Individual> {
+		self.best.as_ref()
+	}
+
+	/// A copy of the network with the parameters of the fittest individual
+	pub fn best_network(&self) -> NeuralNetWork {
+		let mut network = self.network.clone();
+		if let Some(best) = &self.best {
+			network.set_parameters(&best.genome);
+		}
+		network
+	}
+
+	fn select(&mut self, ranked : &[usize]) -> usize {
+		match self.options.selection {
+			Selection::Tournament { size } => (0..size)
+				.map(|_| self.rng.gen_range(0..ranked.len()))
+				.min()
+				.map(|rank| ranked[rank])
+				.unwrap(),
+			Selection::Truncation { fraction } => {
+				let kept = ((ranked.len() as f64 * fraction).ceil() as usize).max(1);
+				ranked[self.rng.gen_range(0..kept)]
+			},
+		}
+	}
+
+	/// Evaluate the population, then replace it with the next generation
+	///
+	/// # Arguments
+	/// * `fitness` - the score of a network, higher is better, called from several threads
+	pub fn step<F>(&mut self, fitness : F) -> GenerationStats
+	where F : Fn(&mut NeuralNetWork) -> f64 + Sync {
+		// the elite keeps the fitness computed in the previous generation
+		let pending : Vec<usize> = (0..self.population.len()).filter(|i| self.population[*i].fitness.is_none()).collect();
+		let genomes : Vec<&[f64]> = pending.iter().map(|i| self.population[*i].genome.as_slice()).collect();
+		let values = evaluate(&self.network, &genomes, &fitness, self.options.threads);
+		for (i,value) in pending.into_iter().zip(values) {
+			self.population[i].fitness = Some(value);
+		}
+
+		let mut ranked : Vec<usize> = (0..self.population.len()).collect();
+		ranked.sort_by(|a,b| rank_value(self.population[*b].fitness).total_cmp(&rank_value(self.population[*a].fitness)));
+		let stats = stats(self.generation, self.population.iter().map(|individual| rank_value(individual.fitness)));
+
+		let fittest = &self.population[ranked[0]];
+		if self.best.as_ref().is_none_or(|best| rank_value(fittest.fitness) > rank_value(best.fitness)) {
+			self.best = Some(fittest.clone());
+		}
+
+		let mut next : Vec<Individual> = ranked[..self.options.elite].iter().map(|i| self.population[*i].clone()).collect();
+		while next.len() < self.options.population {
+			let first = self.select(&ranked);
+			let mut genome = if self.rng.gen::<f64>() < self.options.crossover_rate {
+				let second = self.select(&ranked);
+				crossover(&self.population[first].genome, &self.population[second].genome, self.options.crossover, &mut self.rng)
+			} else {
+				self.population[first].genome.clone()
+			};
+			mutate(&mut genome, self.options.mutation_rate, self.options.mutation_std, &mut self.rng);
+			next.push(Individual { genome, fitness : None });
+		}
+
+		self.population = next;
+		self.generation += 1;
+		stats
+	}
+
+	/// Run `generations` steps and return the statistics of each one
+	pub fn run<F>(&mut self, generations : usize, fitness : F) -> Vec<GenerationStats>
+	where F : Fn(&mut NeuralNetWork) -> f64 + Sync {
+		(0..generations).map(|_| self.step(&fitness)).collect()
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                            Evolution strategies                            */
+/* -------------------------------------------------------------------------- */
+
+
+/// Hyperparameters of `EvolutionStrategy`
+#[derive(Debug,Clone,PartialEq)]
+pub struct EsOptions {
+	/// number of noise vectors per step, each is evaluated with both signs
+	pub population : usize,
+	/// deviation of the noise added to the parameters
+	pub sigma : f64,
+	pub learning_rate : f64,
+	pub threads : usize,
+	pub seed : u64,
+}
+
+impl Default for EsOptions {
+	fn default() -> Self {
+		EsOptions { population : 25, sigma : 0.05, learning_rate : 0.02, threads : 1, seed : 0 }
+	}
+}
+
+/// Natural evolution strategy : the parameters move along the noise directions weighted by the
+/// rank of the fitness they lead to, an estimate of the gradient of the expected fitness
+#[derive(Debug,Clone)]
+pub struct EvolutionStrategy {
+	network : NeuralNetWork,
+	options : EsOptions,
+	parameters : Vec<f64>,
+	generation : usize,
+	rng : ChaCha8Rng,
+}
+
+impl EvolutionStrategy {
+
+	/// # Arguments
+	/// * `network` - the architecture and the starting parameters
+	/// * `options` - the hyperparameters and the seed
+	pub fn new(network : &NeuralNetWork, options : EsOptions) -> EvolutionStrategy {
+		assert!(options.population >= 1, "the population should have at least 1 individual");
+		assert!(options.sigma > 0.0, "sigma should be positive");
+		let rng = ChaCha8Rng::seed_from_u64(options.seed);
+		EvolutionStrategy { parameters : network.parameters(), network : network.clone(), options, generation : 0, rng }
+	}
+
+	/// The current parameters, the mean of the search distribution
+	pub fn parameters(&self) -> &[f64] {
+		&self.parameters
+	}
+
+	/// A copy of the network with the current parameters
+	pub fn network(&self) -> NeuralNetWork {
+		let mut network = self.network.clone();
+		network.set_parameters(&self.parameters);
+		network
+	}
+
+	/// Evaluate the perturbed parameters and move the parameters towards the fittest ones
+	///
+	/// # Arguments
+	/// * `fitness` - the score of a network, higher is better, called from several threads
+	pub fn step<F>(&mut self, fitness : F) -> GenerationStats
+	where F : Fn(&mut NeuralNetWork) -> f64 + Sync {
+		let sigma = self.options.sigma;
+		let noises : Vec<Vec<f64>> = (0..self.options.population)
+			.map(|_| (0..self.parameters.len()).map(|_| standard_normal(&mut self.rng)).collect())
+			.collect();
+		// mirrored sampling : parameters + sigma*noise then parameters - sigma*noise
+		let genomes : Vec<Vec<f64>> = noises.iter().flat_map(|noise| [1.0,-1.0].map(|sign| {
+			self.parameters.iter().zip(noise).map(|(p,n)| p + sign*sigma*n).collect::<Vec<f64>>()
+		})).collect();
+		let slices : Vec<&[f64]> = genomes.iter().map(Vec::as_slice).collect();
+		let values : Vec<f64> = evaluate(&self.network, &slices, &fitness, self.options.threads).into_iter().map(|value| rank_value(Some(value))).collect();
+
+		// centered ranks in [-0.5,0.5] are insensitive to the scale of the fitness
+		let mut order : Vec<usize> = (0..values.len()).collect();
+		order.sort_by(|a,b| values[*a].total_cmp(&values[*b]));
+		let mut ranks = vec![0.0;values.len()];
+		for (rank,index) in order.into_iter().enumerate() {
+			ranks[index] = rank as f64 / (values.len()-1).max(1) as f64 - 0.5;
+		}
+
+		let scale = self.options.learning_rate / (noises.len() as f64 * sigma);
+		for (i,noise) in noises.iter().enumerate() {
+			let weight = ranks[2*i] - ranks[2*i+1];
+			for (p,n) in self.parameters.iter_mut().zip(noise) {
+				*p += scale*weight*n;
+			}
+		}
+
+		let stats = stats(self.generation, values.into_iter());
+		self.generation += 1;
+		stats
+	}
+
+	/// Run `generations` steps and return the statistics of each one
+	pub fn run<F>(&mut self, generations : usize, fitness : F) -> Vec<GenerationStats>
+	where F : Fn(&mut NeuralNetWork) -> f64 + Sync {
+		(0..generations).map(|_| self.step(&fitness)).collect()
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn network() -> NeuralNetWork {
+		let mut nn = NeuralNetWork::empty(2, "quadratic", ChaCha8Rng::seed_from_u64(1));
+		nn.add(4, "tanh");
+		nn.add(1, "sigmoid");
+		nn
+	}
+
+	fn xor() -> Vec<Sample> {
+		vec![(vec![0.0,0.0],vec![0.0]),(vec![0.0,1.0],vec![1.0]),(vec![1.0,0.0],vec![1.0]),(vec![1.0,1.0],vec![0.0])]
+	}
+
+	#[test]
+	fn crossover_and_mutation(){
+		let mut rng = ChaCha8Rng::seed_from_u64(0);
+		let (a,b) = (vec![0.0;20],vec![1.0;20]);
+		assert!(crossover(&a, &b, Crossover::None, &mut rng) == a);
+		let child = crossover(&a, &b, Crossover::Uniform, &mut rng);
+		assert!(child.iter().all(|gene| *gene == 0.0 || *gene == 1.0) && child.contains(&0.0) && child.contains(&1.0));
+		let child = crossover(&a, &b, Crossover::SinglePoint, &mut rng);
+		assert!(child.windows(2).all(|pair| pair[0] <= pair[1]));
+		assert!(crossover(&a, &b, Crossover::Arithmetic, &mut rng).iter().all(|gene| (0.0..=1.0).contains(gene)));
+
+		let mut genome = vec![0.0;100];
+		mutate(&mut genome, 0.0, 1.0, &mut rng);
+		assert!(genome.iter().all(|gene| *gene == 0.0));
+		mutate(&mut genome, 1.0, 1.0, &mut rng);
+		assert!(genome.iter().all(|gene| *gene != 0.0));
+	}
+
+	#[test]
+	fn genetic_algorithm_improves_and_is_reproducible(){
+		let data = xor();
+		let fitness = |nn : &mut NeuralNetWork| -nn.batch_cost(&data);
+		let options = GeneticOptions { population : 30, seed : 4, ..GeneticOptions::default() };
+
+		let mut ga = GeneticAlgorithm::new(&network(), options.clone());
+		let history = ga.run(30, fitness);
+		assert!(history.last().unwrap().best > history[0].best);
+		// the elite keeps the best fitness from one generation to the next
+		assert!(history.windows(2).all(|pair| pair[1].best >= pair[0].best));
+		assert!(ga.best_network().batch_cost(&data) == -ga.best().unwrap().fitness.unwrap());
+
+		let mut parallel = GeneticAlgorithm::new(&network(), GeneticOptions { threads : 4, ..options });
+		assert!(parallel.run(30, fitness) == history);
+	}
+
+	#[test]
+	fn truncation_selection(){
+		let data = xor();
+		let options = GeneticOptions { population : 20, selection : Selection::Truncation { fraction : 0.3 }, crossover : Crossover::Arithmetic, ..GeneticOptions::default() };
+		let mut ga = GeneticAlgorithm::new(&network(), options);
+		let history = ga.run(20, |nn : &mut NeuralNetWork| -nn.batch_cost(&data));
+		assert!(history.last().unwrap().best > history[0].best);
+	}
+
+	#[test]
+	fn evolution_strategy_improves(){
+		let data = xor();
+		let fitness = |nn : &mut NeuralNetWork| -nn.batch_cost(&data);
+		let start = network().batch_cost(&data);
+
+		let mut es = EvolutionStrategy::new(&network(), EsOptions { learning_rate : 0.1, threads : 2, ..EsOptions::default() });
+		es.run(50, fitness);
+		assert!(es.network().batch_cost(&data) < start);
+		assert!(es.parameters().len() == network().nb_parameters());
+	}
+}
@@ -0,0 +1,511 @@
+use std::fmt;
+
+use crate::block::*;
+use crate::nn::*;
+use crate::optimizer::*;
+use crate::tensor::*;
+
+/// The input tensors of a graph and the expected value of each of its outputs
+pub type GraphSample = (Vec<Tensor>,Vec<Vec<f64>>);
+
+
+/// A node of a `Graph`, returned when it's added
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub struct NodeId(usize);
+
+
+/// How the inputs of a merge node are combined
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Merge {
+	/// sum of inputs of the same shape, ex : a residual connection
+	Add,
+	/// element-wise product of inputs of the same shape, ex : a gate
+	Multiply,
+	/// inputs put one after the other along the first dimension, the other dimensions should match
+	Concat,
+}
+
+impl Merge {
+
+	fn name(&self) -> &'static str {
+		match self {
+			Merge::Add => "add",
+			Merge::Multiply => "multiply",
+			Merge::Concat => "concat",
+		}
+	}
+
+	fn output_shape(&self, shapes : &[&[usize]]) -> Vec<usize> {
+		assert!(shapes.len() >= 2,"a merge needs at least two inputs");
+		match self {
+			Merge::Add | Merge::Multiply => {
+				assert!(shapes.iter().all(|shape| *shape==shapes[0]),"{} needs inputs of the same shape, got {shapes:?}",self.name());
+				shapes[0].to_vec()
+			},
+			Merge::Concat => {
+				assert!(shapes.iter().all(|shape| !shape.is_empty() && shape[1..]==shapes[0][1..]),"concat needs inputs with the same dimensions after the first, got {shapes:?}");
+				let mut shape = shapes[0].to_vec();
+				shape[0] = shapes.iter().map(|shape| shape[0]).sum();
+				shape
+			},
+		}
+	}
+
+	fn forward(&self, inputs : &[&Tensor]) -> Tensor {
+		let shapes : Vec<&[usize]> = inputs.iter().map(|input| input.shape.as_slice()).collect();
+		let shape = self.output_shape(&shapes);
+		match self {
+			Merge::Add => {
+				let mut output = inputs[0].clone();
+				inputs[1..].iter().for_each(|input| output.add_mut(input));
+				output
+			},
+			Merge::Multiply => {
+				let mut output = inputs[0].clone();
+				for input in &inputs[1..] {
+					output.values.iter_mut().zip(&input.values).for_each(|(a,b)| *a *= b);
+				}
+				output
+			},
+			Merge::Concat => Tensor::from_vec(&shape, inputs.iter().flat_map(|input| input.values.iter().copied()).collect()),
+		}
+	}
+
+	/// Gradient of each input
+	fn backward(&self, inputs : &[&Tensor], grad_output : &Tensor) -> Vec<Tensor> {
+		match self {
+			Merge::Add => vec![grad_output.clone();inputs.len()],
+			Merge::Multiply => (0..inputs.len()).map(|i| {
+				let mut grad = grad_output.clone();
+				for input in inputs.iter().enumerate().filter(|(j,_)| *j != i).map(|(_,input)| input) {
+					grad.values.iter_mut().zip(&input.values).for_each(|(g,x)| *g *= x);
+				}
+				grad
+			}).collect(),
+			Merge::Concat => {
+				let mut start = 0;
+				inputs.iter().map(|input| {
+					let grad = Tensor::from_vec(&input.shape, grad_output.values[start..start+input.len()].to_vec());
+					start += input.len();
+					grad
+				}).collect()
+			},
+		}
+	}
+}
+
+
+#[derive(Debug,Clone)]
+enum Operation {
+	Input,
+	Block(Box<dyn Block>),
+	Merge(Merge),
+}
+
+#[derive(Debug,Clone)]
+struct Node {
+	operation : Operation,
+	inputs : Vec<usize>,
+	shape : Vec<usize>,
+	/// output of the last forward pass
+	value : Tensor,
+}
+
+/// An output of the graph, its cost is multiplied by `weight` in the total cost
+#[derive(Debug,Clone)]
+struct Output {
+	node : usize,
+	cost_name : String,
+	cost_function : fn(&[f64],&[f64])->f64,
+	cost_derivative : fn(f64,f64)->f64,
+	weight : f64,
+}
+
+
+/// A network whose nodes are blocks or merges of several nodes, so it can have skip connections,
+/// several inputs and several outputs each with its own cost. Nodes only take nodes added before
+/// them, so the order they are added in is a valid order for the forward pass.
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand_chacha::ChaCha8Rng;
+/// use rust_simple_nn::block::*;
+/// use rust_simple_nn::graph::*;
+/// use rust_simple_nn::init::Init;
+/// use rust_simple_nn::tensor::Tensor;
+///
+/// let mut rng = ChaCha8Rng::seed_from_u64(0);
+/// let mut graph = Graph::new();
+/// let input = graph.input(&[4]);
+/// let hidden = graph.add(Dense::new(4, 8, "relu", Init::He, &mut rng), input);
+/// let hidden = graph.residual(Dense::new(8, 8, "relu", Init::He, &mut rng), hidden);
+/// let value = graph.add(Dense::new(8, 1, "identity", Init::Xavier, &mut rng), hidden);
+/// let class = graph.add(Dense::new(8, 3, "sigmoid", Init::Xavier, &mut rng), hidden);
+/// graph.output(value, "quadratic", 1.0);
+/// graph.output(class, "cross_entropy", 0.5);
+///
+/// let outputs = graph.predict(&[Tensor::new(&[4])]);
+/// assert_eq!((outputs[0].len(),outputs[1].len()), (1,3));
+/// ```
+#[derive(Debug,Clone,Default)]
+pub struct Graph {
+	nodes : Vec<Node>,
+	inputs : Vec<usize>,
+	outputs : Vec<Output>,
+}
+
+impl Graph {
+
+	pub fn new() -> Graph {
+		Graph::default()
+	}
+
+	fn push(&mut self, operation : Operation, inputs : Vec<usize>, shape : Vec<usize>) -> NodeId {
+		self.nodes.push(Node { operation, inputs, value : Tensor::new(&shape), shape });
+		NodeId(self.nodes.len()-1)
+	}
+
+	fn check(&self, node : NodeId) -> usize {
+		assert!(node.0 < self.nodes.len(),"the node {} isn't in this graph",node.0);
+		node.0
+	}
+
+	/// Add an input of the graph, the inputs are given to `predict` in the order they are added
+	pub fn input(&mut self, shape : &[usize]) -> NodeId {
+		let node = self.push(Operation::Input, vec![], shape.to_vec());
+		self.inputs.push(node.0);
+		node
+	}
+
+	/// Add a block fed with the output of `input`
+	pub fn add(&mut self, block : impl Block + 'static, input : NodeId) -> NodeId {
+		let input = self.check(input);
+		let shape = block.output_shape(&self.nodes[input].shape);
+		self.push(Operation::Block(Box::new(block)), vec![input], shape)
+	}
+
+	/// Add a node combining the outputs of several nodes
+	pub fn merge(&mut self, merge : Merge, inputs : &[NodeId]) -> NodeId {
+		let inputs : Vec<usize> = inputs.iter().map(|input| self.check(*input)).collect();
+		let shapes : Vec<&[usize]> = inputs.iter().map(|input| self.nodes[*input].shape.as_slice()).collect();
+		let shape = merge.output_shape(&shapes);
+		self.push(Operation::Merge(merge), inputs, shape)
+	}
+
+	/// `input + block(input)`, the block should keep the shape of its input
+	pub fn residual(&mut self, block : impl Block + 'static, input : NodeId) -> NodeId {
+		let output = self.add(block, input);
+		self.merge(Merge::Add, &[input,output])
+	}
+
+	/// Make a node an output of the graph, with the cost comparing it to its targets
+	///
+	/// # Arguments
+	/// * `node` - the node, its output is flattened
+	/// * `cost_str` - name of the cost function, panics if it's unknown
+	/// * `weight` - factor of this cost in the total cost
+	pub fn output(&mut self, node : NodeId, cost_str : &str, weight : f64){
+		let node = self.check(node);
+		let Some((cost_function,cost_derivative)) = get_cost(cost_str) else {
+			panic!("unknown cost `{cost_str}`");
+		};
+		assert!(weight >= 0.0,"the weight of an output can't be negative");
+		self.outputs.push(Output { node, cost_name : cost_str.trim().to_lowercase(), cost_function, cost_derivative, weight });
+	}
+
+	/// Shape of the output of a node
+	pub fn shape(&self, node : NodeId) -> &[usize] {
+		&self.nodes[self.check(node)].shape
+	}
+
+	pub fn nb_inputs(&self) -> usize {
+		self.inputs.len()
+	}
+
+	pub fn nb_outputs(&self) -> usize {
+		self.outputs.len()
+	}
+
+	fn blocks(&self) -> impl Iterator<Item = &Box<dyn Block>> {
+		self.nodes.iter().filter_map(|node| match &node.operation {
+			Operation::Block(block) => Some(block),
+			_ => None,
+		})
+	}
+
+	fn blocks_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn Block>> {
+		self.nodes.iter_mut().filter_map(|node| match &mut node.operation {
+			Operation::Block(block) => Some(block),
+			_ => None,
+		})
+	}
+
+	pub fn nb_parameters(&self) -> usize {
+		self.blocks().map(|block| block.nb_parameters()).sum()
+	}
+
+	/// Every trainable value flattened, block after block in the order they were added
+	pub fn parameters(&self) -> Vec<f64> {
+		self.blocks().flat_map(|block| block.params().into_iter().flat_map(|param| param.value.values.iter().copied()).collect::<Vec<f64>>()).collect()
+	}
+
+	/// Replace the trainable values, in the order of `parameters`
+	pub fn set_parameters(&mut self, parameters : &[f64]){
+		assert!(parameters.len()==self.nb_parameters(),"the graph has {} parameters, got {}",self.nb_parameters(),parameters.len());
+		let mut start = 0;
+		for block in self.blocks_mut() {
+			for param in block.params_mut() {
+				let len = param.value.values.len();
+				param.value.values.copy_from_slice(&parameters[start..start+len]);
+				start += len;
+			}
+		}
+	}
+
+	fn forward(&mut self, inputs : &[Tensor]){
+		assert!(!self.outputs.is_empty(),"the graph needs at least one output");
+		assert!(inputs.len()==self.inputs.len(),"the graph has {} inputs, got {}",self.inputs.len(),inputs.len());
+		for (node,input) in self.inputs.iter().zip(inputs) {
+			assert!(input.shape==self.nodes[*node].shape,"the input {} should have the shape {:?}, got {:?}",node,self.nodes[*node].shape,input.shape);
+			self.nodes[*node].value = input.clone();
+		}
+
+		for i in 0..self.nodes.len() {
+			let (before,rest) = self.nodes.split_at_mut(i);
+			let node = &mut rest[0];
+			node.value = match &mut node.operation {
+				Operation::Input => continue,
+				Operation::Block(block) => block.forward(&before[node.inputs[0]].value),
+				Operation::Merge(merge) => merge.forward(&node.inputs.iter().map(|input| &before[*input].value).collect::<Vec<&Tensor>>()),
+			};
+		}
+	}
+
+	/// Output of each output node, flattened
+	pub fn predict(&mut self, inputs : &[Tensor]) -> Vec<Vec<f64>> {
+		self.forward(inputs);
+		self.outputs.iter().map(|output| self.nodes[output.node].value.values.clone()).collect()
+	}
+
+	/// Mean cost of each output over the data, before the weights are applied
+	pub fn output_costs(&mut self, data : &[GraphSample]) -> Vec<f64> {
+		let mut costs = vec![0.0;self.outputs.len()];
+		for (inputs,targets) in data {
+			self.forward(inputs);
+			for ((output,target),cost) in self.outputs.iter().zip(targets).zip(costs.iter_mut()) {
+				*cost += (output.cost_function)(&self.nodes[output.node].value.values, target);
+			}
+		}
+		costs.iter().map(|cost| cost / data.len() as f64).collect()
+	}
+
+	/// Weighted sum of the mean cost of each output
+	pub fn batch_cost(&mut self, data : &[GraphSample]) -> f64 {
+		self.output_costs(data).iter().zip(&self.outputs).map(|(cost,output)| cost*output.weight).sum()
+	}
+
+	/// Add the gradient of one sample to every parameter
+	fn backprop(&mut self, inputs : &[Tensor], targets : &[Vec<f64>]){
+		assert!(targets.len()==self.outputs.len(),"the graph has {} outputs, got {} targets",self.outputs.len(),targets.len());
+		self.forward(inputs);
+
+		// nodes feeding several others get the sum of their gradients
+		let mut grads : Vec<Option<Tensor>> = vec![None;self.nodes.len()];
+		let accumulate = |grads : &mut Vec<Option<Tensor>>, node : usize, grad : Tensor| match &mut grads[node] {
+			Some(sum) => sum.add_mut(&grad),
+			None => grads[node] = Some(grad),
+		};
+
+		for (output,target) in self.outputs.iter().zip(targets) {
+			let value = &self.nodes[output.node].value;
+			assert!(target.len()==value.len(),"the output {} has {} values, got a target of {}",output.node,value.len(),target.len());
+			let grad = value.values.iter().zip(target).map(|(x,y)| output.weight*(output.cost_derivative)(*x,*y)).collect();
+			accumulate(&mut grads, output.node, Tensor::from_vec(&value.shape, grad));
+		}
+
+		for i in (0..self.nodes.len()).rev() {
+			let Some(grad) = grads[i].take() else {
+				continue;
+			};
+			let (before,rest) = self.nodes.split_at_mut(i);
+			let node = &mut rest[0];
+			match &mut node.operation {
+				Operation::Input => {},
+				Operation::Block(block) => accumulate(&mut grads, node.inputs[0], block.backward(&grad)),
+				Operation::Merge(merge) => {
+					let inputs : Vec<&Tensor> = node.inputs.iter().map(|input| &before[*input].value).collect();
+					for (input,grad) in node.inputs.iter().zip(merge.backward(&inputs, &grad)) {
+						accumulate(&mut grads, *input, grad);
+					}
+				},
+			}
+		}
+	}
+
+	/// Train every block like `NeuralNetWork::train_with`, returns the total cost at the end of each epoch.
+	/// The mini batches follow the order of `data`, shuffle it beforehand if it's sorted.
+	pub fn train_with(&mut self, data : &[GraphSample], options : &TrainOptions) -> Vec<f64> {
+		self.try_train_with(data, options).unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	/// `train_with` returning the error that stopped the training when `options.non_finite` is `NonFinite::Abort`
+	pub fn try_train_with(&mut self, data : &[GraphSample], options : &TrainOptions) -> Result<Vec<f64>,NonFiniteError> {
+		train_epochs(self, data, options)
+	}
+}
+
+impl MiniBatchModel for Graph {
+	type Sample = GraphSample;
+
+	fn zero_grad(&mut self){
+		for block in self.blocks_mut() {
+			block.zero_grad();
+		}
+	}
+
+	fn backprop(&mut self, (inputs,targets) : &GraphSample){
+		Graph::backprop(self, inputs, targets);
+	}
+
+	fn gradients_mut(&mut self) -> Vec<Vec<&mut [f64]>> {
+		self.blocks_mut().map(|block| block.params_mut().into_iter().map(|param| param.grad.values.as_mut_slice()).collect()).collect()
+	}
+
+	fn update(&mut self, mean_value : f64, learning_rate : f64, optimizer : &Optimizer){
+		for block in self.blocks_mut() {
+			block.update(mean_value, learning_rate, optimizer);
+		}
+	}
+
+	fn batch_cost(&mut self, data : &[GraphSample]) -> f64 {
+		Graph::batch_cost(self, data)
+	}
+}
+
+impl fmt::Display for Graph {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		for (i,node) in self.nodes.iter().enumerate() {
+			let (name,parameters) = match &node.operation {
+				Operation::Input => ("input".to_string(),0),
+				Operation::Block(block) => (block.name(),block.nb_parameters()),
+				Operation::Merge(merge) => (merge.name().to_string(),0),
+			};
+			let inputs = node.inputs.iter().map(|input| input.to_string()).collect::<Vec<String>>().join(",");
+			write!(f, "{i:<4}{name:<24}{:>16}{:>10}  <- [{inputs}]",format!("{:?}",node.shape),parameters)?;
+			for output in self.outputs.iter().filter(|output| output.node == i) {
+				write!(f, "  output {} x{}",output.cost_name,output.weight)?;
+			}
+			writeln!(f)?;
+		}
+		write!(f, "total parameters: {}",self.nb_parameters())
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::embedding::*;
+	use crate::init::*;
+	use rand::{Rng, SeedableRng};
+	use rand_chacha::ChaCha8Rng;
+
+	/// two inputs, a residual branch, a gate and two outputs
+	fn test_graph() -> Graph {
+		let mut rng = ChaCha8Rng::seed_from_u64(0);
+		let mut graph = Graph::new();
+		let first = graph.input(&[3]);
+		let second = graph.input(&[2]);
+		let features = graph.merge(Merge::Concat, &[first,second]);
+		let hidden = graph.add(Dense::new(5, 4, "tanh", Init::Xavier, &mut rng), features);
+		let hidden = graph.residual(Dense::new(4, 4, "tanh", Init::Xavier, &mut rng), hidden);
+		let gate = graph.add(Dense::new(2, 4, "sigmoid", Init::Xavier, &mut rng), second);
+		let gated = graph.merge(Merge::Multiply, &[hidden,gate]);
+		let value = graph.add(Dense::new(4, 1, "identity", Init::Xavier, &mut rng), gated);
+		let class = graph.add(Dense::new(4, 2, "sigmoid", Init::Xavier, &mut rng), hidden);
+		graph.output(value, "quadratic", 1.0);
+		graph.output(class, "cross_entropy", 0.5);
+		graph
+	}
+
+	#[test]
+	fn graph_gradient_check(){
+		const EPSILON : f64 = 1e-6;
+		let mut graph = test_graph();
+		let sample : GraphSample = (vec![Tensor::from_vec(&[3], vec![0.5,-1.0,2.0]),Tensor::from_vec(&[2], vec![0.3,-0.7])],vec![vec![0.4],vec![1.0,0.0]]);
+
+		for block in graph.blocks_mut() {
+			block.zero_grad();
+		}
+		graph.backprop(&sample.0, &sample.1);
+		let grads : Vec<f64> = graph.blocks().flat_map(|block| block.params().into_iter().flat_map(|param| param.grad.values.clone()).collect::<Vec<f64>>()).collect();
+
+		let parameters = graph.parameters();
+		for (i,grad) in grads.iter().enumerate() {
+			let mut cost_at = |delta : f64| {
+				let mut shifted = parameters.clone();
+				shifted[i] += delta;
+				graph.set_parameters(&shifted);
+				graph.batch_cost(std::slice::from_ref(&sample))
+			};
+			let numeric = (cost_at(EPSILON) - cost_at(-EPSILON)) / (2.0*EPSILON);
+			assert!((numeric-grad).abs() < 1e-5, "param {i}: {numeric} != {grad}");
+		}
+	}
+
+	#[test]
+	fn merges(){
+		let (a,b) = (Tensor::from_vec(&[2], vec![1.0,2.0]),Tensor::from_vec(&[2], vec![3.0,-1.0]));
+		assert!(Merge::Add.forward(&[&a,&b]).values == vec![4.0,1.0]);
+		assert!(Merge::Multiply.forward(&[&a,&b]).values == vec![3.0,-2.0]);
+		assert!(Merge::Concat.forward(&[&a,&b]).values == vec![1.0,2.0,3.0,-1.0]);
+		assert!(Merge::Concat.output_shape(&[&[2,3],&[4,3]]) == vec![6,3]);
+		assert!(Merge::Multiply.backward(&[&a,&b], &Tensor::from_vec(&[2], vec![1.0,1.0]))[0].values == vec![3.0,-1.0]);
+	}
+
+	#[test]
+	#[should_panic]
+	fn add_needs_the_same_shapes(){
+		let mut graph = Graph::new();
+		let (a,b) = (graph.input(&[2]),graph.input(&[3]));
+		graph.merge(Merge::Add, &[a,b]);
+	}
+
+	#[test]
+	fn tabular_and_embedding_inputs_with_two_heads(){
+		// a category id and two numeric features, the value depends on both and the class on the category
+		let mut rng = ChaCha8Rng::seed_from_u64(1);
+		let offsets = [-1.0,0.0,0.5,1.0];
+		let data : Vec<GraphSample> = (0..80).map(|i| {
+			let category = i%4;
+			let features = vec![rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0)];
+			let value = features[0] - 0.5*features[1] + offsets[category];
+			(vec![Tensor::from_vec(&[1], vec![category as f64]),Tensor::from_vec(&[2], features)],vec![vec![value],vec![(category >= 2) as usize as f64]])
+		}).collect();
+
+		let mut graph = Graph::new();
+		let category = graph.input(&[1]);
+		let features = graph.input(&[2]);
+		let embedded = graph.add(Embedding::new(4, 3, Init::Normal { std : 0.1 }, &mut rng), category);
+		let embedded = graph.add(crate::conv::Flatten::new(), embedded);
+		let joined = graph.merge(Merge::Concat, &[embedded,features]);
+		let hidden = graph.add(Dense::new(5, 8, "tanh", Init::Xavier, &mut rng), joined);
+		let hidden = graph.residual(Dense::new(8, 8, "tanh", Init::Xavier, &mut rng), hidden);
+		let value = graph.add(Dense::new(8, 1, "identity", Init::Xavier, &mut rng), hidden);
+		let class = graph.add(Dense::new(8, 1, "sigmoid", Init::Xavier, &mut rng), hidden);
+		graph.output(value, "quadratic", 1.0);
+		graph.output(class, "cross_entropy", 0.2);
+
+		let options = adam_options(8, 200);
+		graph.train_with(&data, &options);
+
+		let costs = graph.output_costs(&data);
+		assert!(costs[0] < 0.01 && costs[1] < 0.05, "{costs:?}");
+		assert!(graph.to_string().contains("output cross_entropy x0.2"));
+	}
+}
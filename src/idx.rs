@@ -0,0 +1,258 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::nn::Sample;
+
+
+/// Type of the values stored in an IDX file, given by the third byte of the magic number
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum IdxType {
+	UnsignedByte,
+	SignedByte,
+	Short,
+	Int,
+	Float,
+	Double,
+}
+
+impl IdxType {
+
+	fn from_code(code : u8) -> Option<IdxType> {
+		match code {
+			0x08 => Some(IdxType::UnsignedByte),
+			0x09 => Some(IdxType::SignedByte),
+			0x0B => Some(IdxType::Short),
+			0x0C => Some(IdxType::Int),
+			0x0D => Some(IdxType::Float),
+			0x0E => Some(IdxType::Double),
+			_ => None,
+		}
+	}
+
+	fn code(&self) -> u8 {
+		match self {
+			IdxType::UnsignedByte => 0x08,
+			IdxType::SignedByte => 0x09,
+			IdxType::Short => 0x0B,
+			IdxType::Int => 0x0C,
+			IdxType::Float => 0x0D,
+			IdxType::Double => 0x0E,
+		}
+	}
+
+	/// size of one value in bytes
+	fn size(&self) -> usize {
+		match self {
+			IdxType::UnsignedByte | IdxType::SignedByte => 1,
+			IdxType::Short => 2,
+			IdxType::Int | IdxType::Float => 4,
+			IdxType::Double => 8,
+		}
+	}
+
+	/// decode one big endian value
+	fn decode(&self, bytes : &[u8]) -> f64 {
+		match self {
+			IdxType::UnsignedByte => bytes[0] as f64,
+			IdxType::SignedByte => bytes[0] as i8 as f64,
+			IdxType::Short => i16::from_be_bytes([bytes[0],bytes[1]]) as f64,
+			IdxType::Int => i32::from_be_bytes([bytes[0],bytes[1],bytes[2],bytes[3]]) as f64,
+			IdxType::Float => f32::from_be_bytes([bytes[0],bytes[1],bytes[2],bytes[3]]) as f64,
+			IdxType::Double => f64::from_be_bytes(bytes.try_into().unwrap()),
+		}
+	}
+}
+
+
+/// The content of an IDX file : an array of `dims` dimensions, values are stored row major
+#[derive(Debug,Clone,PartialEq)]
+pub struct IdxArray {
+	pub kind : IdxType,
+	pub dims : Vec<usize>,
+	pub values : Vec<f64>,
+}
+
+impl IdxArray {
+
+	/// number of items (the first dimension)
+	pub fn len(&self) -> usize {
+		self.dims.first().copied().unwrap_or(0)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// number of values in one item (28*28 for an MNIST image, 1 for a label)
+	pub fn item_size(&self) -> usize {
+		self.dims.iter().skip(1).product()
+	}
+
+	/// The items as input vectors, unsigned bytes are scaled from [0,255] to [0,1]
+	pub fn to_inputs(&self) -> Vec<Vec<f64>> {
+		let scale = if self.kind == IdxType::UnsignedByte { 1.0/255.0 } else { 1.0 };
+		if self.item_size() == 0 {
+			return vec![vec![];self.len()];
+		}
+		self.values.chunks(self.item_size()).map(|item| item.iter().map(|value| value*scale).collect()).collect()
+	}
+
+	/// The items as one-hot output vectors, every item must be a single label in [0,nb_classes)
+	pub fn to_one_hot(&self, nb_classes : usize) -> io::Result<Vec<Vec<f64>>> {
+		if self.item_size() != 1 {
+			return Err(invalid_data(format!("labels should have one value per item, found {}",self.item_size())));
+		}
+		self.values.iter().enumerate().map(|(i,label)| {
+			if label.fract() != 0.0 || *label < 0.0 || *label >= nb_classes as f64 {
+				return Err(invalid_data(format!("label {i} is {label}, expected an integer in [0,{nb_classes})")));
+			}
+			let mut output = vec![0.0;nb_classes];
+			output[*label as usize] = 1.0;
+			Ok(output)
+		}).collect()
+	}
+}
+
+
+fn invalid_data(message : String) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+
+/// Read an IDX file (the format of the MNIST files)
+///
+/// # Arguments
+/// * `reader` - the uncompressed IDX content
+pub fn read_idx<R : Read>(mut reader : R) -> io::Result<IdxArray> {
+	let mut magic = [0u8;4];
+	reader.read_exact(&mut magic)?;
+	if magic[0] != 0 || magic[1] != 0 {
+		return Err(invalid_data("not an IDX file (the magic number should start with two zero bytes)".to_string()));
+	}
+	let kind = IdxType::from_code(magic[2]).ok_or_else(|| invalid_data(format!("unknown IDX value type 0x{:02x}",magic[2])))?;
+
+	let mut dims = Vec::with_capacity(magic[3] as usize);
+	for _ in 0..magic[3] {
+		let mut dim = [0u8;4];
+		reader.read_exact(&mut dim)?;
+		dims.push(u32::from_be_bytes(dim) as usize);
+	}
+
+	let count : usize = dims.iter().product();
+	let mut bytes = vec![];
+	reader.read_to_end(&mut bytes)?;
+	if bytes.len() != count*kind.size() {
+		return Err(invalid_data(format!("the dimensions {dims:?} need {} bytes of data, found {}",count*kind.size(),bytes.len())));
+	}
+	let values = bytes.chunks(kind.size()).map(|value| kind.decode(value)).collect();
+
+	Ok(IdxArray { kind, dims, values })
+}
+
+pub fn read_idx_file(path : impl AsRef<Path>) -> io::Result<IdxArray> {
+	read_idx(BufReader::new(File::open(path)?))
+}
+
+/// Write unsigned bytes as an IDX file
+pub fn write_idx_bytes<W : Write>(writer : &mut W, dims : &[usize], values : &[u8]) -> io::Result<()> {
+	assert!(dims.iter().product::<usize>() == values.len(), "the dimensions don't match the number of values");
+	writer.write_all(&[0,0,IdxType::UnsignedByte.code(),dims.len() as u8])?;
+	for dim in dims {
+		writer.write_all(&(*dim as u32).to_be_bytes())?;
+	}
+	writer.write_all(values)
+}
+
+pub fn write_idx_bytes_file(path : impl AsRef<Path>, dims : &[usize], values : &[u8]) -> io::Result<()> {
+	let mut writer = BufWriter::new(File::create(path)?);
+	write_idx_bytes(&mut writer, dims, values)?;
+	writer.flush()
+}
+
+/// Read an image file and its label file (like `train-images-idx3-ubyte` and `train-labels-idx1-ubyte`)
+/// into (pixels in [0,1], one-hot label) pairs
+///
+/// # Arguments
+/// * `images` - the IDX file of images
+/// * `labels` - the IDX file of labels, one per image
+/// * `nb_classes` - size of the one-hot vectors (10 for MNIST)
+pub fn load_labeled_images(images : impl AsRef<Path>, labels : impl AsRef<Path>, nb_classes : usize) -> io::Result<Vec<Sample>> {
+	let images = read_idx_file(images)?;
+	let labels = read_idx_file(labels)?;
+	if images.len() != labels.len() {
+		return Err(invalid_data(format!("{} images but {} labels",images.len(),labels.len())));
+	}
+
+	Ok(images.to_inputs().into_iter().zip(labels.to_one_hot(nb_classes)?).collect())
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn read_written_bytes(){
+		let mut bytes = vec![];
+		write_idx_bytes(&mut bytes, &[3,2,2], &[0,255,51,0, 1,2,3,4, 5,6,7,8]).unwrap();
+		let array = read_idx(bytes.as_slice()).unwrap();
+
+		assert!(array.kind == IdxType::UnsignedByte && array.dims == vec![3,2,2]);
+		assert!(array.len() == 3 && array.item_size() == 4);
+		assert!(array.to_inputs()[0] == vec![0.0,1.0,0.2,0.0]);
+	}
+
+	#[test]
+	fn read_other_types(){
+		// 2 big endian shorts and 1 float
+		let shorts = [0,0,0x0B,1, 0,0,0,2, 0xFF,0xFE, 0x01,0x00];
+		assert!(read_idx(&shorts[..]).unwrap().values == vec![-2.0,256.0]);
+
+		let mut floats = vec![0,0,0x0D,1, 0,0,0,1];
+		floats.extend(1.5f32.to_be_bytes());
+		assert!(read_idx(floats.as_slice()).unwrap().values == vec![1.5]);
+	}
+
+	#[test]
+	fn invalid_files(){
+		assert!(read_idx(&[1,0,8,1,0,0,0,0][..]).is_err());
+		assert!(read_idx(&[0,0,7,1,0,0,0,0][..]).is_err());
+		// 3 values announced, 2 given
+		let error = read_idx(&[0,0,8,1,0,0,0,3,1,2][..]).unwrap_err();
+		assert!(error.kind() == io::ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn labels_to_one_hot(){
+		let mut bytes = vec![];
+		write_idx_bytes(&mut bytes, &[3], &[2,0,1]).unwrap();
+		let labels = read_idx(bytes.as_slice()).unwrap();
+
+		assert!(labels.to_one_hot(3).unwrap() == vec![vec![0.0,0.0,1.0],vec![1.0,0.0,0.0],vec![0.0,1.0,0.0]]);
+		assert!(labels.to_one_hot(2).is_err());
+	}
+
+	#[test]
+	fn load_image_files(){
+		let dir = std::env::temp_dir().join(format!("rust_simple_nn_idx_{}",std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let images = dir.join("images-idx3-ubyte");
+		let labels = dir.join("labels-idx1-ubyte");
+		write_idx_bytes_file(&images, &[2,2,2], &[255,0,0,255, 0,255,255,0]).unwrap();
+		write_idx_bytes_file(&labels, &[2], &[1,0]).unwrap();
+
+		let data = load_labeled_images(&images, &labels, 2).unwrap();
+		assert!(data == vec![(vec![1.0,0.0,0.0,1.0],vec![0.0,1.0]),(vec![0.0,1.0,1.0,0.0],vec![1.0,0.0])]);
+
+		write_idx_bytes_file(&labels, &[1], &[1]).unwrap();
+		assert!(load_labeled_images(&images, &labels, 2).is_err());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,145 @@
+use rand::Rng;
+
+use crate::matrix::*;
+use crate::matrix_at;
+
+const MIN_RAND : f64 = 0.0;
+const MAX_RAND : f64 = 0.1;
+
+
+/// Strategy used to draw the initial parameters of a layer
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub enum Init {
+	/// weights and biases drawn uniformly in [0,0.1)
+	#[default]
+	Default,
+	/// weights and biases drawn uniformly in [min,max)
+	Uniform { min : f64, max : f64 },
+	/// weights drawn from a centered normal distribution, zero biases
+	Normal { std : f64 },
+	/// weights drawn from a centered normal distribution truncated to 2 std, zero biases
+	TruncatedNormal { std : f64 },
+	/// Glorot uniform : U(-a,a) with a = sqrt(6/(fan_in+fan_out)), zero biases
+	Xavier,
+	/// Kaiming normal : N(0,2/fan_in), suited to relu, zero biases
+	He,
+	/// LeCun normal : N(0,1/fan_in), zero biases
+	LeCun,
+	/// (semi-)orthogonal weights scaled by `gain`, zero biases
+	Orthogonal { gain : f64 },
+}
+
+
+impl Init {
+
+	/// Returns the initialisation matching a name from a config file
+	pub fn from_name(name : &str) -> Option<Init> {
+		match name.trim().to_lowercase().as_str() {
+			"default" => Some(Init::Default),
+			"uniform" => Some(Init::Uniform { min : -0.05, max : 0.05 }),
+			"normal" => Some(Init::Normal { std : 0.05 }),
+			"truncated_normal" => Some(Init::TruncatedNormal { std : 0.05 }),
+			"xavier" | "glorot" => Some(Init::Xavier),
+			"he" | "kaiming" => Some(Init::He),
+			"lecun" => Some(Init::LeCun),
+			"orthogonal" => Some(Init::Orthogonal { gain : 1.0 }),
+			_ => None,
+		}
+	}
+
+	/// Draw the (fan_out,fan_in) weight matrix of a layer
+	pub fn weights<R : Rng + ?Sized>(&self, fan_out : usize, fan_in : usize, rng : &mut R) -> Matrix<f64> {
+		match *self {
+			Init::Default => Matrix::new_uniform(fan_out, fan_in, MIN_RAND, MAX_RAND, rng),
+			Init::Uniform { min, max } => Matrix::new_uniform(fan_out, fan_in, min, max, rng),
+			Init::Normal { std } => Matrix::new_normal(fan_out, fan_in, 0.0, std, rng),
+			Init::TruncatedNormal { std } => Matrix::new_truncated_normal(fan_out, fan_in, 0.0, std, rng),
+			Init::Xavier => {
+				let limit = (6.0 / (fan_in+fan_out) as f64).sqrt();
+				Matrix::new_uniform(fan_out, fan_in, -limit, limit, rng)
+			},
+			Init::He => Matrix::new_normal(fan_out, fan_in, 0.0, (2.0 / fan_in as f64).sqrt(), rng),
+			Init::LeCun => Matrix::new_normal(fan_out, fan_in, 0.0, (1.0 / fan_in as f64).sqrt(), rng),
+			Init::Orthogonal { gain } => orthogonal(fan_out, fan_in, gain, rng),
+		}
+	}
+
+	/// Draw the (fan_out,1) bias matrix of a layer
+	pub fn biases<R : Rng + ?Sized>(&self, fan_out : usize, rng : &mut R) -> Matrix<f64> {
+		match *self {
+			Init::Default => Matrix::new_uniform(fan_out, 1, MIN_RAND, MAX_RAND, rng),
+			Init::Uniform { min, max } => Matrix::new_uniform(fan_out, 1, min, max, rng),
+			_ => Matrix::new(fan_out, 1),
+		}
+	}
+}
+
+
+/// Orthonormal rows (or columns if the matrix is tall) from the QR decomposition of a gaussian matrix
+fn orthogonal<R : Rng + ?Sized>(rows : usize, cols : usize, gain : f64, rng : &mut R) -> Matrix<f64> {
+	let (tall,short) = (rows.max(cols),rows.min(cols));
+	let gaussian = Matrix::new_normal(tall, short, 0.0, 1.0, rng);
+	let qr = gaussian.qr();
+
+	// fix the sign of each column so the result is uniformly distributed
+	let mut q = qr.q;
+	for j in 0..short {
+		let sign = if matrix_at!(j,j,qr.r) < 0.0 { -gain } else { gain };
+		for i in 0..tall {
+			matrix_at!(i,j,q) *= sign;
+		}
+	}
+
+	if rows >= cols { q } else { q.transpose() }
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	#[test]
+	fn orthogonal_rows(){
+		let mut rng = ChaCha8Rng::seed_from_u64(0);
+		for (rows,cols) in [(4,4),(3,6),(6,3)] {
+			let w = Init::Orthogonal { gain : 1.0 }.weights(rows, cols, &mut rng);
+			assert!(w.rows == rows && w.cols == cols);
+
+			// the smallest dimension is orthonormal
+			let gram = if rows <= cols {
+				let mut gram = Matrix::new(rows, rows);
+				w.transpose().trans_dot(&mut gram, &w.transpose());
+				gram
+			} else {
+				let mut gram = Matrix::new(cols, cols);
+				w.trans_dot(&mut gram, &w);
+				gram
+			};
+			let n = gram.rows;
+			let error : f64 = gram.values.iter().zip(Matrix::identity(n).values.iter()).map(|(a,b)| (a-b).abs()).sum();
+			assert!(error < 1e-9);
+		}
+	}
+
+	#[test]
+	fn xavier_bounds(){
+		let w = Init::Xavier.weights(10, 20, &mut ChaCha8Rng::seed_from_u64(0));
+		let limit = (6.0/30.0_f64).sqrt();
+		assert!(w.values.iter().all(|x| x.abs() <= limit));
+		assert!(Init::Xavier.biases(10, &mut ChaCha8Rng::seed_from_u64(0)).values.iter().all(|&x| x==0.0));
+	}
+
+	#[test]
+	fn init_names(){
+		assert!(Init::from_name("He") == Some(Init::He));
+		assert!(Init::from_name(" glorot") == Some(Init::Xavier));
+		assert!(Init::from_name("zeros").is_none());
+	}
+}
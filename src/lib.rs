@@ -1,3 +1,28 @@
 pub mod matrix;
+pub mod linalg;
+pub mod tensor;
+pub mod init;
+pub mod optimizer;
 pub mod nn;
+pub mod autodiff;
+pub mod block;
+pub mod conv;
+pub mod rnn;
+pub mod embedding;
+pub mod attention;
+pub mod graph;
+pub mod config;
+pub mod model;
+pub mod checkpoint;
+pub mod summary;
+pub mod dot;
+pub mod csv;
+pub mod idx;
+pub mod dataset;
+pub mod preprocessing;
+pub mod metrics;
+pub mod model_selection;
+pub mod search;
+pub mod evolution;
+pub mod datasets;
 pub mod utils;
\ No newline at end of file
@@ -0,0 +1,560 @@
+use crate::matrix::*;
+use crate::matrix_at;
+
+/// Pivots smaller than this (relative to the largest entry of the matrix) are treated as zero
+const SINGULAR_TOLERANCE : f64 = 1e-12;
+
+
+/// LU decomposition with partial pivoting : `P*A = L*U`
+///
+/// `L` (unit diagonal, not stored) and `U` are packed in the same matrix.
+#[derive(Debug,Clone)]
+pub struct Lu {
+	pub lu : Matrix<f64>,
+	/// row `i` of `P*A` is the row `pivots[i]` of `A`
+	pub pivots : Vec<usize>,
+	sign : f64,
+	singular : bool,
+}
+
+/// Thin QR decomposition of a (m,n) matrix with m>=n : `A = Q*R`
+#[derive(Debug,Clone)]
+pub struct Qr {
+	/// (m,n) matrix with orthonormal columns
+	pub q : Matrix<f64>,
+	/// (n,n) upper triangular matrix
+	pub r : Matrix<f64>,
+}
+
+/// Cholesky decomposition of a symmetric positive definite matrix : `A = L*L^T`
+#[derive(Debug,Clone)]
+pub struct Cholesky {
+	pub l : Matrix<f64>,
+}
+
+
+impl Matrix<f64> {
+
+	/// Decompose a square matrix with partial pivoting. The decomposition always succeeds,
+	/// use `Lu::is_singular` before solving.
+	pub fn lu(&self) -> Lu {
+		assert!(self.rows == self.cols,"LU decomposition needs a square matrix");
+		let n = self.rows;
+		let tolerance = SINGULAR_TOLERANCE * self.max_abs().max(1.0);
+		let mut lu = self.clone();
+		let mut pivots : Vec<usize> = (0..n).collect();
+		let mut sign = 1.0;
+		let mut singular = false;
+
+		for k in 0..n {
+			let mut pivot_row = k;
+			for i in k+1..n {
+				if matrix_at!(i,k,lu).abs() > matrix_at!(pivot_row,k,lu).abs() {
+					pivot_row = i;
+				}
+			}
+
+			if pivot_row != k {
+				lu.swap_rows(k, pivot_row);
+				pivots.swap(k, pivot_row);
+				sign = -sign;
+			}
+
+			let pivot = matrix_at!(k,k,lu);
+			if pivot.abs() <= tolerance {
+				singular = true;
+				continue;
+			}
+
+			for i in k+1..n {
+				matrix_at!(i,k,lu) /= pivot;
+				let factor = matrix_at!(i,k,lu);
+				for j in k+1..n {
+					matrix_at!(i,j,lu) -= factor * matrix_at!(k,j,lu);
+				}
+			}
+		}
+
+		Lu { lu, pivots, sign, singular }
+	}
+
+	/// Determinant of a square matrix (0 if the matrix is singular)
+	pub fn determinant(&self) -> f64 {
+		self.lu().determinant()
+	}
+
+	/// Inverse of a square matrix, `None` if the matrix is singular
+	pub fn inverse(&self) -> Option<Matrix<f64>> {
+		self.lu().inverse()
+	}
+
+	/// Solve `A*X = B` for a square matrix `A`, `None` if `A` is singular
+	///
+	/// # Argument
+	/// * `b` - right hand side, one system per column
+	pub fn solve(&self, b : &Matrix<f64>) -> Option<Matrix<f64>> {
+		self.lu().solve(b)
+	}
+
+	/// Householder QR decomposition of a (m,n) matrix with m>=n
+	pub fn qr(&self) -> Qr {
+		assert!(self.rows >= self.cols,"QR decomposition needs at least as many rows as columns");
+		let (m,n) = (self.rows,self.cols);
+		let mut r = self.clone();
+		let mut q = Matrix::identity(m);
+
+		for k in 0..n.min(m.saturating_sub(1)) {
+			let norm = (k..m).map(|i| matrix_at!(i,k,r).powi(2)).sum::<f64>().sqrt();
+			if norm == 0.0 {
+				continue;
+			}
+			let alpha = if matrix_at!(k,k,r) > 0.0 { -norm } else { norm };
+
+			let mut v : Vec<f64> = (k..m).map(|i| matrix_at!(i,k,r)).collect();
+			v[0] -= alpha;
+			let v_norm = v.iter().map(|x| x*x).sum::<f64>().sqrt();
+			if v_norm == 0.0 {
+				continue;
+			}
+			v.iter_mut().for_each(|x| *x /= v_norm);
+
+			// R = H*R with H = I - 2*v*v^T
+			for j in 0..n {
+				let projection : f64 = v.iter().enumerate().map(|(i,vi)| vi * matrix_at!(k+i,j,r)).sum();
+				for (i,vi) in v.iter().enumerate() {
+					matrix_at!(k+i,j,r) -= 2.0 * vi * projection;
+				}
+			}
+
+			// Q = Q*H
+			for i in 0..m {
+				let projection : f64 = v.iter().enumerate().map(|(j,vj)| vj * matrix_at!(i,k+j,q)).sum();
+				for (j,vj) in v.iter().enumerate() {
+					matrix_at!(i,k+j,q) -= 2.0 * vj * projection;
+				}
+			}
+		}
+
+		let mut thin_q = Matrix::new(m, n);
+		let mut thin_r = Matrix::new(n, n);
+		for i in 0..m {
+			for j in 0..n {
+				matrix_at!(i,j,thin_q) = matrix_at!(i,j,q);
+				if i<n && j>=i {
+					matrix_at!(i,j,thin_r) = matrix_at!(i,j,r);
+				}
+			}
+		}
+
+		Qr { q : thin_q, r : thin_r }
+	}
+
+	/// Cholesky decomposition, `None` if the matrix isn't symmetric positive definite
+	pub fn cholesky(&self) -> Option<Cholesky> {
+		assert!(self.rows == self.cols,"Cholesky decomposition needs a square matrix");
+		let n = self.rows;
+		let tolerance = SINGULAR_TOLERANCE * self.max_abs().max(1.0);
+		let mut l = Matrix::new(n, n);
+
+		for i in 0..n {
+			for j in 0..=i {
+				if (matrix_at!(i,j,self) - matrix_at!(j,i,self)).abs() > tolerance.sqrt() {
+					return None;
+				}
+
+				let sum : f64 = (0..j).map(|k| matrix_at!(i,k,l) * matrix_at!(j,k,l)).sum();
+				if i == j {
+					let diagonal = matrix_at!(i,i,self) - sum;
+					if diagonal <= tolerance {
+						return None;
+					}
+					matrix_at!(i,i,l) = diagonal.sqrt();
+				} else {
+					matrix_at!(i,j,l) = (matrix_at!(i,j,self) - sum) / matrix_at!(j,j,l);
+				}
+			}
+		}
+
+		Some(Cholesky { l })
+	}
+
+	/// Least squares solution of `A*X = B` (minimise `||A*X - B||`) using a QR decomposition.
+	/// Returns `None` if `A` doesn't have full column rank, ex : when it has fewer rows than columns.
+	///
+	/// # Argument
+	/// * `b` - right hand side, one problem per column
+	pub fn least_squares(&self, b : &Matrix<f64>) -> Option<Matrix<f64>> {
+		assert!(self.rows == b.rows,"right hand side should have as many rows as the matrix");
+		if self.rows < self.cols {
+			return None;
+		}
+		let Qr { q, r } = self.qr();
+		let tolerance = SINGULAR_TOLERANCE * r.max_abs().max(1.0);
+		if (0..r.rows).any(|i| matrix_at!(i,i,r).abs() <= tolerance) {
+			return None;
+		}
+
+		let mut qtb = Matrix::new(q.cols, b.cols);
+		q.trans_dot(&mut qtb, b);
+		Some(back_substitution(&r, &qtb))
+	}
+
+	/// Ridge regression solution of `A*X = B` : solve `(A^T*A + lambda*I)*X = A^T*B`.
+	/// Returns `None` if the regularised normal matrix isn't positive definite.
+	///
+	/// # Argument
+	/// * `b` - right hand side, one problem per column
+	/// * `lambda` - L2 penalty
+	pub fn ridge(&self, b : &Matrix<f64>, lambda : f64) -> Option<Matrix<f64>> {
+		assert!(self.rows == b.rows,"right hand side should have as many rows as the matrix");
+		assert!(lambda >= 0.0,"ridge penalty should be positive");
+
+		let mut normal = Matrix::new(self.cols, self.cols);
+		self.trans_dot(&mut normal, self);
+		for i in 0..normal.rows {
+			matrix_at!(i,i,normal) += lambda;
+		}
+
+		let mut atb = Matrix::new(self.cols, b.cols);
+		self.trans_dot(&mut atb, b);
+		Some(normal.cholesky()?.solve(&atb))
+	}
+
+	fn swap_rows(&mut self, a : usize, b : usize){
+		for j in 0..self.cols {
+			self.values.swap(a*self.cols + j, b*self.cols + j);
+		}
+	}
+
+	fn max_abs(&self) -> f64 {
+		self.values.iter().fold(0.0, |acc : f64,x| acc.max(x.abs()))
+	}
+}
+
+
+impl Lu {
+
+	pub fn is_singular(&self) -> bool {
+		self.singular
+	}
+
+	pub fn determinant(&self) -> f64 {
+		if self.singular {
+			return 0.0;
+		}
+		(0..self.lu.rows).map(|i| matrix_at!(i,i,self.lu)).product::<f64>() * self.sign
+	}
+
+	/// Solve `A*X = B`, `None` if `A` is singular
+	pub fn solve(&self, b : &Matrix<f64>) -> Option<Matrix<f64>> {
+		assert!(b.rows == self.lu.rows,"right hand side should have as many rows as the matrix");
+		if self.singular {
+			return None;
+		}
+		let n = self.lu.rows;
+
+		// forward substitution on P*B with the unit lower triangular L
+		let mut y = Matrix::new(n, b.cols);
+		for (i,&pivot) in self.pivots.iter().enumerate() {
+			for j in 0..b.cols {
+				let sum : f64 = (0..i).map(|k| matrix_at!(i,k,self.lu) * matrix_at!(k,j,y)).sum();
+				matrix_at!(i,j,y) = matrix_at!(pivot,j,b) - sum;
+			}
+		}
+
+		Some(back_substitution(&self.lu, &y))
+	}
+
+	pub fn inverse(&self) -> Option<Matrix<f64>> {
+		self.solve(&Matrix::identity(self.lu.rows))
+	}
+}
+
+
+impl Cholesky {
+
+	/// Solve `A*X = B` with `A = L*L^T`
+	pub fn solve(&self, b : &Matrix<f64>) -> Matrix<f64> {
+		assert!(b.rows == self.l.rows,"right hand side should have as many rows as the matrix");
+		let n = self.l.rows;
+
+		let mut y = Matrix::new(n, b.cols);
+		for i in 0..n {
+			for j in 0..b.cols {
+				let sum : f64 = (0..i).map(|k| matrix_at!(i,k,self.l) * matrix_at!(k,j,y)).sum();
+				matrix_at!(i,j,y) = (matrix_at!(i,j,b) - sum) / matrix_at!(i,i,self.l);
+			}
+		}
+
+		back_substitution(&self.l.transpose(), &y)
+	}
+}
+
+
+/// Solve `U*X = Y` with `U` upper triangular (only the upper part of `u` is read)
+fn back_substitution(u : &Matrix<f64>, y : &Matrix<f64>) -> Matrix<f64> {
+	let n = u.cols;
+	let mut x = Matrix::new(n, y.cols);
+	for i in (0..n).rev() {
+		for j in 0..y.cols {
+			let sum : f64 = (i+1..n).map(|k| matrix_at!(i,k,u) * matrix_at!(k,j,x)).sum();
+			matrix_at!(i,j,x) = (matrix_at!(i,j,y) - sum) / matrix_at!(i,i,u);
+		}
+	}
+	x
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                         Closed-form linear baseline                        */
+/* -------------------------------------------------------------------------- */
+
+
+/// Linear (or ridge) regression fitted in closed form, used as a baseline for the networks
+#[derive(Debug,Clone)]
+pub struct LinearModel {
+	/// (outputs,inputs) matrix
+	pub weights : Matrix<f64>,
+	pub bias : Vec<f64>,
+}
+
+impl LinearModel {
+
+	/// Fit the model on the same data format as `NeuralNetWork::train`.
+	/// The bias isn't penalised. Returns `None` if the problem is ill-conditioned,
+	/// ex : fewer samples than inputs without penalty.
+	///
+	/// # Argument
+	/// * `data` - (input,output) pairs
+	/// * `lambda` - L2 penalty, 0 for ordinary least squares
+	pub fn fit(data : &[(Vec<f64>,Vec<f64>)], lambda : f64) -> Option<LinearModel> {
+		assert!(!data.is_empty(),"no data to fit");
+		let (nb_inputs,nb_outputs) = (data[0].0.len(),data[0].1.len());
+		assert!(nb_inputs > 0,"the samples should have at least one input");
+		let nb_samples = data.len() as f64;
+
+		let mut input_mean = vec![0.0;nb_inputs];
+		let mut output_mean = vec![0.0;nb_outputs];
+		for (input,output) in data {
+			input_mean.iter_mut().zip(input).for_each(|(mean,x)| *mean += x / nb_samples);
+			output_mean.iter_mut().zip(output).for_each(|(mean,y)| *mean += y / nb_samples);
+		}
+
+		let mut x = Matrix::new(data.len(), nb_inputs);
+		let mut y = Matrix::new(data.len(), nb_outputs);
+		for (i,(input,output)) in data.iter().enumerate() {
+			for (j,value) in input.iter().enumerate() {
+				matrix_at!(i,j,x) = value - input_mean[j];
+			}
+			for (j,value) in output.iter().enumerate() {
+				matrix_at!(i,j,y) = value - output_mean[j];
+			}
+		}
+
+		let solution = if lambda == 0.0 { x.least_squares(&y)? } else { x.ridge(&y, lambda)? };
+		let weights = solution.transpose();
+
+		let mut bias = output_mean;
+		for (i,b) in bias.iter_mut().enumerate() {
+			*b -= (0..nb_inputs).map(|j| matrix_at!(i,j,weights) * input_mean[j]).sum::<f64>();
+		}
+
+		Some(LinearModel { weights, bias })
+	}
+
+	pub fn predict(&self, input : &[f64]) -> Vec<f64> {
+		let mut result = Matrix::new(self.weights.rows, 1);
+		self.weights.dot_vec(&mut result, input);
+		result.values.iter().zip(&self.bias).map(|(x,b)| x+b).collect()
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PRECISION : f64 = 1e-9;
+
+	fn product(ma : &Matrix<f64>, mb : &Matrix<f64>) -> Matrix<f64> {
+		let mut result = Matrix::new_dot_result(ma, mb);
+		ma.dot(&mut result, mb);
+		result
+	}
+
+	fn distance(ma : &Matrix<f64>, mb : &Matrix<f64>) -> f64 {
+		ma.values.iter().zip(mb.values.iter()).map(|(&a,&b)|(a-b).abs()).sum()
+	}
+
+	fn example() -> Matrix<f64> {
+		Matrix::from_vec(3, 3, vec![
+			2.0, 1.0, 1.0,
+			4.0, -6.0, 0.0,
+			-2.0, 7.0, 2.0,
+		])
+	}
+
+
+	/* ----------------------------------- LU ----------------------------------- */
+	#[test]
+	fn lu_solve_test(){
+		let a = example();
+		let b = Matrix::from_vec(3, 1, vec![5.0,-2.0,9.0]);
+		let x = a.solve(&b).unwrap();
+
+		assert!(distance(&x, &Matrix::from_vec(3, 1, vec![1.0,1.0,2.0])) < PRECISION);
+	}
+
+	#[test]
+	fn determinant_test(){
+		assert!((example().determinant() - -16.0).abs() < PRECISION);
+		assert!((Matrix::identity(4).determinant() - 1.0).abs() < PRECISION);
+	}
+
+	#[test]
+	fn inverse_test(){
+		let a = example();
+		let inverse = a.inverse().unwrap();
+
+		assert!(distance(&product(&a, &inverse), &Matrix::identity(3)) < PRECISION);
+	}
+
+	#[test]
+	fn singular_matrix_test(){
+		let a = Matrix::from_vec(3, 3, vec![
+			1.0, 2.0, 3.0,
+			2.0, 4.0, 6.0,
+			1.0, 0.0, 1.0,
+		]);
+
+		assert!(a.lu().is_singular());
+		assert!(a.determinant() == 0.0);
+		assert!(a.inverse().is_none());
+	}
+
+	#[test]
+	#[should_panic]
+	fn lu_not_square(){
+		Matrix::new(2, 3).lu();
+	}
+
+
+	/* ----------------------------------- QR ----------------------------------- */
+	#[test]
+	fn qr_test(){
+		let a = Matrix::from_vec(4, 3, vec![
+			1.0, -1.0, 4.0,
+			1.0, 4.0, -2.0,
+			1.0, 4.0, 2.0,
+			1.0, -1.0, 0.0,
+		]);
+		let Qr { q, r } = a.qr();
+
+		let mut qtq = Matrix::new(3, 3);
+		q.trans_dot(&mut qtq, &q);
+		assert!(distance(&qtq, &Matrix::identity(3)) < PRECISION);
+		assert!(distance(&product(&q, &r), &a) < PRECISION);
+		for i in 0..3 {
+			for j in 0..i {
+				assert!(matrix_at!(i,j,r) == 0.0);
+			}
+		}
+	}
+
+	#[test]
+	fn qr_empty(){
+		let Qr { q, r } = Matrix::<f64>::new(0, 0).qr();
+		assert!((q.rows,q.cols,r.rows,r.cols) == (0,0,0,0));
+	}
+
+
+	/* -------------------------------- Cholesky -------------------------------- */
+	#[test]
+	fn cholesky_test(){
+		let a = Matrix::from_vec(3, 3, vec![
+			4.0, 12.0, -16.0,
+			12.0, 37.0, -43.0,
+			-16.0, -43.0, 98.0,
+		]);
+		let cholesky = a.cholesky().unwrap();
+		let confirm = Matrix::from_vec(3, 3, vec![
+			2.0, 0.0, 0.0,
+			6.0, 1.0, 0.0,
+			-8.0, 5.0, 3.0,
+		]);
+		assert!(distance(&cholesky.l, &confirm) < PRECISION);
+
+		let b = Matrix::from_vec(3, 1, vec![1.0,2.0,3.0]);
+		let x = cholesky.solve(&b);
+		assert!(distance(&product(&a, &x), &b) < PRECISION);
+	}
+
+	#[test]
+	fn cholesky_not_positive_definite(){
+		assert!(example().cholesky().is_none());
+		assert!(Matrix::from_vec(2, 2, vec![1.0,2.0,2.0,1.0]).cholesky().is_none());
+	}
+
+
+	/* ------------------------------ Least squares ----------------------------- */
+	#[test]
+	fn least_squares_test(){
+		// best line through (0,6) (1,0) (2,0) is y = 5 - 3x
+		let a = Matrix::from_vec(3, 2, vec![
+			1.0, 0.0,
+			1.0, 1.0,
+			1.0, 2.0,
+		]);
+		let b = Matrix::from_vec(3, 1, vec![6.0,0.0,0.0]);
+		let x = a.least_squares(&b).unwrap();
+
+		assert!(distance(&x, &Matrix::from_vec(2, 1, vec![5.0,-3.0])) < PRECISION);
+		assert!(distance(&a.ridge(&b, 0.0).unwrap(), &x) < PRECISION);
+	}
+
+	#[test]
+	fn least_squares_rank_deficient(){
+		let a = Matrix::from_vec(3, 2, vec![
+			1.0, 2.0,
+			2.0, 4.0,
+			3.0, 6.0,
+		]);
+		assert!(a.least_squares(&Matrix::new(3, 1)).is_none());
+		assert!(a.ridge(&Matrix::new(3, 1), 0.1).is_some());
+	}
+
+	#[test]
+	fn linear_model_fit(){
+		let data : Vec<(Vec<f64>,Vec<f64>)> = (0..20).map(|i| {
+			let x = vec![i as f64, (i*i % 7) as f64];
+			let y = vec![3.0*x[0] - 2.0*x[1] + 1.0, x[1] - 4.0];
+			(x,y)
+		}).collect();
+		let model = LinearModel::fit(&data, 0.0).unwrap();
+
+		for (input,output) in &data {
+			let prediction = model.predict(input);
+			assert!(prediction.iter().zip(output).all(|(a,b)| (a-b).abs() < 1e-6));
+		}
+	}
+
+	#[test]
+	fn linear_model_underdetermined(){
+		let data = vec![(vec![1.0,2.0,3.0],vec![1.0]),(vec![0.0,1.0,-1.0],vec![2.0])];
+		assert!(LinearModel::fit(&data, 0.0).is_none());
+		assert!(LinearModel::fit(&data, 0.1).is_some());
+		assert!(Matrix::from_vec(1, 2, vec![1.0,2.0]).least_squares(&Matrix::new(1, 1)).is_none());
+	}
+
+	#[test]
+	#[should_panic(expected = "the samples should have at least one input")]
+	fn linear_model_without_inputs(){
+		LinearModel::fit(&[(vec![],vec![1.0])], 0.0);
+	}
+}
@@ -1,128 +1,643 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use rust_simple_nn::checkpoint::Checkpoints;
+use rust_simple_nn::config::*;
+use rust_simple_nn::csv::{self, Column, CsvLoader, MissingValues};
+use rust_simple_nn::dataset::*;
+use rust_simple_nn::datasets::*;
+use rust_simple_nn::metrics::*;
+use rust_simple_nn::model::ModelFile;
 use rust_simple_nn::nn::*;
-use rand::{self, Rng, seq::SliceRandom, thread_rng};
+use rust_simple_nn::search::{self, SearchConfig};
+
+const USAGE : &str = "usage:
+  rust_simple_nn train --config <file> [--data <csv> | --builtin <name> [--samples <n>]] --output <model> [--verbose]
+        [--features <columns>] [--targets <columns>] [--categorical <columns>] [--missing error|skip|mean|<value>]
+        [--checkpoints <dir> [--checkpoint-every <epochs>] [--checkpoint-minutes <m>] [--keep <n>] [--resume]]
+  rust_simple_nn search --config <file> --space <file> [--data <csv> | --builtin <name> [--samples <n>]]
+        [--threads <n>] [--output <best config>] [train column options]
+  rust_simple_nn predict --model <model> [--input <csv>] [--output <csv>]
+  rust_simple_nn evaluate --model <model> (--data <csv> | --builtin <name> [--samples <n>]) [--seed <n>]
+        [--task regression|classification] [--threshold <t>]
+  rust_simple_nn inspect --model <model> [--json]
+
+CSV files have the inputs first and the targets in the last columns unless --features/--targets
+select them, by header name or index (from 0) separated by commas. Categorical columns are one-hot
+encoded and rows with missing values are rejected, skipped or imputed depending on --missing.
+predict and evaluate read CSV files with the columns and categories used for training.
+predict reads the inputs from stdin when --input is missing or `-`.
+train --resume continues from the newest checkpoint of the directory, with the same config and data.
+inspect prints the layers with their parameters, memory and FLOPs per sample, as JSON with --json.
+search trains variations of the config listed in the space file and prints them ranked by its metric.
+builtin datasets: quadrant, function1, function2, xor";
+
+
+/// Error of the command line tool, usage errors exit with 2 and the others with 1
+#[derive(Debug,PartialEq)]
+enum CliError {
+	Usage(String),
+	Failed(String),
+}
+
+impl fmt::Display for CliError {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			CliError::Usage(message) => write!(f, "{message}\n\n{USAGE}"),
+			CliError::Failed(message) => write!(f, "{message}"),
+		}
+	}
+}
+
+fn failed(message : impl fmt::Display) -> CliError {
+	CliError::Failed(message.to_string())
+}
+
+
+#[derive(Debug,Clone,PartialEq)]
+enum DataArg {
+	Csv(String),
+	Builtin { name : String, samples : Option<usize> },
+}
+
+/// CSV column options of `train`, they override the `[data]` section of the config
+#[derive(Debug,Clone,PartialEq,Default)]
+struct ColumnArgs {
+	features : Option<Vec<Column>>,
+	targets : Option<Vec<Column>>,
+	categorical : Option<Vec<Column>>,
+	missing : Option<MissingValues>,
+}
+
+impl ColumnArgs {
+
+	fn apply(&self, mut loader : CsvLoader) -> CsvLoader {
+		if let Some(features) = &self.features {
+			loader.features = features.clone();
+		}
+		if let Some(targets) = &self.targets {
+			loader.targets = targets.clone();
+		}
+		if let Some(categorical) = &self.categorical {
+			loader.categorical = categorical.clone();
+		}
+		if let Some(missing) = self.missing {
+			loader.missing = missing;
+		}
+		loader
+	}
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum Command {
+	Train { config : String, data : Option<DataArg>, columns : ColumnArgs, output : String, verbose : bool, checkpoints : Option<Checkpoints>, resume : bool },
+	Search { config : String, space : String, data : Option<DataArg>, columns : ColumnArgs, threads : Option<usize>, output : Option<String> },
+	Predict { model : String, input : Option<String>, output : Option<String> },
+	Evaluate { model : String, data : DataArg, seed : Option<u64>, task : Task },
+	Inspect { model : String, json : bool },
+	Help,
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                              Argument parsing                              */
+/* -------------------------------------------------------------------------- */
+
+
+/// `--name value` options and `--flag` switches following the subcommand
+struct Options {
+	values : Vec<(String,String)>,
+	flags : Vec<String>,
+}
+
+impl Options {
 
-fn generate_data()->Vec<(Vec<f64>,Vec<f64>)>{
-	let mut result = vec![];
-	let mut rng = rand::thread_rng();
-	for _ in 0..1000000 {
+	fn parse(args : &[String], flag_names : &[&str]) -> Result<Options,CliError> {
+		let mut options = Options { values : vec![], flags : vec![] };
+		let mut args = args.iter();
 
-		let input = vec![rng.gen_range(1.0..2.0),rng.gen_range(1.0..2.0)];
-		let ouput = function1(&input);
-		result.push((input,ouput));
+		while let Some(arg) = args.next() {
+			let Some(name) = arg.strip_prefix("--") else {
+				return Err(CliError::Usage(format!("unexpected argument `{arg}`")));
+			};
+			if flag_names.contains(&name) {
+				options.flags.push(name.to_string());
+				continue;
+			}
+			match args.next() {
+				Some(value) => options.values.push((name.to_string(),value.clone())),
+				None => return Err(CliError::Usage(format!("missing value for `--{name}`"))),
+			}
+		}
+
+		Ok(options)
+	}
+
+	/// Errors on the options the command doesn't know
+	fn check(&self, allowed : &[&str]) -> Result<(),CliError> {
+		match self.values.iter().find(|(name,_)| !allowed.contains(&name.as_str())) {
+			Some((name,_)) => Err(CliError::Usage(format!("unknown option `--{name}`"))),
+			None => Ok(()),
+		}
+	}
+
+	fn get(&self, name : &str) -> Option<String> {
+		self.values.iter().rev().find(|(key,_)| key == name).map(|(_,value)| value.clone())
+	}
+
+	fn required(&self, name : &str) -> Result<String,CliError> {
+		self.get(name).ok_or_else(|| CliError::Usage(format!("missing `--{name}`")))
+	}
+
+	fn parsed<T : std::str::FromStr>(&self, name : &str) -> Result<Option<T>,CliError> {
+		match self.get(name) {
+			Some(value) => value.parse().map(Some).map_err(|_| CliError::Usage(format!("invalid value `{value}` for `--{name}`"))),
+			None => Ok(None),
+		}
+	}
+
+	fn flag(&self, name : &str) -> bool {
+		self.flags.iter().any(|flag| flag == name)
+	}
+
+	/// comma separated column names or indexes
+	fn columns(&self, name : &str) -> Option<Vec<Column>> {
+		self.get(name).map(|value| value.split(',').filter(|column| !column.trim().is_empty()).map(|column| column.parse().unwrap()).collect())
+	}
+
+	fn column_args(&self) -> Result<ColumnArgs,CliError> {
+		Ok(ColumnArgs {
+			features : self.columns("features"),
+			targets : self.columns("targets"),
+			categorical : self.columns("categorical"),
+			missing : self.parsed("missing")?,
+		})
+	}
+
+	fn checkpoints(&self) -> Result<Option<Checkpoints>,CliError> {
+		let Some(dir) = self.get("checkpoints") else {
+			return match ["checkpoint-every","checkpoint-minutes","keep"].into_iter().find(|name| self.get(name).is_some()) {
+				Some(name) => Err(CliError::Usage(format!("`--{name}` needs `--checkpoints`"))),
+				None => Ok(None),
+			};
+		};
+		let mut checkpoints = Checkpoints::new(dir);
+		match self.parsed("checkpoint-every")? {
+			Some(0) => return Err(CliError::Usage("`--checkpoint-every` should be at least 1".to_string())),
+			Some(epochs) => checkpoints = checkpoints.every_epochs(epochs),
+			None => {},
+		}
+		match self.parsed::<f64>("checkpoint-minutes")? {
+			Some(minutes) if !(minutes > 0.0 && minutes.is_finite()) => return Err(CliError::Usage("`--checkpoint-minutes` should be positive".to_string())),
+			Some(minutes) => checkpoints = checkpoints.every(Duration::from_secs_f64(minutes*60.0)),
+			None => {},
+		}
+		match self.parsed("keep")? {
+			Some(0) => return Err(CliError::Usage("`--keep` should be at least 1".to_string())),
+			Some(keep) => checkpoints = checkpoints.keep_last(keep),
+			None => {},
+		}
+		Ok(Some(checkpoints))
+	}
+
+	fn data(&self) -> Result<Option<DataArg>,CliError> {
+		match (self.get("data"),self.get("builtin")) {
+			(Some(_),Some(_)) => Err(CliError::Usage("`--data` and `--builtin` can't be used together".to_string())),
+			(Some(path),None) => Ok(Some(DataArg::Csv(path))),
+			(None,Some(name)) => Ok(Some(DataArg::Builtin { name, samples : self.parsed("samples")? })),
+			(None,None) => Ok(None),
+		}
+	}
+}
+
+
+fn parse_args(args : &[String]) -> Result<Command,CliError> {
+	let Some(command) = args.first() else {
+		return Err(CliError::Usage("missing command".to_string()));
 	};
+	let rest = &args[1..];
 
-	result
+	match command.as_str() {
+		"train" => {
+			let options = Options::parse(rest, &["verbose","resume"])?;
+			options.check(&["config","data","builtin","samples","output","features","targets","categorical","missing",
+				"checkpoints","checkpoint-every","checkpoint-minutes","keep"])?;
+			let checkpoints = options.checkpoints()?;
+			if options.flag("resume") && checkpoints.is_none() {
+				return Err(CliError::Usage("`--resume` needs `--checkpoints`".to_string()));
+			}
+			Ok(Command::Train {
+				config : options.required("config")?,
+				data : options.data()?,
+				columns : options.column_args()?,
+				output : options.required("output")?,
+				verbose : options.flag("verbose"),
+				checkpoints,
+				resume : options.flag("resume"),
+			})
+		},
+		"search" => {
+			let options = Options::parse(rest, &[])?;
+			options.check(&["config","space","data","builtin","samples","features","targets","categorical","missing","threads","output"])?;
+			Ok(Command::Search {
+				config : options.required("config")?,
+				space : options.required("space")?,
+				data : options.data()?,
+				columns : options.column_args()?,
+				threads : options.parsed("threads")?,
+				output : options.get("output"),
+			})
+		},
+		"predict" => {
+			let options = Options::parse(rest, &[])?;
+			options.check(&["model","input","output"])?;
+			Ok(Command::Predict {
+				model : options.required("model")?,
+				input : options.get("input").filter(|input| input != "-"),
+				output : options.get("output"),
+			})
+		},
+		"evaluate" => {
+			let options = Options::parse(rest, &[])?;
+			options.check(&["model","data","builtin","samples","seed","task","threshold"])?;
+			let threshold : Option<f64> = options.parsed("threshold")?;
+			let task = match (options.get("task").as_deref(),threshold) {
+				(Some("classification"),_) | (None,Some(_)) => Task::Classification { threshold : threshold.unwrap_or(0.5) },
+				(Some("regression") | None,None) => Task::Regression,
+				(Some("regression"),Some(_)) => return Err(CliError::Usage("`--threshold` is only used by classification".to_string())),
+				(Some(other),_) => return Err(CliError::Usage(format!("unknown task `{other}` (expected regression or classification)"))),
+			};
+			Ok(Command::Evaluate {
+				model : options.required("model")?,
+				data : options.data()?.ok_or_else(|| CliError::Usage("missing `--data` or `--builtin`".to_string()))?,
+				seed : options.parsed("seed")?,
+				task,
+			})
+		},
+		"inspect" => {
+			let options = Options::parse(rest, &["json"])?;
+			options.check(&["model"])?;
+			Ok(Command::Inspect { model : options.required("model")?, json : options.flag("json") })
+		},
+		"help" | "--help" | "-h" => Ok(Command::Help),
+		other => Err(CliError::Usage(format!("unknown command `{other}`"))),
+	}
 }
 
-fn generate_id()->Vec<(Vec<f64>,Vec<f64>)>{
-	let mut result = vec![];
-	let mut rng = rand::thread_rng();
-	for _ in 0..1000000 {
 
-		let input = vec![rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0),rng.gen_range(-1.0..1.0)];
-		let ouput = function2(&input);
-		result.push((input,ouput));
+
+/* -------------------------------------------------------------------------- */
+/*                                  Commands                                  */
+/* -------------------------------------------------------------------------- */
+
+
+fn rng_from_seed(seed : Option<u64>) -> ChaCha8Rng {
+	match seed {
+		Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+		None => ChaCha8Rng::from_entropy(),
+	}
+}
+
+/// Load labeled data and check it fits the network, CSV files also return the fitted loader
+fn load_data(data : &DataArg, loader : &CsvLoader, nn : &NeuralNetWork, rng : &mut ChaCha8Rng) -> Result<(Vec<Sample>,Option<CsvLoader>),CliError> {
+	let (result,fitted) = match data {
+		DataArg::Csv(path) => {
+			let dataset = loader.load_path(path).map_err(|e| failed(format!("{path}: {e}")))?;
+			if dataset.skipped > 0 {
+				eprintln!("{path}: skipped {} rows with missing values",dataset.skipped);
+			}
+			(dataset.samples,Some(dataset.fitted))
+		},
+		DataArg::Builtin { name, samples } => {
+			builtin(name, samples.unwrap_or(DEFAULT_SAMPLES), rng)
+				.map(|samples| (samples,None))
+				.ok_or_else(|| failed(format!("unknown builtin dataset `{name}` (expected one of {})",BUILTIN_DATASETS.join(", "))))?
+		},
+	};
+
+	if result.is_empty() {
+		return Err(failed("the dataset is empty"));
+	}
+	if let Some(index) = result.iter().position(|(input,_)| input.len() != nn.input_size()) {
+		return Err(failed(format!("sample {} has {} inputs but the network expects {}",index+1,result[index].0.len(),nn.input_size())));
+	}
+	if let Some(index) = result.iter().position(|(_,output)| output.len() != nn.output_size()) {
+		return Err(failed(format!("sample {} has {} targets but the network outputs {}",index+1,result[index].1.len(),nn.output_size())));
+	}
+
+	Ok((result,fitted))
+}
+
+
+/// Training data from the arguments or the `[data]` section of the config
+fn training_data(config : &NetworkConfig, data : Option<DataArg>, columns : &ColumnArgs, nn : &NeuralNetWork, rng : &mut ChaCha8Rng) -> Result<(Vec<Sample>,Option<CsvLoader>),CliError> {
+	let data = match (data,&config.data) {
+		(Some(data),_) => data,
+		(None,Some(DataSource::Csv { path, .. })) => DataArg::Csv(path.clone()),
+		(None,Some(DataSource::Builtin { name, samples })) => DataArg::Builtin { name : name.clone(), samples : *samples },
+		(None,None) => return Err(CliError::Usage("no training data, use `--data`, `--builtin` or a [data] section in the config".to_string())),
 	};
 
-	result
+	let loader = config.data.as_ref().and_then(|source| source.csv_loader(nn.output_size()))
+		.unwrap_or_else(|| CsvLoader::new().last_targets(nn.output_size()));
+	load_data(&data, &columns.apply(loader), nn, rng)
 }
 
 
-fn gen_quadrant()->Vec<(Vec<f64>,Vec<f64>)>{
-	let mut result = vec![];
-	let mut rng = rand::thread_rng();
-	for _ in 0..1000000 {
+fn train(config_path : &str, data : Option<DataArg>, columns : &ColumnArgs, output : &str, verbose : bool, checkpoints : Option<Checkpoints>, resume : bool) -> Result<(),CliError> {
+	let config = NetworkConfig::from_file(config_path).map_err(|e| failed(format!("{config_path}: {e}")))?;
+	let (mut nn,mut options) = config.build().map_err(|e| failed(format!("{config_path}: {e}")))?;
+	options.verbose = verbose;
 
-		let input = vec![rng.gen_range(-10.0..10.0),rng.gen_range(-10.0..10.0)];
-		let ouput = some_quadrant(&input);
-		result.push((input,ouput));
+	let mut rng = rng_from_seed(config.seed);
+	let (data,fitted) = training_data(&config, data, columns, &nn, &mut rng)?;
+	let nb_samples = data.len();
+	let (input_scaler,target_scaler) = config.scalers().map_err(|e| failed(format!("{config_path}: {e}")))?;
+	nn.fit_scalers(&data, input_scaler, target_scaler);
+
+	// reshuffled at every epoch
+	let mut loader = DataLoader::new(MatrixDataset::from_samples(&data), options.mini_batch_size).shuffle(rng.gen());
+	drop(data);
+	let costs = match checkpoints {
+		None => nn.train_loader(&mut loader, &options).map_err(|e| failed(format!("training stopped: {e}")))?,
+		Some(checkpoints) => {
+			let checkpoint_error = |e : io::Error| failed(format!("{}: {e}",checkpoints.dir.display()));
+			let latest = if resume { checkpoints.latest().map_err(checkpoint_error)? } else { None };
+			match latest {
+				Some(checkpoint) => {
+					println!("resuming at epoch {}, batch {}",checkpoint.epoch,checkpoint.batch);
+					nn.resume_from(&checkpoint, &mut loader, &options, Some(&checkpoints))
+				},
+				None => nn.train_checkpointed(&mut loader, &options, &checkpoints),
+			}.map_err(checkpoint_error)?
+		},
 	};
+	let mut file = nn.to_model_file();
+	file.csv = fitted;
+	file.save(output).map_err(|e| failed(format!("{output}: {e}")))?;
+
+	println!("trained on {nb_samples} samples, final cost {:.6}, model written to {output}",costs.last().unwrap());
+	Ok(())
+}
 
-	result
+
+fn search(config_path : &str, space_path : &str, data : Option<DataArg>, columns : &ColumnArgs, threads : Option<usize>, output : Option<String>) -> Result<(),CliError> {
+	let config = NetworkConfig::from_file(config_path).map_err(|e| failed(format!("{config_path}: {e}")))?;
+	let space = SearchConfig::from_file(space_path).map_err(|e| failed(format!("{space_path}: {e}")))?;
+	let mut options = space.options().map_err(|e| failed(format!("{space_path}: {e}")))?;
+	if let Some(threads) = threads {
+		options.threads = threads;
+	}
+	let (nn,_) = config.build().map_err(|e| failed(format!("{config_path}: {e}")))?;
+
+	let (data,_) = training_data(&config, data, columns, &nn, &mut rng_from_seed(config.seed))?;
+	let result = search::search(&config, &space.space(), &data, &options).map_err(|e| failed(format!("{space_path}: {e}")))?;
+	print!("{result}");
+
+	match (&result.best_config,output) {
+		(Some(best),Some(output)) => {
+			fs::write(&output, best.to_toml_string()).map_err(|e| failed(format!("{output}: {e}")))?;
+			println!("best config written to {output}");
+			Ok(())
+		},
+		(None,_) => Err(failed("every trial failed")),
+		(Some(_),None) => Ok(()),
+	}
+}
+
+
+/// Read a model file and build its network
+fn load_model(model : &str) -> Result<(NeuralNetWork,ModelFile),CliError> {
+	let file = ModelFile::load(model).map_err(|e| failed(format!("{model}: {e}")))?;
+	let nn = NeuralNetWork::from_model_file(&file).map_err(|e| failed(format!("{model}: {e}")))?;
+	Ok((nn,file))
 }
 
-fn generate_xor_data()->Vec<(Vec<f64>,Vec<f64>)>{
-	let mut result = vec![];
-	for _ in 0..10000 {
 
-		result.push((vec![0.0,0.0],vec![0.0]));
-		result.push((vec![0.0,1.0],vec![1.0]));
-		result.push((vec![1.0,0.0],vec![1.0]));
-		result.push((vec![1.0,1.0],vec![0.0]));
+fn predict(model : &str, input : Option<String>, output : Option<String>) -> Result<(),CliError> {
+	let (mut nn,file) = load_model(model)?;
+	let loader = file.csv.map(|loader| loader.inputs_only()).unwrap_or_default();
+
+	let (source,inputs) = match &input {
+		Some(path) => (path.as_str(),loader.load_path(path)),
+		None => ("stdin",loader.load(io::stdin().lock())),
+	};
+	let inputs = inputs.map_err(|e| failed(format!("{source}: {e}")))?.samples;
+
+	let mut predictions = Vec::with_capacity(inputs.len());
+	for (index,(input,_)) in inputs.iter().enumerate() {
+		if input.len() != nn.input_size() {
+			return Err(failed(format!("{source}: row {} has {} values but the network expects {}",index+1,input.len(),nn.input_size())));
+		}
+		predictions.push(nn.predict(input));
 	}
 
-	result
+	let result = match &output {
+		Some(path) => File::create(path).and_then(|file| csv::write_rows(&mut BufWriter::new(file), &predictions)),
+		None => csv::write_rows(&mut io::stdout().lock(), &predictions),
+	};
+	result.map_err(|e| failed(format!("{}: {e}",output.as_deref().unwrap_or("stdout"))))
 }
 
-//2 inputs, 1 output
-fn function1(input : &[f64]) -> Vec<f64>{
-	vec![((input[0]+input[1]).powi(2))]
+
+fn evaluate(model : &str, data : &DataArg, seed : Option<u64>, task : Task) -> Result<(),CliError> {
+	let (mut nn,file) = load_model(model)?;
+	let loader = file.csv.unwrap_or_else(|| CsvLoader::new().last_targets(nn.output_size()));
+	let (data,_) = load_data(data, &loader, &nn, &mut rng_from_seed(seed))?;
+
+	let cost = nn.batch_cost(&data);
+	println!("cost ({}): {cost:.6}",nn.cost_name());
+	print!("{}",nn.evaluate(&data, task));
+	Ok(())
 }
 
-//5 inputs, 5 outputs
-fn function2(input : &[f64]) -> Vec<f64>{
-	vec![
-		(input[0]+input[1]),
-		(input[0]+input[2]),
-		(input[3]+input[1]),
-		(input[4]+input[0]-input[3]*4.0),
-		(input[4]-input[0]-input[3]),
-	]
+
+fn inspect(model : &str, json : bool) -> Result<(),CliError> {
+	let nn = NeuralNetWork::load(model).map_err(|e| failed(format!("{model}: {e}")))?;
+	let summary = nn.summary();
+	let result = match json {
+		true => writeln!(io::stdout(), "{}",serde_json::to_string_pretty(&summary).expect("a summary is always representable in JSON")),
+		false => writeln!(io::stdout(), "{summary}"),
+	};
+	result.map_err(|e| failed(format!("stdout: {e}")))
 }
 
-// if the coordoante is in a certain qudrant, then it's 1, otherwise it's 0
-fn some_quadrant(input : &[f64])-> Vec<f64>{
-	let (x,y) = (input[0],input[1]);
-	if x<0.0 && y>0.0 {
-		return vec![1.0];
+
+fn run(args : &[String]) -> Result<(),CliError> {
+	match parse_args(args)? {
+		Command::Train { config, data, columns, output, verbose, checkpoints, resume } => train(&config, data, &columns, &output, verbose, checkpoints, resume),
+		Command::Search { config, space, data, columns, threads, output } => search(&config, &space, data, &columns, threads, output),
+		Command::Predict { model, input, output } => predict(&model, input, output),
+		Command::Evaluate { model, data, seed, task } => evaluate(&model, &data, seed, task),
+		Command::Inspect { model, json } => inspect(&model, json),
+		Command::Help => {
+			println!("{USAGE}");
+			Ok(())
+		},
 	}
+}
 
-	return vec![0.0];
+fn main() -> ExitCode {
+	let args : Vec<String> = std::env::args().skip(1).collect();
+
+	match run(&args) {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(error) => {
+			eprintln!("error: {error}");
+			match error {
+				CliError::Usage(_) => ExitCode::from(2),
+				CliError::Failed(_) => ExitCode::from(1),
+			}
+		}
+	}
 }
 
-fn main(){
 
 
-	let mut neural_network = NeuralNetWork::new(
-		&vec![2,4,1],
-		"default",
-		"relu",
-		"sigmoid"
-	);
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-	println!("===========================");
-	neural_network.input(&vec![1.0,2.0]);
-	neural_network.print_output();
+	fn args(line : &str) -> Vec<String> {
+		line.split_whitespace().map(|arg| arg.to_string()).collect()
+	}
 
-	println!("Generating data ....");
-	let mut data = gen_quadrant();
-	println!("data generated, strating shuffling");
-	let mut rng = thread_rng();
-	data.shuffle(&mut rng);
-	println!("data shuffled, strating training");	
-	neural_network.train(&data, 120,100, 0.5,true);
-	
-	println!("===========================");
-	neural_network.input(&vec![10.0,20.0]);
-	neural_network.print_output();
+	#[test]
+	fn parse_train(){
+		let command = parse_args(&args("train --config net.toml --builtin xor --samples 40 --output model.json --verbose")).unwrap();
+		assert!(command == Command::Train {
+			config : "net.toml".to_string(),
+			data : Some(DataArg::Builtin { name : "xor".to_string(), samples : Some(40) }),
+			columns : ColumnArgs::default(),
+			output : "model.json".to_string(),
+			verbose : true,
+			checkpoints : None,
+			resume : false,
+		});
 
-	println!("===========================");
-	neural_network.input(&vec![-30000.0,2.1]);
-	neural_network.print_output();
+		let command = parse_args(&args("train --config net.toml --output model.json --checkpoints runs --checkpoint-every 5 --keep 3 --resume")).unwrap();
+		assert!(matches!(command,Command::Train { checkpoints : Some(checkpoints), resume : true, .. } if checkpoints == Checkpoints::new("runs").every_epochs(5).keep_last(3)));
+	}
 
-	println!("===========================");
-	neural_network.input(&vec![-2.1,3000000.0]);
-	neural_network.print_output();
+	#[test]
+	fn parse_predict_stdin(){
+		let command = parse_args(&args("predict --model model.json --input -")).unwrap();
+		assert!(command == Command::Predict { model : "model.json".to_string(), input : None, output : None });
+	}
 
-	println!("===========================");
-	neural_network.input(&vec![0.0,0.0]);
-	neural_network.print_output();
+	#[test]
+	fn usage_errors(){
+		assert!(matches!(parse_args(&args("")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("fit --model a")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("inspect")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("inspect --model")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("evaluate --model m --data a.csv --builtin xor")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("predict --model m --samples 3")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("evaluate --model m --builtin xor --samples many")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("evaluate --model m --builtin xor --task ranking")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("train --config c --output m --resume")),Err(CliError::Usage(_))));
+		assert!(matches!(parse_args(&args("train --config c --output m --keep 2")),Err(CliError::Usage(_))));
+	}
 
-	println!("===========================");
-	neural_network.input(&vec![-10.0,10.0]);
-	neural_network.print_output();
+	#[test]
+	fn train_evaluate_inspect(){
+		let dir = std::env::temp_dir().join(format!("rust_simple_nn_cli_{}",std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let config = dir.join("net.toml");
+		let model = dir.join("model.json");
+		std::fs::write(&config, "input = 2\nepochs = 2\nseed = 1\n[[layers]]\nsize = 3\n[[layers]]\nsize = 1\nactivation = \"sigmoid\"\n").unwrap();
 
+		let train_args = format!("train --config {} --builtin xor --samples 40 --output {}",config.display(),model.display());
+		assert!(run(&args(&train_args)).is_ok());
+		assert!(run(&args(&format!("evaluate --model {} --builtin quadrant --samples 10",model.display()))).is_ok());
+		assert!(run(&args(&format!("evaluate --model {} --builtin xor --samples 8 --threshold 0.4",model.display()))).is_ok());
+		assert!(run(&args(&format!("inspect --model {}",model.display()))).is_ok());
+		assert!(run(&args(&format!("inspect --model {} --json",model.display()))).is_ok());
 
-}
+		// function2 has 5 inputs, the model expects 2
+		assert!(matches!(run(&args(&format!("evaluate --model {} --builtin function2 --samples 10",model.display()))),Err(CliError::Failed(_))));
+		assert!(matches!(run(&args(&format!("inspect --model {}",dir.join("missing.json").display()))),Err(CliError::Failed(_))));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
 
+	#[test]
+	fn resumed_training_gives_the_same_model(){
+		let dir = std::env::temp_dir().join(format!("rust_simple_nn_cli_resume_{}",std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let config = dir.join("net.toml");
+		let checkpoints = dir.join("checkpoints");
+		std::fs::write(&config, "input = 2\nepochs = 3\nbatch_size = 8\nseed = 1\n[optimizer]\ntype = \"adam\"\n[[layers]]\nsize = 3\n[[layers]]\nsize = 1\nactivation = \"sigmoid\"\n").unwrap();
 
+		let train = |model : &str, extra : &str| {
+			let train_args = format!("train --config {} --builtin xor --samples 40 --output {} {extra}",config.display(),dir.join(model).display());
+			assert!(run(&args(&train_args)).is_ok());
+			ModelFile::load(dir.join(model)).unwrap()
+		};
+		let uninterrupted = train("uninterrupted.json", "");
+		let checkpointed = train("checkpointed.json", &format!("--checkpoints {} --checkpoint-every 2 --keep 1",checkpoints.display()));
+		assert!(checkpointed == uninterrupted);
+		assert!(Checkpoints::new(&checkpoints).latest().unwrap().unwrap().epoch == 2);
+
+		let resumed = train("resumed.json", &format!("--checkpoints {} --resume",checkpoints.display()));
+		assert!(resumed == uninterrupted);
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn search_writes_best_config(){
+		let dir = std::env::temp_dir().join(format!("rust_simple_nn_search_{}",std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let (config,space,best) = (dir.join("net.toml"),dir.join("space.toml"),dir.join("best.toml"));
+		std::fs::write(&config, "input = 2\nepochs = 4\nseed = 1\n[[layers]]\nsize = 3\n[[layers]]\nsize = 1\nactivation = \"sigmoid\"\n").unwrap();
+		std::fs::write(&space, "metric = \"accuracy\"\nstrategy = \"grid\"\n[params]\nlearning_rate = [0.01, 0.5]\n\"hidden.size\" = [2, 4]\n").unwrap();
+
+		let search_args = format!("search --config {} --space {} --builtin quadrant --samples 100 --threads 2 --output {}",config.display(),space.display(),best.display());
+		assert!(run(&args(&search_args)).is_ok());
+		let best = NetworkConfig::from_file(&best).unwrap();
+		assert!(best.layers[0].size == 2 || best.layers[0].size == 4);
+
+		assert!(matches!(parse_args(&args("search --config net.toml --builtin xor")),Err(CliError::Usage(_))));
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn train_predict_csv_columns(){
+		let dir = std::env::temp_dir().join(format!("rust_simple_nn_cli_csv_{}",std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let config = dir.join("net.toml");
+		let data = dir.join("train.csv");
+		let new = dir.join("new.csv");
+		let model = dir.join("model.json");
+		let output = dir.join("predictions.csv");
+		std::fs::write(&config, "input = 3\nepochs = 2\nseed = 1\ninput_scaler = \"standard\"\n[[layers]]\nsize = 1\nactivation = \"sigmoid\"\n").unwrap();
+		std::fs::write(&data, "label,city,age\n1,paris,31\n0,lyon,NA\n1,paris,25\n").unwrap();
+		std::fs::write(&new, "age,city\n40,lyon\nNA,berlin\n").unwrap();
+
+		let train_args = format!("train --config {} --data {} --targets label --categorical city --missing mean --output {}",config.display(),data.display(),model.display());
+		assert!(run(&args(&train_args)).is_ok());
+		let file = ModelFile::load(&model).unwrap();
+		assert!(file.csv.unwrap().vocabulary.unwrap()["city"] == vec!["lyon","paris"]);
+		assert!(file.input_scaler.is_some());
+
+		assert!(run(&args(&format!("evaluate --model {} --data {}",model.display(),data.display()))).is_ok());
+		assert!(run(&args(&format!("predict --model {} --input {} --output {}",model.display(),new.display(),output.display()))).is_ok());
+		assert!(std::fs::read_to_string(&output).unwrap().lines().count() == 2);
+
+		let wrong_column = format!("train --config {} --data {} --targets price --output {}",config.display(),data.display(),model.display());
+		assert!(matches!(run(&args(&wrong_column)),Err(CliError::Failed(message)) if message.contains("price")));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -1,4 +1,5 @@
-use rand::{self, Rng};
+use rand::{self, Rng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
 
 #[macro_export]
 macro_rules! matrix_at {
@@ -8,7 +9,7 @@ macro_rules! matrix_at {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
 pub struct Matrix<T>{
 	pub rows : usize,
 	pub cols : usize,
@@ -26,26 +27,138 @@ impl Matrix<f64> {
 	/// * `cols` - number of columns
 	pub fn new(rows : usize,cols : usize) -> Matrix<f64>{
 		Matrix{
-			rows: rows,
-			cols: cols,
+			rows,
+			cols,
 			values: vec![0.0;rows*cols],
 		}
 	}
 
+	/// Create a Matrix of dim (rows,cols) from row-major values
+	/// 
+	/// # Argument
+	/// * `rows` - number of rows
+	/// * `cols` - number of columns
+	/// * `values` - the values, row after row
+	pub fn from_vec(rows : usize,cols : usize,values : Vec<f64>) -> Matrix<f64>{
+		assert!(values.len() == rows*cols,"values don't match the matrix dimensions");
+		Matrix{
+			rows,
+			cols,
+			values,
+		}
+	}
+
+	/// Create the identity Matrix of dim (n,n)
+	pub fn identity(n : usize) -> Matrix<f64>{
+		let mut result = Matrix::new(n, n);
+		for i in 0..n {
+			matrix_at!(i,i,result) = 1.0;
+		}
+		result
+	}
+
 	pub fn new_radom_gen_range(rows : usize,cols : usize, min : f64, max: f64) -> Matrix<f64>{
-		let mut values = vec![];
-		let mut rng = rand::thread_rng();
-		for _ in 0..rows*cols {
-			values.push(rng.gen_range(min..max));
+		Matrix::new_uniform(rows, cols, min, max, &mut rand::thread_rng())
+	}
+
+	/// Create a Matrix filled with values drawn uniformly in [min,max)
+	/// 
+	/// # Argument
+	/// * `rows` - number of rows
+	/// * `cols` - number of columns
+	/// * `min` - lower bound (included)
+	/// * `max` - upper bound (excluded)
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new_uniform<R : Rng + ?Sized>(rows : usize,cols : usize, min : f64, max: f64, rng : &mut R) -> Matrix<f64>{
+		let values = (0..rows*cols).map(|_| rng.gen_range(min..max)).collect();
+
+		Matrix{
+			rows,
+			cols,
+			values,
+		}
+	}
+
+	/// Create a Matrix filled with values drawn from a normal distribution
+	/// 
+	/// # Argument
+	/// * `rows` - number of rows
+	/// * `cols` - number of columns
+	/// * `mean` - mean of the distribution
+	/// * `std` - standard deviation of the distribution
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new_normal<R : Rng + ?Sized>(rows : usize,cols : usize, mean : f64, std : f64, rng : &mut R) -> Matrix<f64>{
+		assert!(std>=0.0,"standard deviation should be positive");
+		let values = (0..rows*cols).map(|_| mean + std*standard_normal(rng)).collect();
+
+		Matrix{
+			rows,
+			cols,
+			values,
+		}
+	}
+
+	/// Create a Matrix filled with values drawn from a normal distribution truncated to
+	/// two standard deviations around the mean (values outside are drawn again)
+	/// 
+	/// # Argument
+	/// * `rows` - number of rows
+	/// * `cols` - number of columns
+	/// * `mean` - mean of the distribution
+	/// * `std` - standard deviation of the distribution (before truncation)
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new_truncated_normal<R : Rng + ?Sized>(rows : usize,cols : usize, mean : f64, std : f64, rng : &mut R) -> Matrix<f64>{
+		assert!(std>=0.0,"standard deviation should be positive");
+		let values = (0..rows*cols).map(|_| {
+			loop {
+				let x = standard_normal(rng);
+				if x.abs() <= 2.0 {
+					return mean + std*x;
+				}
+			}
+		}).collect();
+
+		Matrix{
+			rows,
+			cols,
+			values,
 		}
+	}
+
+	/// Create a mask Matrix where each value is 1 with probability `p` and 0 otherwise
+	/// 
+	/// # Argument
+	/// * `rows` - number of rows
+	/// * `cols` - number of columns
+	/// * `p` - probability of drawing a 1
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new_bernoulli<R : Rng + ?Sized>(rows : usize,cols : usize, p : f64, rng : &mut R) -> Matrix<f64>{
+		assert!((0.0..=1.0).contains(&p),"probability should be in [0,1]");
+		let values = (0..rows*cols).map(|_| if rng.gen_bool(p) { 1.0 } else { 0.0 }).collect();
 
 		Matrix{
-			rows: rows,
-			cols: cols,
+			rows,
+			cols,
 			values,
 		}
 	}
 
+	/// Create a random (n,n) permutation Matrix, multiplying by it shuffles rows (or columns)
+	/// 
+	/// # Argument
+	/// * `n` - size of the matrix
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new_permutation<R : Rng + ?Sized>(n : usize, rng : &mut R) -> Matrix<f64>{
+		let mut permutation : Vec<usize> = (0..n).collect();
+		permutation.shuffle(rng);
+
+		let mut result = Matrix::new(n, n);
+		for (i,j) in permutation.into_iter().enumerate() {
+			matrix_at!(i,j,result) = 1.0;
+		}
+		result
+	}
+
 	pub fn new_dot_result(ma: &Self,mb: &Self) -> Self {
 		Matrix { rows: ma.rows, cols: mb.cols, values: vec![0.0;ma.rows*mb.cols] }
 	}
@@ -91,7 +204,7 @@ impl Matrix<f64> {
 
 		for j in 0..dest.cols {
 			for i in 0..dest.rows {
-				for k in 0..self.cols {
+				for k in 0..self.rows {
 					matrix_at!(i,j,dest) += matrix_at!(k,i,self) * matrix_at!(k,j,mb);
 				}
 			}
@@ -99,8 +212,24 @@ impl Matrix<f64> {
 		}
 	}
 
+	/// Add `self · mbᵀ` to `dest`, used to accumulate weight gradients
+	pub fn dot_trans_add(&self,dest : &mut Matrix<f64>, mb : &Matrix<f64>){
+		assert!(self.cols == mb.cols,"matrix not suited for dot_trans_add prodcut");
+		assert!(dest.rows == self.rows && dest.cols == mb.rows,"destination matrix doesn'have suited dimension for dot_trans_add product");
 
-	pub fn dot_vec(&self,dest : &mut Matrix<f64>,mb :&Vec<f64>) {
+		for i in 0..dest.rows {
+			for j in 0..dest.cols {
+				let mut sum = 0.0;
+				for k in 0..self.cols {
+					sum += matrix_at!(i,k,self) * matrix_at!(j,k,mb);
+				}
+				matrix_at!(i,j,dest) += sum;
+			}
+		}
+	}
+
+
+	pub fn dot_vec(&self,dest : &mut Matrix<f64>,mb :&[f64]) {
 		assert!(self.cols == mb.len(),"matrix not suited for dot prodcut");
 		assert!(dest.cols == 1 && dest.rows == self.rows,"destination matrix doesn'have suited dimension for dot product");
 		assert!(self.cols!=0,"Empty matrix");
@@ -109,8 +238,8 @@ impl Matrix<f64> {
 
 		for i in 0..dest.rows {
 			matrix_at!(i,0,dest)=0.0;
-			for k in 0..self.cols {
-				matrix_at!(i,0,dest) += matrix_at!(i,k,self) * mb[k];
+			for (k,value) in mb.iter().enumerate() {
+				matrix_at!(i,0,dest) += matrix_at!(i,k,self) * value;
 			}
 		}
 
@@ -132,7 +261,7 @@ impl Matrix<f64> {
 
 		for i in 0..self.rows {
 			for j in 0..self.cols {
-				matrix_at!(i,j,self) = matrix_at!(i,j,self)+matrix_at!(i,j,mb);
+				matrix_at!(i,j,self) += matrix_at!(i,j,mb);
 			}
 		}
 	}
@@ -148,7 +277,7 @@ impl Matrix<f64> {
 			assert!(self.rows == mb.rows && self.cols == mb.cols,"matrix dimensions not equals");
 
 			for (i, elem) in &mut self.values.iter_mut().enumerate() {
-				*elem = *elem * mb.values[i];
+				*elem *= mb.values[i];
 			}
 	}
 
@@ -182,7 +311,7 @@ impl Matrix<f64> {
 	/// * `self` - caller Matrix, can be overwritten
 	/// * `function` - the cost derivative function
 	/// * `output` - output layer
-	pub fn cost_derivative_mut(&mut self,output:&Vec<f64>, function : fn(f64,f64)->f64) -> &mut Self
+	pub fn cost_derivative_mut(&mut self,output:&[f64], function : fn(f64,f64)->f64) -> &mut Self
 	{
 		assert!(output.len() == self.values.len());
 		for (elem,output) in &mut self.values.iter_mut().zip(output) {
@@ -218,13 +347,13 @@ impl Matrix<f64> {
 		}
 	}
 
-	pub fn matrix_weight_compute(&mut self, prev_activation : &Vec<f64>, delta_vec : &Vec<f64>){
-		assert!(delta_vec.len()!=0 && prev_activation.len()!=0);
+	pub fn matrix_weight_compute(&mut self, prev_activation : &[f64], delta_vec : &[f64]){
+		assert!(!delta_vec.is_empty() && !prev_activation.is_empty());
 		assert!(self.cols == prev_activation.len() && self.rows==delta_vec.len());
 
-		for i in 0..self.rows{
-			for j in 0..self.cols {
-				matrix_at!(i,j,self) += prev_activation[j] * delta_vec[i];
+		for (i,delta) in delta_vec.iter().enumerate(){
+			for (j,activation) in prev_activation.iter().enumerate() {
+				matrix_at!(i,j,self) += activation * delta;
 			}
 		}
 
@@ -232,6 +361,17 @@ impl Matrix<f64> {
 	}
 
 
+	/// Returns the transposed matrix
+	pub fn transpose(&self) -> Matrix<f64>{
+		let mut result = Matrix::new(self.cols, self.rows);
+		for i in 0..self.rows {
+			for j in 0..self.cols {
+				matrix_at!(j,i,result) = matrix_at!(i,j,self);
+			}
+		}
+		result
+	}
+
 	pub fn zero(&mut self){
 		self.values.fill(0.0);
 	}
@@ -241,12 +381,20 @@ impl Matrix<f64> {
 			for j in 0..self.cols {
 				print!("{:.5} ",matrix_at!(i,j,self));
 			}
-			print!("\n");
+			println!();
 		}
 	}
 }
 
 
+/// Draw a value from the standard normal distribution (Box-Muller transform)
+pub fn standard_normal<R : Rng + ?Sized>(rng : &mut R) -> f64 {
+	// 1-u is in (0,1] so the logarithm is always defined
+	let u1 : f64 = 1.0 - rng.gen::<f64>();
+	let u2 : f64 = rng.gen();
+	(-2.0*u1.ln()).sqrt() * (2.0*std::f64::consts::PI*u2).cos()
+}
+
 
 /* -------------------------------------------------------------------------- */
 /*                                    Tests                                   */
@@ -255,6 +403,7 @@ impl Matrix<f64> {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use rand::{SeedableRng, rngs::StdRng};
 
 
 	/* ---------------------------- Dot product test ---------------------------- */
@@ -273,7 +422,7 @@ mod tests {
 		let mut ma = Matrix::new(2, 2);
 		let mut mb = Matrix::new(2, 3);
 		let mut result = Matrix::new(ma.rows,mb.cols);
-		let confirm =vec![4.0,0.0,2.0,2.0,0.0,1.0];
+		let confirm =[4.0,0.0,2.0,2.0,0.0,1.0];
 
 		matrix_at!(0,0,ma) = 2.0;
 		matrix_at!(0,1,ma) = 0.0;
@@ -343,7 +492,7 @@ mod tests {
 		let mut ma = Matrix::new(2, 2);
 		let mut mb = Matrix::new(2, 2);
 		let mut result = Matrix::new(2,2);
-		let confirm =vec![3.0,9.0,3.0,7.0];
+		let confirm =[3.0,9.0,3.0,7.0];
 
 
 		matrix_at!(0,0,ma) = 2.0;
@@ -442,4 +591,87 @@ mod tests {
 		ma.multiply_by_mut(&mb);
 	}
 
+
+	/* ------------------------ Random matrix constructors ----------------------- */
+	fn mean_std(values : &[f64]) -> (f64,f64) {
+		let mean = values.iter().sum::<f64>() / values.len() as f64;
+		let var = values.iter().map(|x| (x-mean).powi(2)).sum::<f64>() / values.len() as f64;
+		(mean,var.sqrt())
+	}
+
+	#[test]
+	fn random_seeded_reproducible(){
+		let ma = Matrix::new_normal(10, 10, 0.0, 1.0, &mut StdRng::seed_from_u64(42));
+		let mb = Matrix::new_normal(10, 10, 0.0, 1.0, &mut StdRng::seed_from_u64(42));
+		let mc = Matrix::new_normal(10, 10, 0.0, 1.0, &mut StdRng::seed_from_u64(43));
+
+		assert!(ma == mb);
+		assert!(ma != mc);
+	}
+
+	#[test]
+	fn random_uniform_range(){
+		let ma = Matrix::new_uniform(50, 50, -2.0, 3.0, &mut StdRng::seed_from_u64(0));
+		assert!(ma.values.iter().all(|&x| (-2.0..3.0).contains(&x)));
+	}
+
+	#[test]
+	fn random_normal_moments(){
+		let ma = Matrix::new_normal(200, 100, 3.0, 2.0, &mut StdRng::seed_from_u64(1));
+		let (mean,std) = mean_std(&ma.values);
+
+		assert!((mean-3.0).abs() < 0.05);
+		assert!((std-2.0).abs() < 0.05);
+	}
+
+	#[test]
+	fn random_truncated_normal_bounds(){
+		let ma = Matrix::new_truncated_normal(200, 100, 1.0, 0.5, &mut StdRng::seed_from_u64(2));
+		let (mean,_) = mean_std(&ma.values);
+
+		assert!(ma.values.iter().all(|&x| (0.0..=2.0).contains(&x)));
+		assert!((mean-1.0).abs() < 0.05);
+	}
+
+	#[test]
+	fn random_bernoulli_mask(){
+		let ma = Matrix::new_bernoulli(200, 100, 0.3, &mut StdRng::seed_from_u64(3));
+		let (mean,_) = mean_std(&ma.values);
+
+		assert!(ma.values.iter().all(|&x| x==0.0 || x==1.0));
+		assert!((mean-0.3).abs() < 0.02);
+	}
+
+	#[test]
+	fn random_permutation_matrix(){
+		let ma = Matrix::new_permutation(8, &mut StdRng::seed_from_u64(4));
+
+		for i in 0..8 {
+			assert!((0..8).map(|j| matrix_at!(i,j,ma)).sum::<f64>() == 1.0);
+			assert!((0..8).map(|j| matrix_at!(j,i,ma)).sum::<f64>() == 1.0);
+		}
+		assert!((ma.determinant().abs()-1.0).abs() < 1e-12);
+	}
+
+	#[test]
+	fn transposed_products_accumulate(){
+		let ma = Matrix::from_vec(3, 2, vec![1.0,2.0,3.0,4.0,5.0,6.0]);
+		let mb = Matrix::from_vec(3, 4, (0..12).map(|x| x as f64).collect());
+
+		// dest += maᵀ · mb with a non square ma
+		let mut dest = Matrix::from_vec(2, 4, vec![1.0;8]);
+		ma.trans_dot_add(&mut dest, &mb);
+		let mut expected = Matrix::new(2, 4);
+		ma.transpose().dot(&mut expected, &mb);
+		assert!(dest.values.iter().zip(&expected.values).all(|(a,b)| *a == b+1.0));
+
+		// dest += ma · mcᵀ
+		let mc = Matrix::from_vec(4, 2, (0..8).map(|x| x as f64).collect());
+		let mut dest = Matrix::new(3, 4);
+		ma.dot_trans_add(&mut dest, &mc);
+		let mut expected = Matrix::new(3, 4);
+		ma.dot(&mut expected, &mc.transpose());
+		assert!(dest == expected);
+	}
+
 }
@@ -0,0 +1,782 @@
+use std::fmt;
+
+use crate::nn::*;
+use crate::preprocessing::quantile;
+
+/// Probabilities are clamped to [EPSILON,1-EPSILON] in the log-loss
+const LOG_LOSS_EPSILON : f64 = 1e-15;
+
+
+/// What the outputs of a network mean, it decides which metrics `NeuralNetWork::evaluate` computes
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Task {
+	/// A single output is a binary classifier, its class is 1 when the output is at least `threshold`.
+	/// With several outputs the class is the index of the largest output (one-hot targets).
+	Classification { threshold : f64 },
+	/// Every output is a real value
+	Regression,
+}
+
+impl Task {
+
+	/// classification with a 0.5 threshold
+	pub fn classification() -> Task {
+		Task::Classification { threshold : 0.5 }
+	}
+}
+
+
+/// Result of `NeuralNetWork::evaluate`
+#[derive(Debug,Clone,PartialEq)]
+pub enum Report {
+	Classification(ClassificationReport),
+	Regression(RegressionReport),
+}
+
+impl Report {
+
+	/// The main metrics as (name,value) pairs, metrics that can't be computed are left out
+	pub fn scores(&self) -> Vec<(&'static str,f64)> {
+		let mut scores = vec![];
+		match self {
+			Report::Classification(report) => {
+				scores.extend([
+					("accuracy",report.accuracy),
+					("precision",report.macro_average.precision),
+					("recall",report.macro_average.recall),
+					("f1",report.macro_average.f1),
+					("log_loss",report.log_loss),
+				]);
+				scores.extend(report.roc_auc.map(|auc| ("roc_auc",auc)));
+				scores.extend(report.pr_auc.map(|auc| ("pr_auc",auc)));
+			},
+			Report::Regression(report) => {
+				let metrics = &report.aggregate;
+				scores.extend([("r2",metrics.r2),("rmse",metrics.rmse),("mae",metrics.mae)]);
+				scores.extend(metrics.mape.map(|mape| ("mape",mape)));
+				scores.extend([("max_error",metrics.max_error),("explained_variance",metrics.explained_variance)]);
+			},
+		}
+		scores
+	}
+
+	/// The value of one of the `scores`
+	pub fn score(&self, name : &str) -> Option<f64> {
+		self.scores().into_iter().find(|(score,_)| *score == name).map(|(_,value)| value)
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Report::Classification(report) => report.fmt(f),
+			Report::Regression(report) => report.fmt(f),
+		}
+	}
+}
+
+
+/// How per class scores are combined
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Average {
+	/// mean of the classes
+	Macro,
+	/// computed on the total counts of every class
+	Micro,
+	/// mean of the classes weighted by their number of samples
+	Weighted,
+}
+
+
+/// `counts[actual][predicted]` is the number of samples of class `actual` predicted as `predicted`
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ConfusionMatrix {
+	pub counts : Vec<Vec<usize>>,
+}
+
+impl ConfusionMatrix {
+
+	pub fn new(nb_classes : usize) -> ConfusionMatrix {
+		ConfusionMatrix { counts : vec![vec![0;nb_classes];nb_classes] }
+	}
+
+	/// # Arguments
+	/// * `actual` - the classes of the samples
+	/// * `predicted` - the predicted classes, in the same order
+	/// * `nb_classes` - classes go from 0 to `nb_classes-1`
+	pub fn from_classes(actual : &[usize], predicted : &[usize], nb_classes : usize) -> ConfusionMatrix {
+		assert!(actual.len() == predicted.len(), "there should be as many predictions as samples");
+		let mut matrix = ConfusionMatrix::new(nb_classes);
+		for (actual,predicted) in actual.iter().zip(predicted) {
+			matrix.add(*actual, *predicted);
+		}
+		matrix
+	}
+
+	pub fn add(&mut self, actual : usize, predicted : usize) {
+		self.counts[actual][predicted] += 1;
+	}
+
+	pub fn nb_classes(&self) -> usize {
+		self.counts.len()
+	}
+
+	pub fn total(&self) -> usize {
+		self.counts.iter().flatten().sum()
+	}
+
+	pub fn true_positives(&self, class : usize) -> usize {
+		self.counts[class][class]
+	}
+
+	/// number of samples predicted as `class`
+	pub fn predicted(&self, class : usize) -> usize {
+		self.counts.iter().map(|row| row[class]).sum()
+	}
+
+	/// number of samples of `class`
+	pub fn support(&self, class : usize) -> usize {
+		self.counts[class].iter().sum()
+	}
+
+	pub fn accuracy(&self) -> f64 {
+		let correct : usize = (0..self.nb_classes()).map(|class| self.true_positives(class)).sum();
+		ratio(correct, self.total())
+	}
+
+	/// 0 when nothing is predicted as `class`
+	pub fn precision(&self, class : usize) -> f64 {
+		ratio(self.true_positives(class), self.predicted(class))
+	}
+
+	/// 0 when there is no sample of `class`
+	pub fn recall(&self, class : usize) -> f64 {
+		ratio(self.true_positives(class), self.support(class))
+	}
+
+	pub fn f1(&self, class : usize) -> f64 {
+		f1(self.precision(class), self.recall(class))
+	}
+
+	pub fn average_precision(&self, average : Average) -> f64 {
+		self.average(average, ConfusionMatrix::precision)
+	}
+
+	pub fn average_recall(&self, average : Average) -> f64 {
+		self.average(average, ConfusionMatrix::recall)
+	}
+
+	pub fn average_f1(&self, average : Average) -> f64 {
+		match average {
+			Average::Micro => f1(self.average_precision(Average::Micro), self.average_recall(Average::Micro)),
+			_ => self.average(average, ConfusionMatrix::f1),
+		}
+	}
+
+	fn average(&self, average : Average, score : fn(&ConfusionMatrix,usize) -> f64) -> f64 {
+		let classes = 0..self.nb_classes();
+		match average {
+			Average::Macro => classes.map(|class| score(self, class)).sum::<f64>() / self.nb_classes() as f64,
+			Average::Weighted => classes.map(|class| score(self, class) * self.support(class) as f64).sum::<f64>() / self.total() as f64,
+			// every sample is predicted once, so micro precision and recall are both the accuracy
+			Average::Micro => self.accuracy(),
+		}
+	}
+}
+
+impl fmt::Display for ConfusionMatrix {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:>14}","actual \\ pred")?;
+		for class in 0..self.nb_classes() {
+			write!(f, "{class:>8}")?;
+		}
+		writeln!(f)?;
+		for (class,row) in self.counts.iter().enumerate() {
+			write!(f, "{class:>14}")?;
+			for count in row {
+				write!(f, "{count:>8}")?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+
+fn ratio(numerator : usize, denominator : usize) -> f64 {
+	if denominator == 0 { 0.0 } else { numerator as f64 / denominator as f64 }
+}
+
+fn f1(precision : f64, recall : f64) -> f64 {
+	if precision + recall == 0.0 { 0.0 } else { 2.0 * precision * recall / (precision + recall) }
+}
+
+
+/// A point of a ROC curve, samples with a score at least `threshold` are positive
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct RocPoint {
+	pub threshold : f64,
+	pub false_positive_rate : f64,
+	pub true_positive_rate : f64,
+}
+
+/// (positives, true positives) counted at each distinct score, from the highest
+fn cumulative_counts(scores : &[f64], labels : &[bool]) -> Vec<(f64,usize,usize)> {
+	assert!(scores.len() == labels.len(), "there should be one label per score");
+	let mut order : Vec<usize> = (0..scores.len()).collect();
+	order.sort_by(|a,b| scores[*b].total_cmp(&scores[*a]));
+
+	let mut counts : Vec<(f64,usize,usize)> = vec![];
+	let (mut predicted,mut true_positives) = (0,0);
+	for (i,index) in order.iter().enumerate() {
+		predicted += 1;
+		true_positives += labels[*index] as usize;
+		// samples of equal score are all positive at the same threshold
+		if i+1 == order.len() || scores[order[i+1]] != scores[*index] {
+			counts.push((scores[*index],predicted,true_positives));
+		}
+	}
+	counts
+}
+
+/// The ROC curve from (0,0) to (1,1), empty if one of the two classes has no sample
+///
+/// # Arguments
+/// * `scores` - output of the classifier, higher means more likely positive
+/// * `labels` - whether each sample is positive
+pub fn roc_curve(scores : &[f64], labels : &[bool]) -> Vec<RocPoint> {
+	let positives = labels.iter().filter(|label| **label).count();
+	let negatives = labels.len() - positives;
+	if positives == 0 || negatives == 0 {
+		return vec![];
+	}
+
+	let mut curve = vec![RocPoint { threshold : f64::INFINITY, false_positive_rate : 0.0, true_positive_rate : 0.0 }];
+	for (threshold,predicted,true_positives) in cumulative_counts(scores, labels) {
+		curve.push(RocPoint {
+			threshold,
+			false_positive_rate : ratio(predicted - true_positives, negatives),
+			true_positive_rate : ratio(true_positives, positives),
+		});
+	}
+	curve
+}
+
+/// Area under the ROC curve (trapezoidal rule), `None` if one of the two classes has no sample
+pub fn roc_auc(scores : &[f64], labels : &[bool]) -> Option<f64> {
+	let curve = roc_curve(scores, labels);
+	if curve.is_empty() {
+		return None;
+	}
+	Some(curve.windows(2).map(|points| {
+		(points[1].false_positive_rate - points[0].false_positive_rate) * (points[1].true_positive_rate + points[0].true_positive_rate) / 2.0
+	}).sum())
+}
+
+/// Area under the precision-recall curve, computed as the average precision
+/// (the precision at each threshold weighted by the recall gained), `None` without positive sample
+pub fn pr_auc(scores : &[f64], labels : &[bool]) -> Option<f64> {
+	let positives = labels.iter().filter(|label| **label).count();
+	if positives == 0 {
+		return None;
+	}
+	let mut area = 0.0;
+	let mut previous_recall = 0.0;
+	for (_,predicted,true_positives) in cumulative_counts(scores, labels) {
+		let recall = ratio(true_positives, positives);
+		area += (recall - previous_recall) * ratio(true_positives, predicted);
+		previous_recall = recall;
+	}
+	Some(area)
+}
+
+
+/// Precision, recall and F1 of one class or of an average
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Scores {
+	pub precision : f64,
+	pub recall : f64,
+	pub f1 : f64,
+}
+
+/// Every classification metric, see `NeuralNetWork::evaluate`
+#[derive(Debug,Clone,PartialEq)]
+pub struct ClassificationReport {
+	pub threshold : f64,
+	pub confusion : ConfusionMatrix,
+	pub accuracy : f64,
+	/// one entry per class
+	pub classes : Vec<Scores>,
+	pub macro_average : Scores,
+	pub micro_average : Scores,
+	pub weighted_average : Scores,
+	pub log_loss : f64,
+	/// ROC curve of the positive class, or of each class against the others with several outputs
+	pub roc : Vec<Vec<RocPoint>>,
+	/// mean of the ROC AUC of each curve, `None` if a class has no sample
+	pub roc_auc : Option<f64>,
+	/// mean of the PR AUC of each curve, `None` if a class has no sample
+	pub pr_auc : Option<f64>,
+}
+
+impl ClassificationReport {
+
+	/// # Arguments
+	/// * `outputs` - outputs of the network, one per sample
+	/// * `targets` - expected outputs, 0/1 for a single output or one-hot vectors
+	/// * `threshold` - decision threshold of a single output
+	pub fn new(outputs : &[Vec<f64>], targets : &[Vec<f64>], threshold : f64) -> ClassificationReport {
+		assert!(outputs.len() == targets.len() && !outputs.is_empty(), "there should be one output per target and at least one sample");
+		let size = targets[0].len();
+		assert!(size > 0 && outputs.iter().chain(targets).all(|values| values.len() == size), "outputs and targets should have the same size");
+
+		let argmax = |values : &[f64]| values.iter().enumerate().fold(0, |best,(i,value)| if *value > values[best] { i } else { best });
+		let binary = size == 1;
+		let (actual,predicted) : (Vec<usize>,Vec<usize>) = outputs.iter().zip(targets).map(|(output,target)| {
+			if binary {
+				((target[0] >= 0.5) as usize,(output[0] >= threshold) as usize)
+			} else {
+				(argmax(target),argmax(output))
+			}
+		}).unzip();
+		let confusion = ConfusionMatrix::from_classes(&actual, &predicted, if binary { 2 } else { size });
+
+		// probability given to the right class, outputs are normalised to sum to 1 with several classes
+		let log_loss = outputs.iter().zip(&actual).map(|(output,class)| {
+			let probability = if binary {
+				if *class == 1 { output[0] } else { 1.0 - output[0] }
+			} else {
+				let sum : f64 = output.iter().map(|value| value.max(0.0)).sum();
+				if sum > 0.0 { output[*class].max(0.0) / sum } else { 0.0 }
+			};
+			-probability.clamp(LOG_LOSS_EPSILON, 1.0 - LOG_LOSS_EPSILON).ln()
+		}).sum::<f64>() / outputs.len() as f64;
+
+		// one score per curve : the positive class alone, or each class against the others
+		let curves : Vec<(Vec<f64>,Vec<bool>)> = if binary {
+			vec![(outputs.iter().map(|output| output[0]).collect(),actual.iter().map(|class| *class == 1).collect())]
+		} else {
+			(0..size).map(|class| (outputs.iter().map(|output| output[class]).collect(),actual.iter().map(|actual| *actual == class).collect())).collect()
+		};
+		let mean = |values : Option<Vec<f64>>| values.map(|values| values.iter().sum::<f64>() / values.len() as f64);
+
+		let scores = |average : Average| Scores {
+			precision : confusion.average_precision(average),
+			recall : confusion.average_recall(average),
+			f1 : confusion.average_f1(average),
+		};
+
+		ClassificationReport {
+			threshold,
+			accuracy : confusion.accuracy(),
+			classes : (0..confusion.nb_classes()).map(|class| Scores {
+				precision : confusion.precision(class),
+				recall : confusion.recall(class),
+				f1 : confusion.f1(class),
+			}).collect(),
+			macro_average : scores(Average::Macro),
+			micro_average : scores(Average::Micro),
+			weighted_average : scores(Average::Weighted),
+			log_loss,
+			roc : curves.iter().map(|(scores,labels)| roc_curve(scores, labels)).collect(),
+			roc_auc : mean(curves.iter().map(|(scores,labels)| roc_auc(scores, labels)).collect()),
+			pr_auc : mean(curves.iter().map(|(scores,labels)| pr_auc(scores, labels)).collect()),
+			confusion,
+		}
+	}
+}
+
+impl fmt::Display for ClassificationReport {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		let optional = |value : Option<f64>| value.map_or("n/a".to_string(), |value| format!("{value:.4}"));
+
+		writeln!(f, "samples: {}",self.confusion.total())?;
+		writeln!(f, "accuracy: {:.4}",self.accuracy)?;
+		writeln!(f, "log loss: {:.4}",self.log_loss)?;
+		writeln!(f, "roc auc: {}",optional(self.roc_auc))?;
+		writeln!(f, "pr auc: {}",optional(self.pr_auc))?;
+		if self.classes.len() == 2 {
+			writeln!(f, "threshold: {}",self.threshold)?;
+		}
+		writeln!(f)?;
+
+		writeln!(f, "{:>14}{:>11}{:>11}{:>11}{:>9}","","precision","recall","f1","support")?;
+		for (class,scores) in self.classes.iter().enumerate() {
+			writeln!(f, "{class:>14}{:>11.4}{:>11.4}{:>11.4}{:>9}",scores.precision,scores.recall,scores.f1,self.confusion.support(class))?;
+		}
+		for (name,scores) in [("macro avg",self.macro_average),("micro avg",self.micro_average),("weighted avg",self.weighted_average)] {
+			writeln!(f, "{name:>14}{:>11.4}{:>11.4}{:>11.4}{:>9}",scores.precision,scores.recall,scores.f1,self.confusion.total())?;
+		}
+		writeln!(f)?;
+
+		write!(f, "{}",self.confusion)
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                                 Regression                                 */
+/* -------------------------------------------------------------------------- */
+
+
+/// Quantiles given in `RegressionReport::residual_quantiles`
+pub const RESIDUAL_QUANTILES : [f64;7] = [0.0,0.05,0.25,0.5,0.75,0.95,1.0];
+
+fn check_lengths(predictions : &[f64], targets : &[f64]) {
+	assert!(predictions.len() == targets.len() && !predictions.is_empty(), "there should be one prediction per target and at least one sample");
+}
+
+fn mean(values : impl ExactSizeIterator<Item = f64>) -> f64 {
+	let len = values.len() as f64;
+	values.sum::<f64>() / len
+}
+
+fn variance(values : &[f64]) -> f64 {
+	let mean = mean(values.iter().copied());
+	values.iter().map(|value| (value-mean).powi(2)).sum::<f64>() / values.len() as f64
+}
+
+/// Coefficient of determination, 1 for a perfect fit and 0 for the mean of the targets.
+/// With constant targets it is 1 for a perfect fit and 0 otherwise.
+pub fn r2(predictions : &[f64], targets : &[f64]) -> f64 {
+	check_lengths(predictions, targets);
+	let residual : f64 = predictions.iter().zip(targets).map(|(prediction,target)| (target-prediction).powi(2)).sum();
+	let total = variance(targets) * targets.len() as f64;
+	match (total == 0.0,residual == 0.0) {
+		(true,true) => 1.0,
+		(true,false) => 0.0,
+		_ => 1.0 - residual/total,
+	}
+}
+
+/// R² penalised by the number of features, `None` when there aren't more than `nb_features+1` samples
+pub fn adjusted_r2(predictions : &[f64], targets : &[f64], nb_features : usize) -> Option<f64> {
+	let n = targets.len();
+	if n <= nb_features + 1 {
+		return None;
+	}
+	Some(1.0 - (1.0 - r2(predictions, targets)) * (n-1) as f64 / (n - nb_features - 1) as f64)
+}
+
+pub fn rmse(predictions : &[f64], targets : &[f64]) -> f64 {
+	check_lengths(predictions, targets);
+	mean(predictions.iter().zip(targets).map(|(prediction,target)| (target-prediction).powi(2))).sqrt()
+}
+
+pub fn mae(predictions : &[f64], targets : &[f64]) -> f64 {
+	check_lengths(predictions, targets);
+	mean(predictions.iter().zip(targets).map(|(prediction,target)| (target-prediction).abs()))
+}
+
+/// Mean absolute percentage error (0.1 is 10%), the samples with a zero target are left out,
+/// `None` if every target is 0
+pub fn mape(predictions : &[f64], targets : &[f64]) -> Option<f64> {
+	check_lengths(predictions, targets);
+	let errors : Vec<f64> = predictions.iter().zip(targets)
+		.filter(|(_,target)| **target != 0.0)
+		.map(|(prediction,target)| ((target-prediction)/target).abs())
+		.collect();
+	(!errors.is_empty()).then(|| mean(errors.into_iter()))
+}
+
+pub fn max_error(predictions : &[f64], targets : &[f64]) -> f64 {
+	check_lengths(predictions, targets);
+	predictions.iter().zip(targets).map(|(prediction,target)| (target-prediction).abs()).fold(0.0, f64::max)
+}
+
+/// Like R² but a constant bias of the predictions isn't counted as an error
+pub fn explained_variance(predictions : &[f64], targets : &[f64]) -> f64 {
+	check_lengths(predictions, targets);
+	let residuals : Vec<f64> = predictions.iter().zip(targets).map(|(prediction,target)| target-prediction).collect();
+	let total = variance(targets);
+	let residual = variance(&residuals);
+	match (total == 0.0,residual == 0.0) {
+		(true,true) => 1.0,
+		(true,false) => 0.0,
+		_ => 1.0 - residual/total,
+	}
+}
+
+
+/// Regression metrics of one output, or of all of them
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct RegressionMetrics {
+	pub r2 : f64,
+	pub adjusted_r2 : Option<f64>,
+	pub rmse : f64,
+	pub mae : f64,
+	pub mape : Option<f64>,
+	pub max_error : f64,
+	pub explained_variance : f64,
+}
+
+impl RegressionMetrics {
+
+	/// # Arguments
+	/// * `predictions` - one predicted value per sample
+	/// * `targets` - the expected values
+	/// * `nb_features` - number of inputs of the model, for the adjusted R²
+	pub fn new(predictions : &[f64], targets : &[f64], nb_features : usize) -> RegressionMetrics {
+		RegressionMetrics {
+			r2 : r2(predictions, targets),
+			adjusted_r2 : adjusted_r2(predictions, targets, nb_features),
+			rmse : rmse(predictions, targets),
+			mae : mae(predictions, targets),
+			mape : mape(predictions, targets),
+			max_error : max_error(predictions, targets),
+			explained_variance : explained_variance(predictions, targets),
+		}
+	}
+}
+
+/// Every regression metric, see `NeuralNetWork::evaluate`
+#[derive(Debug,Clone,PartialEq)]
+pub struct RegressionReport {
+	pub samples : usize,
+	/// one entry per output
+	pub outputs : Vec<RegressionMetrics>,
+	/// R², adjusted R² and explained variance are the mean over the outputs,
+	/// the errors are computed on the values of all the outputs together
+	pub aggregate : RegressionMetrics,
+	/// (quantile,residual) for each of `RESIDUAL_QUANTILES`, a residual is target - prediction
+	pub residual_quantiles : Vec<(f64,f64)>,
+}
+
+impl RegressionReport {
+
+	/// # Arguments
+	/// * `outputs` - outputs of the network, one per sample
+	/// * `targets` - expected outputs
+	/// * `nb_features` - number of inputs of the model, for the adjusted R²
+	pub fn new(outputs : &[Vec<f64>], targets : &[Vec<f64>], nb_features : usize) -> RegressionReport {
+		assert!(outputs.len() == targets.len() && !outputs.is_empty(), "there should be one output per target and at least one sample");
+		let size = targets[0].len();
+		assert!(size > 0 && outputs.iter().chain(targets).all(|values| values.len() == size), "outputs and targets should have the same size");
+
+		let column = |rows : &[Vec<f64>], index : usize| -> Vec<f64> { rows.iter().map(|row| row[index]).collect() };
+		let per_output : Vec<RegressionMetrics> = (0..size)
+			.map(|index| RegressionMetrics::new(&column(outputs, index), &column(targets, index), nb_features))
+			.collect();
+
+		let (all_outputs,all_targets) : (Vec<f64>,Vec<f64>) = (outputs.concat(),targets.concat());
+		let average = |metric : fn(&RegressionMetrics) -> f64| mean(per_output.iter().map(metric));
+		let aggregate = RegressionMetrics {
+			r2 : average(|metrics| metrics.r2),
+			adjusted_r2 : per_output.iter().map(|metrics| metrics.adjusted_r2).sum::<Option<f64>>().map(|sum| sum / size as f64),
+			explained_variance : average(|metrics| metrics.explained_variance),
+			..RegressionMetrics::new(&all_outputs, &all_targets, nb_features)
+		};
+
+		let mut residuals : Vec<f64> = all_targets.iter().zip(&all_outputs).map(|(target,output)| target-output).collect();
+		residuals.sort_by(f64::total_cmp);
+
+		RegressionReport {
+			samples : outputs.len(),
+			outputs : per_output,
+			aggregate,
+			residual_quantiles : RESIDUAL_QUANTILES.iter().map(|q| (*q,quantile(&residuals, *q))).collect(),
+		}
+	}
+}
+
+impl fmt::Display for RegressionReport {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		let optional = |value : Option<f64>| value.map_or("n/a".to_string(), |value| format!("{value:.4}"));
+		let row = |f : &mut fmt::Formatter, name : &str, metrics : &RegressionMetrics| {
+			writeln!(f, "{name:>10}{:>11.4}{:>11}{:>12.4}{:>12.4}{:>10}{:>12.4}{:>11.4}",
+				metrics.r2,optional(metrics.adjusted_r2),metrics.rmse,metrics.mae,optional(metrics.mape),metrics.max_error,metrics.explained_variance)
+		};
+
+		writeln!(f, "samples: {}",self.samples)?;
+		writeln!(f, "{:>10}{:>11}{:>11}{:>12}{:>12}{:>10}{:>12}{:>11}","output","r2","adj r2","rmse","mae","mape","max error","expl var")?;
+		for (index,metrics) in self.outputs.iter().enumerate() {
+			row(f, &index.to_string(), metrics)?;
+		}
+		if self.outputs.len() > 1 {
+			row(f, "all", &self.aggregate)?;
+		}
+		writeln!(f)?;
+
+		write!(f, "residuals:")?;
+		for (q,residual) in &self.residual_quantiles {
+			write!(f, "  q{:.2}={residual:.4}",q)?;
+		}
+		writeln!(f)
+	}
+}
+
+
+impl NeuralNetWork {
+
+	/// Compute the metrics of `task` on held-out data
+	///
+	/// # Arguments
+	/// * `data` - (input,expected output) pairs that weren't used for training
+	/// * `task` - what the outputs mean
+	pub fn evaluate(&mut self, data : &[Sample], task : Task) -> Report {
+		let outputs : Vec<Vec<f64>> = data.iter().map(|(input,_)| self.predict(input)).collect();
+		let targets : Vec<Vec<f64>> = data.iter().map(|(_,target)| target.clone()).collect();
+
+		match task {
+			Task::Classification { threshold } => Report::Classification(ClassificationReport::new(&outputs, &targets, threshold)),
+			Task::Regression => Report::Regression(RegressionReport::new(&outputs, &targets, self.input_size())),
+		}
+	}
+
+	/// `evaluate` each output head separately, returns the name and the report of each head
+	///
+	/// # Arguments
+	/// * `data` - (input,expected output) pairs that weren't used for training
+	/// * `tasks` - what the outputs of each head mean, in the order of `heads`
+	pub fn evaluate_heads(&mut self, data : &[Sample], tasks : &[Task]) -> Vec<(String,Report)> {
+		let heads : Vec<Head> = self.heads().into_iter().cloned().collect();
+		assert!(!heads.is_empty(),"the network has no output heads");
+		assert!(tasks.len()==heads.len(),"the network has {} heads, got {} tasks",heads.len(),tasks.len());
+		let outputs : Vec<Vec<f64>> = data.iter().map(|(input,_)| self.predict(input)).collect();
+
+		let mut start = 0;
+		heads.iter().zip(tasks).map(|(head,task)| {
+			let range = start..start+head.size;
+			start += head.size;
+			let outputs : Vec<Vec<f64>> = outputs.iter().map(|output| output[range.clone()].to_vec()).collect();
+			let targets : Vec<Vec<f64>> = data.iter().map(|(_,target)| target[range.clone()].to_vec()).collect();
+			let report = match task {
+				Task::Classification { threshold } => Report::Classification(ClassificationReport::new(&outputs, &targets, *threshold)),
+				Task::Regression => Report::Regression(RegressionReport::new(&outputs, &targets, self.input_size())),
+			};
+			(head.name.clone(),report)
+		}).collect()
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+
+	fn close(a : f64, b : f64) -> bool {
+		(a-b).abs() < 1e-9
+	}
+
+	#[test]
+	fn confusion_matrix_scores(){
+		// class 0 : 3 samples, class 1 : 2 samples, class 2 : 1 sample
+		let matrix = ConfusionMatrix::from_classes(&[0,0,0,1,1,2], &[0,0,1,1,2,2], 3);
+		assert!(matrix.counts == vec![vec![2,1,0],vec![0,1,1],vec![0,0,1]]);
+		assert!(close(matrix.accuracy(), 4.0/6.0));
+		assert!(close(matrix.precision(1), 0.5) && close(matrix.recall(0), 2.0/3.0));
+		assert!(close(matrix.f1(2), 2.0*0.5*1.0/1.5));
+
+		assert!(close(matrix.average_precision(Average::Macro), (1.0+0.5+0.5)/3.0));
+		assert!(close(matrix.average_recall(Average::Weighted), (2.0+1.0+1.0)/6.0));
+		assert!(close(matrix.average_f1(Average::Micro), 4.0/6.0));
+	}
+
+	#[test]
+	fn roc_and_pr_curves(){
+		let scores = [0.9,0.8,0.7,0.6,0.55,0.4];
+		let labels = [true,true,false,true,false,false];
+
+		let curve = roc_curve(&scores, &labels);
+		assert!(curve.len() == 7 && curve.last().unwrap().true_positive_rate == 1.0);
+		// 8 of the 9 (positive,negative) pairs are ranked correctly
+		assert!(close(roc_auc(&scores, &labels).unwrap(), 8.0/9.0));
+		assert!(close(pr_auc(&scores, &labels).unwrap(), (1.0 + 1.0 + 0.75)/3.0));
+
+		assert!(roc_auc(&scores, &[true;6]).is_none());
+		// a perfect ranking
+		assert!(roc_auc(&[0.1,0.2,0.8], &[false,false,true]) == Some(1.0));
+	}
+
+	#[test]
+	fn binary_report_threshold(){
+		let outputs = vec![vec![0.9],vec![0.6],vec![0.4],vec![0.2]];
+		let targets = vec![vec![1.0],vec![0.0],vec![1.0],vec![0.0]];
+
+		let report = ClassificationReport::new(&outputs, &targets, 0.5);
+		assert!(report.confusion.counts == vec![vec![1,1],vec![1,1]]);
+
+		let report = ClassificationReport::new(&outputs, &targets, 0.3);
+		assert!(report.confusion.counts == vec![vec![1,1],vec![0,2]]);
+		assert!(close(report.log_loss, -(0.9f64.ln() + 0.4f64.ln() + 0.4f64.ln() + 0.8f64.ln())/4.0));
+		assert!(close(report.roc_auc.unwrap(), 0.75));
+	}
+
+	#[test]
+	fn one_hot_report(){
+		let outputs = vec![vec![0.8,0.1,0.1],vec![0.2,0.7,0.1],vec![0.1,0.2,0.7],vec![0.5,0.4,0.1]];
+		let targets = vec![vec![1.0,0.0,0.0],vec![0.0,1.0,0.0],vec![0.0,0.0,1.0],vec![0.0,1.0,0.0]];
+
+		let report = ClassificationReport::new(&outputs, &targets, 0.5);
+		assert!(report.accuracy == 0.75 && report.classes.len() == 3 && report.roc.len() == 3);
+		assert!(report.to_string().contains("weighted avg"));
+	}
+
+	#[test]
+	fn evaluate_network(){
+		let mut nn = NeuralNetWork::new(&[2,1], "quadratic", "sigmoid", "sigmoid");
+		let data = crate::datasets::generate_xor_data(8);
+		let Report::Classification(report) = nn.evaluate(&data, Task::classification()) else {
+			panic!("expected a classification report");
+		};
+		assert!(report.confusion.total() == 8);
+
+		let Report::Regression(report) = nn.evaluate(&data, Task::Regression) else {
+			panic!("expected a regression report");
+		};
+		assert!(report.samples == 8 && report.outputs.len() == 1);
+		assert!(Report::Regression(report.clone()).score("rmse") == Some(report.aggregate.rmse));
+	}
+
+	#[test]
+	fn evaluate_each_head(){
+		let mut nn = NeuralNetWork::empty(2, "default", rand_chacha::ChaCha8Rng::seed_from_u64(0));
+		nn.add_heads(&[Head::new("value", 1, "identity", "quadratic"),Head::new("class", 2, "sigmoid", "cross_entropy")], crate::init::Init::Xavier);
+		let data = vec![(vec![0.0,1.0],vec![0.5,1.0,0.0]),(vec![1.0,0.0],vec![-0.5,0.0,1.0])];
+
+		let reports = nn.evaluate_heads(&data, &[Task::Regression,Task::classification()]);
+		assert!(reports[0].0 == "value" && reports[1].0 == "class");
+		let (Report::Regression(value),Report::Classification(class)) = (&reports[0].1,&reports[1].1) else {
+			panic!("expected a regression and a classification report");
+		};
+		assert!(value.outputs.len() == 1 && class.confusion.nb_classes() == 2);
+	}
+
+	#[test]
+	fn regression_metrics(){
+		let targets = [3.0,-0.5,2.0,7.0];
+		let predictions = [2.5,0.0,2.0,8.0];
+
+		assert!(close(r2(&predictions, &targets), 0.948_608_137_044_967_9));
+		assert!(close(mae(&predictions, &targets), 0.5));
+		assert!(close(rmse(&predictions, &targets), 0.375f64.sqrt()));
+		assert!(close(max_error(&predictions, &targets), 1.0));
+		assert!(close(explained_variance(&predictions, &targets), 0.957_173_447_537_473_2));
+		assert!(close(mape(&predictions, &targets).unwrap(), (0.5/3.0 + 1.0 + 0.0 + 1.0/7.0)/4.0));
+		assert!(close(adjusted_r2(&predictions, &targets, 1).unwrap(), 1.0 - (1.0 - 0.948_608_137_044_967_9)*3.0/2.0));
+		assert!(adjusted_r2(&predictions, &targets, 3).is_none());
+
+		// constant targets
+		assert!(r2(&[1.0,1.0], &[1.0,1.0]) == 1.0 && r2(&[1.0,2.0], &[1.0,1.0]) == 0.0);
+		assert!(mape(&[1.0], &[0.0]).is_none());
+	}
+
+	#[test]
+	fn regression_report(){
+		let outputs = vec![vec![1.0,10.0],vec![2.0,20.0],vec![3.5,29.0]];
+		let targets = vec![vec![1.0,10.0],vec![2.0,20.0],vec![3.0,30.0]];
+		let report = RegressionReport::new(&outputs, &targets, 1);
+
+		assert!(report.outputs.len() == 2);
+		assert!(close(report.aggregate.max_error, 1.0));
+		assert!(close(report.aggregate.r2, (report.outputs[0].r2 + report.outputs[1].r2)/2.0));
+		assert!(report.residual_quantiles[0] == (0.0,-0.5) && report.residual_quantiles[6] == (1.0,1.0));
+		assert!(report.to_string().contains("all"));
+	}
+}
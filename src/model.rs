@@ -0,0 +1,254 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::csv::CsvLoader;
+use crate::init::*;
+use crate::matrix::*;
+use crate::nn::*;
+use crate::preprocessing::*;
+
+/// Bumped when the layout of the model file changes
+const MODEL_FORMAT : u32 = 1;
+
+
+/// What is written in a model file : the architecture and the trained parameters
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct ModelFile {
+	pub format : u32,
+	pub input_size : usize,
+	pub cost : String,
+	pub layers : Vec<LayerFile>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub input_scaler : Option<Scaler>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub target_scaler : Option<Scaler>,
+	/// partition of the last layer, see `NeuralNetWork::add_heads`
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub heads : Vec<Head>,
+	/// how the training CSV was read, so new data is encoded the same way
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub csv : Option<CsvLoader>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct LayerFile {
+	pub activation : String,
+	pub dropout : f64,
+	pub weights : Matrix<f64>,
+	pub biases : Matrix<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub norm : Option<NormFile>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct NormFile {
+	pub gain : Matrix<f64>,
+	pub shift : Matrix<f64>,
+}
+
+
+pub(crate) fn invalid_data(message : String) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The activation and the parameters of each layer
+pub(crate) fn layer_files(layers : &[RefCell<Layer>]) -> Vec<LayerFile> {
+	layers.iter().map(|layer| {
+		let layer = layer.borrow();
+		LayerFile {
+			activation : layer.activation_name.clone(),
+			dropout : layer.dropout,
+			weights : layer.w_matrix.clone(),
+			biases : layer.b_matrix.clone(),
+			norm : layer.norm.as_ref().map(|norm| NormFile { gain : norm.gamma.clone(), shift : norm.beta.clone() }),
+		}
+	}).collect()
+}
+
+
+impl ModelFile {
+
+	pub fn save(&self, path : impl AsRef<Path>) -> io::Result<()> {
+		fs::write(path, serde_json::to_string_pretty(self).expect("a model is always representable in JSON"))
+	}
+
+	/// Read a model file without building the network
+	pub fn load(path : impl AsRef<Path>) -> io::Result<ModelFile> {
+		serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| invalid_data(e.to_string()))
+	}
+}
+
+
+impl NeuralNetWork {
+
+	pub fn to_model_file(&self) -> ModelFile {
+		ModelFile {
+			format : MODEL_FORMAT,
+			input_size : self.input_size(),
+			cost : self.cost_name().to_string(),
+			layers : layer_files(&self.layers),
+			input_scaler : self.input_scaler().cloned(),
+			target_scaler : self.target_scaler().cloned(),
+			heads : self.heads().into_iter().cloned().collect(),
+			csv : None,
+		}
+	}
+
+	/// Rebuild a network from a model file, checking that every matrix has the right dimensions
+	pub fn from_model_file(file : &ModelFile) -> io::Result<NeuralNetWork> {
+		if file.format != MODEL_FORMAT {
+			return Err(invalid_data(format!("unsupported model format {} (expected {MODEL_FORMAT})",file.format)));
+		}
+		if get_cost(&file.cost).is_none() {
+			return Err(invalid_data(format!("unknown cost function `{}`",file.cost)));
+		}
+		if file.layers.is_empty() {
+			return Err(invalid_data("the model has no layer".to_string()));
+		}
+
+		let mut nn = NeuralNetWork::empty(file.input_size, &file.cost, ChaCha8Rng::from_entropy());
+		let mut cols = file.input_size;
+		for (i,layer_file) in file.layers.iter().enumerate() {
+			let rows = layer_file.biases.rows;
+			if get_activation(&layer_file.activation).is_none() {
+				return Err(invalid_data(format!("layer {i}: unknown activation `{}`",layer_file.activation)));
+			}
+			if layer_file.weights.rows != rows || layer_file.weights.cols != cols || layer_file.biases.cols != 1
+				|| layer_file.weights.values.len() != rows*cols || layer_file.biases.values.len() != rows || rows == 0 {
+				return Err(invalid_data(format!("layer {i}: weights should be ({rows},{cols}) and biases ({rows},1)")));
+			}
+			if !(0.0..1.0).contains(&layer_file.dropout) {
+				return Err(invalid_data(format!("layer {i}: dropout should be in [0,1)")));
+			}
+
+			nn.add_layer(rows, &layer_file.activation, Init::Default, layer_file.dropout, layer_file.norm.is_some());
+			let mut layer = nn.layers[i].borrow_mut();
+			layer.w_matrix = layer_file.weights.clone();
+			layer.b_matrix = layer_file.biases.clone();
+
+			if let (Some(norm),Some(norm_file)) = (&mut layer.norm,&layer_file.norm) {
+				if norm_file.gain.values.len() != rows || norm_file.shift.values.len() != rows {
+					return Err(invalid_data(format!("layer {i}: normalisation parameters should have {rows} values")));
+				}
+				norm.gamma = Matrix::from_vec(rows, 1, norm_file.gain.values.clone());
+				norm.beta = Matrix::from_vec(rows, 1, norm_file.shift.values.clone());
+			}
+			cols = rows;
+		}
+
+		if !file.heads.is_empty() {
+			if let Some(error) = file.heads.iter().find_map(|head| head.validate().err()) {
+				return Err(invalid_data(error));
+			}
+			let last = file.layers.last().unwrap();
+			if !matches!(last.activation.trim().to_lowercase().as_str(), "identity" | "id") {
+				return Err(invalid_data("the layer split in heads should have an identity activation".to_string()));
+			}
+			if file.heads.iter().map(|head| head.size).sum::<usize>() != cols {
+				return Err(invalid_data(format!("the heads should have {cols} neurons in total")));
+			}
+			nn.set_heads(&file.heads);
+		}
+
+		for (name,scaler,size) in [("input",&file.input_scaler,file.input_size),("target",&file.target_scaler,cols)] {
+			if scaler.as_ref().is_some_and(|scaler| scaler.len() != size) {
+				return Err(invalid_data(format!("the {name} scaler should have {size} values")));
+			}
+		}
+		nn.set_input_scaler(file.input_scaler.clone());
+		nn.set_target_scaler(file.target_scaler.clone());
+
+		Ok(nn)
+	}
+
+	pub fn to_json_string(&self) -> String {
+		serde_json::to_string_pretty(&self.to_model_file()).expect("a model is always representable in JSON")
+	}
+
+	pub fn from_json_str(text : &str) -> io::Result<NeuralNetWork> {
+		let file : ModelFile = serde_json::from_str(text).map_err(|e| invalid_data(e.to_string()))?;
+		NeuralNetWork::from_model_file(&file)
+	}
+
+	/// Write the architecture and the parameters of the network to a JSON file
+	pub fn save(&self, path : impl AsRef<Path>) -> io::Result<()> {
+		self.to_model_file().save(path)
+	}
+
+	/// Read a network written by `save`
+	pub fn load(path : impl AsRef<Path>) -> io::Result<NeuralNetWork> {
+		NeuralNetWork::from_model_file(&ModelFile::load(path)?)
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::*;
+
+	#[test]
+	fn json_round_trip(){
+		let mut nn = NetworkBuilder::new(3)
+			.layer_config(LayerConfig::new(5, "tanh").norm("layer").dropout(0.2))
+			.layer(2, "sigmoid")
+			.loss("cross_entropy")
+			.seed(3)
+			.build()
+			.unwrap();
+		let mut loaded = NeuralNetWork::from_json_str(&nn.to_json_string()).unwrap();
+
+		assert!(loaded.to_model_file() == nn.to_model_file());
+		assert!(loaded.predict(&[0.1,0.2,0.3]) == nn.predict(&[0.1,0.2,0.3]));
+	}
+
+	#[test]
+	fn scalers_are_saved(){
+		let mut nn = NeuralNetWork::new(&[2,3,1], "quadratic", "relu", "identity");
+		let data = vec![(vec![-30000.0,1.0],vec![100.0]),(vec![30000.0,2.0],vec![300.0])];
+		nn.fit_scalers(&data, Some(ScalerKind::Standard), Some(ScalerKind::MinMax));
+
+		let mut loaded = NeuralNetWork::from_json_str(&nn.to_json_string()).unwrap();
+		assert!(loaded.input_scaler() == nn.input_scaler() && loaded.target_scaler() == nn.target_scaler());
+		assert!(loaded.predict(&[-30000.0,1.0]) == nn.predict(&[-30000.0,1.0]));
+
+		let mut file = nn.to_model_file();
+		file.input_scaler = Some(Scaler::fit(ScalerKind::Robust, [[1.0].as_slice()]));
+		assert!(NeuralNetWork::from_model_file(&file).unwrap_err().to_string().contains("input scaler"));
+	}
+
+	#[test]
+	fn heads_are_saved(){
+		let mut nn = NeuralNetWork::new(&[2,3], "default", "relu", "relu");
+		nn.add_heads(&[Head::new("value", 1, "identity", "quadratic"),Head::new("class", 2, "sigmoid", "cross_entropy").weight(0.5)], Init::Xavier);
+		let mut loaded = NeuralNetWork::from_json_str(&nn.to_json_string()).unwrap();
+		assert!(loaded.heads() == nn.heads());
+		assert!(loaded.predict(&[0.4,-0.2]) == nn.predict(&[0.4,-0.2]));
+
+		let mut file = nn.to_model_file();
+		file.heads[1].size = 3;
+		assert!(NeuralNetWork::from_model_file(&file).unwrap_err().to_string().contains("3 neurons"));
+	}
+
+	#[test]
+	fn wrong_dimensions_rejected(){
+		let nn = NeuralNetWork::new(&[2,3,1], "default", "relu", "sigmoid");
+		let mut file = nn.to_model_file();
+		file.layers[1].weights = Matrix::new(1, 2);
+
+		let error = NeuralNetWork::from_model_file(&file).unwrap_err();
+		assert!(error.kind() == io::ErrorKind::InvalidData);
+		assert!(error.to_string().contains("layer 1"));
+	}
+}
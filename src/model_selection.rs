@@ -0,0 +1,295 @@
+use std::fmt;
+
+use rand::{SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
+
+use crate::config::*;
+use crate::dataset::*;
+use crate::metrics::*;
+use crate::nn::*;
+
+
+/// Class of a classification target : a single output is 1 from 0.5, several outputs are one-hot
+pub fn class_of(target : &[f64]) -> usize {
+	if target.len() == 1 {
+		(target[0] >= 0.5) as usize
+	} else {
+		target.iter().enumerate().fold(0, |best,(i,value)| if *value > target[best] { i } else { best })
+	}
+}
+
+/// indexes of the samples of each class, each class shuffled
+fn shuffled_classes(data : &[Sample], rng : &mut ChaCha8Rng) -> Vec<Vec<usize>> {
+	let mut classes : Vec<Vec<usize>> = vec![];
+	for (index,(_,target)) in data.iter().enumerate() {
+		let class = class_of(target);
+		if class >= classes.len() {
+			classes.resize(class+1, vec![]);
+		}
+		classes[class].push(index);
+	}
+	for class in &mut classes {
+		class.shuffle(rng);
+	}
+	classes
+}
+
+
+/// Split the samples in a training set and a test set
+///
+/// # Arguments
+/// * `data` - the samples
+/// * `test_fraction` - part of the samples going to the test set, in (0,1)
+/// * `stratify` - keep the proportion of each class (see `class_of`) in both sets
+/// * `seed` - seed of the shuffle
+pub fn train_test_split(data : &[Sample], test_fraction : f64, stratify : bool, seed : u64) -> (Vec<Sample>,Vec<Sample>) {
+	assert!(test_fraction > 0.0 && test_fraction < 1.0, "the test fraction should be in (0,1)");
+	let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+	let groups = if stratify {
+		shuffled_classes(data, &mut rng)
+	} else {
+		let mut indexes : Vec<usize> = (0..data.len()).collect();
+		indexes.shuffle(&mut rng);
+		vec![indexes]
+	};
+
+	let (mut train,mut test) = (vec![],vec![]);
+	for group in groups {
+		let nb_test = (group.len() as f64 * test_fraction).round() as usize;
+		test.extend(group[..nb_test].iter().map(|index| data[*index].clone()));
+		train.extend(group[nb_test..].iter().map(|index| data[*index].clone()));
+	}
+	(train,test)
+}
+
+
+/// Indexes of the training and test samples of one fold
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Fold {
+	pub train : Vec<usize>,
+	pub test : Vec<usize>,
+}
+
+/// Iterator over the folds of `k_fold` and `stratified_k_fold`, each sample is in the test set of exactly one fold
+#[derive(Debug,Clone)]
+pub struct KFold {
+	folds : Vec<Vec<usize>>,
+	current : usize,
+}
+
+impl Iterator for KFold {
+	type Item = Fold;
+
+	fn next(&mut self) -> Option<Fold> {
+		let test = self.folds.get(self.current)?.clone();
+		let train = self.folds.iter().enumerate()
+			.filter(|(i,_)| *i != self.current)
+			.flat_map(|(_,fold)| fold.iter().copied())
+			.collect();
+		self.current += 1;
+		Some(Fold { train, test })
+	}
+}
+
+/// `k` folds of `len` samples, the sizes of the folds differ by at most one
+///
+/// # Arguments
+/// * `len` - number of samples
+/// * `k` - number of folds, at least 2
+/// * `seed` - shuffle the samples first, `None` keeps them in order
+pub fn k_fold(len : usize, k : usize, seed : Option<u64>) -> KFold {
+	assert!(k >= 2 && k <= len, "k should be between 2 and the number of samples");
+	let mut indexes : Vec<usize> = (0..len).collect();
+	if let Some(seed) = seed {
+		indexes.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+	}
+
+	let mut folds = Vec::with_capacity(k);
+	let mut start = 0;
+	for fold in 0..k {
+		let size = len/k + (fold < len%k) as usize;
+		folds.push(indexes[start..start+size].to_vec());
+		start += size;
+	}
+	KFold { folds, current : 0 }
+}
+
+/// Like `k_fold` but every fold keeps the proportion of each class (see `class_of`)
+pub fn stratified_k_fold(data : &[Sample], k : usize, seed : u64) -> KFold {
+	assert!(k >= 2 && k <= data.len(), "k should be between 2 and the number of samples");
+	let mut folds = vec![vec![];k];
+	// dealt like cards, the next class starts where the previous one stopped
+	let mut next = 0;
+	for class in shuffled_classes(data, &mut ChaCha8Rng::seed_from_u64(seed)) {
+		for index in class {
+			folds[next].push(index);
+			next = (next+1) % k;
+		}
+	}
+	KFold { folds, current : 0 }
+}
+
+
+/// Mean and standard deviation of a score over the folds
+#[derive(Debug,Clone,PartialEq)]
+pub struct ScoreSummary {
+	pub name : &'static str,
+	pub mean : f64,
+	/// population standard deviation
+	pub std : f64,
+}
+
+/// Result of `cross_validate`
+#[derive(Debug,Clone,PartialEq)]
+pub struct CrossValidation {
+	/// the report of each fold, computed on its test samples
+	pub folds : Vec<Report>,
+	/// one entry per score of `Report::scores` present in every fold
+	pub summary : Vec<ScoreSummary>,
+}
+
+impl CrossValidation {
+
+	pub fn new(folds : Vec<Report>) -> CrossValidation {
+		let scores : Vec<Vec<(&'static str,f64)>> = folds.iter().map(Report::scores).collect();
+		let summary = scores.first().map_or(vec![], |first| {
+			first.iter().filter_map(|(name,_)| {
+				let values : Vec<f64> = scores.iter().map(|fold| fold.iter().find(|(score,_)| score == name).map(|(_,value)| *value)).collect::<Option<_>>()?;
+				let mean = values.iter().sum::<f64>() / values.len() as f64;
+				let std = (values.iter().map(|value| (value-mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt();
+				Some(ScoreSummary { name, mean, std })
+			}).collect()
+		});
+		CrossValidation { folds, summary }
+	}
+
+	pub fn score(&self, name : &str) -> Option<&ScoreSummary> {
+		self.summary.iter().find(|summary| summary.name == name)
+	}
+}
+
+impl fmt::Display for CrossValidation {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{} folds",self.folds.len())?;
+		for summary in &self.summary {
+			writeln!(f, "{:<20}{:>10.4} ± {:.4}",summary.name,summary.mean,summary.std)?;
+		}
+		Ok(())
+	}
+}
+
+
+/// Train and evaluate a fresh network from `config` on each fold.
+/// Classification folds are stratified. Each fold seeds its network and its shuffling from `seed`,
+/// so the result only depends on the arguments.
+///
+/// # Arguments
+/// * `config` - the network, its scalers and its training hyperparameters (its `data` and `seed` are ignored)
+/// * `data` - all the samples
+/// * `k` - number of folds
+/// * `task` - the metrics computed on each fold
+/// * `seed` - seed of the folds, of the initialisations and of the shuffling
+pub fn cross_validate(config : &NetworkConfig, data : &[Sample], k : usize, task : Task, seed : u64) -> Result<CrossValidation,ConfigError> {
+	let folds = match task {
+		Task::Classification { .. } => stratified_k_fold(data, k, seed),
+		Task::Regression => k_fold(data.len(), k, Some(seed)),
+	};
+	let (input_scaler,target_scaler) = config.scalers()?;
+
+	let mut reports = Vec::with_capacity(k);
+	for (i,fold) in folds.enumerate() {
+		let fold_seed = seed.wrapping_add(i as u64 + 1);
+		let mut fold_config = config.clone();
+		fold_config.seed = Some(fold_seed);
+		let (mut nn,options) = fold_config.build()?;
+
+		let train : Vec<Sample> = fold.train.iter().map(|index| data[*index].clone()).collect();
+		let test : Vec<Sample> = fold.test.iter().map(|index| data[*index].clone()).collect();
+		nn.fit_scalers(&train, input_scaler, target_scaler);
+
+		let mut loader = DataLoader::new(train, options.mini_batch_size).shuffle(fold_seed);
+		nn.train_loader(&mut loader, &options).map_err(|error| ConfigError::Training(error.to_string()))?;
+		reports.push(nn.evaluate(&test, task));
+	}
+
+	Ok(CrossValidation::new(reports))
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// 30 samples of class 0 and 10 of class 1
+	fn unbalanced() -> Vec<Sample> {
+		(0..40).map(|i| (vec![i as f64],vec![(i%4 == 0) as usize as f64])).collect()
+	}
+
+	#[test]
+	fn split_sizes_and_stratification(){
+		let data = unbalanced();
+		let (train,test) = train_test_split(&data, 0.2, false, 1);
+		assert!(train.len() == 32 && test.len() == 8);
+
+		let (train,test) = train_test_split(&data, 0.2, true, 1);
+		assert!(test.iter().filter(|(_,target)| target[0] == 1.0).count() == 2);
+		assert!(train.iter().filter(|(_,target)| target[0] == 1.0).count() == 8);
+
+		// every sample ends up in one of the sets
+		let mut inputs : Vec<f64> = train.iter().chain(&test).map(|(input,_)| input[0]).collect();
+		inputs.sort_by(f64::total_cmp);
+		assert!(inputs == (0..40).map(|i| i as f64).collect::<Vec<_>>());
+
+		assert!(train_test_split(&data, 0.2, true, 1) == (train,test));
+	}
+
+	#[test]
+	fn k_fold_covers_every_sample_once(){
+		let folds : Vec<Fold> = k_fold(10, 3, None).collect();
+		assert!(folds.iter().map(|fold| fold.test.len()).collect::<Vec<_>>() == vec![4,3,3]);
+		assert!(folds[1].test == vec![4,5,6] && folds[1].train == vec![0,1,2,3,7,8,9]);
+
+		let mut tested : Vec<usize> = k_fold(10, 3, Some(2)).flat_map(|fold| fold.test).collect();
+		tested.sort();
+		assert!(tested == (0..10).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn stratified_folds_keep_proportions(){
+		let data = unbalanced();
+		for fold in stratified_k_fold(&data, 5, 3) {
+			assert!(fold.test.len() == 8);
+			assert!(fold.test.iter().filter(|index| data[**index].1[0] == 1.0).count() == 2);
+		}
+	}
+
+	#[test]
+	fn cross_validation_is_reproducible(){
+		let config = NetworkConfig::from_toml_str("input = 2\nepochs = 50\nbatch_size = 10\nlearning_rate = 0.05\ninput_scaler = \"standard\"\ntarget_scaler = \"standard\"\n[optimizer]\ntype = \"adam\"\n[schedule]\ntype = \"constant\"\n[[layers]]\nsize = 1\nactivation = \"identity\"\n").unwrap();
+		let data : Vec<Sample> = (0..60).map(|i| {
+			let x = vec![(i%6) as f64, (i/6) as f64];
+			(x.clone(),vec![3.0*x[0] - x[1]])
+		}).collect();
+
+		let result = cross_validate(&config, &data, 3, Task::Regression, 7).unwrap();
+		assert!(result.folds.len() == 3);
+		assert!(result.score("r2").unwrap().mean > 0.9);
+		assert!(result == cross_validate(&config, &data, 3, Task::Regression, 7).unwrap());
+		assert!(result.to_string().contains("rmse"));
+	}
+
+	#[test]
+	fn cross_validation_training_error(){
+		let config = NetworkConfig::from_toml_str("input = 1\nepochs = 2\nbatch_size = 5\nnon_finite = \"abort\"\n[[layers]]\nsize = 1\nactivation = \"identity\"\n").unwrap();
+		let data : Vec<Sample> = (0..10).map(|i| (vec![i as f64],vec![f64::NAN])).collect();
+
+		let error = cross_validate(&config, &data, 2, Task::Regression, 0).unwrap_err();
+		assert!(matches!(&error,ConfigError::Training(message) if message.contains("non-finite")), "{error}");
+	}
+}
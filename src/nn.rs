@@ -1,37 +1,211 @@
-use std::cell::RefCell;
+use std::cell::{RefCell, RefMut};
+use std::fmt;
+use std::io;
 
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::LoaderState;
+use crate::dataset::*;
+use crate::init::*;
 use crate::matrix::*;
+use crate::optimizer::*;
+use crate::preprocessing::*;
 use crate::utils::*;
 
-const MIN_RAND : f64 = 0.0;
-const MAX_RAND : f64 = 0.1;
+pub(crate) const NORM_EPSILON : f64 = 1e-5;
+
+/// One (input,expected output) pair
+pub type Sample = (Vec<f64>,Vec<f64>);
 
 
-#[derive(Debug)]
+#[derive(Clone)]
 pub struct NeuralNetWork {
 	pub layers : Vec<RefCell<Layer>>,
 	nb_layer : usize,
 	input_size : usize,
-	cost_function : fn(&Vec<f64>,&Vec<f64>)->f64,
+	cost_name : String,
+	cost_function : fn(&[f64],&[f64])->f64,
 	cost_derivative : fn(f64,f64) -> f64,
+	rng : ChaCha8Rng,
+	training : bool,
+	input_scaler : Option<Scaler>,
+	target_scaler : Option<Scaler>,
+	/// partition of the output layer, empty when the cost applies to the whole output
+	heads : Vec<OutputHead>,
+}
+
+/// The architecture without the raw parameters, `summary` gives the details
+impl fmt::Debug for NeuralNetWork {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		let layers : Vec<String> = self.layers.iter().map(|layer| {
+			let layer = layer.borrow();
+			format!("{} {}",layer.len(),layer.activation_name())
+		}).collect();
+		f.debug_struct("NeuralNetWork")
+			.field("input_size", &self.input_size)
+			.field("cost", &self.cost_name)
+			.field("layers", &layers)
+			.field("parameters", &self.nb_parameters())
+			.finish()
+	}
+}
+
+/// A slice of the output layer with its own activation and cost, see `NeuralNetWork::add_heads`
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Head {
+	pub name : String,
+	pub size : usize,
+	pub activation : String,
+	pub cost : String,
+	/// factor of the cost of this head in the total cost
+	pub weight : f64,
+}
+
+/// A head with its functions and its position in the output layer
+#[derive(Debug,Clone)]
+struct OutputHead {
+	head : Head,
+	start : usize,
+	activation_function : fn(f64)->f64,
+	activation_derivative : fn(f64)->f64,
+	cost_function : fn(&[f64],&[f64])->f64,
+	cost_derivative : fn(f64,f64)->f64,
+}
+
+impl OutputHead {
+	fn range(&self) -> std::ops::Range<usize> {
+		self.start..self.start+self.head.size
+	}
+}
+
+/// Hyperparameters of `NeuralNetWork::train_with`
+#[derive(Debug,Clone,PartialEq)]
+pub struct TrainOptions {
+	pub mini_batch_size : usize,
+	pub epochs : usize,
+	pub learning_rate : f64,
+	pub optimizer : Optimizer,
+	pub schedule : LearningRateSchedule,
+	pub verbose : bool,
+	/// limit of the mean gradient of a mini batch
+	pub clip : Clip,
+	/// what training does when an activation, a gradient or the cost isn't finite,
+	/// the activations of the blocks aren't checked but their non-finite values reach the gradient
+	pub non_finite : NonFinite,
+}
+
+/// What training does when a value becomes NaN or infinite
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub enum NonFinite {
+	/// don't check the values
+	#[default]
+	Ignore,
+	/// stop the training, `try_train_with` and `train_loader` return the error and the other train methods panic
+	Abort,
+	/// drop the mini batch, the parameters aren't updated
+	SkipBatch,
+	/// restore the parameters and the optimizer state of the end of the last epoch with a finite cost
+	Rollback,
+}
+
+impl NonFinite {
+
+	/// Apply the policy to an error, `rollback` restores the last good parameters.
+	/// Returns the error if the training should stop
+	pub(crate) fn handle(self,error : NonFiniteError,verbose : bool,rollback : impl FnOnce()) -> Result<(),NonFiniteError> {
+		match self {
+			NonFinite::Ignore | NonFinite::Abort => return Err(error),
+			NonFinite::SkipBatch => {},
+			NonFinite::Rollback => rollback(),
+		}
+		if verbose {
+			eprintln!("{error}, {}",if self == NonFinite::SkipBatch { "mini batch skipped" } else { "parameters rolled back" });
+		}
+		Ok(())
+	}
+}
+
+/// Where a NaN or an infinite value was found
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum NonFiniteValue {
+	Activation,
+	Gradient,
+	Cost,
+}
+
+/// A NaN or an infinite value found during training
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct NonFiniteError {
+	pub value : NonFiniteValue,
+	/// the layer, `None` for the cost
+	pub layer : Option<usize>,
+	pub epoch : usize,
+	/// the mini batch in the epoch, `None` for the cost at the end of the epoch
+	pub batch : Option<usize>,
+}
+
+impl fmt::Display for NonFiniteError {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		let value = match self.value {
+			NonFiniteValue::Activation => "activation",
+			NonFiniteValue::Gradient => "gradient",
+			NonFiniteValue::Cost => "cost",
+		};
+		write!(f, "non-finite {value}")?;
+		if let Some(layer) = self.layer {
+			write!(f, " in layer {layer}")?;
+		}
+		write!(f, " at epoch {}",self.epoch)?;
+		if let Some(batch) = self.batch {
+			write!(f, ", batch {batch}")?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for NonFiniteError {}
+
+/// How far a `train_loader` run went, what a checkpoint needs to continue it
+#[derive(Debug,Clone)]
+pub(crate) struct TrainProgress {
+	/// the epoch being trained
+	pub epoch : usize,
+	/// number of batches of `epoch` already trained
+	pub batch : usize,
+	pub learning_rate : f64,
+	/// cost at the end of each finished epoch
+	pub costs : Vec<f64>,
+	/// state of the loader before `epoch`, to replay its batches
+	pub loader : LoaderState,
+	/// layers restored by `NonFinite::Rollback`
+	pub last_good : Vec<RefCell<Layer>>,
+}
+
+impl Default for TrainOptions {
+	fn default() -> Self {
+		TrainOptions {
+			mini_batch_size : 100,
+			epochs : 10,
+			learning_rate : 0.1,
+			optimizer : Optimizer::Sgd,
+			schedule : LearningRateSchedule::HalveOnIncrease,
+			verbose : false,
+			clip : Clip::None,
+			non_finite : NonFinite::Ignore,
+		}
+	}
 }
 
 impl NeuralNetWork {
 
-	pub fn new(config : &Vec<u32>, cost_str : &str, activation_str : &str, output_activation_str :&str)-> NeuralNetWork {
+	pub fn new(config : &[u32], cost_str : &str, activation_str : &str, output_activation_str :&str)-> NeuralNetWork {
 		if config.len()<2 {
 			panic!("network should at least have 2 layers (input and output)");
 		};
 
-		let (cost_function,cost_derivative) = get_cost_from_string(cost_str);
-
-		let mut nn = NeuralNetWork{
-			layers : vec![],
-			nb_layer : 0,
-			input_size : config[0] as usize,
-			cost_function : cost_function,
-			cost_derivative : cost_derivative,
-		};
+		let mut nn = NeuralNetWork::empty(config[0] as usize, cost_str, ChaCha8Rng::from_entropy());
 
 		for elem in &config[1..config.len()-1] {
 			nn.add(*elem as usize,activation_str);
@@ -42,23 +216,62 @@ impl NeuralNetWork {
 		nn
 	}
 
+	/// Create a network without layers, the random source is used for initialisation and dropout
+	/// 
+	/// # Argument
+	/// * `input_size` - size of the input vector
+	/// * `cost_str` - name of the cost function
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn empty(input_size : usize, cost_str : &str, rng : ChaCha8Rng) -> NeuralNetWork {
+		let (cost_function,cost_derivative) = get_cost_from_string(cost_str);
+
+		NeuralNetWork{
+			layers : vec![],
+			nb_layer : 0,
+			input_size,
+			cost_name : cost_str.trim().to_lowercase(),
+			cost_function,
+			cost_derivative,
+			rng,
+			training : false,
+			input_scaler : None,
+			target_scaler : None,
+			heads : vec![],
+		}
+	}
+
 	pub fn add(&mut self,nb_neurons : usize,activation_str : &str)
 	{
+		self.add_layer(nb_neurons, activation_str, Init::Default, 0.0, false);
+	}
+
+	/// Add a fully connected layer
+	/// 
+	/// # Argument
+	/// * `nb_neurons` - size of the layer
+	/// * `activation_str` - name of the activation function
+	/// * `init` - how the weights and biases are drawn
+	/// * `dropout` - probability of dropping a neuron during training, training panics if the output layer has some
+	/// * `layer_norm` - normalise the pre-activation over the neurons of the layer
+	pub fn add_layer(&mut self,nb_neurons : usize,activation_str : &str, init : Init, dropout : f64, layer_norm : bool)
+	{
+		assert!(self.heads.is_empty(),"layers can't be added after the output heads");
 
 		let (activation_function,activation_derivative) = get_actvation_from_string(activation_str);
-		if nb_neurons<=0 {
+		if nb_neurons==0 {
 			panic!("Layer should at least have one neuron");
 		};
+		assert!((0.0..1.0).contains(&dropout),"dropout should be in [0,1)");
 
 		let cols = match self.layers.last() {
-			Some(layer) => layer.borrow().len as usize,
+			Some(layer) => layer.borrow().len,
 			None => self.input_size
 		};
 
 
 		let layer = RefCell::new(Layer{
-			w_matrix : Matrix::new_radom_gen_range(nb_neurons, cols,MIN_RAND, MAX_RAND),
-			b_matrix : Matrix::new_radom_gen_range(nb_neurons, 1, MIN_RAND, MAX_RAND),
+			w_matrix : init.weights(nb_neurons, cols, &mut self.rng),
+			b_matrix : init.biases(nb_neurons, &mut self.rng),
 			pre_acvtivation : Matrix::new(nb_neurons, 1),
 			post_activation : Matrix::new(nb_neurons, 1),
 			activation_function,
@@ -66,30 +279,234 @@ impl NeuralNetWork {
 			len	: nb_neurons,
 			grad_w : Matrix::new(nb_neurons, cols),
 			grad_b : Matrix::new(nb_neurons, 1),
+			activation_name : activation_str.trim().to_lowercase(),
+			dropout,
+			dropout_mask : Matrix::new(nb_neurons, 1),
+			norm : if layer_norm { Some(LayerNorm::new(nb_neurons)) } else { None },
+			optimizer_state : vec![ParamState::default();4],
 		});
 
 		self.layers.push(layer);
 		self.nb_layer+=1
 	}
 
+	/// Add an output layer split in heads, each with its own activation and cost in place of the cost of the network.
+	/// The output of the network is the output of every head, one after the other.
+	///
+	/// ```
+	/// use rand::SeedableRng;
+	/// use rand_chacha::ChaCha8Rng;
+	/// use rust_simple_nn::init::Init;
+	/// use rust_simple_nn::nn::*;
+	///
+	/// let mut nn = NeuralNetWork::empty(4, "default", ChaCha8Rng::seed_from_u64(0));
+	/// nn.add(8, "relu");
+	/// nn.add_heads(&[Head::new("price", 1, "identity", "quadratic"),Head::new("class", 3, "sigmoid", "cross_entropy").weight(0.5)], Init::Xavier);
+	/// assert_eq!(nn.output_size(), 4);
+	/// ```
+	pub fn add_heads(&mut self,heads : &[Head],init : Init){
+		if let Some(error) = heads.iter().find_map(|head| head.validate().err()) {
+			panic!("{error}");
+		}
+		assert!(!heads.is_empty(),"there should be at least one head");
+		self.add_layer(heads.iter().map(|head| head.size).sum(), "identity", init, 0.0, false);
+		self.set_heads(heads);
+	}
+
+	/// Random source of the dropout
+	pub(crate) fn rng(&self) -> &ChaCha8Rng {
+		&self.rng
+	}
+
+	/// Replace the random source of the dropout, ex : by the one saved in a checkpoint
+	pub(crate) fn set_rng(&mut self,rng : ChaCha8Rng){
+		self.rng = rng;
+	}
+
+	/// Split the output layer, which has an identity activation, in already validated heads
+	pub(crate) fn set_heads(&mut self,heads : &[Head]){
+		let mut start = 0;
+		self.heads = heads.iter().map(|head| {
+			let (activation_function,activation_derivative) = get_activation(&head.activation).expect("the head is validated");
+			let (cost_function,cost_derivative) = get_cost(&head.cost).expect("the head is validated");
+			start += head.size;
+			OutputHead { head : head.clone(), start : start-head.size, activation_function, activation_derivative, cost_function, cost_derivative }
+		}).collect();
+	}
+
+	pub fn heads(&self) -> Vec<&Head> {
+		self.heads.iter().map(|head| &head.head).collect()
+	}
+
+	pub fn input_size(&self) -> usize {
+		self.input_size
+	}
+
+	pub fn output_size(&self) -> usize {
+		self.layers.last().map_or(0, |layer| layer.borrow().len)
+	}
+
+	pub fn cost_name(&self) -> &str {
+		&self.cost_name
+	}
+
 	pub fn print_output(&self){
 		self.layers.last().unwrap().borrow().post_activation.dump();
 	}
 
-	pub fn input(&mut self,input : &Vec<f64>){
+	/// Returns a copy of the output of the last forward pass, before undoing the target scaling
+	pub fn output(&self) -> Vec<f64> {
+		self.layers.last().unwrap().borrow().post_activation.values.clone()
+	}
+
+	pub fn input_scaler(&self) -> Option<&Scaler> {
+		self.input_scaler.as_ref()
+	}
+
+	pub fn target_scaler(&self) -> Option<&Scaler> {
+		self.target_scaler.as_ref()
+	}
+
+	/// Transform applied to every input before the first layer
+	pub fn set_input_scaler(&mut self,scaler : Option<Scaler>){
+		if let Some(scaler) = &scaler {
+			assert!(scaler.len()==self.input_size,"the input scaler should have the size of the input");
+		}
+		self.input_scaler = scaler;
+	}
+
+	/// Transform applied to the targets during training, `predict` applies the inverse to the output
+	pub fn set_target_scaler(&mut self,scaler : Option<Scaler>){
+		if let Some(scaler) = &scaler {
+			assert!(scaler.len()==self.output_size(),"the target scaler should have the size of the output");
+		}
+		self.target_scaler = scaler;
+	}
+
+	/// Fit the input and target scalers on the training data, `None` removes a scaler
+	pub fn fit_scalers(&mut self,data : &[(Vec<f64>,Vec<f64>)],inputs : Option<ScalerKind>,targets : Option<ScalerKind>){
+		self.set_input_scaler(inputs.map(|kind| Scaler::fit(kind, data.iter().map(|(input,_)| input.as_slice()))));
+		self.set_target_scaler(targets.map(|kind| Scaler::fit(kind, data.iter().map(|(_,output)| output.as_slice()))));
+	}
+
+	/// Number of trainable values, the length of `parameters`
+	pub fn nb_parameters(&self) -> usize {
+		self.layers.iter().map(|layer| layer.borrow().parameters().map(|values| values.len()).sum::<usize>()).sum()
+	}
+
+	/// Every trainable value flattened : for each layer its weights (row major), its biases,
+	/// then the gain and shift of its normalisation if it has one
+	pub fn parameters(&self) -> Vec<f64> {
+		let mut parameters = Vec::with_capacity(self.nb_parameters());
+		for layer in &self.layers {
+			for values in layer.borrow().parameters() {
+				parameters.extend_from_slice(values);
+			}
+		}
+		parameters
+	}
+
+	/// Replace the trainable values, in the order of `parameters`
+	pub fn set_parameters(&mut self,parameters : &[f64]){
+		assert!(parameters.len()==self.nb_parameters(),"the network has {} parameters, got {}",self.nb_parameters(),parameters.len());
+		let mut start = 0;
+		for layer in &self.layers {
+			for values in layer.borrow_mut().parameters_mut() {
+				values.copy_from_slice(&parameters[start..start+values.len()]);
+				start += values.len();
+			}
+		}
+	}
+
+	/// Forward pass, the input is scaled first if the network has an input scaler
+	pub fn input(&mut self,input : &[f64]){
 		assert!(input.len()==self.input_size,"input should have the same lenght");
-		assert!(self.layers.len()!=0,"the network should have at least two layers (input and output)");
+		let input = scale(&self.input_scaler, input);
+		self.forward(&input);
+	}
+
+	/// Forward pass of an already scaled input
+	fn forward(&mut self,input : &[f64]){
+		assert!(!self.layers.is_empty(),"the network should have at least two layers (input and output)");
+		// the delta of the output layer doesn't go through a dropout mask
+		assert!(!self.training || self.layers[self.nb_layer-1].borrow().dropout == 0.0,"the output layer can't use dropout");
 		
 		self.layers[0].borrow_mut().input_pass(input);
+		if self.training {
+			self.layers[0].borrow_mut().apply_dropout(&mut self.rng);
+		}
 
 		for i in 1..self.nb_layer {
-			self.layers[i].borrow_mut().layer_pass(&self.layers[i-1].borrow_mut().post_activation)
+			self.layers[i].borrow_mut().layer_pass(&self.layers[i-1].borrow_mut().post_activation);
+			if self.training {
+				self.layers[i].borrow_mut().apply_dropout(&mut self.rng);
+			}
 		}
 
+		if !self.heads.is_empty() {
+			let mut last = self.layers[self.nb_layer-1].borrow_mut();
+			let last = &mut *last;
+			for head in &self.heads {
+				for i in head.range() {
+					last.post_activation.values[i] = (head.activation_function)(last.pre_acvtivation.values[i]);
+				}
+			}
+		}
+	}
+
+	/// Forward pass returning the output of the network, in the scale of the targets
+	pub fn predict(&mut self,input : &[f64]) -> Vec<f64> {
+		self.input(input);
+		match &self.target_scaler {
+			Some(scaler) => scaler.inverse_transform(&self.output()),
+			None => self.output(),
+		}
 	}
 
 	pub fn train(&mut self,data:&[(Vec<f64>,Vec<f64>)],mini_batch_size : usize,epochs: usize,learning_rate : f64,verbose : bool){
+		let options = TrainOptions {
+			mini_batch_size,
+			epochs,
+			learning_rate,
+			verbose,
+			..TrainOptions::default()
+		};
+		self.train_with(data, &options);
+	}
 
+	/// Train the network with an optimizer and a learning rate schedule, returns the cost at the end of each epoch
+	pub fn train_with(&mut self,data:&[(Vec<f64>,Vec<f64>)],options : &TrainOptions) -> Vec<f64> {
+		self.train_samples(data, None, options).unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	/// `train_with` returning the error that stopped the training when `options.non_finite` is `NonFinite::Abort`
+	pub fn try_train_with(&mut self,data:&[(Vec<f64>,Vec<f64>)],options : &TrainOptions) -> Result<Vec<f64>,NonFiniteError> {
+		self.train_samples(data, None, options)
+	}
+
+	/// `train_with` where the gradient and the cost of each sample are multiplied by its weight,
+	/// the gradient of a mini batch is divided by the sum of its weights instead of its size
+	///
+	/// # Argument
+	/// * `data` - the samples
+	/// * `weights` - the weight of each sample, positive
+	/// * `options` - the hyperparameters
+	pub fn train_weighted(&mut self,data:&[(Vec<f64>,Vec<f64>)],weights : &[f64],options : &TrainOptions) -> Vec<f64> {
+		assert!(weights.len()==data.len(),"there are {} samples and {} weights",data.len(),weights.len());
+		assert!(weights.iter().all(|weight| *weight >= 0.0 && weight.is_finite()),"the sample weights should be positive numbers");
+		self.train_samples(data, Some(weights), options).unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	/// `train_weighted` with the weight of the class of each sample, see `ClassWeights`
+	pub fn train_with_class_weights(&mut self,data:&[(Vec<f64>,Vec<f64>)],class_weights : &ClassWeights,options : &TrainOptions) -> Vec<f64> {
+		self.train_weighted(data, &class_weights.sample_weights(data), options)
+	}
+
+	fn train_samples(&mut self,data:&[(Vec<f64>,Vec<f64>)],weights : Option<&[f64]>,options : &TrainOptions) -> Result<Vec<f64>,NonFiniteError> {
+		let TrainOptions { mini_batch_size, epochs, learning_rate, optimizer, schedule, verbose, clip, non_finite } = *options;
+		let weight_chunks = |start : usize, len : usize| weights.map(|weights| &weights[start..start+len]);
+
+		let mut last_good = self.layers.clone();
 		let mut lr_calculated = learning_rate;
 		let mut cost_array :Vec<f64> = vec![];
 		for epoch in 0..epochs{
@@ -100,24 +517,22 @@ impl NeuralNetWork {
 			let mut i = 0;
 			if verbose {
 				let (datum_input,datum_output) = &data[0];
-				self.input(&datum_input);
-				let cost = (self.cost_function)(&self.layers.last().unwrap().borrow().post_activation.values,&datum_output);
+				let cost = self.cost_of(std::iter::once((datum_input.as_slice(),datum_output.as_slice())));
 				display_progress(i, chunks_size,cost,epoch,epochs);
 			}
 	
 			for data in data_chunks {
 	
-				for layer in &self.layers {
-					layer.borrow_mut().grad_w.zero();
-					layer.borrow_mut().grad_b.zero();
+				self.zero_grad();
+				let batch_weights = weight_chunks(i as usize*mini_batch_size, data.len());
+				let result = self.update_minibatch(data.iter().map(|(input,output)| (input.as_slice(),output.as_slice())),batch_weights,lr_calculated,&optimizer,clip,non_finite);
+				if let Err(error) = result {
+					self.handle_non_finite(NonFiniteError { epoch, batch : Some(i as usize), ..error }, non_finite, &last_good, verbose)?;
 				}
-				self.update_minibatch(data,lr_calculated);
-				let (datum_input,datum_output) = &data[0];
-				self.input(&datum_input);
-	
 	
 				if verbose && i%50==0 {
-					let cost = (self.cost_function)(&self.layers.last().unwrap().borrow().post_activation.values,&datum_output);
+					let (datum_input,datum_output) = &data[0];
+					let cost = self.cost_of(std::iter::once((datum_input.as_slice(),datum_output.as_slice())));
 					println!("\x1b[3F");
 					display_progress(i, chunks_size,cost,epoch,epochs);
 				}
@@ -125,7 +540,12 @@ impl NeuralNetWork {
 				i+=1
 			}
 
-			let cost = self.batch_cost(&data[0..mini_batch_size.min(data.len())]);
+			let first_batch = &data[0..mini_batch_size.min(data.len())];
+			let cost = match weight_chunks(0, first_batch.len()) {
+				Some(weights) => self.weighted_batch_cost(first_batch, weights),
+				None => self.batch_cost(first_batch),
+			};
+			self.end_epoch(cost, epoch, non_finite, &mut last_good, verbose)?;
 			cost_array.push(cost);
 
 			if verbose {
@@ -134,124 +554,377 @@ impl NeuralNetWork {
 				display_progress(i, chunks_size,cost,epoch,epochs);
 			}
 			
-			if cost_array.len()>3 && cost_array.last().unwrap() > &cost_array[cost_array.len()-2] {
-					lr_calculated/=2.0;
+			let next_lr = schedule.next(learning_rate, lr_calculated, epoch, &cost_array);
+			if verbose && next_lr != lr_calculated {
 					println!("Changed learning rate ");
 			}
+			lr_calculated = next_lr;
 		}
 		
+		Ok(cost_array)
 	}
 
-	fn update_minibatch(&mut self,data:&[(Vec<f64>,Vec<f64>)],learning_rate : f64){
+	/// Train the network on the batches of a `Loader`, returns the cost at the end of each epoch.
+	/// The batch size of the loader replaces `options.mini_batch_size`.
+	/// The `NonFiniteError` stopping the training is returned as an `io::Error` of kind `Other`.
+	/// An epoch without any batch is an error of kind `InvalidInput`.
+	pub fn train_loader(&mut self,loader : &mut impl Loader,options : &TrainOptions) -> io::Result<Vec<f64>> {
+		let progress = TrainProgress {
+			epoch : 0,
+			batch : 0,
+			learning_rate : options.learning_rate,
+			costs : vec![],
+			loader : loader.state(),
+			last_good : self.layers.clone(),
+		};
+		self.train_loader_from(loader, options, progress, &mut |_,_| Ok(()))
+	}
 
-		//compute the gradient sum overt the mini batch
-		for (input,output) in data {
-			self.input(input);
-			
-			//last layer
-			let last_layer = self.layers.last().unwrap();
-			last_layer.borrow_mut().compute_delta_last_layer(output, self.cost_derivative);
-			
+	/// `train_loader` continuing the run at `progress`, the batches of the epoch already trained are skipped.
+	/// `on_step` is called after every batch and at the end of every epoch, its error stops the training.
+	pub(crate) fn train_loader_from(&mut self,loader : &mut impl Loader,options : &TrainOptions,mut progress : TrainProgress,on_step : &mut dyn FnMut(&NeuralNetWork,&TrainProgress) -> io::Result<()>) -> io::Result<Vec<f64>> {
+		let TrainOptions { epochs, learning_rate, optimizer, schedule, verbose, clip, non_finite, .. } = *options;
+
+		while progress.epoch < epochs {
+			let epoch = progress.epoch;
+			let trained = progress.batch;
+			progress.loader = loader.state();
+
+			// the cost of the epoch is measured on its first batch, like train_with
+			let mut first_batch = None;
+			let mut i = 0;
+			for batch in loader.epoch() {
+				if i >= trained {
+					self.zero_grad();
+					if let Err(error) = self.update_minibatch(batch.iter(),None,progress.learning_rate,&optimizer,clip,non_finite) {
+						let error = NonFiniteError { epoch, batch : Some(i), ..error };
+						self.handle_non_finite(error, non_finite, &progress.last_good, verbose).map_err(io::Error::other)?;
+					}
+					progress.batch = i+1;
+					on_step(self, &progress)?;
+				}
+				first_batch.get_or_insert(batch);
+				i += 1;
+			}
 
-			last_layer.borrow_mut().compute_w_grad(&self.layers[self.layers.len()-2].borrow().post_activation.values);
-			last_layer.borrow_mut().compute_b_grad();
-
-			//others layers exepct the first one
-			for i in (1..self.nb_layer-1).rev() {
-				let prev_layer = &self.layers[i-1];
-				let follow_layer = &self.layers[i+1];
-				let layer = &self.layers[i]; 
-				//delta calculation
-				layer.borrow_mut().compute_delta(&follow_layer.borrow());
-				layer.borrow_mut().compute_w_grad(&prev_layer.borrow().post_activation.values);
-				layer.borrow_mut().compute_b_grad();
+			// ex : a loader dropping its last batch over fewer samples than its batch size
+			let Some(first_batch) = first_batch else {
+				return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("the loader produced no batch at epoch {epoch}")));
 			};
+			let cost = self.cost_of(first_batch.iter());
+			self.end_epoch(cost, epoch, non_finite, &mut progress.last_good, verbose).map_err(io::Error::other)?;
+			progress.costs.push(cost);
 
-			//first layer (using input)
-			let follow_layer = &self.layers[1];
-			let layer = &self.layers[0];
+			if verbose {
+				display_progress(i as i32, i,cost,epoch,epochs);
+			}
 
-			layer.borrow_mut().compute_delta(&follow_layer.borrow());
-			layer.borrow_mut().compute_w_grad(input);
+			let next_lr = schedule.next(learning_rate, progress.learning_rate, epoch, &progress.costs);
+			if verbose && next_lr != progress.learning_rate {
+					println!("Changed learning rate ");
+			}
+			progress.learning_rate = next_lr;
+			progress.epoch += 1;
+			progress.batch = 0;
+			progress.loader = loader.state();
+			on_step(self, &progress)?;
 		}
 
+		Ok(progress.costs)
+	}
+
+	/// Apply the `non_finite` policy to the error of a mini batch, returns the error if the training should stop
+	pub(crate) fn handle_non_finite(&mut self,error : NonFiniteError,non_finite : NonFinite,last_good : &[RefCell<Layer>],verbose : bool) -> Result<(),NonFiniteError> {
+		non_finite.handle(error, verbose, || self.layers = last_good.to_vec())
+	}
+
+	/// Check the cost of an epoch, the parameters are kept as the last good ones when it's finite
+	pub(crate) fn end_epoch(&mut self,cost : f64,epoch : usize,non_finite : NonFinite,last_good : &mut Vec<RefCell<Layer>>,verbose : bool) -> Result<(),NonFiniteError> {
+		if non_finite == NonFinite::Ignore {
+			return Ok(());
+		}
+		if cost.is_finite() {
+			if non_finite == NonFinite::Rollback {
+				*last_good = self.layers.clone();
+			}
+			return Ok(());
+		}
+		self.handle_non_finite(NonFiniteError { value : NonFiniteValue::Cost, layer : None, epoch, batch : None }, non_finite, last_good, verbose)
+	}
 
+	/// Sum the gradient of the mini batch then update the parameters. With a `non_finite` policy other than `Ignore`,
+	/// the activations, the costs and the gradient are checked first and nothing is updated if one isn't finite.
+	fn update_minibatch<'a>(&mut self,data : impl ExactSizeIterator<Item = (&'a [f64],&'a [f64])>,weights : Option<&[f64]>,learning_rate : f64,optimizer : &Optimizer,clip : Clip,non_finite : NonFinite) -> Result<(),NonFiniteError> {
+		let check = non_finite != NonFinite::Ignore;
 
 		//aplied the meaned gradient to the network
-		let mean_value = data.len() as f64;
-		//let mut layer_index = 0;
+		let mean_value = weights.map_or(data.len() as f64, |weights| weights.iter().sum());
 
+		//compute the gradient sum overt the mini batch
+		for (i,(input,output)) in data.enumerate() {
+			self.backprop_weighted(input, output, weights.map_or(1.0, |weights| weights[i]), check)?;
+		}
 
-		// println!("{:_^50}","postactivation");
-		// self.layers.last().unwrap().borrow().post_activation.dump();
-		// println!("{:_^50}","");
-		// println!("");
+		self.apply_gradients(mean_value, learning_rate, optimizer, clip, non_finite)
+	}
 
-		for layer in &self.layers{
-			// println!("{:=^10}",layer_index);
-			// layer.borrow().w_matrix.dump();
-			// println!("{:=^10}","");
-			layer.borrow_mut().update_parameters(mean_value,learning_rate);
+	/// Check and clip the gradient summed over `mean_value` samples then update the parameters,
+	/// nothing is updated if the gradient isn't finite and `non_finite` isn't `Ignore`
+	pub(crate) fn apply_gradients(&mut self,mean_value : f64,learning_rate : f64,optimizer : &Optimizer,clip : Clip,non_finite : NonFinite) -> Result<(),NonFiniteError> {
+		if non_finite != NonFinite::Ignore {
+			if let Some(layer) = self.layers.iter().position(|layer| layer.borrow().gradients().any(|grad| grad.iter().any(|g| !g.is_finite()))) {
+				return Err(NonFiniteError { value : NonFiniteValue::Gradient, layer : Some(layer), epoch : 0, batch : None });
+			}
 		}
 
+		// a mini batch whose samples all have a zero weight doesn't move the parameters
+		if mean_value > 0.0 {
+			self.clip_gradients(clip, mean_value);
+			self.update_parameters(mean_value,learning_rate,optimizer);
+		}
+		Ok(())
+	}
+
+	/// Forward pass in training mode followed by the backward pass, the gradient is added to the layers gradient
+	fn backprop(&mut self,input : &[f64],output : &[f64]){
+		let _ = self.backprop_weighted(input, output, 1.0, false);
+	}
+
+	/// `backprop` with the gradient of the sample multiplied by `weight`.
+	/// With `check` the activations and the cost are checked before the backward pass.
+	fn backprop_weighted(&mut self,input : &[f64],output : &[f64],weight : f64,check : bool) -> Result<(),NonFiniteError> {
+		let input = scale(&self.input_scaler, input);
+		let output = scale(&self.target_scaler, output);
+
+		self.training = true;
+		self.forward(&input);
+		self.training = false;
+
+		if check {
+			let non_finite = |value : NonFiniteValue, layer : Option<usize>| Err(NonFiniteError { value, layer, epoch : 0, batch : None });
+			if let Some(layer) = self.layers.iter().position(|layer| layer.borrow().post_activation.values.iter().any(|x| !x.is_finite())) {
+				return non_finite(NonFiniteValue::Activation, Some(layer));
+			}
+			if !self.output_cost(&self.layers[self.nb_layer-1].borrow().post_activation.values, &output).is_finite() {
+				return non_finite(NonFiniteValue::Cost, None);
+			}
+		}
+		
+		for i in (0..self.nb_layer).rev() {
+			let layer = &self.layers[i];
+
+			//delta calculation, the last layer uses the cost derivative
+			// the deltas of the other layers come from the last one, so they are weighted too
+			if i == self.nb_layer-1 && !self.heads.is_empty() {
+				layer.borrow_mut().compute_delta_heads(&output, &self.heads, weight);
+			} else if i == self.nb_layer-1 {
+				layer.borrow_mut().compute_delta_last_layer(&output, self.cost_derivative, weight);
+			} else {
+				layer.borrow_mut().compute_delta(&self.layers[i+1].borrow());
+			}
+
+			//the first layer uses the input
+			if i == 0 {
+				layer.borrow_mut().compute_w_grad(&input);
+			} else {
+				layer.borrow_mut().compute_w_grad(&self.layers[i-1].borrow().post_activation.values);
+			}
+			layer.borrow_mut().compute_b_grad();
+		}
+		Ok(())
+	}
+
+	/// `backprop` that also returns the gradient of the cost with respect to the input,
+	/// used when the network is the dense head of a model whose blocks come first
+	pub(crate) fn backprop_input(&mut self,input : &[f64],output : &[f64]) -> Vec<f64> {
+		assert!(self.input_scaler.is_none(),"a network fed by other blocks can't have an input scaler");
+		self.backprop(input, output);
+		// the delta of the first layer is left in its post activation
+		let first = self.layers[0].borrow();
+		let mut gradient = Matrix::new(self.input_size, 1);
+		first.w_matrix.trans_dot(&mut gradient, &first.post_activation);
+		gradient.values
+	}
+
+	pub(crate) fn zero_grad(&mut self){
+		for layer in &self.layers {
+			layer.borrow_mut().zero_grad();
+		}
+	}
+
+	fn clip_gradients(&mut self,clip : Clip,mean_value : f64){
+		if clip == Clip::None {
+			return;
+		}
+		let mut layers : Vec<RefMut<Layer>> = self.layers.iter().map(|layer| layer.borrow_mut()).collect();
+		let mut gradients : Vec<Vec<&mut [f64]>> = layers.iter_mut().map(|layer| layer.gradients_mut().collect()).collect();
+		clip.apply(&mut gradients, mean_value);
+	}
+
+	pub(crate) fn update_parameters(&mut self,mean_value : f64,learning_rate : f64,optimizer : &Optimizer){
+		for layer in &self.layers{
+			layer.borrow_mut().update_parameters(mean_value,learning_rate,optimizer);
+		}
 	}
 
 	pub fn batch_cost(&mut self,data : &[(Vec<f64>,Vec<f64>)]) -> f64 {
+		self.cost_of(data.iter().map(|(input,output)| (input.as_slice(),output.as_slice())))
+	}
+
+	/// Mean cost where each sample counts as much as its weight, the unweighted mean if every weight is 0
+	pub fn weighted_batch_cost(&mut self,data : &[(Vec<f64>,Vec<f64>)],weights : &[f64]) -> f64 {
+		assert!(weights.len()==data.len(),"there are {} samples and {} weights",data.len(),weights.len());
+		let total : f64 = weights.iter().sum();
+		if total == 0.0 {
+			return self.batch_cost(data);
+		}
+		let cost : f64 = data.iter().zip(weights).map(|((input,output),weight)| weight * self.sample_cost(input, output)).sum();
+		cost / total
+	}
+
+	pub(crate) fn cost_of<'a>(&mut self,data : impl ExactSizeIterator<Item = (&'a [f64],&'a [f64])>) -> f64 {
 		let mut cost = 0.0;
 		let mean_divider = data.len() as f64;
 
 		for (datum_input,datum_output) in data {
-
-			self.input(&datum_input);
-			cost += (self.cost_function)(&self.layers.last().unwrap().borrow().post_activation.values,&datum_output);
+			cost += self.sample_cost(datum_input, datum_output);
 		};
 		cost /= mean_divider;
 		cost
 	}
+
+	fn sample_cost(&mut self,datum_input : &[f64],datum_output : &[f64]) -> f64 {
+		self.input(datum_input);
+		let datum_output = scale(&self.target_scaler, datum_output);
+		self.output_cost(&self.layers.last().unwrap().borrow().post_activation.values, &datum_output)
+	}
+
+	/// Cost of an output for a scaled target
+	fn output_cost(&self,output : &[f64],target : &[f64]) -> f64 {
+		match self.heads.is_empty() {
+			true => (self.cost_function)(output,target),
+			false => self.heads.iter().map(|head| head.head.weight * (head.cost_function)(&output[head.range()],&target[head.range()])).sum(),
+		}
+	}
+
+	/// Mean cost of each head over the data, before the weights are applied
+	pub fn head_costs(&mut self,data : &[(Vec<f64>,Vec<f64>)]) -> Vec<f64> {
+		assert!(!self.heads.is_empty(),"the network has no output heads");
+		let mut costs = vec![0.0;self.heads.len()];
+		for (datum_input,datum_output) in data {
+			self.input(datum_input);
+			let datum_output = scale(&self.target_scaler, datum_output);
+			let output = &self.layers.last().unwrap().borrow().post_activation.values;
+			for (head,cost) in self.heads.iter().zip(costs.iter_mut()) {
+				*cost += (head.cost_function)(&output[head.range()],&datum_output[head.range()]);
+			}
+		}
+		costs.iter().map(|cost| cost / data.len() as f64).collect()
+	}
 }
 
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct Layer {
-	w_matrix : Matrix<f64>,
-	b_matrix : Matrix<f64>,
-	pre_acvtivation : Matrix<f64>,
-	post_activation : Matrix<f64>,
-	activation_function : fn(f64)->f64,
-	activation_derivative : fn(f64)->f64,
-	len : usize,
-	grad_w : Matrix<f64>,
-	grad_b : Matrix<f64>,
+	pub(crate) w_matrix : Matrix<f64>,
+	pub(crate) b_matrix : Matrix<f64>,
+	pub(crate) pre_acvtivation : Matrix<f64>,
+	pub(crate) post_activation : Matrix<f64>,
+	pub(crate) activation_function : fn(f64)->f64,
+	pub(crate) activation_derivative : fn(f64)->f64,
+	pub(crate) len : usize,
+	pub(crate) grad_w : Matrix<f64>,
+	pub(crate) grad_b : Matrix<f64>,
+	pub(crate) activation_name : String,
+	pub(crate) dropout : f64,
+	pub(crate) dropout_mask : Matrix<f64>,
+	pub(crate) norm : Option<LayerNorm>,
+	/// weights, biases, norm gain, norm shift
+	pub(crate) optimizer_state : Vec<ParamState>,
+}
+
+/// Normalisation of the pre-activation over the neurons of a layer, followed by a learned gain and shift
+#[derive(Debug,Clone)]
+pub struct LayerNorm {
+	pub(crate) gamma : Matrix<f64>,
+	pub(crate) beta : Matrix<f64>,
+	pub(crate) grad_gamma : Matrix<f64>,
+	pub(crate) grad_beta : Matrix<f64>,
+	pub(crate) normalized : Matrix<f64>,
+	pub(crate) inv_std : f64,
 }
 
 
 impl Layer {
-	pub fn input_pass(&mut self,input :&Vec<f64>){
+	pub fn input_pass(&mut self,input :&[f64]){
 		self.w_matrix.dot_vec(&mut self.pre_acvtivation, input);
 		self.pre_acvtivation.add_mut(&self.b_matrix);
+		if let Some(norm) = &mut self.norm {
+			norm.forward(&mut self.pre_acvtivation);
+		}
 		self.pre_acvtivation.apply_to(&mut self.post_activation, self.activation_function)
 	}
 
 	pub fn layer_pass(&mut self, input : &Matrix<f64>){
 		self.w_matrix.dot(&mut self.pre_acvtivation, input);
 		self.pre_acvtivation.add_mut(&self.b_matrix);
+		if let Some(norm) = &mut self.norm {
+			norm.forward(&mut self.pre_acvtivation);
+		}
 		self.pre_acvtivation.apply_to(&mut self.post_activation, self.activation_function)
 	}
 
-	pub fn compute_delta_last_layer(&mut self,output : &Vec<f64>,cost_derivative :fn(f64,f64) -> f64)
+	/// Drop neurons with the layer probability, the kept ones are scaled so the expected output doesn't change
+	pub fn apply_dropout(&mut self, rng : &mut ChaCha8Rng){
+		if self.dropout == 0.0 {
+			return;
+		}
+		let keep = 1.0-self.dropout;
+		self.dropout_mask = Matrix::new_bernoulli(self.len, 1, keep, rng);
+		self.dropout_mask.values.iter_mut().for_each(|x| *x /= keep);
+		self.post_activation.multiply_by_mut(&self.dropout_mask);
+	}
+
+	/// Delta of the output layer for a sample whose cost is multiplied by `weight`
+	pub fn compute_delta_last_layer(&mut self,output : &[f64],cost_derivative :fn(f64,f64) -> f64,weight : f64)
 	{
 		self.post_activation.cost_derivative_mut(output,cost_derivative);
 		self.pre_acvtivation.apply_mut(self.activation_derivative);
 		self.post_activation.multiply_by_mut(&self.pre_acvtivation);
+		if weight != 1.0 {
+			self.post_activation.values.iter_mut().for_each(|delta| *delta *= weight);
+		}
+		if let Some(norm) = &mut self.norm {
+			norm.backward(&mut self.post_activation);
+		}
+	}
+
+	/// Delta of an output layer split in heads, the layer itself has an identity activation
+	fn compute_delta_heads(&mut self,output : &[f64],heads : &[OutputHead],weight : f64){
+		for head in heads {
+			for i in head.range() {
+				let derivative = (head.cost_derivative)(self.post_activation.values[i],output[i]) * (head.activation_derivative)(self.pre_acvtivation.values[i]);
+				self.post_activation.values[i] = weight * head.head.weight * derivative;
+			}
+		}
+		if let Some(norm) = &mut self.norm {
+			norm.backward(&mut self.post_activation);
+		}
 	}
 
 	pub fn compute_delta(&mut self,following_layer : &Self){
 		following_layer.w_matrix.trans_dot(&mut self.post_activation,&following_layer.post_activation);
+		if self.dropout != 0.0 {
+			self.post_activation.multiply_by_mut(&self.dropout_mask);
+		}
 		self.pre_acvtivation.apply_mut(self.activation_derivative);
 		self.post_activation.multiply_by_mut(&self.pre_acvtivation);
+		if let Some(norm) = &mut self.norm {
+			norm.backward(&mut self.post_activation);
+		}
 	}
 
-	pub fn compute_w_grad(&mut self,prev_layer_values : &Vec<f64>)
+	pub fn compute_w_grad(&mut self,prev_layer_values : &[f64])
 	{
 		self.grad_w.matrix_weight_compute(prev_layer_values,&self.post_activation.values);
 		
@@ -262,30 +935,163 @@ impl Layer {
 		self.grad_b.add_mut(&self.post_activation);
 	}
 
-	pub fn update_parameters(&mut self, mean_value : f64, learning_rate : f64){
-		for (grad,bias) in self.grad_b.values.iter_mut().zip(self.b_matrix.values.iter_mut()){
-			*bias -= *grad*learning_rate/mean_value;
+	pub fn zero_grad(&mut self){
+		self.grad_w.zero();
+		self.grad_b.zero();
+		if let Some(norm) = &mut self.norm {
+			norm.grad_gamma.zero();
+			norm.grad_beta.zero();
 		}
+	}
 
-		for (grad,weight) in self.grad_w.values.iter_mut().zip(self.w_matrix.values.iter_mut()) {
-			*weight -= *grad*learning_rate/mean_value;
+	pub fn update_parameters(&mut self, mean_value : f64, learning_rate : f64, optimizer : &Optimizer){
+		optimizer.update(&mut self.w_matrix.values, &self.grad_w.values, &mut self.optimizer_state[0], learning_rate, mean_value);
+		optimizer.update(&mut self.b_matrix.values, &self.grad_b.values, &mut self.optimizer_state[1], learning_rate, mean_value);
 
+		if let Some(norm) = &mut self.norm {
+			optimizer.update(&mut norm.gamma.values, &norm.grad_gamma.values, &mut self.optimizer_state[2], learning_rate, mean_value);
+			optimizer.update(&mut norm.beta.values, &norm.grad_beta.values, &mut self.optimizer_state[3], learning_rate, mean_value);
 		}
+	}
 
+	pub fn len(&self) -> usize {
+		self.len
+	}
 
-		// println!("{:=^10}","BIASES");
-		// self.b_matrix.dump();
-		// println!("{:=^10}","");
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
 
-		// println!("{:=^10}","WEIGHTs");
-		// self.w_matrix.dump();
-		// println!("{:=^10}","");
-		// println!("");
+	pub fn activation_name(&self) -> &str {
+		&self.activation_name
+	}
+
+	pub fn dropout(&self) -> f64 {
+		self.dropout
+	}
+
+	pub fn norm(&self) -> Option<&LayerNorm> {
+		self.norm.as_ref()
+	}
+
+	pub fn weights(&self) -> &Matrix<f64> {
+		&self.w_matrix
+	}
+
+	pub fn biases(&self) -> &Matrix<f64> {
+		&self.b_matrix
+	}
+
+	/// weights, biases, norm gain, norm shift (same order as `optimizer_state`)
+	fn parameters(&self) -> impl Iterator<Item = &[f64]> {
+		let norm = self.norm.iter().flat_map(|norm| [norm.gamma.values.as_slice(),norm.beta.values.as_slice()]);
+		[self.w_matrix.values.as_slice(),self.b_matrix.values.as_slice()].into_iter().chain(norm)
+	}
+
+	/// gradients of the parameters, in the same order
+	fn gradients(&self) -> impl Iterator<Item = &[f64]> {
+		let norm = self.norm.iter().flat_map(|norm| [norm.grad_gamma.values.as_slice(),norm.grad_beta.values.as_slice()]);
+		[self.grad_w.values.as_slice(),self.grad_b.values.as_slice()].into_iter().chain(norm)
+	}
+
+	pub(crate) fn gradients_mut(&mut self) -> impl Iterator<Item = &mut [f64]> {
+		let norm = self.norm.iter_mut().flat_map(|norm| [norm.grad_gamma.values.as_mut_slice(),norm.grad_beta.values.as_mut_slice()]);
+		[self.grad_w.values.as_mut_slice(),self.grad_b.values.as_mut_slice()].into_iter().chain(norm)
+	}
+
+	fn parameters_mut(&mut self) -> impl Iterator<Item = &mut [f64]> {
+		let norm = self.norm.iter_mut().flat_map(|norm| [norm.gamma.values.as_mut_slice(),norm.beta.values.as_mut_slice()]);
+		[self.w_matrix.values.as_mut_slice(),self.b_matrix.values.as_mut_slice()].into_iter().chain(norm)
 	}
 
 }
 
 
+impl Head {
+
+	/// A head with a weight of 1
+	pub fn new(name : &str, size : usize, activation : &str, cost : &str) -> Head {
+		Head { name : name.to_string(), size, activation : activation.to_string(), cost : cost.to_string(), weight : 1.0 }
+	}
+
+	pub fn weight(mut self, weight : f64) -> Head {
+		self.weight = weight;
+		self
+	}
+
+	/// Check the size, the weight and the function names
+	pub fn validate(&self) -> Result<(),String> {
+		if self.size == 0 {
+			return Err(format!("head `{}`: should have at least one neuron",self.name));
+		}
+		if !(self.weight >= 0.0 && self.weight.is_finite()) {
+			return Err(format!("head `{}`: the weight should be a positive number",self.name));
+		}
+		if get_activation(&self.activation).is_none() {
+			return Err(format!("head `{}`: unknown activation `{}`",self.name,self.activation));
+		}
+		if get_cost(&self.cost).is_none() {
+			return Err(format!("head `{}`: unknown cost `{}`",self.name,self.cost));
+		}
+		Ok(())
+	}
+}
+
+
+impl LayerNorm {
+
+	fn new(len : usize) -> LayerNorm {
+		LayerNorm {
+			gamma : Matrix::from_vec(len, 1, vec![1.0;len]),
+			beta : Matrix::new(len, 1),
+			grad_gamma : Matrix::new(len, 1),
+			grad_beta : Matrix::new(len, 1),
+			normalized : Matrix::new(len, 1),
+			inv_std : 0.0,
+		}
+	}
+
+	pub fn gain(&self) -> &Matrix<f64> {
+		&self.gamma
+	}
+
+	pub fn shift(&self) -> &Matrix<f64> {
+		&self.beta
+	}
+
+	/// Normalise `values` in place and apply the gain and shift
+	fn forward(&mut self, values : &mut Matrix<f64>){
+		let n = values.values.len() as f64;
+		let mean = values.values.iter().sum::<f64>() / n;
+		let var = values.values.iter().map(|x| (x-mean).powi(2)).sum::<f64>() / n;
+		self.inv_std = 1.0 / (var + NORM_EPSILON).sqrt();
+
+		for (i,value) in values.values.iter_mut().enumerate() {
+			self.normalized.values[i] = (*value - mean) * self.inv_std;
+			*value = self.gamma.values[i] * self.normalized.values[i] + self.beta.values[i];
+		}
+	}
+
+	/// Turn the delta of the normalised output into the delta of the raw pre-activation (in place)
+	/// and accumulate the gradient of the gain and shift
+	fn backward(&mut self, delta : &mut Matrix<f64>){
+		let n = delta.values.len() as f64;
+		self.grad_beta.add_mut(delta);
+		for (i,d) in delta.values.iter().enumerate() {
+			self.grad_gamma.values[i] += d * self.normalized.values[i];
+		}
+
+		let d_normalized : Vec<f64> = delta.values.iter().zip(&self.gamma.values).map(|(d,g)| d*g).collect();
+		let sum : f64 = d_normalized.iter().sum();
+		let dot : f64 = d_normalized.iter().zip(&self.normalized.values).map(|(d,x)| d*x).sum();
+
+		for (i,d) in delta.values.iter_mut().enumerate() {
+			*d = self.inv_std / n * (n*d_normalized[i] - sum - self.normalized.values[i]*dot);
+		}
+	}
+}
+
+
 
 
 /* -------------------------------------------------------------------------- */
@@ -293,34 +1099,55 @@ impl Layer {
 /* -------------------------------------------------------------------------- */
 
 
-fn get_actvation_from_string(name : &str) -> (fn(f64) -> f64,fn(f64) -> f64)
-{
+type ActivationPair = (fn(f64) -> f64,fn(f64) -> f64);
+type CostPair = (fn(&[f64],&[f64]) -> f64, fn(f64,f64) -> f64);
 
-	match name.trim().to_lowercase().as_str() {
-		"sigmoid" | "sigmoïd" => return (sigmoid,d_sigmoid),
-		"relu" => return (relu,d_relu),
-		"id" | "identity" => return (identity,d_indentity),
-		"default" => return (relu,d_relu),
-		x  => {
-			eprintln!("no function named {x}, using default Relu activation function");
-			return (relu,d_relu);
+fn get_actvation_from_string(name : &str) -> ActivationPair
+{
+	match get_activation(name) {
+		Some(pair) => pair,
+		None => {
+			eprintln!("no function named {}, using default Relu activation function",name.trim());
+			(relu,d_relu)
 		}
-
 	}
 }
 
+/// Returns the activation function and its derivative, `None` if the name is unknown
+pub fn get_activation(name : &str) -> Option<ActivationPair>
+{
 
-fn get_cost_from_string(name : &str) -> (fn(&Vec<f64>,&Vec<f64>) -> f64, fn(f64,f64) -> f64){
 	match name.trim().to_lowercase().as_str() {
-		"quadratic" => return (quadratic_cost , d_quadratic_cost),
-		"default" => return (quadratic_cost , d_quadratic_cost),
-		x => {
-			eprintln!("no function named {x}, using default quadratic cost function");
-			return (quadratic_cost,d_quadratic_cost);
+		"sigmoid" | "sigmoïd" => Some((sigmoid,d_sigmoid)),
+		"relu" => Some((relu,d_relu)),
+		"tanh" => Some((tanh,d_tanh)),
+		"id" | "identity" => Some((identity,d_indentity)),
+		"default" => Some((relu,d_relu)),
+		_ => None,
+	}
+}
+
+
+fn get_cost_from_string(name : &str) -> CostPair{
+	match get_cost(name) {
+		Some(pair) => pair,
+		None => {
+			eprintln!("no function named {}, using default quadratic cost function",name.trim());
+			(quadratic_cost,d_quadratic_cost)
 		}
 	}
 }
 
+/// Returns the cost function and its derivative, `None` if the name is unknown
+pub fn get_cost(name : &str) -> Option<CostPair>{
+	match name.trim().to_lowercase().as_str() {
+		"quadratic" => Some((quadratic_cost , d_quadratic_cost)),
+		"cross_entropy" | "crossentropy" => Some((cross_entropy_cost , d_cross_entropy_cost)),
+		"default" => Some((quadratic_cost , d_quadratic_cost)),
+		_ => None,
+	}
+}
+
 
 /* -------------------------------------------------------------------------- */
 /*                        Activation and cost functions                       */
@@ -356,7 +1183,7 @@ fn relu(x: f64) -> f64
 	if x>0.0{
 		return x
 	}
-	return x*0.0;
+	x*0.0
 }
 
 fn d_relu(x: f64) -> f64
@@ -364,12 +1191,23 @@ fn d_relu(x: f64) -> f64
 	if x>0.0{
 		return 1.0;
 	}
-	return 0.0;
+	0.0
+}
+
+
+fn tanh(x: f64) -> f64
+{
+	x.tanh()
+}
+
+fn d_tanh(x: f64) -> f64
+{
+	1.0 - x.tanh().powi(2)
 }
 
 /* ----------------------------- Cost functions ----------------------------- */
 
-fn quadratic_cost(x : &Vec<f64>, y : &Vec<f64>) -> f64
+fn quadratic_cost(x : &[f64], y : &[f64]) -> f64
 {
 	//x.iter().zip(y.iter()).map(|(&a, &b)|(b-a).abs()).sum::<f64>() 
 	x.iter().zip(y.iter()).map(|(&a, &b)|(b-a).powf(2.0)).sum::<f64>()
@@ -378,4 +1216,325 @@ fn quadratic_cost(x : &Vec<f64>, y : &Vec<f64>) -> f64
 fn d_quadratic_cost(x:f64, y:f64) -> f64
 {
 	2.0*(x-y)
-}
\ No newline at end of file
+}
+
+
+/// probabilities are clamped so the logarithm stays finite
+pub(crate) const CROSS_ENTROPY_CLAMP : f64 = 1e-12;
+
+fn cross_entropy_cost(x : &[f64], y : &[f64]) -> f64
+{
+	x.iter().zip(y.iter()).map(|(&a, &b)|{
+		let a = a.clamp(CROSS_ENTROPY_CLAMP, 1.0-CROSS_ENTROPY_CLAMP);
+		-(b*a.ln() + (1.0-b)*(1.0-a).ln())
+	}).sum::<f64>()
+}
+
+fn d_cross_entropy_cost(x:f64, y:f64) -> f64
+{
+	let x = x.clamp(CROSS_ENTROPY_CLAMP, 1.0-CROSS_ENTROPY_CLAMP);
+	(x-y)/(x*(1.0-x))
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const EPSILON : f64 = 1e-6;
+
+	fn test_network(layer_norm : bool) -> NeuralNetWork {
+		let mut nn = NeuralNetWork::empty(3, "cross_entropy", ChaCha8Rng::seed_from_u64(0));
+		nn.add_layer(4, "tanh", Init::Normal { std : 0.5 }, 0.0, layer_norm);
+		nn.add_layer(3, "relu", Init::Uniform { min : 0.1, max : 0.6 }, 0.0, false);
+		nn.add_layer(2, "sigmoid", Init::Xavier, 0.0, false);
+		nn
+	}
+
+	/// compare the backprop gradient of every weight and bias with finite differences
+	fn gradient_check(mut nn : NeuralNetWork){
+		let (input,output) = (vec![0.5,-1.0,2.0],vec![1.0,0.0]);
+		for layer in &nn.layers {
+			layer.borrow_mut().zero_grad();
+		}
+		nn.backprop(&input, &output);
+
+		for l in 0..nn.layers.len() {
+			let (grad_w,grad_b) = {
+				let layer = nn.layers[l].borrow();
+				(layer.grad_w.clone(),layer.grad_b.clone())
+			};
+			for (params,grads) in [(0,grad_w),(1,grad_b)] {
+				for (i,grad) in grads.values.iter().enumerate() {
+					let mut cost_at = |delta : f64| {
+						{
+							let mut layer = nn.layers[l].borrow_mut();
+							let values = if params == 0 { &mut layer.w_matrix.values } else { &mut layer.b_matrix.values };
+							values[i] += delta;
+						}
+						nn.batch_cost(&[(input.clone(),output.clone())])
+					};
+					let plus = cost_at(EPSILON);
+					let minus = cost_at(-2.0*EPSILON);
+					cost_at(EPSILON);
+
+					let numeric = (plus-minus)/(2.0*EPSILON);
+					assert!((numeric-grad).abs() < 1e-5, "layer {l} param {i}: {numeric} != {grad}");
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn backprop_gradient_check(){
+		gradient_check(test_network(false));
+	}
+
+	#[test]
+	fn backprop_layer_norm_gradient_check(){
+		gradient_check(test_network(true));
+	}
+
+	#[test]
+	fn backprop_heads_gradient_check(){
+		let mut nn = NeuralNetWork::empty(3, "default", ChaCha8Rng::seed_from_u64(0));
+		nn.add_layer(4, "tanh", Init::Normal { std : 0.5 }, 0.0, false);
+		nn.add_heads(&[Head::new("value", 1, "tanh", "quadratic").weight(0.3),Head::new("class", 1, "sigmoid", "cross_entropy")], Init::Xavier);
+		gradient_check(nn);
+	}
+
+	#[test]
+	fn heads_learn_a_value_and_a_class(){
+		let data : Vec<(Vec<f64>,Vec<f64>)> = (0..100).map(|i| {
+			let x = vec![(i%10) as f64 / 10.0, (i/10) as f64 / 10.0];
+			(x.clone(),vec![x[0] - 0.5*x[1],(x[0] > x[1]) as usize as f64])
+		}).collect();
+		let mut nn = NeuralNetWork::empty(2, "default", ChaCha8Rng::seed_from_u64(3));
+		nn.add_layer(16, "tanh", Init::Xavier, 0.0, false);
+		nn.add_heads(&[Head::new("value", 1, "identity", "quadratic"),Head::new("class", 1, "sigmoid", "cross_entropy").weight(0.1)], Init::Xavier);
+
+		let options = TrainOptions { mini_batch_size : 10, epochs : 300, learning_rate : 0.01, optimizer : Optimizer::adam(), schedule : LearningRateSchedule::Constant, ..TrainOptions::default() };
+		nn.train_with(&data, &options);
+
+		let costs = nn.head_costs(&data);
+		assert!(costs[0] < 1e-3 && costs[1] < 0.2, "{costs:?}");
+		assert!((nn.batch_cost(&data) - (costs[0] + 0.1*costs[1])).abs() < 1e-12);
+	}
+
+	#[test]
+	#[should_panic]
+	fn heads_are_the_last_layer(){
+		let mut nn = NeuralNetWork::empty(3, "default", ChaCha8Rng::seed_from_u64(0));
+		nn.add_heads(&[Head::new("value", 1, "identity", "quadratic")], Init::Default);
+		nn.add(2, "relu");
+	}
+
+	#[test]
+	fn single_layer_network_trains(){
+		let mut nn = NeuralNetWork::empty(2, "quadratic", ChaCha8Rng::seed_from_u64(1));
+		nn.add(1, "identity");
+		let data : Vec<(Vec<f64>,Vec<f64>)> = (0..100).map(|i| {
+			let x = vec![(i%10) as f64 / 10.0, (i/10) as f64 / 10.0];
+			let y = vec![2.0*x[0] - x[1] + 0.5];
+			(x,y)
+		}).collect();
+
+		let options = TrainOptions { mini_batch_size : 10, epochs : 200, learning_rate : 0.1, schedule : LearningRateSchedule::Constant, ..TrainOptions::default() };
+		let costs = nn.train_with(&data, &options);
+		assert!(*costs.last().unwrap() < 1e-4);
+	}
+
+	#[test]
+	fn train_loader_matches_train_with(){
+		let mut nn = NeuralNetWork::empty(2, "quadratic", ChaCha8Rng::seed_from_u64(4));
+		nn.add(3, "tanh");
+		nn.add(1, "sigmoid");
+		let data = crate::datasets::generate_xor_data(40);
+		let options = TrainOptions { mini_batch_size : 8, epochs : 5, optimizer : Optimizer::adam(), ..TrainOptions::default() };
+
+		let mut with_loader = nn.clone();
+		let costs = nn.train_with(&data, &options);
+		let loader_costs = with_loader.train_loader(&mut DataLoader::new(MatrixDataset::from_samples(&data), 8), &options).unwrap();
+		assert!(costs == loader_costs);
+
+		// fewer samples than a batch and the last batch dropped
+		let error = nn.clone().train_loader(&mut DataLoader::new(data[0..5].to_vec(), 8).drop_last(true), &options).unwrap_err();
+		assert!(error.kind() == io::ErrorKind::InvalidInput);
+
+		let shuffled_costs = nn.clone().train_loader(&mut DataLoader::new(data, 8).shuffle(1), &options).unwrap();
+		assert!(shuffled_costs.len() == 5 && shuffled_costs != costs);
+	}
+
+	#[test]
+	fn sample_weight_counts_like_a_duplicate(){
+		// the weight also reaches the gain and shift of a normalised output layer
+		let mut output_norm = NeuralNetWork::empty(3, "cross_entropy", ChaCha8Rng::seed_from_u64(0));
+		output_norm.add_layer(4, "tanh", Init::Normal { std : 0.5 }, 0.0, false);
+		output_norm.add_layer(2, "sigmoid", Init::Xavier, 0.0, true);
+
+		for mut nn in [test_network(true),output_norm] {
+			let (a,b) = ((vec![0.5,-1.0,2.0],vec![1.0,0.0]),(vec![-0.2,0.3,0.1],vec![0.0,1.0]));
+			let options = TrainOptions { mini_batch_size : 3, epochs : 3, optimizer : Optimizer::adam(), ..TrainOptions::default() };
+
+			let mut duplicated = nn.clone();
+			let costs = duplicated.train_with(&[a.clone(),a.clone(),b.clone()], &options);
+			let weighted_costs = nn.train_weighted(&[a.clone(),b.clone()], &[2.0,1.0], &options);
+			let close = |x : &[f64], y : &[f64]| x.iter().zip(y).all(|(x,y)| (x-y).abs() < 1e-12);
+			assert!(close(&nn.parameters(), &duplicated.parameters()) && close(&costs, &weighted_costs));
+
+			// a zero weight ignores the sample
+			let mut alone = nn.clone();
+			alone.train_with(std::slice::from_ref(&b), &options);
+			nn.train_weighted(&[a,b], &[0.0,1.0], &options);
+			assert!(close(&nn.parameters(), &alone.parameters()));
+		}
+	}
+
+	#[test]
+	fn zero_weight_first_batch(){
+		let (a,b) = ((vec![0.5,-1.0,2.0],vec![1.0,0.0]),(vec![-0.2,0.3,0.1],vec![0.0,1.0]));
+		let options = TrainOptions { mini_batch_size : 1, epochs : 2, non_finite : NonFinite::Abort, ..TrainOptions::default() };
+
+		// the first batch doesn't train but its cost is still reported
+		let mut nn = test_network(false);
+		let mut alone = nn.clone();
+		let costs = nn.train_weighted(&[a.clone(),b.clone()], &[0.0,1.0], &options);
+		alone.train_with(std::slice::from_ref(&b), &TrainOptions { non_finite : NonFinite::Ignore, ..options });
+		assert!(costs.len() == 2 && costs.iter().all(|cost| cost.is_finite()));
+		assert!(costs[1] == nn.batch_cost(std::slice::from_ref(&a)) && nn.parameters() == alone.parameters());
+	}
+
+	#[test]
+	fn clipping_limits_the_step(){
+		let mut nn = test_network(false);
+		let data = vec![(vec![50.0,-80.0,20.0],vec![1.0,0.0])];
+		let options = TrainOptions { mini_batch_size : 1, epochs : 1, learning_rate : 1.0, schedule : LearningRateSchedule::Constant, clip : Clip::Value(1e-3), ..TrainOptions::default() };
+		let before = nn.parameters();
+		nn.train_with(&data, &options);
+		assert!(nn.parameters().iter().zip(&before).all(|(after,before)| (after-before).abs() <= 1e-3 + 1e-12));
+
+		let mut nn = test_network(false);
+		nn.train_with(&data, &TrainOptions { clip : Clip::GlobalNorm(0.5), ..options });
+		let step = nn.parameters().iter().zip(&before).map(|(after,before)| (after-before).powi(2)).sum::<f64>().sqrt();
+		assert!(step <= 0.5 + 1e-9);
+	}
+
+	#[test]
+	fn non_finite_policies(){
+		let good = (vec![0.5,-1.0,2.0],vec![1.0,0.0]);
+		let data = vec![good.clone(),(vec![f64::NAN,0.0,0.0],vec![1.0,0.0]),good.clone()];
+		let options = TrainOptions { mini_batch_size : 1, epochs : 1, schedule : LearningRateSchedule::Constant, ..TrainOptions::default() };
+
+		let mut nn = test_network(false);
+		let error = nn.try_train_with(&data, &TrainOptions { non_finite : NonFinite::Abort, ..options }).unwrap_err();
+		assert!(error == NonFiniteError { value : NonFiniteValue::Activation, layer : Some(0), epoch : 0, batch : Some(1) });
+		assert!(error.to_string() == "non-finite activation in layer 0 at epoch 0, batch 1");
+		let error = test_network(false).train_loader(&mut DataLoader::new(data.clone(), 1), &TrainOptions { non_finite : NonFinite::Abort, ..options }).unwrap_err();
+		assert!(error.kind() == io::ErrorKind::Other && error.into_inner().unwrap().downcast_ref::<NonFiniteError>() == Some(&NonFiniteError { value : NonFiniteValue::Activation, layer : Some(0), epoch : 0, batch : Some(1) }));
+
+		// skipping the batch is training without the sample
+		let mut skipped = test_network(false);
+		skipped.train_with(&data, &TrainOptions { non_finite : NonFinite::SkipBatch, ..options });
+		let mut clean = test_network(false);
+		clean.train_with(&[good.clone(),good.clone()], &options);
+		assert!(skipped.parameters() == clean.parameters());
+
+		// the rollback goes back to the weights before the epoch
+		let mut rolled_back = test_network(false);
+		rolled_back.train_with(&data, &TrainOptions { non_finite : NonFinite::Rollback, ..options });
+		let mut clean = test_network(false);
+		clean.train_with(std::slice::from_ref(&good), &options);
+		assert!(rolled_back.parameters() == clean.parameters());
+
+		// huge inputs overflow the cost of a regression
+		let mut nn = NeuralNetWork::empty(1, "quadratic", ChaCha8Rng::seed_from_u64(0));
+		nn.add_layer(4, "relu", Init::Uniform { min : 0.1, max : 0.6 }, 0.0, false);
+		nn.add_layer(1, "identity", Init::Uniform { min : 0.1, max : 0.6 }, 0.0, false);
+		let error = nn.try_train_with(&[(vec![1e200],vec![0.0])], &TrainOptions { non_finite : NonFinite::Abort, ..options }).unwrap_err();
+		assert!(error.to_string() == "non-finite cost at epoch 0, batch 0");
+	}
+
+	#[test]
+	fn class_weights_help_the_minority(){
+		// 1 positive for 19 negatives, the positives are a small corner of the square
+		let data : Vec<(Vec<f64>,Vec<f64>)> = (0..400).map(|i| {
+			let x = vec![(i%20) as f64 / 20.0, (i/20) as f64 / 20.0];
+			let y = (x[0] > 0.75 && x[1] > 0.75) as usize as f64;
+			(x,vec![y])
+		}).collect();
+		let recall = |nn : &mut NeuralNetWork| {
+			let positives : Vec<&(Vec<f64>,Vec<f64>)> = data.iter().filter(|(_,y)| y[0] == 1.0).collect();
+			positives.iter().filter(|(x,_)| nn.predict(x)[0] >= 0.5).count() as f64 / positives.len() as f64
+		};
+
+		let mut nn = NeuralNetWork::empty(2, "cross_entropy", ChaCha8Rng::seed_from_u64(5));
+		nn.add_layer(1, "sigmoid", Init::Xavier, 0.0, false);
+		let options = TrainOptions { mini_batch_size : 20, epochs : 100, learning_rate : 0.1, optimizer : Optimizer::adam(), schedule : LearningRateSchedule::Constant, ..TrainOptions::default() };
+		let mut weighted = nn.clone();
+		nn.train_with(&data, &options);
+		weighted.train_with_class_weights(&data, &ClassWeights::Balanced, &options);
+		assert!(recall(&mut weighted) > recall(&mut nn) && recall(&mut weighted) > 0.9, "{} {}",recall(&mut weighted),recall(&mut nn));
+	}
+
+	#[test]
+	fn flattened_parameters(){
+		let mut nn = test_network(true);
+		// 4*3+4 + 4+4 (norm) + 3*4+3 + 2*3+2
+		assert!(nn.nb_parameters() == 47);
+		let parameters = nn.parameters();
+		assert!(parameters[..12] == nn.layers[0].borrow().weights().values[..]);
+
+		let changed : Vec<f64> = (0..47).map(|i| i as f64).collect();
+		nn.set_parameters(&changed);
+		assert!(nn.parameters() == changed);
+		assert!(nn.layers[0].borrow().norm().unwrap().gain().values == vec![16.0,17.0,18.0,19.0]);
+		assert!(nn.layers[2].borrow().biases().values == vec![45.0,46.0]);
+	}
+
+	#[test]
+	fn scalers_used_in_training_and_predict(){
+		// inputs in the thousands and targets in the millions, hopeless without scaling
+		let data : Vec<(Vec<f64>,Vec<f64>)> = (0..100).map(|i| {
+			let x = vec![(i%10) as f64 * 1000.0, (i/10) as f64 * -500.0];
+			let y = vec![2000.0*x[0] - 1000.0*x[1] + 3.0e6];
+			(x,y)
+		}).collect();
+		let mut nn = NeuralNetWork::empty(2, "quadratic", ChaCha8Rng::seed_from_u64(1));
+		nn.add(1, "identity");
+		nn.fit_scalers(&data, Some(ScalerKind::Standard), Some(ScalerKind::Standard));
+
+		let options = TrainOptions { mini_batch_size : 10, epochs : 100, learning_rate : 0.1, schedule : LearningRateSchedule::Constant, ..TrainOptions::default() };
+		nn.train_with(&data, &options);
+
+		let (input,output) = &data[37];
+		assert!((nn.predict(input)[0] - output[0]).abs() / output[0] < 1e-3);
+	}
+
+	#[test]
+	fn dropout_only_during_training(){
+		let mut nn = NeuralNetWork::empty(2, "quadratic", ChaCha8Rng::seed_from_u64(2));
+		nn.add_layer(50, "relu", Init::Default, 0.5, false);
+		nn.add(1, "identity");
+
+		let first = nn.predict(&[1.0,1.0]);
+		assert!(nn.predict(&[1.0,1.0]) == first);
+
+		nn.training = true;
+		nn.input(&[1.0,1.0]);
+		let dropped = nn.layers[0].borrow().post_activation.values.iter().filter(|&&x| x == 0.0).count();
+		assert!(dropped > 10 && dropped < 40);
+	}
+
+	#[test]
+	#[should_panic(expected = "the output layer can't use dropout")]
+	fn no_dropout_on_the_output(){
+		let mut nn = NeuralNetWork::empty(2, "quadratic", ChaCha8Rng::seed_from_u64(2));
+		nn.add_layer(1, "identity", Init::Default, 0.5, false);
+		nn.train(&[(vec![1.0,1.0],vec![0.0])], 1, 1, 0.1, false);
+	}
+}
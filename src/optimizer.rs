@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+/// Rule used to apply the gradient to the parameters of a layer
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub enum Optimizer {
+	/// plain gradient descent
+	#[default]
+	Sgd,
+	/// gradient descent with a velocity term
+	Momentum { momentum : f64 },
+	/// gradient divided by a running average of its magnitude
+	RmsProp { decay : f64, epsilon : f64 },
+	/// adaptive moment estimation
+	Adam { beta1 : f64, beta2 : f64, epsilon : f64 },
+}
+
+/// Running values kept by the optimizer for one parameter tensor (weights, biases...)
+#[derive(Debug,Clone,Default,PartialEq,Serialize,Deserialize)]
+pub struct ParamState {
+	pub first_moment : Vec<f64>,
+	pub second_moment : Vec<f64>,
+	pub step : u64,
+}
+
+
+impl Optimizer {
+
+	pub fn momentum() -> Optimizer {
+		Optimizer::Momentum { momentum : 0.9 }
+	}
+
+	pub fn rms_prop() -> Optimizer {
+		Optimizer::RmsProp { decay : 0.9, epsilon : 1e-8 }
+	}
+
+	pub fn adam() -> Optimizer {
+		Optimizer::Adam { beta1 : 0.9, beta2 : 0.999, epsilon : 1e-8 }
+	}
+
+	/// Update the parameters from the gradient summed over a mini batch
+	///
+	/// # Argument
+	/// * `params` - the parameters, updated in place
+	/// * `grads` - the gradient summed over the mini batch
+	/// * `state` - the running values of this parameter tensor
+	/// * `learning_rate` - the current learning rate
+	/// * `mean_value` - the gradient is divided by this value (size of the mini batch)
+	pub fn update(&self, params : &mut [f64], grads : &[f64], state : &mut ParamState, learning_rate : f64, mean_value : f64){
+		assert!(params.len() == grads.len(),"gradient and parameters should have the same size");
+
+		if state.first_moment.len() != params.len() {
+			state.first_moment = vec![0.0;params.len()];
+			state.second_moment = vec![0.0;params.len()];
+			state.step = 0;
+		}
+		state.step += 1;
+
+		match *self {
+			Optimizer::Sgd => {
+				for (param,grad) in params.iter_mut().zip(grads) {
+					*param -= grad*learning_rate/mean_value;
+				}
+			},
+			Optimizer::Momentum { momentum } => {
+				for ((param,grad),velocity) in params.iter_mut().zip(grads).zip(state.first_moment.iter_mut()) {
+					*velocity = momentum * *velocity + grad/mean_value;
+					*param -= learning_rate * *velocity;
+				}
+			},
+			Optimizer::RmsProp { decay, epsilon } => {
+				for ((param,grad),square) in params.iter_mut().zip(grads).zip(state.second_moment.iter_mut()) {
+					let grad = grad/mean_value;
+					*square = decay * *square + (1.0-decay) * grad * grad;
+					*param -= learning_rate * grad / (square.sqrt() + epsilon);
+				}
+			},
+			Optimizer::Adam { beta1, beta2, epsilon } => {
+				let correction1 = 1.0 - beta1.powi(state.step as i32);
+				let correction2 = 1.0 - beta2.powi(state.step as i32);
+				for (i,(param,grad)) in params.iter_mut().zip(grads).enumerate() {
+					let grad = grad/mean_value;
+					state.first_moment[i] = beta1 * state.first_moment[i] + (1.0-beta1) * grad;
+					state.second_moment[i] = beta2 * state.second_moment[i] + (1.0-beta2) * grad * grad;
+					let m_hat = state.first_moment[i] / correction1;
+					let v_hat = state.second_moment[i] / correction2;
+					*param -= learning_rate * m_hat / (v_hat.sqrt() + epsilon);
+				}
+			},
+		}
+	}
+}
+
+
+
+/// Limit applied to the mean gradient of a mini batch before the update
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub enum Clip {
+	/// keep the gradient as it is
+	#[default]
+	None,
+	/// clamp every value to [-max,max]
+	Value(f64),
+	/// scale the gradient of each layer down so its norm is at most the value
+	LayerNorm(f64),
+	/// scale the whole gradient down so its norm is at most the value
+	GlobalNorm(f64),
+}
+
+impl Clip {
+
+	/// Clip gradients summed over a mini batch, in place
+	///
+	/// # Argument
+	/// * `layers` - the gradient tensors of each layer
+	/// * `mean_value` - the gradients are divided by this value before the update (size of the mini batch),
+	///   the limit applies to the mean gradient
+	pub fn apply(&self, layers : &mut [Vec<&mut [f64]>], mean_value : f64){
+		let norm = |grads : &[&mut [f64]]| grads.iter().flat_map(|grad| grad.iter()).map(|g| g*g).sum::<f64>().sqrt() / mean_value;
+		let scale = |grads : &mut [&mut [f64]], factor : f64| grads.iter_mut().for_each(|grad| grad.iter_mut().for_each(|g| *g *= factor));
+
+		match *self {
+			Clip::None => {},
+			Clip::Value(max) => {
+				let max = max * mean_value;
+				layers.iter_mut().flatten().for_each(|grad| grad.iter_mut().for_each(|g| *g = g.clamp(-max, max)));
+			},
+			Clip::LayerNorm(max) => {
+				for grads in layers.iter_mut() {
+					let norm = norm(grads);
+					if norm > max {
+						scale(grads, max/norm);
+					}
+				}
+			},
+			Clip::GlobalNorm(max) => {
+				let norm = layers.iter().map(|grads| norm(grads).powi(2)).sum::<f64>().sqrt();
+				if norm > max {
+					layers.iter_mut().for_each(|grads| scale(grads, max/norm));
+				}
+			},
+		}
+	}
+}
+
+
+/// How the learning rate evolves between epochs
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub enum LearningRateSchedule {
+	/// keep the initial learning rate
+	Constant,
+	/// multiply the learning rate by `gamma` every `step_size` epochs, `step_size` is at least 1
+	Step { step_size : usize, gamma : f64 },
+	/// multiply the learning rate by `gamma` after each epoch
+	Exponential { gamma : f64 },
+	/// cosine annealing from the initial learning rate to `min_lr` over `epochs` epochs
+	Cosine { epochs : usize, min_lr : f64 },
+	/// halve the learning rate each time the epoch cost goes up
+	#[default]
+	HalveOnIncrease,
+}
+
+impl LearningRateSchedule {
+
+	/// Returns the learning rate to use for the next epoch
+	///
+	/// # Argument
+	/// * `initial` - the learning rate of the first epoch
+	/// * `current` - the learning rate used during the epoch that just ended
+	/// * `epoch` - index of the epoch that just ended
+	/// * `costs` - cost at the end of each epoch so far
+	pub fn next(&self, initial : f64, current : f64, epoch : usize, costs : &[f64]) -> f64 {
+		match *self {
+			LearningRateSchedule::Constant => initial,
+			LearningRateSchedule::Step { step_size, gamma } => {
+				assert!(step_size > 0,"the step size of the schedule should be at least 1");
+				initial * gamma.powi(((epoch+1)/step_size) as i32)
+			},
+			LearningRateSchedule::Exponential { gamma } => initial * gamma.powi(epoch as i32 + 1),
+			LearningRateSchedule::Cosine { epochs, min_lr } => {
+				let progress = ((epoch+1) as f64 / epochs as f64).min(1.0);
+				min_lr + 0.5 * (initial-min_lr) * (1.0 + (std::f64::consts::PI*progress).cos())
+			},
+			LearningRateSchedule::HalveOnIncrease => {
+				if costs.len()>3 && costs[costs.len()-1] > costs[costs.len()-2] {
+					current/2.0
+				} else {
+					current
+				}
+			},
+		}
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// minimise (x-3)^2 with each optimizer
+	fn minimise(optimizer : Optimizer, learning_rate : f64) -> f64 {
+		let mut params = vec![0.0];
+		let mut state = ParamState::default();
+		for _ in 0..2000 {
+			let grads = vec![2.0*(params[0]-3.0)];
+			optimizer.update(&mut params, &grads, &mut state, learning_rate, 1.0);
+		}
+		params[0]
+	}
+
+	#[test]
+	fn optimizers_converge(){
+		assert!((minimise(Optimizer::Sgd, 0.1)-3.0).abs() < 1e-6);
+		assert!((minimise(Optimizer::momentum(), 0.01)-3.0).abs() < 1e-6);
+		assert!((minimise(Optimizer::rms_prop(), 0.01)-3.0).abs() < 1e-2);
+		assert!((minimise(Optimizer::adam(), 0.05)-3.0).abs() < 1e-3);
+	}
+
+	#[test]
+	fn sgd_uses_mean_gradient(){
+		let mut params = vec![1.0,1.0];
+		Optimizer::Sgd.update(&mut params, &[4.0,-2.0], &mut ParamState::default(), 0.5, 2.0);
+		assert!(params == vec![0.0,1.5]);
+	}
+
+	#[test]
+	fn clipping(){
+		let clipped = |clip : Clip| {
+			let (mut first,mut second) = (vec![6.0,-8.0],vec![0.0,2.0]);
+			clip.apply(&mut [vec![first.as_mut_slice()],vec![second.as_mut_slice()]], 2.0);
+			(first,second)
+		};
+		// mean gradients : (3,-4) with a norm of 5 and (0,1)
+		assert!(clipped(Clip::None) == (vec![6.0,-8.0],vec![0.0,2.0]));
+		assert!(clipped(Clip::Value(2.0)) == (vec![4.0,-4.0],vec![0.0,2.0]));
+		assert!(clipped(Clip::LayerNorm(2.5)) == (vec![3.0,-4.0],vec![0.0,2.0]));
+		let (first,second) = clipped(Clip::GlobalNorm(26f64.sqrt()/2.0));
+		assert!((first[0]-3.0).abs() < 1e-12 && (second[1]-1.0).abs() < 1e-12);
+	}
+
+	#[test]
+	fn schedules(){
+		assert!(LearningRateSchedule::Constant.next(0.1, 0.1, 5, &[]) == 0.1);
+		assert!((LearningRateSchedule::Step { step_size : 2, gamma : 0.5 }.next(1.0, 1.0, 3, &[]) - 0.25).abs() < 1e-12);
+		assert!((LearningRateSchedule::Exponential { gamma : 0.5 }.next(1.0, 1.0, 1, &[]) - 0.25).abs() < 1e-12);
+		assert!((LearningRateSchedule::Cosine { epochs : 10, min_lr : 0.0 }.next(1.0, 1.0, 9, &[])).abs() < 1e-12);
+		assert!(LearningRateSchedule::HalveOnIncrease.next(1.0, 0.5, 4, &[1.0,0.9,0.8,0.7,0.75]) == 0.25);
+		assert!(LearningRateSchedule::HalveOnIncrease.next(1.0, 0.5, 4, &[1.0,0.9,0.8,0.7,0.6]) == 0.5);
+	}
+
+	#[test]
+	#[should_panic(expected = "the step size of the schedule should be at least 1")]
+	fn step_size_zero(){
+		LearningRateSchedule::Step { step_size : 0, gamma : 0.5 }.next(1.0, 1.0, 0, &[]);
+	}
+}
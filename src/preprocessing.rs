@@ -0,0 +1,268 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+
+/// Standardization : every column gets a mean of 0 and a standard deviation of 1
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct StandardScaler {
+	pub mean : Vec<f64>,
+	pub std : Vec<f64>,
+}
+
+/// Every column is mapped from its [min,max] to [0,1]
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct MinMaxScaler {
+	pub min : Vec<f64>,
+	pub max : Vec<f64>,
+}
+
+/// Like `StandardScaler` with the median and the interquartile range, so outliers have less weight
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct RobustScaler {
+	pub median : Vec<f64>,
+	pub iqr : Vec<f64>,
+}
+
+
+/// Values of each column of the rows, panics if the rows don't have the same size
+fn columns<'a>(rows : impl IntoIterator<Item = &'a [f64]>) -> Vec<Vec<f64>> {
+	let mut columns : Vec<Vec<f64>> = vec![];
+	for (i,row) in rows.into_iter().enumerate() {
+		if i == 0 {
+			columns = vec![vec![];row.len()];
+		}
+		assert!(row.len() == columns.len(), "row {i} has {} values instead of {}",row.len(),columns.len());
+		for (column,value) in columns.iter_mut().zip(row) {
+			column.push(*value);
+		}
+	}
+	assert!(!columns.is_empty() && !columns[0].is_empty(), "a scaler can't be fit without data");
+	columns
+}
+
+/// a constant column would be divided by 0, it is only shifted
+fn non_zero(scale : f64) -> f64 {
+	if scale.abs() < f64::EPSILON { 1.0 } else { scale }
+}
+
+/// linear interpolation between the closest ranks of sorted values
+pub(crate) fn quantile(sorted : &[f64], q : f64) -> f64 {
+	let position = q * (sorted.len()-1) as f64;
+	let (low,high) = (position.floor() as usize,position.ceil() as usize);
+	sorted[low] + (sorted[high]-sorted[low]) * (position - low as f64)
+}
+
+
+impl StandardScaler {
+
+	/// # Arguments
+	/// * `rows` - the training vectors, all of the same size
+	pub fn fit<'a>(rows : impl IntoIterator<Item = &'a [f64]>) -> StandardScaler {
+		let columns = columns(rows);
+		let mean : Vec<f64> = columns.iter().map(|column| column.iter().sum::<f64>() / column.len() as f64).collect();
+		let std = columns.iter().zip(&mean).map(|(column,mean)| {
+			non_zero((column.iter().map(|value| (value-mean).powi(2)).sum::<f64>() / column.len() as f64).sqrt())
+		}).collect();
+		StandardScaler { mean, std }
+	}
+
+	pub fn transform(&self, values : &[f64]) -> Vec<f64> {
+		values.iter().zip(self.mean.iter().zip(&self.std)).map(|(value,(mean,std))| (value-mean)/std).collect()
+	}
+
+	pub fn inverse_transform(&self, values : &[f64]) -> Vec<f64> {
+		values.iter().zip(self.mean.iter().zip(&self.std)).map(|(value,(mean,std))| value*std + mean).collect()
+	}
+}
+
+impl MinMaxScaler {
+
+	/// # Arguments
+	/// * `rows` - the training vectors, all of the same size
+	pub fn fit<'a>(rows : impl IntoIterator<Item = &'a [f64]>) -> MinMaxScaler {
+		let columns = columns(rows);
+		MinMaxScaler {
+			min : columns.iter().map(|column| column.iter().copied().fold(f64::INFINITY, f64::min)).collect(),
+			max : columns.iter().map(|column| column.iter().copied().fold(f64::NEG_INFINITY, f64::max)).collect(),
+		}
+	}
+
+	pub fn transform(&self, values : &[f64]) -> Vec<f64> {
+		values.iter().zip(self.min.iter().zip(&self.max)).map(|(value,(min,max))| (value-min)/non_zero(max-min)).collect()
+	}
+
+	pub fn inverse_transform(&self, values : &[f64]) -> Vec<f64> {
+		values.iter().zip(self.min.iter().zip(&self.max)).map(|(value,(min,max))| value*non_zero(max-min) + min).collect()
+	}
+}
+
+impl RobustScaler {
+
+	/// # Arguments
+	/// * `rows` - the training vectors, all of the same size
+	pub fn fit<'a>(rows : impl IntoIterator<Item = &'a [f64]>) -> RobustScaler {
+		let mut columns = columns(rows);
+		for column in &mut columns {
+			column.sort_by(f64::total_cmp);
+		}
+		RobustScaler {
+			median : columns.iter().map(|column| quantile(column, 0.5)).collect(),
+			iqr : columns.iter().map(|column| non_zero(quantile(column, 0.75) - quantile(column, 0.25))).collect(),
+		}
+	}
+
+	pub fn transform(&self, values : &[f64]) -> Vec<f64> {
+		values.iter().zip(self.median.iter().zip(&self.iqr)).map(|(value,(median,iqr))| (value-median)/iqr).collect()
+	}
+
+	pub fn inverse_transform(&self, values : &[f64]) -> Vec<f64> {
+		values.iter().zip(self.median.iter().zip(&self.iqr)).map(|(value,(median,iqr))| value*iqr + median).collect()
+	}
+}
+
+
+/// One of the scalers, this is what a network stores for its inputs and its targets
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Scaler {
+	Standard(StandardScaler),
+	MinMax(MinMaxScaler),
+	Robust(RobustScaler),
+}
+
+/// Which scaler to fit, see `Scaler::fit`
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum ScalerKind {
+	Standard,
+	MinMax,
+	Robust,
+}
+
+impl ScalerKind {
+
+	/// names : standard, min_max (or minmax), robust
+	pub fn from_name(name : &str) -> Option<ScalerKind> {
+		match name.trim().to_lowercase().as_str() {
+			"standard" => Some(ScalerKind::Standard),
+			"min_max" | "minmax" => Some(ScalerKind::MinMax),
+			"robust" => Some(ScalerKind::Robust),
+			_ => None,
+		}
+	}
+}
+
+impl Scaler {
+
+	pub fn fit<'a>(kind : ScalerKind, rows : impl IntoIterator<Item = &'a [f64]>) -> Scaler {
+		match kind {
+			ScalerKind::Standard => Scaler::Standard(StandardScaler::fit(rows)),
+			ScalerKind::MinMax => Scaler::MinMax(MinMaxScaler::fit(rows)),
+			ScalerKind::Robust => Scaler::Robust(RobustScaler::fit(rows)),
+		}
+	}
+
+	/// number of values the scaler was fit on
+	pub fn len(&self) -> usize {
+		match self {
+			Scaler::Standard(scaler) => scaler.mean.len(),
+			Scaler::MinMax(scaler) => scaler.min.len(),
+			Scaler::Robust(scaler) => scaler.median.len(),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn transform(&self, values : &[f64]) -> Vec<f64> {
+		assert!(values.len() == self.len(), "the scaler was fit on vectors of {} values, got {}",self.len(),values.len());
+		match self {
+			Scaler::Standard(scaler) => scaler.transform(values),
+			Scaler::MinMax(scaler) => scaler.transform(values),
+			Scaler::Robust(scaler) => scaler.transform(values),
+		}
+	}
+
+	pub fn inverse_transform(&self, values : &[f64]) -> Vec<f64> {
+		assert!(values.len() == self.len(), "the scaler was fit on vectors of {} values, got {}",self.len(),values.len());
+		match self {
+			Scaler::Standard(scaler) => scaler.inverse_transform(values),
+			Scaler::MinMax(scaler) => scaler.inverse_transform(values),
+			Scaler::Robust(scaler) => scaler.inverse_transform(values),
+		}
+	}
+}
+
+/// `values` transformed by the scaler, borrowed when there is none
+pub(crate) fn scale<'a>(scaler : &Option<Scaler>, values : &'a [f64]) -> Cow<'a,[f64]> {
+	match scaler {
+		Some(scaler) => Cow::Owned(scaler.transform(values)),
+		None => Cow::Borrowed(values),
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rows() -> Vec<Vec<f64>> {
+		vec![vec![1.0,10.0,5.0],vec![2.0,20.0,5.0],vec![3.0,30.0,5.0],vec![10.0,40.0,5.0]]
+	}
+
+	fn close(a : &[f64], b : &[f64]) -> bool {
+		a.len() == b.len() && a.iter().zip(b).all(|(x,y)| (x-y).abs() < 1e-12)
+	}
+
+	#[test]
+	fn standard_scaler(){
+		let rows = rows();
+		let scaler = StandardScaler::fit(rows.iter().map(Vec::as_slice));
+		assert!(close(&scaler.mean, &[4.0,25.0,5.0]));
+
+		let scaled : Vec<Vec<f64>> = rows.iter().map(|row| scaler.transform(row)).collect();
+		let mean : f64 = scaled.iter().map(|row| row[1]).sum::<f64>() / 4.0;
+		let variance : f64 = scaled.iter().map(|row| row[1].powi(2)).sum::<f64>() / 4.0;
+		assert!(mean.abs() < 1e-12 && (variance-1.0).abs() < 1e-12);
+
+		// the constant column is only centered
+		assert!(scaled[0][2] == 0.0);
+	}
+
+	#[test]
+	fn min_max_scaler(){
+		let scaler = MinMaxScaler::fit(rows().iter().map(Vec::as_slice));
+		assert!(close(&scaler.transform(&[1.0,40.0,5.0]), &[0.0,1.0,0.0]));
+		assert!(close(&scaler.transform(&[5.5,25.0,6.0]), &[0.5,0.5,1.0]));
+	}
+
+	#[test]
+	fn robust_scaler(){
+		let scaler = RobustScaler::fit(rows().iter().map(Vec::as_slice));
+		assert!(close(&scaler.median, &[2.5,25.0,5.0]));
+		assert!(close(&scaler.iqr, &[(4.75-1.75),15.0,1.0]));
+	}
+
+	#[test]
+	fn inverse_round_trip(){
+		let rows = rows();
+		for kind in [ScalerKind::Standard,ScalerKind::MinMax,ScalerKind::Robust] {
+			let scaler = Scaler::fit(kind, rows.iter().map(Vec::as_slice));
+			for row in &rows {
+				assert!(close(&scaler.inverse_transform(&scaler.transform(row)), row));
+			}
+		}
+	}
+
+	#[test]
+	#[should_panic]
+	fn wrong_size_panics(){
+		Scaler::fit(ScalerKind::Standard, rows().iter().map(Vec::as_slice)).transform(&[1.0]);
+	}
+}
@@ -0,0 +1,587 @@
+use std::fmt;
+
+use rand::Rng;
+
+use crate::block::*;
+use crate::init::*;
+use crate::matrix::*;
+use crate::tensor::*;
+
+
+fn sigmoid(x : f64) -> f64 {
+	1.0 / (1.0 + (-x).exp())
+}
+
+/// `w·z + b`
+fn affine(w : &Param, b : &Param, z : &[f64]) -> Vec<f64> {
+	let mut result = Matrix::new(w.value.rows, 1);
+	w.value.dot_vec(&mut result, z);
+	result.values.iter_mut().zip(&b.value.values).for_each(|(x,b)| *x += b);
+	result.values
+}
+
+/// Accumulate the gradients of `affine` for the gradient `da` of its result, returns the gradient of `z`
+fn affine_backward(w : &mut Param, b : &mut Param, z : &[f64], da : &[f64]) -> Vec<f64> {
+	w.grad.matrix_weight_compute(z, da);
+	b.grad.values.iter_mut().zip(da).for_each(|(g,d)| *g += d);
+	let mut grad_z = Matrix::new(w.value.cols, 1);
+	w.value.trans_dot(&mut grad_z, &Matrix::from_vec(da.len(), 1, da.to_vec()));
+	grad_z.values
+}
+
+fn concat(a : &[f64], b : &[f64]) -> Vec<f64> {
+	a.iter().chain(b).copied().collect()
+}
+
+
+/// One step of a recurrent layer. The state starts with the hidden vector, which is the output of the step.
+pub trait Cell : Clone + fmt::Debug + Send + 'static {
+	/// What `backward` needs from a step
+	type Cache : Clone + fmt::Debug + Send;
+
+	fn name(&self) -> &'static str;
+
+	fn input_size(&self) -> usize;
+
+	fn hidden_size(&self) -> usize;
+
+	/// Size of the state, the hidden vector followed by the cell memory if there is one
+	fn state_size(&self) -> usize {
+		self.hidden_size()
+	}
+
+	/// Returns the next state
+	fn forward(&self, input : &[f64], state : &[f64]) -> (Vec<f64>,Self::Cache);
+
+	/// Accumulate the gradients of the parameters, returns the gradients of the input and of the previous state
+	fn backward(&mut self, cache : &Self::Cache, grad_state : &[f64]) -> (Vec<f64>,Vec<f64>);
+
+	fn params(&self) -> Vec<&Param>;
+
+	fn params_mut(&mut self) -> Vec<&mut Param>;
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Cells                                   */
+/* -------------------------------------------------------------------------- */
+
+
+/// `h' = tanh(W·[x,h] + b)`
+#[derive(Debug,Clone)]
+pub struct RnnCell {
+	input_size : usize,
+	weights : Param,
+	biases : Param,
+}
+
+#[derive(Debug,Clone)]
+pub struct RnnCache {
+	z : Vec<f64>,
+	hidden : Vec<f64>,
+}
+
+impl RnnCell {
+
+	pub fn new<R : Rng + ?Sized>(input_size : usize, hidden_size : usize, init : Init, rng : &mut R) -> RnnCell {
+		RnnCell {
+			input_size,
+			weights : Param::new(init.weights(hidden_size, input_size+hidden_size, rng)),
+			biases : Param::new(init.biases(hidden_size, rng)),
+		}
+	}
+}
+
+impl Cell for RnnCell {
+	type Cache = RnnCache;
+
+	fn name(&self) -> &'static str {
+		"rnn"
+	}
+
+	fn input_size(&self) -> usize {
+		self.input_size
+	}
+
+	fn hidden_size(&self) -> usize {
+		self.biases.value.rows
+	}
+
+	fn forward(&self, input : &[f64], state : &[f64]) -> (Vec<f64>,RnnCache) {
+		let z = concat(input, state);
+		let hidden : Vec<f64> = affine(&self.weights, &self.biases, &z).into_iter().map(f64::tanh).collect();
+		(hidden.clone(),RnnCache { z, hidden })
+	}
+
+	fn backward(&mut self, cache : &RnnCache, grad_state : &[f64]) -> (Vec<f64>,Vec<f64>) {
+		let da : Vec<f64> = grad_state.iter().zip(&cache.hidden).map(|(g,h)| g*(1.0-h*h)).collect();
+		let mut grad_z = affine_backward(&mut self.weights, &mut self.biases, &cache.z, &da);
+		let grad_state = grad_z.split_off(self.input_size);
+		(grad_z,grad_state)
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		vec![&self.weights,&self.biases]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![&mut self.weights,&mut self.biases]
+	}
+}
+
+
+/// Long short-term memory : input, forget and output gates around a cell memory, the state is `[h,c]`
+#[derive(Debug,Clone)]
+pub struct LstmCell {
+	input_size : usize,
+	/// the rows of the input, forget, candidate and output gates
+	weights : Param,
+	biases : Param,
+}
+
+#[derive(Debug,Clone)]
+pub struct LstmCache {
+	z : Vec<f64>,
+	memory : Vec<f64>,
+	/// input, forget, candidate and output gates
+	gates : [Vec<f64>;4],
+	tanh_memory : Vec<f64>,
+}
+
+impl LstmCell {
+
+	/// The forget gate starts with a bias of 1 so the memory is kept at first
+	pub fn new<R : Rng + ?Sized>(input_size : usize, hidden_size : usize, init : Init, rng : &mut R) -> LstmCell {
+		let mut biases = init.biases(4*hidden_size, rng);
+		biases.values[hidden_size..2*hidden_size].fill(1.0);
+		LstmCell {
+			input_size,
+			weights : Param::new(init.weights(4*hidden_size, input_size+hidden_size, rng)),
+			biases : Param::new(biases),
+		}
+	}
+}
+
+impl Cell for LstmCell {
+	type Cache = LstmCache;
+
+	fn name(&self) -> &'static str {
+		"lstm"
+	}
+
+	fn input_size(&self) -> usize {
+		self.input_size
+	}
+
+	fn hidden_size(&self) -> usize {
+		self.biases.value.rows/4
+	}
+
+	fn state_size(&self) -> usize {
+		2*self.hidden_size()
+	}
+
+	fn forward(&self, input : &[f64], state : &[f64]) -> (Vec<f64>,LstmCache) {
+		let size = self.hidden_size();
+		let (hidden,memory) = state.split_at(size);
+		let z = concat(input, hidden);
+		let a = affine(&self.weights, &self.biases, &z);
+		let gate = |k : usize, function : fn(f64) -> f64| a[k*size..(k+1)*size].iter().map(|x| function(*x)).collect::<Vec<f64>>();
+		let gates = [gate(0, sigmoid),gate(1, sigmoid),gate(2, f64::tanh),gate(3, sigmoid)];
+		let [i,f,g,o] = &gates;
+
+		let next_memory : Vec<f64> = (0..size).map(|j| f[j]*memory[j] + i[j]*g[j]).collect();
+		let tanh_memory : Vec<f64> = next_memory.iter().map(|c| c.tanh()).collect();
+		let next_hidden : Vec<f64> = (0..size).map(|j| o[j]*tanh_memory[j]).collect();
+
+		(concat(&next_hidden, &next_memory),LstmCache { z, memory : memory.to_vec(), gates, tanh_memory })
+	}
+
+	fn backward(&mut self, cache : &LstmCache, grad_state : &[f64]) -> (Vec<f64>,Vec<f64>) {
+		let size = self.hidden_size();
+		let (grad_hidden,grad_memory) = grad_state.split_at(size);
+		let [i,f,g,o] = &cache.gates;
+
+		let mut da = vec![0.0;4*size];
+		let mut grad_prev_memory = vec![0.0;size];
+		for j in 0..size {
+			let tc = cache.tanh_memory[j];
+			let dc = grad_memory[j] + grad_hidden[j]*o[j]*(1.0-tc*tc);
+			da[j] = dc*g[j] * i[j]*(1.0-i[j]);
+			da[size+j] = dc*cache.memory[j] * f[j]*(1.0-f[j]);
+			da[2*size+j] = dc*i[j] * (1.0-g[j]*g[j]);
+			da[3*size+j] = grad_hidden[j]*tc * o[j]*(1.0-o[j]);
+			grad_prev_memory[j] = dc*f[j];
+		}
+
+		let mut grad_z = affine_backward(&mut self.weights, &mut self.biases, &cache.z, &da);
+		let grad_prev_hidden = grad_z.split_off(self.input_size);
+		(grad_z,concat(&grad_prev_hidden, &grad_prev_memory))
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		vec![&self.weights,&self.biases]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![&mut self.weights,&mut self.biases]
+	}
+}
+
+
+/// Gated recurrent unit : `h' = (1-u)·n + u·h` with the update gate `u` and `n = tanh(Wn·[x,r·h] + bn)`
+#[derive(Debug,Clone)]
+pub struct GruCell {
+	input_size : usize,
+	/// the rows of the update and reset gates
+	gate_weights : Param,
+	gate_biases : Param,
+	candidate_weights : Param,
+	candidate_biases : Param,
+}
+
+#[derive(Debug,Clone)]
+pub struct GruCache {
+	z : Vec<f64>,
+	hidden : Vec<f64>,
+	update : Vec<f64>,
+	reset : Vec<f64>,
+	/// `[x,r·h]`
+	reset_z : Vec<f64>,
+	candidate : Vec<f64>,
+}
+
+impl GruCell {
+
+	pub fn new<R : Rng + ?Sized>(input_size : usize, hidden_size : usize, init : Init, rng : &mut R) -> GruCell {
+		GruCell {
+			input_size,
+			gate_weights : Param::new(init.weights(2*hidden_size, input_size+hidden_size, rng)),
+			gate_biases : Param::new(init.biases(2*hidden_size, rng)),
+			candidate_weights : Param::new(init.weights(hidden_size, input_size+hidden_size, rng)),
+			candidate_biases : Param::new(init.biases(hidden_size, rng)),
+		}
+	}
+}
+
+impl Cell for GruCell {
+	type Cache = GruCache;
+
+	fn name(&self) -> &'static str {
+		"gru"
+	}
+
+	fn input_size(&self) -> usize {
+		self.input_size
+	}
+
+	fn hidden_size(&self) -> usize {
+		self.candidate_biases.value.rows
+	}
+
+	fn forward(&self, input : &[f64], state : &[f64]) -> (Vec<f64>,GruCache) {
+		let size = self.hidden_size();
+		let z = concat(input, state);
+		let gates : Vec<f64> = affine(&self.gate_weights, &self.gate_biases, &z).into_iter().map(sigmoid).collect();
+		let (update,reset) = (gates[..size].to_vec(),gates[size..].to_vec());
+
+		let reset_hidden : Vec<f64> = reset.iter().zip(state).map(|(r,h)| r*h).collect();
+		let reset_z = concat(input, &reset_hidden);
+		let candidate : Vec<f64> = affine(&self.candidate_weights, &self.candidate_biases, &reset_z).into_iter().map(f64::tanh).collect();
+		let next : Vec<f64> = (0..size).map(|j| (1.0-update[j])*candidate[j] + update[j]*state[j]).collect();
+
+		(next,GruCache { z, hidden : state.to_vec(), update, reset, reset_z, candidate })
+	}
+
+	fn backward(&mut self, cache : &GruCache, grad_state : &[f64]) -> (Vec<f64>,Vec<f64>) {
+		let size = self.hidden_size();
+		let GruCache { z, hidden, update, reset, reset_z, candidate } = cache;
+
+		let mut grad_hidden : Vec<f64> = (0..size).map(|j| grad_state[j]*update[j]).collect();
+		let da_candidate : Vec<f64> = (0..size).map(|j| grad_state[j]*(1.0-update[j]) * (1.0-candidate[j]*candidate[j])).collect();
+		let mut grad_input = affine_backward(&mut self.candidate_weights, &mut self.candidate_biases, reset_z, &da_candidate);
+		let grad_reset_hidden = grad_input.split_off(self.input_size);
+
+		let mut da_gates = vec![0.0;2*size];
+		for j in 0..size {
+			grad_hidden[j] += grad_reset_hidden[j]*reset[j];
+			da_gates[j] = grad_state[j]*(hidden[j]-candidate[j]) * update[j]*(1.0-update[j]);
+			da_gates[size+j] = grad_reset_hidden[j]*hidden[j] * reset[j]*(1.0-reset[j]);
+		}
+		let grad_z = affine_backward(&mut self.gate_weights, &mut self.gate_biases, z, &da_gates);
+
+		grad_input.iter_mut().zip(&grad_z).for_each(|(g,d)| *g += d);
+		grad_hidden.iter_mut().zip(&grad_z[self.input_size..]).for_each(|(g,d)| *g += d);
+		(grad_input,grad_hidden)
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		vec![&self.gate_weights,&self.gate_biases,&self.candidate_weights,&self.candidate_biases]
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		vec![&mut self.gate_weights,&mut self.gate_biases,&mut self.candidate_weights,&mut self.candidate_biases]
+	}
+}
+
+
+/* -------------------------------------------------------------------------- */
+/*                               Recurrent layer                              */
+/* -------------------------------------------------------------------------- */
+
+
+/// A cell applied to each step of a `[time,features]` sequence, trained with backpropagation through time
+#[derive(Debug,Clone)]
+pub struct Recurrent<C : Cell> {
+	cell : C,
+	return_sequences : bool,
+	stateful : bool,
+	truncation : Option<usize>,
+	clip : Option<f64>,
+	/// the state at the end of the last sequence, the next one starts from it when stateful
+	state : Vec<f64>,
+	caches : Vec<C::Cache>,
+}
+
+pub type SimpleRnn = Recurrent<RnnCell>;
+pub type Lstm = Recurrent<LstmCell>;
+pub type Gru = Recurrent<GruCell>;
+
+impl SimpleRnn {
+
+	/// # Arguments
+	/// * `input_size` - features of each step
+	/// * `hidden_size` - size of the hidden state, the output of each step
+	/// * `init` - how the weights and biases are drawn
+	/// * `rng` - the random source, seed it for reproducible results
+	pub fn new<R : Rng + ?Sized>(input_size : usize, hidden_size : usize, init : Init, rng : &mut R) -> SimpleRnn {
+		Recurrent::with_cell(RnnCell::new(input_size, hidden_size, init, rng))
+	}
+}
+
+impl Lstm {
+
+	/// See `SimpleRnn::new`
+	pub fn new<R : Rng + ?Sized>(input_size : usize, hidden_size : usize, init : Init, rng : &mut R) -> Lstm {
+		Recurrent::with_cell(LstmCell::new(input_size, hidden_size, init, rng))
+	}
+}
+
+impl Gru {
+
+	/// See `SimpleRnn::new`
+	pub fn new<R : Rng + ?Sized>(input_size : usize, hidden_size : usize, init : Init, rng : &mut R) -> Gru {
+		Recurrent::with_cell(GruCell::new(input_size, hidden_size, init, rng))
+	}
+}
+
+impl<C : Cell> Recurrent<C> {
+
+	/// Outputs the last hidden state, each sequence starts from a zero state
+	pub fn with_cell(cell : C) -> Recurrent<C> {
+		let state = vec![0.0;cell.state_size()];
+		Recurrent { cell, return_sequences : false, stateful : false, truncation : None, clip : None, state, caches : vec![] }
+	}
+
+	/// Output the hidden state of every step, `[time,hidden]`, instead of the last one
+	pub fn return_sequences(mut self, return_sequences : bool) -> Recurrent<C> {
+		self.return_sequences = return_sequences;
+		self
+	}
+
+	/// Start each sequence from the state reached at the end of the previous one, for inference on a stream
+	/// cut in several calls. The gradient doesn't flow back to the previous calls.
+	pub fn stateful(mut self, stateful : bool) -> Recurrent<C> {
+		self.stateful = stateful;
+		self
+	}
+
+	/// Truncated backpropagation through time : the sequence is cut in windows of `steps` and
+	/// the gradient doesn't flow from one window to the previous one
+	pub fn truncate(mut self, steps : usize) -> Recurrent<C> {
+		assert!(steps > 0,"the truncation should be at least one step");
+		self.truncation = Some(steps);
+		self
+	}
+
+	/// Rescale the gradient of the state to a norm of at most `max_norm` at each step of the backward pass
+	pub fn clip_gradient(mut self, max_norm : f64) -> Recurrent<C> {
+		assert!(max_norm > 0.0,"the gradient norm should be positive");
+		self.clip = Some(max_norm);
+		self
+	}
+
+	/// Forget the state kept by a stateful layer
+	pub fn reset_state(&mut self){
+		self.state.fill(0.0);
+	}
+
+	/// The state at the end of the last sequence
+	pub fn state(&self) -> &[f64] {
+		&self.state
+	}
+
+	pub fn cell(&self) -> &C {
+		&self.cell
+	}
+}
+
+impl<C : Cell> Block for Recurrent<C> {
+
+	fn name(&self) -> String {
+		format!("{} {}->{}",self.cell.name(),self.cell.input_size(),self.cell.hidden_size())
+	}
+
+	fn output_shape(&self, input_shape : &[usize]) -> Vec<usize> {
+		assert!(input_shape.len()==2 && input_shape[1]==self.cell.input_size(),"expected a [time,{}] sequence, got {input_shape:?}",self.cell.input_size());
+		assert!(input_shape[0] > 0,"the sequence is empty");
+		if self.return_sequences { vec![input_shape[0],self.cell.hidden_size()] } else { vec![self.cell.hidden_size()] }
+	}
+
+	fn forward(&mut self, input : &Tensor) -> Tensor {
+		let shape = self.output_shape(&input.shape);
+		let (steps,hidden_size) = (input.shape[0],self.cell.hidden_size());
+		let mut state = if self.stateful { self.state.clone() } else { vec![0.0;self.cell.state_size()] };
+
+		self.caches.clear();
+		let mut output = Vec::with_capacity(steps*hidden_size);
+		for t in 0..steps {
+			let (next,cache) = self.cell.forward(input.row(t), &state);
+			state = next;
+			self.caches.push(cache);
+			if self.return_sequences || t == steps-1 {
+				output.extend_from_slice(&state[..hidden_size]);
+			}
+		}
+
+		self.state = state;
+		Tensor::from_vec(&shape, output)
+	}
+
+	fn backward(&mut self, grad_output : &Tensor) -> Tensor {
+		let (steps,hidden_size) = (self.caches.len(),self.cell.hidden_size());
+		let mut grad_input = Tensor::new(&[steps,self.cell.input_size()]);
+		let mut grad_state = vec![0.0;self.cell.state_size()];
+
+		for t in (0..steps).rev() {
+			let grad_hidden = if self.return_sequences { Some(grad_output.row(t)) } else if t == steps-1 { Some(&grad_output.values[..]) } else { None };
+			if let Some(grad_hidden) = grad_hidden {
+				grad_state[..hidden_size].iter_mut().zip(grad_hidden).for_each(|(g,d)| *g += d);
+			}
+			if let Some(max_norm) = self.clip {
+				let norm = grad_state.iter().map(|g| g*g).sum::<f64>().sqrt();
+				if norm > max_norm {
+					grad_state.iter_mut().for_each(|g| *g *= max_norm/norm);
+				}
+			}
+
+			let (grad_x,grad_previous) = self.cell.backward(&self.caches[t], &grad_state);
+			grad_input.row_mut(t).copy_from_slice(&grad_x);
+			grad_state = grad_previous;
+			if self.truncation.is_some_and(|steps| t % steps == 0) {
+				grad_state.fill(0.0);
+			}
+		}
+
+		grad_input
+	}
+
+	fn params(&self) -> Vec<&Param> {
+		self.cell.params()
+	}
+
+	fn params_mut(&mut self) -> Vec<&mut Param> {
+		self.cell.params_mut()
+	}
+
+	fn clone_box(&self) -> Box<dyn Block> {
+		Box::new(self.clone())
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	fn sequence(steps : usize, features : usize, seed : u64) -> Tensor {
+		let mut rng = ChaCha8Rng::seed_from_u64(seed);
+		Tensor::from_vec(&[steps,features], (0..steps*features).map(|_| rng.gen_range(-1.0..1.0)).collect())
+	}
+
+	#[test]
+	fn recurrent_gradient_checks(){
+		let mut rng = ChaCha8Rng::seed_from_u64(0);
+		let init = Init::Normal { std : 0.5 };
+		for return_sequences in [false,true] {
+			check_gradients(&mut SimpleRnn::new(3, 4, init, &mut rng).return_sequences(return_sequences), &sequence(5, 3, 1));
+			check_gradients(&mut Lstm::new(3, 4, init, &mut rng).return_sequences(return_sequences), &sequence(5, 3, 2));
+			check_gradients(&mut Gru::new(3, 4, init, &mut rng).return_sequences(return_sequences), &sequence(5, 3, 3));
+		}
+	}
+
+	#[test]
+	fn truncation_and_clipping(){
+		let mut rng = ChaCha8Rng::seed_from_u64(4);
+		let mut lstm = Lstm::new(2, 3, Init::Normal { std : 0.5 }, &mut rng).truncate(2);
+		let input = sequence(6, 2, 5);
+		lstm.forward(&input);
+		let grad = lstm.backward(&Tensor::from_vec(&[3], vec![1.0;3]));
+		// only the last window [4,6) receives the gradient of the last state
+		assert!(grad.values[..8].iter().all(|g| *g == 0.0) && grad.values[8..].iter().any(|g| *g != 0.0));
+
+		let mut clipped = SimpleRnn::new(2, 3, Init::Normal { std : 0.5 }, &mut rng);
+		let mut unclipped = clipped.clone();
+		clipped = clipped.clip_gradient(1e-3);
+		clipped.forward(&input);
+		unclipped.forward(&input);
+		let large = Tensor::from_vec(&[3], vec![100.0;3]);
+		let norm = |grad : Tensor| grad.values.iter().map(|g| g*g).sum::<f64>().sqrt();
+		assert!(norm(clipped.backward(&large)) < norm(unclipped.backward(&large)));
+	}
+
+	#[test]
+	fn stateful_inference_continues_the_sequence(){
+		let mut rng = ChaCha8Rng::seed_from_u64(6);
+		let mut whole = Gru::new(2, 3, Init::Xavier, &mut rng);
+		let mut stream = whole.clone().stateful(true);
+		let input = sequence(6, 2, 7);
+
+		let expected = whole.forward(&input);
+		stream.forward(&Tensor::from_vec(&[4,2], input.values[..8].to_vec()));
+		assert!(stream.forward(&Tensor::from_vec(&[2,2], input.values[8..].to_vec())) == expected);
+
+		stream.reset_state();
+		assert!(stream.state().iter().all(|x| *x == 0.0));
+	}
+
+	#[test]
+	fn sine_wave_forecasting(){
+		// predict the next value of a sine wave from the 8 previous ones
+		let wave = |i : usize| (i as f64 * 0.3).sin();
+		let data : Vec<TensorSample> = (0..120).map(|start| {
+			(Tensor::sequence(&(start..start+8).map(|i| vec![wave(i)]).collect::<Vec<_>>()),vec![wave(start+8)])
+		}).collect();
+
+		let mut rng = ChaCha8Rng::seed_from_u64(8);
+		let mut model = Sequential::new(&[8,1], "quadratic", ChaCha8Rng::seed_from_u64(9));
+		model.add(Lstm::new(1, 8, Init::Xavier, &mut rng));
+		model.dense(1, "identity");
+
+		let options = adam_options(10, 60);
+		model.train_with(&data, &options);
+
+		let error = (200..220).map(|start| {
+			let input = Tensor::from_vec(&[8,1], (start..start+8).map(wave).collect());
+			(model.predict(&input)[0] - wave(start+8)).abs()
+		}).fold(0.0, f64::max);
+		assert!(error < 0.1, "largest error {error}");
+	}
+}
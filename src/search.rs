@@ -0,0 +1,734 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::config::*;
+use crate::dataset::*;
+use crate::metrics::*;
+use crate::model_selection::*;
+use crate::nn::*;
+
+/// Scores where lower is better, every other score is maximised
+const LOWER_IS_BETTER : [&str;6] = ["log_loss","rmse","mae","mape","max_error","cost"];
+/// Scores computed by `Task::Classification`
+const CLASSIFICATION_SCORES : [&str;7] = ["accuracy","precision","recall","f1","log_loss","roc_auc","pr_auc"];
+
+
+/// A value given to a hyperparameter
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+	Int(i64),
+	Float(f64),
+	Text(String),
+}
+
+impl ParamValue {
+
+	fn as_f64(&self, key : &str) -> Result<f64,ConfigError> {
+		match self {
+			ParamValue::Int(value) => Ok(*value as f64),
+			ParamValue::Float(value) => Ok(*value),
+			ParamValue::Text(_) => Err(ConfigError::invalid(key, "should be a number")),
+		}
+	}
+
+	fn as_usize(&self, key : &str) -> Result<usize,ConfigError> {
+		match self {
+			ParamValue::Int(value) if *value >= 0 => Ok(*value as usize),
+			_ => Err(ConfigError::invalid(key, "should be a positive integer")),
+		}
+	}
+
+	fn as_text(&self, key : &str) -> Result<String,ConfigError> {
+		match self {
+			ParamValue::Text(value) => Ok(value.clone()),
+			_ => Err(ConfigError::invalid(key, "should be a string")),
+		}
+	}
+}
+
+impl fmt::Display for ParamValue {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ParamValue::Int(value) => write!(f, "{value}"),
+			ParamValue::Float(value) => write!(f, "{value:.4e}"),
+			ParamValue::Text(value) => write!(f, "{value}"),
+		}
+	}
+}
+
+impl From<i64> for ParamValue {
+	fn from(value : i64) -> Self {
+		ParamValue::Int(value)
+	}
+}
+
+impl From<f64> for ParamValue {
+	fn from(value : f64) -> Self {
+		ParamValue::Float(value)
+	}
+}
+
+impl From<&str> for ParamValue {
+	fn from(value : &str) -> Self {
+		ParamValue::Text(value.to_string())
+	}
+}
+
+
+/// The values a hyperparameter can take
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(untagged)]
+pub enum Domain {
+	/// one of the values
+	Choice(Vec<ParamValue>),
+	/// a float whose logarithm is uniform in [ln min,ln max], for learning rates
+	LogUniform { log_uniform : [f64;2] },
+	/// a float in [min,max)
+	Uniform { uniform : [f64;2] },
+	/// an integer in [min,max]
+	IntRange { int_range : [i64;2] },
+}
+
+impl Domain {
+
+	fn sample(&self, rng : &mut ChaCha8Rng) -> ParamValue {
+		match self {
+			Domain::Choice(values) => values[rng.gen_range(0..values.len())].clone(),
+			Domain::LogUniform { log_uniform : [min,max] } => ParamValue::Float(rng.gen_range(min.ln()..=max.ln()).exp()),
+			Domain::Uniform { uniform : [min,max] } => ParamValue::Float(rng.gen_range(*min..*max)),
+			Domain::IntRange { int_range : [min,max] } => ParamValue::Int(rng.gen_range(*min..=*max)),
+		}
+	}
+
+	fn check(&self, key : &str) -> Result<(),ConfigError> {
+		let valid = match self {
+			Domain::Choice(values) => !values.is_empty(),
+			Domain::LogUniform { log_uniform : [min,max] } => *min > 0.0 && min <= max,
+			Domain::Uniform { uniform : [min,max] } => min < max,
+			Domain::IntRange { int_range : [min,max] } => min <= max,
+		};
+		if valid { Ok(()) } else { Err(ConfigError::invalid(key, "empty domain")) }
+	}
+}
+
+
+/// Hyperparameters to search and their domains, the keys are the ones of `apply_param`
+///
+/// ```
+/// use rust_simple_nn::search::*;
+///
+/// let space = SearchSpace::new()
+///     .log_uniform("learning_rate", 1e-4, 1e-1)
+///     .choice("batch_size", [16, 32, 64])
+///     .choice("hidden.activation", ["relu", "tanh"]);
+/// assert_eq!(space.params.len(), 3);
+/// ```
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+pub struct SearchSpace {
+	pub params : BTreeMap<String,Domain>,
+}
+
+impl SearchSpace {
+
+	pub fn new() -> SearchSpace {
+		SearchSpace::default()
+	}
+
+	pub fn choice<V : Into<ParamValue>>(mut self, key : &str, values : impl IntoIterator<Item = V>) -> SearchSpace {
+		self.params.insert(key.to_string(), Domain::Choice(values.into_iter().map(Into::into).collect()));
+		self
+	}
+
+	pub fn log_uniform(mut self, key : &str, min : f64, max : f64) -> SearchSpace {
+		self.params.insert(key.to_string(), Domain::LogUniform { log_uniform : [min,max] });
+		self
+	}
+
+	pub fn uniform(mut self, key : &str, min : f64, max : f64) -> SearchSpace {
+		self.params.insert(key.to_string(), Domain::Uniform { uniform : [min,max] });
+		self
+	}
+
+	pub fn int_range(mut self, key : &str, min : i64, max : i64) -> SearchSpace {
+		self.params.insert(key.to_string(), Domain::IntRange { int_range : [min,max] });
+		self
+	}
+
+	fn sample(&self, rng : &mut ChaCha8Rng) -> Vec<(String,ParamValue)> {
+		self.params.iter().map(|(key,domain)| (key.clone(),domain.sample(rng))).collect()
+	}
+
+	/// Every combination of the values, only for `Domain::Choice`
+	fn grid(&self) -> Result<Vec<Vec<(String,ParamValue)>>,ConfigError> {
+		let mut combinations = vec![vec![]];
+		for (key,domain) in &self.params {
+			let Domain::Choice(values) = domain else {
+				return Err(ConfigError::invalid(key, "a grid search needs a list of values"));
+			};
+			combinations = combinations.into_iter().flat_map(|combination : Vec<(String,ParamValue)>| {
+				values.iter().map(move |value| {
+					let mut combination = combination.clone();
+					combination.push((key.clone(),value.clone()));
+					combination
+				})
+			}).collect();
+		}
+		Ok(combinations)
+	}
+}
+
+
+/// Set one hyperparameter of a config
+///
+/// # Arguments
+/// * `key` - `learning_rate`, `batch_size`, `epochs`, `loss`, `input_scaler`, `target_scaler`,
+///   `optimizer.<field>`, `schedule.<field>`, `layers[i].<field>` (size, activation, init, dropout, norm),
+///   `hidden.<field>` for every layer but the output one, or `hidden_layers` to repeat the first hidden layer
+/// * `value` - the new value
+pub fn apply_param(config : &mut NetworkConfig, key : &str, value : &ParamValue) -> Result<(),ConfigError> {
+	match key {
+		"learning_rate" => config.learning_rate = value.as_f64(key)?,
+		"batch_size" => config.batch_size = value.as_usize(key)?,
+		"epochs" => config.epochs = value.as_usize(key)?,
+		"loss" => config.loss = value.as_text(key)?,
+		"input_scaler" => config.input_scaler = Some(value.as_text(key)?),
+		"target_scaler" => config.target_scaler = Some(value.as_text(key)?),
+		"optimizer.type" => config.optimizer.kind = value.as_text(key)?,
+		"optimizer.momentum" => config.optimizer.momentum = Some(value.as_f64(key)?),
+		"optimizer.decay" => config.optimizer.decay = Some(value.as_f64(key)?),
+		"optimizer.beta1" => config.optimizer.beta1 = Some(value.as_f64(key)?),
+		"optimizer.beta2" => config.optimizer.beta2 = Some(value.as_f64(key)?),
+		"optimizer.epsilon" => config.optimizer.epsilon = Some(value.as_f64(key)?),
+		"schedule.type" => config.schedule.kind = value.as_text(key)?,
+		"schedule.step_size" => config.schedule.step_size = Some(value.as_usize(key)?),
+		"schedule.gamma" => config.schedule.gamma = Some(value.as_f64(key)?),
+		"schedule.min_lr" => config.schedule.min_lr = Some(value.as_f64(key)?),
+		"hidden_layers" => {
+			let count = value.as_usize(key)?;
+			if config.layers.len() < 2 {
+				return Err(ConfigError::invalid(key, "the config has no hidden layer to repeat"));
+			}
+			let output = config.layers.pop().unwrap();
+			let hidden = config.layers[0].clone();
+			config.layers.resize(count, hidden);
+			config.layers.push(output);
+		},
+		_ => {
+			let (layers,field) = key.split_once('.').ok_or_else(|| ConfigError::invalid(key, "unknown hyperparameter"))?;
+			let indexes : Vec<usize> = if layers == "hidden" {
+				(0..config.layers.len().saturating_sub(1)).collect()
+			} else {
+				let index = layers.strip_prefix("layers[").and_then(|rest| rest.strip_suffix(']')).and_then(|index| index.parse().ok())
+					.ok_or_else(|| ConfigError::invalid(key, "unknown hyperparameter"))?;
+				if index >= config.layers.len() {
+					return Err(ConfigError::invalid(key, format!("the config has {} layers",config.layers.len())));
+				}
+				vec![index]
+			};
+			for index in indexes {
+				let layer = &mut config.layers[index];
+				match field {
+					"size" => layer.size = value.as_usize(key)?,
+					"activation" => layer.activation = value.as_text(key)?,
+					"init" => layer.init = value.as_text(key)?,
+					"dropout" => layer.dropout = value.as_f64(key)?,
+					"norm" => layer.norm = value.as_text(key)?,
+					_ => return Err(ConfigError::invalid(key, "unknown layer field")),
+				}
+			}
+		},
+	}
+	Ok(())
+}
+
+/// `config` with the hyperparameters applied, `hidden_layers` first so the other layer keys apply to the new layers
+pub fn with_params(config : &NetworkConfig, params : &[(String,ParamValue)]) -> Result<NetworkConfig,ConfigError> {
+	let mut config = config.clone();
+	let mut ordered : Vec<&(String,ParamValue)> = params.iter().collect();
+	ordered.sort_by_key(|(key,_)| key != "hidden_layers");
+	for (key,value) in ordered {
+		apply_param(&mut config, key, value)?;
+	}
+	Ok(config)
+}
+
+
+/// How the configurations are chosen
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Strategy {
+	/// every combination, every domain must be a list
+	Grid,
+	/// `trials` configurations drawn at random
+	Random { trials : usize },
+	/// `trials` random configurations trained `min_epochs`, the best `1/eta` are trained `eta` times longer
+	/// until one is left or they reach the epochs of the config
+	SuccessiveHalving { trials : usize, min_epochs : usize, eta : usize },
+	/// successive halving brackets going from many short trials to a few trials with all the epochs of the config
+	Hyperband { eta : usize },
+}
+
+/// Parameters of `search`
+#[derive(Debug,Clone,PartialEq)]
+pub struct SearchOptions {
+	pub strategy : Strategy,
+	/// score of `Report::scores` to optimise, the task (classification or regression) follows from it
+	pub metric : String,
+	/// part of the data kept to score the trials
+	pub validation_fraction : f64,
+	/// number of trials trained at the same time
+	pub threads : usize,
+	pub seed : u64,
+}
+
+impl Default for SearchOptions {
+	fn default() -> Self {
+		SearchOptions {
+			strategy : Strategy::Random { trials : 10 },
+			metric : "rmse".to_string(),
+			validation_fraction : 0.2,
+			threads : 1,
+			seed : 0,
+		}
+	}
+}
+
+fn lower_is_better(metric : &str) -> bool {
+	LOWER_IS_BETTER.contains(&metric)
+}
+
+/// The task computing `metric`
+pub fn task_of(metric : &str) -> Task {
+	if CLASSIFICATION_SCORES.contains(&metric) { Task::classification() } else { Task::Regression }
+}
+
+
+/// One trained configuration
+#[derive(Debug,Clone,PartialEq)]
+pub struct Trial {
+	/// the configuration, trials of successive halving rounds share the id of their configuration
+	pub id : usize,
+	pub params : Vec<(String,ParamValue)>,
+	pub epochs : usize,
+	/// `None` if the configuration is invalid or the score isn't a number
+	pub score : Option<f64>,
+	pub error : Option<String>,
+}
+
+/// Result of `search`
+#[derive(Debug,Clone,PartialEq)]
+pub struct SearchResult {
+	pub metric : String,
+	pub trials : Vec<Trial>,
+	/// index in `trials` of the best trial
+	pub best : Option<usize>,
+	/// the base config with the best hyperparameters
+	pub best_config : Option<NetworkConfig>,
+}
+
+impl fmt::Display for SearchResult {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		let keys : Vec<&String> = self.trials.first().map_or(vec![], |trial| trial.params.iter().map(|(key,_)| key).collect());
+		let mut order : Vec<&Trial> = self.trials.iter().collect();
+		let sign = if lower_is_better(&self.metric) { 1.0 } else { -1.0 };
+		order.sort_by(|a,b| match (a.score,b.score) {
+			(Some(a),Some(b)) => (sign*a).total_cmp(&(sign*b)),
+			(a,b) => b.is_some().cmp(&a.is_some()),
+		});
+
+		write!(f, "{:>6}{:>8}{:>12}",  "trial","epochs",self.metric)?;
+		let widths : Vec<usize> = keys.iter().map(|key| key.len().max(12)).collect();
+		for (key,width) in keys.iter().zip(&widths) {
+			write!(f, "  {key:>width$}")?;
+		}
+		writeln!(f)?;
+		for trial in order {
+			let score = trial.score.map_or("failed".to_string(), |score| format!("{score:.5}"));
+			write!(f, "{:>6}{:>8}{score:>12}",trial.id,trial.epochs)?;
+			for ((_,value),width) in trial.params.iter().zip(&widths) {
+				write!(f, "  {:>width$}",value.to_string())?;
+			}
+			if let Some(error) = &trial.error {
+				write!(f, "  {error}")?;
+			}
+			writeln!(f)?;
+		}
+
+		if let Some(config) = &self.best_config {
+			writeln!(f, "\nbest config:\n{}",config.to_toml_string())?;
+		}
+		Ok(())
+	}
+}
+
+
+/// A configuration to train for some epochs
+struct Job {
+	id : usize,
+	params : Vec<(String,ParamValue)>,
+	epochs : usize,
+}
+
+/// Shared by the trials of a search
+struct Context<'a> {
+	config : &'a NetworkConfig,
+	train : &'a [Sample],
+	validation : &'a [Sample],
+	options : &'a SearchOptions,
+	task : Task,
+}
+
+impl Context<'_> {
+
+	fn run(&self, job : &Job) -> Trial {
+		let trial = |score,error| Trial { id : job.id, params : job.params.clone(), epochs : job.epochs, score, error };
+		match self.score(job) {
+			Ok(score) if score.is_finite() => trial(Some(score),None),
+			Ok(_) => trial(None,Some("the score isn't a number".to_string())),
+			Err(error) => trial(None,Some(error.to_string())),
+		}
+	}
+
+	fn score(&self, job : &Job) -> Result<f64,ConfigError> {
+		let mut config = with_params(self.config, &job.params)?;
+		config.epochs = job.epochs;
+		// a configuration gets the same initialisation in every round
+		let seed = self.options.seed.wrapping_add(job.id as u64 + 1);
+		config.seed = Some(seed);
+
+		let (mut nn,options) = config.build()?;
+		let (input_scaler,target_scaler) = config.scalers()?;
+		nn.fit_scalers(self.train, input_scaler, target_scaler);
+
+		let mut loader = DataLoader::new(self.train.to_vec(), options.mini_batch_size).shuffle(seed);
+		nn.train_loader(&mut loader, &options).map_err(|error| ConfigError::Training(error.to_string()))?;
+
+		nn.evaluate(self.validation, self.task).score(&self.options.metric)
+			.ok_or_else(|| ConfigError::invalid("metric", format!("`{}` isn't computed for this data",self.options.metric)))
+	}
+
+	/// Run the jobs on `threads` threads, the trials are in the order of the jobs
+	fn run_all(&self, jobs : &[Job]) -> Vec<Trial> {
+		let next = AtomicUsize::new(0);
+		let trials : Mutex<Vec<Option<Trial>>> = Mutex::new(vec![None;jobs.len()]);
+
+		std::thread::scope(|scope| {
+			for _ in 0..self.options.threads.clamp(1, jobs.len().max(1)) {
+				scope.spawn(|| loop {
+					let index = next.fetch_add(1, Ordering::Relaxed);
+					let Some(job) = jobs.get(index) else {
+						break;
+					};
+					let trial = self.run(job);
+					trials.lock().unwrap()[index] = Some(trial);
+				});
+			}
+		});
+
+		trials.into_inner().unwrap().into_iter().map(|trial| trial.expect("every job is run")).collect()
+	}
+
+	/// Successive halving of `candidates`, starting at `min_epochs`
+	fn successive_halving(&self, mut candidates : Vec<Job>, min_epochs : usize, eta : usize, trials : &mut Vec<Trial>) {
+		let max_epochs = self.config.epochs;
+		let mut epochs = min_epochs.clamp(1, max_epochs);
+		loop {
+			for job in &mut candidates {
+				job.epochs = epochs;
+			}
+			let round = self.run_all(&candidates);
+
+			if candidates.len() <= 1 || epochs >= max_epochs {
+				trials.extend(round);
+				return;
+			}
+
+			// the best 1/eta go to the next round
+			let mut ranked : Vec<(usize,f64)> = round.iter().enumerate().filter_map(|(i,trial)| trial.score.map(|score| (i,score))).collect();
+			let sign = if lower_is_better(&self.options.metric) { 1.0 } else { -1.0 };
+			ranked.sort_by(|a,b| (sign*a.1).total_cmp(&(sign*b.1)));
+			let keep : Vec<usize> = ranked.iter().take((candidates.len()/eta).max(1)).map(|(i,_)| *i).collect();
+			trials.extend(round);
+
+			candidates = candidates.into_iter().enumerate().filter(|(i,_)| keep.contains(i)).map(|(_,job)| job).collect();
+			if candidates.is_empty() {
+				return;
+			}
+			epochs = (epochs*eta).min(max_epochs);
+		}
+	}
+}
+
+
+/// Train configurations derived from `config` and score them on a validation split of `data`
+///
+/// # Arguments
+/// * `config` - the base configuration, its `epochs` is the largest budget of a trial
+/// * `space` - the hyperparameters to change
+/// * `data` - all the samples, a part is kept for validation (stratified for classification metrics)
+/// * `options` - the strategy, the metric and the number of threads
+pub fn search(config : &NetworkConfig, space : &SearchSpace, data : &[Sample], options : &SearchOptions) -> Result<SearchResult,ConfigError> {
+	for (key,domain) in &space.params {
+		domain.check(key)?;
+	}
+	config.build()?;
+	let task = task_of(&options.metric);
+	if options.threads == 0 {
+		return Err(ConfigError::invalid("threads", "should be at least 1"));
+	}
+	if !(options.validation_fraction > 0.0 && options.validation_fraction < 1.0) {
+		return Err(ConfigError::invalid("validation", "should be in (0,1)"));
+	}
+
+	let stratify = matches!(task,Task::Classification { .. });
+	let (train,validation) = train_test_split(data, options.validation_fraction, stratify, options.seed);
+	let context = Context { config, train : &train, validation : &validation, options, task };
+	let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+	let random_jobs = |count : usize, first_id : usize, rng : &mut ChaCha8Rng| -> Vec<Job> {
+		(0..count).map(|i| Job { id : first_id+i, params : space.sample(rng), epochs : config.epochs }).collect()
+	};
+
+	let mut trials = vec![];
+	match options.strategy {
+		Strategy::Grid => {
+			let jobs : Vec<Job> = space.grid()?.into_iter().enumerate().map(|(id,params)| Job { id, params, epochs : config.epochs }).collect();
+			trials = context.run_all(&jobs);
+		},
+		Strategy::Random { trials : count } => trials = context.run_all(&random_jobs(count, 0, &mut rng)),
+		Strategy::SuccessiveHalving { trials : count, min_epochs, eta } => {
+			if eta < 2 {
+				return Err(ConfigError::invalid("eta", "should be at least 2"));
+			}
+			context.successive_halving(random_jobs(count, 0, &mut rng), min_epochs, eta, &mut trials);
+		},
+		Strategy::Hyperband { eta } => {
+			if eta < 2 {
+				return Err(ConfigError::invalid("eta", "should be at least 2"));
+			}
+			// s_max = floor(log_eta(max_epochs)), bracket s starts eta^s configurations at max_epochs/eta^s epochs
+			let max_epochs = config.epochs;
+			let s_max = (max_epochs as f64).log(eta as f64).floor() as u32;
+			let mut next_id = 0;
+			for s in (0..=s_max).rev() {
+				let count = (((s_max+1) as f64 / (s+1) as f64) * (eta as f64).powi(s as i32)).ceil() as usize;
+				let min_epochs = (max_epochs / eta.pow(s)).max(1);
+				context.successive_halving(random_jobs(count, next_id, &mut rng), min_epochs, eta, &mut trials);
+				next_id += count;
+			}
+		},
+	}
+
+	let sign = if lower_is_better(&options.metric) { 1.0 } else { -1.0 };
+	let best = trials.iter().enumerate()
+		.filter_map(|(i,trial)| trial.score.map(|score| (i,score)))
+		.min_by(|a,b| (sign*a.1).total_cmp(&(sign*b.1)))
+		.map(|(i,_)| i);
+	let best_config = best.map(|i| {
+		let mut best_config = with_params(config, &trials[i].params).expect("the best trial has a valid config");
+		best_config.epochs = trials[i].epochs;
+		best_config
+	});
+
+	Ok(SearchResult { metric : options.metric.clone(), trials, best, best_config })
+}
+
+
+/// A search read from a TOML file
+///
+/// ```toml
+/// metric = "accuracy"
+/// strategy = "hyperband"  # grid, random, successive_halving or hyperband
+/// trials = 20             # random and successive_halving
+/// eta = 3                 # successive_halving and hyperband
+/// min_epochs = 1          # successive_halving
+/// threads = 4
+///
+/// [params]
+/// learning_rate = { log_uniform = [1e-4, 1e-1] }
+/// batch_size = [16, 32, 64]
+/// "hidden.size" = { int_range = [4, 32] }
+/// "hidden.activation" = ["relu", "tanh"]
+/// ```
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchConfig {
+	pub metric : String,
+	#[serde(default = "default_strategy")]
+	pub strategy : String,
+	#[serde(default = "default_trials")]
+	pub trials : usize,
+	#[serde(default = "default_eta")]
+	pub eta : usize,
+	#[serde(default = "default_min_epochs")]
+	pub min_epochs : usize,
+	#[serde(default = "default_threads")]
+	pub threads : usize,
+	/// part of the data kept to score the trials
+	#[serde(default = "default_validation")]
+	pub validation : f64,
+	#[serde(default)]
+	pub seed : u64,
+	pub params : BTreeMap<String,Domain>,
+}
+
+fn default_strategy() -> String { "random".to_string() }
+fn default_trials() -> usize { 10 }
+fn default_eta() -> usize { 3 }
+fn default_min_epochs() -> usize { 1 }
+fn default_threads() -> usize { 1 }
+fn default_validation() -> f64 { 0.2 }
+
+impl SearchConfig {
+
+	pub fn from_toml_str(text : &str) -> Result<SearchConfig,ConfigError> {
+		toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+	}
+
+	pub fn from_file(path : impl AsRef<Path>) -> Result<SearchConfig,ConfigError> {
+		let path = path.as_ref();
+		let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(format!("{}: {e}",path.display())))?;
+		SearchConfig::from_toml_str(&text)
+	}
+
+	pub fn space(&self) -> SearchSpace {
+		SearchSpace { params : self.params.clone() }
+	}
+
+	pub fn options(&self) -> Result<SearchOptions,ConfigError> {
+		let strategy = match self.strategy.as_str() {
+			"grid" => Strategy::Grid,
+			"random" => Strategy::Random { trials : self.trials },
+			"successive_halving" | "halving" => Strategy::SuccessiveHalving { trials : self.trials, min_epochs : self.min_epochs, eta : self.eta },
+			"hyperband" => Strategy::Hyperband { eta : self.eta },
+			other => return Err(ConfigError::invalid("strategy", format!("unknown strategy `{other}` (expected grid, random, successive_halving or hyperband)"))),
+		};
+		if !(self.validation > 0.0 && self.validation < 1.0) {
+			return Err(ConfigError::invalid("validation", "should be in (0,1)"));
+		}
+		Ok(SearchOptions { strategy, metric : self.metric.clone(), validation_fraction : self.validation, threads : self.threads, seed : self.seed })
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn base_config() -> NetworkConfig {
+		NetworkConfig::from_toml_str("input = 2\nepochs = 9\nbatch_size = 10\ninput_scaler = \"standard\"\n[optimizer]\ntype = \"adam\"\n[schedule]\ntype = \"constant\"\n[[layers]]\nsize = 4\n[[layers]]\nsize = 1\nactivation = \"identity\"\n").unwrap()
+	}
+
+	fn linear_data() -> Vec<Sample> {
+		(0..100).map(|i| {
+			let x = vec![(i%10) as f64, (i/10) as f64];
+			(x.clone(),vec![(x[0] - 2.0*x[1]) / 10.0])
+		}).collect()
+	}
+
+	#[test]
+	fn apply_params(){
+		let params = vec![
+			("hidden.size".to_string(),ParamValue::Int(7)),
+			("hidden_layers".to_string(),ParamValue::Int(3)),
+			("learning_rate".to_string(),ParamValue::Int(1)),
+			("layers[3].activation".to_string(),ParamValue::from("sigmoid")),
+		];
+		let config = with_params(&base_config(), &params).unwrap();
+		assert!(config.layers.iter().map(|layer| layer.size).collect::<Vec<_>>() == vec![7,7,7,1]);
+		assert!(config.layers[3].activation == "sigmoid" && config.learning_rate == 1.0);
+
+		let wrong = [("layers[9].size".to_string(),ParamValue::Int(1))];
+		assert!(matches!(with_params(&base_config(), &wrong),Err(ConfigError::Invalid { .. })));
+		let wrong = [("batch_size".to_string(),ParamValue::from("big"))];
+		assert!(with_params(&base_config(), &wrong).is_err());
+	}
+
+	#[test]
+	fn grid_search(){
+		let space = SearchSpace::new().choice("learning_rate", [0.0001,0.05]).choice("hidden.activation", ["tanh","wrong"]);
+		let result = search(&base_config(), &space, &linear_data(), &SearchOptions { strategy : Strategy::Grid, ..SearchOptions::default() }).unwrap();
+
+		assert!(result.trials.len() == 4);
+		assert!(result.trials.iter().filter(|trial| trial.score.is_none()).count() == 2);
+		let best = &result.trials[result.best.unwrap()];
+		assert!(best.params[1].1 == ParamValue::Float(0.05));
+		assert!(result.best_config.unwrap().learning_rate == 0.05);
+
+		let space = SearchSpace::new().log_uniform("learning_rate", 0.001, 0.1);
+		assert!(search(&base_config(), &space, &linear_data(), &SearchOptions { strategy : Strategy::Grid, ..SearchOptions::default() }).is_err());
+	}
+
+	#[test]
+	fn random_search_threads_are_reproducible(){
+		let space = SearchSpace::new().log_uniform("learning_rate", 0.001, 0.1).int_range("hidden.size", 2, 6);
+		let options = SearchOptions { strategy : Strategy::Random { trials : 6 }, seed : 3, ..SearchOptions::default() };
+
+		let single = search(&base_config(), &space, &linear_data(), &options).unwrap();
+		let parallel = search(&base_config(), &space, &linear_data(), &SearchOptions { threads : 3, ..options }).unwrap();
+		assert!(single == parallel);
+		assert!(single.to_string().contains("best config"));
+	}
+
+	#[test]
+	fn successive_halving_and_hyperband(){
+		let space = SearchSpace::new().log_uniform("learning_rate", 0.0001, 0.1);
+		let options = SearchOptions { strategy : Strategy::SuccessiveHalving { trials : 9, min_epochs : 1, eta : 3 }, threads : 2, ..SearchOptions::default() };
+		let result = search(&base_config(), &space, &linear_data(), &options).unwrap();
+
+		// 9 trials of 1 epoch, 3 of 3 epochs and 1 of 9 epochs
+		let epochs : Vec<usize> = result.trials.iter().map(|trial| trial.epochs).collect();
+		assert!(epochs == [vec![1;9],vec![3;3],vec![9]].concat());
+
+		let options = SearchOptions { strategy : Strategy::Hyperband { eta : 3 }, threads : 2, ..SearchOptions::default() };
+		let result = search(&base_config(), &space, &linear_data(), &options).unwrap();
+		assert!(result.trials.iter().any(|trial| trial.epochs == 9) && result.best.is_some());
+	}
+
+	#[test]
+	fn training_errors_are_reported(){
+		let mut config = base_config();
+		config.non_finite = Some("abort".to_string());
+		let data : Vec<Sample> = linear_data().into_iter().map(|(x,_)| (x,vec![f64::NAN])).collect();
+		let space = SearchSpace::new().choice("learning_rate", [0.01]);
+		let result = search(&config, &space, &data, &SearchOptions { strategy : Strategy::Grid, ..SearchOptions::default() }).unwrap();
+
+		let error = result.trials[0].error.as_deref().unwrap();
+		assert!(error.starts_with("training failed: non-finite") && result.best.is_none(), "{error}");
+	}
+
+	#[test]
+	fn invalid_options(){
+		let space = SearchSpace::new().log_uniform("learning_rate", 0.0001, 0.1);
+		for validation_fraction in [0.0,1.0,f64::NAN] {
+			let options = SearchOptions { validation_fraction, ..SearchOptions::default() };
+			let error = search(&base_config(), &space, &linear_data(), &options).unwrap_err();
+			assert!(matches!(error,ConfigError::Invalid { key, .. } if key == "validation"));
+		}
+	}
+
+	#[test]
+	fn search_config_file(){
+		let file = SearchConfig::from_toml_str("metric = \"r2\"\nstrategy = \"halving\"\ntrials = 4\n[params]\nlearning_rate = { log_uniform = [1e-4, 1e-1] }\nbatch_size = [16, 32]\n\"hidden.size\" = { int_range = [2, 8] }\n\"hidden.activation\" = [\"relu\", \"tanh\"]\n").unwrap();
+		let space = file.space();
+		assert!(space.params["learning_rate"] == Domain::LogUniform { log_uniform : [1e-4,1e-1] });
+		assert!(space.params["batch_size"] == Domain::Choice(vec![ParamValue::Int(16),ParamValue::Int(32)]));
+		assert!(space.params["hidden.size"] == Domain::IntRange { int_range : [2,8] });
+		assert!(file.options().unwrap().strategy == Strategy::SuccessiveHalving { trials : 4, min_epochs : 1, eta : 3 });
+
+		let wrong = SearchConfig::from_toml_str("metric = \"r2\"\nstrategy = \"genetic\"\n[params]\n").unwrap();
+		assert!(matches!(wrong.options(),Err(ConfigError::Invalid { key, .. }) if key == "strategy"));
+	}
+}
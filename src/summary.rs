@@ -0,0 +1,195 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::nn::*;
+use crate::preprocessing::Scaler;
+
+/// Bytes of a stored value
+const VALUE_SIZE : usize = std::mem::size_of::<f64>();
+/// Operations of a layer normalisation per neuron : mean, variance, normalisation, gain and shift
+const NORM_FLOPS : usize = 5;
+
+
+/// One row of a `Summary` : a dense layer or a scaler
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct LayerSummary {
+	pub name : String,
+	pub output_shape : Vec<usize>,
+	/// activation of a layer, kind of a scaler
+	pub activation : String,
+	pub trainable : usize,
+	/// values fit on the data and not by the gradient, like the ones of the scalers
+	pub non_trainable : usize,
+	/// size of the parameters in bytes
+	pub memory : usize,
+	/// floating point operations of the forward pass of one sample, a multiply-add counts as 2
+	pub flops : usize,
+}
+
+/// Layers of a network with their parameter counts, memory and cost, printed as a table
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Summary {
+	pub input_size : usize,
+	pub cost : String,
+	pub layers : Vec<LayerSummary>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub heads : Vec<Head>,
+}
+
+impl Summary {
+
+	pub fn trainable(&self) -> usize {
+		self.layers.iter().map(|layer| layer.trainable).sum()
+	}
+
+	pub fn non_trainable(&self) -> usize {
+		self.layers.iter().map(|layer| layer.non_trainable).sum()
+	}
+
+	pub fn memory(&self) -> usize {
+		self.layers.iter().map(|layer| layer.memory).sum()
+	}
+
+	pub fn flops(&self) -> usize {
+		self.layers.iter().map(|layer| layer.flops).sum()
+	}
+}
+
+fn scaler_summary(name : &str, scaler : &Scaler) -> LayerSummary {
+	let kind = match scaler {
+		Scaler::Standard(_) => "standard",
+		Scaler::MinMax(_) => "min_max",
+		Scaler::Robust(_) => "robust",
+	};
+	// a shift and a scale per value
+	let non_trainable = 2*scaler.len();
+	LayerSummary {
+		name : name.to_string(),
+		output_shape : vec![scaler.len()],
+		activation : kind.to_string(),
+		trainable : 0,
+		non_trainable,
+		memory : non_trainable*VALUE_SIZE,
+		flops : 2*scaler.len(),
+	}
+}
+
+/// 1536 -> 1.5 KiB
+fn format_bytes(bytes : usize) -> String {
+	let mut value = bytes as f64;
+	for unit in ["B","KiB","MiB"] {
+		if value < 1024.0 {
+			return if unit == "B" { format!("{bytes} B") } else { format!("{value:.1} {unit}") };
+		}
+		value /= 1024.0;
+	}
+	format!("{value:.1} GiB")
+}
+
+impl fmt::Display for Summary {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "input: {}, cost: {}",self.input_size,self.cost)?;
+		writeln!(f, "{:<16}{:>10}  {:<12}{:>12}{:>15}{:>12}{:>12}","layer","output","activation","trainable","non-trainable","memory","flops")?;
+		for layer in &self.layers {
+			writeln!(f, "{:<16}{:>10}  {:<12}{:>12}{:>15}{:>12}{:>12}",
+				layer.name,format!("{:?}",layer.output_shape),layer.activation,layer.trainable,layer.non_trainable,format_bytes(layer.memory),layer.flops)?;
+		}
+		write!(f, "{:<16}{:>10}  {:<12}{:>12}{:>15}{:>12}{:>12}","total","","",self.trainable(),self.non_trainable(),format_bytes(self.memory()),self.flops())?;
+		for head in &self.heads {
+			write!(f, "\nhead {}: {} {} {} x{}",head.name,head.size,head.activation,head.cost,head.weight)?;
+		}
+		Ok(())
+	}
+}
+
+
+impl NeuralNetWork {
+
+	/// Each layer with its output shape, activation, parameter counts, memory and forward FLOPs.
+	/// The scalers are rows with non-trainable values.
+	pub fn summary(&self) -> Summary {
+		let mut layers = vec![];
+		if let Some(scaler) = self.input_scaler() {
+			layers.push(scaler_summary("input_scaler", scaler));
+		}
+
+		let mut inputs = self.input_size();
+		for (i,layer) in self.layers.iter().enumerate() {
+			let layer = layer.borrow();
+			let outputs = layer.len();
+			let norm = layer.norm().is_some();
+			let trainable = layer.weights().values.len() + layer.biases().values.len() + if norm { 2*outputs } else { 0 };
+			// weights product, bias and activation
+			let flops = 2*inputs*outputs + 2*outputs + if norm { NORM_FLOPS*outputs } else { 0 };
+			layers.push(LayerSummary {
+				name : format!("dense_{i}"),
+				output_shape : vec![outputs],
+				activation : layer.activation_name().to_string(),
+				trainable,
+				non_trainable : 0,
+				memory : trainable*VALUE_SIZE,
+				flops,
+			});
+			inputs = outputs;
+		}
+
+		if let Some(scaler) = self.target_scaler() {
+			layers.push(scaler_summary("target_scaler", scaler));
+		}
+
+		Summary {
+			input_size : self.input_size(),
+			cost : self.cost_name().to_string(),
+			layers,
+			heads : self.heads().into_iter().cloned().collect(),
+		}
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::init::Init;
+	use crate::preprocessing::ScalerKind;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	#[test]
+	fn counts_memory_and_flops(){
+		let mut nn = NeuralNetWork::empty(3, "cross_entropy", ChaCha8Rng::seed_from_u64(0));
+		nn.add_layer(4, "relu", Init::He, 0.0, true);
+		nn.add_layer(2, "sigmoid", Init::Xavier, 0.0, false);
+		nn.fit_scalers(&[(vec![1.0,2.0,3.0],vec![0.0,1.0]),(vec![0.0,1.0,5.0],vec![1.0,0.0])], Some(ScalerKind::Standard), None);
+		let summary = nn.summary();
+
+		let names : Vec<&str> = summary.layers.iter().map(|layer| layer.name.as_str()).collect();
+		assert!(names == ["input_scaler","dense_0","dense_1"]);
+		let dense = &summary.layers[1];
+		assert!(dense.output_shape == [4] && dense.activation == "relu");
+		assert!(dense.trainable == 3*4+4+2*4 && dense.memory == 24*8);
+		assert!(dense.flops == 2*3*4 + 2*4 + 5*4);
+		assert!(summary.trainable() == nn.nb_parameters() && summary.non_trainable() == 6);
+		assert!(summary.flops() == 6 + 52 + 2*4*2 + 2*2);
+
+		let table = summary.to_string();
+		assert!(table.lines().count() == 2 + 3 + 1);
+		assert!(table.lines().last().unwrap().starts_with("total") && table.contains("320 B"));
+
+		let json : Summary = serde_json::from_str(&serde_json::to_string(&summary).unwrap()).unwrap();
+		assert!(json == summary);
+	}
+
+	#[test]
+	fn readable_sizes(){
+		assert!(format_bytes(100) == "100 B");
+		assert!(format_bytes(1536) == "1.5 KiB");
+		assert!(format_bytes(3*1024*1024) == "3.0 MiB");
+	}
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+use crate::matrix::*;
+
+
+/// An n-dimensional array stored in row major order : the last dimension is contiguous.
+/// Images are `[channels,height,width]` and sequences `[time,features]`.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Tensor {
+	pub shape : Vec<usize>,
+	pub values : Vec<f64>,
+}
+
+/// Number of values of a tensor of this shape
+pub fn shape_len(shape : &[usize]) -> usize {
+	shape.iter().product()
+}
+
+impl Tensor {
+
+	/// A zeroed tensor
+	pub fn new(shape : &[usize]) -> Tensor {
+		Tensor { shape : shape.to_vec(), values : vec![0.0;shape_len(shape)] }
+	}
+
+	pub fn from_vec(shape : &[usize], values : Vec<f64>) -> Tensor {
+		assert!(values.len()==shape_len(shape),"a tensor of shape {shape:?} has {} values, got {}",shape_len(shape),values.len());
+		Tensor { shape : shape.to_vec(), values }
+	}
+
+	/// A `[time,features]` sequence from the features of each step
+	pub fn sequence(steps : &[Vec<f64>]) -> Tensor {
+		assert!(!steps.is_empty(),"the sequence is empty");
+		let features = steps[0].len();
+		assert!(steps.iter().all(|step| step.len()==features),"the steps of a sequence should have the same number of features");
+		Tensor { shape : vec![steps.len(),features], values : steps.concat() }
+	}
+
+	/// A `[rows,cols]` tensor
+	pub fn from_matrix(matrix : Matrix<f64>) -> Tensor {
+		Tensor { shape : vec![matrix.rows,matrix.cols], values : matrix.values }
+	}
+
+	/// The matrix of a 2 dimensional tensor
+	pub fn into_matrix(self) -> Matrix<f64> {
+		assert!(self.rank()==2,"only a 2 dimensional tensor is a matrix, the shape is {:?}",self.shape);
+		Matrix::from_vec(self.shape[0], self.shape[1], self.values)
+	}
+
+	pub fn rank(&self) -> usize {
+		self.shape.len()
+	}
+
+	pub fn len(&self) -> usize {
+		self.values.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.values.is_empty()
+	}
+
+	/// Position in `values` of a multi-dimensional index
+	pub fn offset(&self, index : &[usize]) -> usize {
+		assert!(index.len()==self.rank(),"the index {index:?} doesn't match the shape {:?}",self.shape);
+		index.iter().zip(&self.shape).fold(0, |offset,(i,dim)| {
+			assert!(i < dim,"the index {index:?} is out of the shape {:?}",self.shape);
+			offset*dim + i
+		})
+	}
+
+	pub fn at(&self, index : &[usize]) -> f64 {
+		self.values[self.offset(index)]
+	}
+
+	pub fn at_mut(&mut self, index : &[usize]) -> &mut f64 {
+		let offset = self.offset(index);
+		&mut self.values[offset]
+	}
+
+	/// Same values with another shape of the same size
+	pub fn reshape(mut self, shape : &[usize]) -> Tensor {
+		assert!(shape_len(shape)==self.len(),"a tensor of {} values can't have the shape {shape:?}",self.len());
+		self.shape = shape.to_vec();
+		self
+	}
+
+	/// The values of `row` along the first dimension, ex : one time step of a sequence
+	pub fn row(&self, row : usize) -> &[f64] {
+		let size = self.len() / self.shape[0];
+		&self.values[row*size..(row+1)*size]
+	}
+
+	pub fn row_mut(&mut self, row : usize) -> &mut [f64] {
+		let size = self.len() / self.shape[0];
+		&mut self.values[row*size..(row+1)*size]
+	}
+
+	pub fn add_mut(&mut self, other : &Tensor){
+		assert!(self.shape==other.shape,"the shapes {:?} and {:?} differ",self.shape,other.shape);
+		self.values.iter_mut().zip(&other.values).for_each(|(a,b)| *a += b);
+	}
+}
+
+
+
+/* -------------------------------------------------------------------------- */
+/*                                    Tests                                   */
+/* -------------------------------------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn row_major_indexing(){
+		let mut tensor = Tensor::from_vec(&[2,3,4], (0..24).map(|x| x as f64).collect());
+		assert!(tensor.offset(&[1,2,3]) == 23 && tensor.at(&[1,0,1]) == 13.0);
+		*tensor.at_mut(&[0,1,0]) = -1.0;
+		assert!(tensor.values[4] == -1.0);
+		assert!(tensor.row(1)[0] == 12.0 && tensor.row(1).len() == 12);
+
+		let matrix = tensor.reshape(&[6,4]).into_matrix();
+		assert!(matrix.rows == 6 && matrix.cols == 4);
+	}
+
+	#[test]
+	#[should_panic]
+	fn wrong_size_panics(){
+		Tensor::from_vec(&[2,2], vec![1.0;3]);
+	}
+}
@@ -19,7 +19,7 @@ fn progress_bar(percentage: f64, size: usize) -> String {
 /// * `current_step` - the current batch
 /// * `last` - the number of batches
 /// * `cost` - the current cost
-pub fn display_progress(current_step: i32,last: usize,cost: f64,epoch : usize,total_epochs : usize) -> (){
+pub fn display_progress(current_step: i32,last: usize,cost: f64,epoch : usize,total_epochs : usize){
 	let percentage:f64 = current_step as f64 * 100.0 / last as f64;
 
 	println!("{} {:.2}% EPOCH {epoch}/{total_epochs}",progress_bar(percentage,50),percentage);